@@ -0,0 +1,337 @@
+//! Names of the extraction APIs.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use url::Url;
+
+use Error;
+
+/// One of the possible diffbot API.
+///
+/// See [the diffbot documentation](https://www.diffbot.com/dev/docs/).
+///
+/// An `API` can be displayed and parsed from its name (`"article"`,
+/// `"my-custom-api"`, ...), and is (de)serialized as that name.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum API {
+    /// The analyze API automatically detects the page type.
+    Analyze,
+    /// The article API for news article.
+    Article,
+    /// The product API for products in online shops.
+    Product,
+    /// The discussion API for forums.
+    Discussion,
+    /// The image API for image-central pages.
+    Image,
+    /// The video API for video pages (youtube, ...).
+    Video,
+    /// Custom-built API with a specific name
+    Custom(String),
+}
+
+impl API {
+    /// Returns a custom API, after checking its name can be used in a URL.
+    pub fn custom<S: Into<String>>(name: S) -> Result<API, Error> {
+        let api = API::Custom(name.into());
+        try!(api.validate());
+        Ok(api)
+    }
+
+    fn get_str(&self) -> &str {
+        match *self {
+            API::Analyze => "analyze",
+            API::Article => "article",
+            API::Product => "product",
+            API::Discussion => "discussion",
+            API::Image => "image",
+            API::Video => "video",
+            API::Custom(ref name) => name.as_ref(),
+        }
+    }
+
+    // Custom names end up in the URL path, so only allow characters that
+    // don't need escaping there.
+    fn validate(&self) -> Result<(), Error> {
+        let name = self.get_str();
+        let valid = !name.is_empty() &&
+                    name.chars().all(|c| {
+            c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.'
+        });
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::InvalidApi(format!("invalid API name: {:?}", name)))
+        }
+    }
+
+    #[cfg(feature = "jobs")]
+    pub(crate) fn get_url_string(&self, base_url: &Url, version: u8)
+                                 -> Result<String, Error> {
+        self.get_url(base_url, version).map(|url| url.into_string())
+    }
+
+    pub(crate) fn get_url(&self, base_url: &Url, version: u8)
+                          -> Result<Url, Error> {
+        try!(self.validate());
+        get_api_url(base_url, self.get_str(), version)
+    }
+}
+
+impl fmt::Display for API {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(self.get_str())
+    }
+}
+
+impl FromStr for API {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<API, Error> {
+        Ok(match name {
+            "analyze" => API::Analyze,
+            "article" => API::Article,
+            "product" => API::Product,
+            "discussion" => API::Discussion,
+            "image" => API::Image,
+            "video" => API::Video,
+            custom => try!(API::custom(custom)),
+        })
+    }
+}
+
+impl Serialize for API {
+    fn serialize<S: Serializer>(&self, serializer: S)
+                                -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.get_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for API {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D)
+                                         -> Result<API, D::Error> {
+        let name = try!(String::deserialize(deserializer));
+        name.parse().map_err(|err: Error| de::Error::custom(err.to_string()))
+    }
+}
+
+/// The type of a page, as detected by the Analyze API.
+///
+/// Used to restrict the types Analyze extracts with `AnalyzeOptions::mode`,
+/// and to tell what it found with `models::ExtractResponse::page_type`.
+/// Converted from and (de)serialized as its name, such as `"article"`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PageType {
+    /// A news article or blog post.
+    Article,
+    /// A product in an online shop.
+    Product,
+    /// A forum thread, comments or reviews.
+    Discussion,
+    /// A page centered on an image.
+    Image,
+    /// A page centered on a video.
+    Video,
+    /// An event listing.
+    Event,
+    /// A list of items, such as search results or an index page.
+    List,
+    /// Any other type, by name.
+    Other(String),
+}
+
+impl PageType {
+    fn get_str(&self) -> &str {
+        match *self {
+            PageType::Article => "article",
+            PageType::Product => "product",
+            PageType::Discussion => "discussion",
+            PageType::Image => "image",
+            PageType::Video => "video",
+            PageType::Event => "event",
+            PageType::List => "list",
+            PageType::Other(ref name) => name.as_ref(),
+        }
+    }
+}
+
+impl fmt::Display for PageType {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(self.get_str())
+    }
+}
+
+impl<'a> From<&'a str> for PageType {
+    fn from(name: &'a str) -> PageType {
+        match name {
+            "article" => PageType::Article,
+            "product" => PageType::Product,
+            "discussion" => PageType::Discussion,
+            "image" => PageType::Image,
+            "video" => PageType::Video,
+            "event" => PageType::Event,
+            "list" => PageType::List,
+            other => PageType::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for PageType {
+    fn from(name: String) -> PageType {
+        PageType::from(name.as_str())
+    }
+}
+
+impl Serialize for PageType {
+    fn serialize<S: Serializer>(&self, serializer: S)
+                                -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.get_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for PageType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D)
+                                         -> Result<PageType, D::Error> {
+        String::deserialize(deserializer).map(PageType::from)
+    }
+}
+
+/// Whether the API accepts a token, as returned by
+/// `Diffbot::validate_token`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TokenStatus {
+    /// The token can be used.
+    Valid,
+    /// The token is unknown, expired or revoked.
+    Unauthorized,
+    /// The token is valid, but has made too many calls for now.
+    Throttled,
+}
+
+// The root of the hosted Diffbot API.
+pub(crate) const DEFAULT_BASE_URL: &'static str = "https://api.diffbot.com/";
+
+// The root of the hosted Knowledge Graph API.
+pub(crate) const DEFAULT_KG_URL: &'static str = "https://kg.diffbot.com/";
+
+// The root of the hosted Natural Language API.
+pub(crate) const DEFAULT_NL_URL: &'static str = "https://nl.diffbot.com/";
+
+// The roots of the same APIs, hosted in the European Union.
+pub(crate) const EU_BASE_URL: &'static str = "https://api.eu.diffbot.com/";
+pub(crate) const EU_KG_URL: &'static str = "https://kg.eu.diffbot.com/";
+pub(crate) const EU_NL_URL: &'static str = "https://nl.eu.diffbot.com/";
+
+// Returns the URL of an endpoint of the Knowledge Graph, such as `dql`,
+// relative to `kg_url`, which must end with a slash.
+#[cfg(feature = "kg")]
+pub(crate) fn get_kg_url(kg_url: &Url, endpoint: &str) -> Url {
+    kg_url.join(&format!("kg/v3/{}", endpoint)).unwrap()
+}
+
+// Returns the URL of an API, relative to `base_url`, which must end with a
+// slash.
+pub(crate) fn get_api_url(base_url: &Url, api: &str, version: u8)
+                          -> Result<Url, Error> {
+    try!(check_version(api, version));
+    Ok(base_url.join(&format!("v{}/{}", version, api)).unwrap())
+}
+
+// Checks that version `version` of the API offers `endpoint`, such as
+// `article` or `crawl/data`.
+fn check_version(endpoint: &str, version: u8) -> Result<(), Error> {
+    if version < 1 || version > 3 {
+        let message = format!("there is no version {} of the API", version);
+        return Err(Error::Unsupported(message));
+    }
+    let name = endpoint.split('/').next().unwrap_or("");
+    // The first and last versions offering the endpoint.
+    let (first_version, last_version) = match name {
+        "analyze" | "product" | "image" => (2, 3),
+        "discussion" | "video" | "crawl" | "bulk" | "search" |
+        "account" => (3, 3),
+        "frontpage" => (2, 2),
+        _ => (1, 3),
+    };
+    if version < first_version || version > last_version {
+        let versions = if first_version == last_version {
+            format!("version {}", first_version)
+        } else if last_version == 3 {
+            format!("version {} or later", first_version)
+        } else {
+            format!("versions {} to {}", first_version, last_version)
+        };
+        let message = format!("{} requires {} of the API, the client uses \
+                               version {}",
+                              name,
+                              versions,
+                              version);
+        return Err(Error::Unsupported(message));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_api_round_trip() {
+    for name in &["analyze", "article", "video", "my-custom_api.v2"] {
+        let api: API = name.parse().unwrap();
+        assert_eq!(api.to_string(), *name);
+    }
+    assert_eq!("product".parse::<API>().unwrap(), API::Product);
+}
+
+#[test]
+fn test_version_check() {
+    let base_url = Url::parse(DEFAULT_BASE_URL).unwrap();
+    assert!(API::Article.get_url(&base_url, 1).is_ok());
+    assert!(API::Analyze.get_url(&base_url, 2).is_ok());
+    assert!(get_api_url(&base_url, "crawl/data", 3).is_ok());
+    let err = get_api_url(&base_url, "crawl/data", 2).unwrap_err();
+    assert_eq!(err.to_string(),
+               "unsupported call: crawl requires version 3 of the API, the \
+                client uses version 2");
+    let err = API::Analyze.get_url(&base_url, 1).unwrap_err();
+    assert_eq!(err.to_string(),
+               "unsupported call: analyze requires version 2 or later of \
+                the API, the client uses version 1");
+    assert!(API::Video.get_url(&base_url, 2).is_err());
+
+    let frontpage = API::custom("frontpage").unwrap();
+    assert!(frontpage.get_url(&base_url, 2).is_ok());
+    for &version in &[1, 3] {
+        let err = frontpage.get_url(&base_url, version).unwrap_err();
+        assert_eq!(err.to_string(),
+                   format!("unsupported call: frontpage requires version 2 \
+                            of the API, the client uses version {}",
+                           version));
+    }
+    assert!(API::Article.get_url(&base_url, 4).is_err());
+}
+
+#[test]
+fn test_invalid_custom_api() {
+    assert!("my/api".parse::<API>().is_err());
+    assert!(API::custom("with space").is_err());
+    let base_url = Url::parse(DEFAULT_BASE_URL).unwrap();
+    assert!(API::Custom("../crawl".to_string())
+                .get_url(&base_url, 3)
+                .is_err());
+}
+
+#[test]
+fn test_api_serde() {
+    let json = ::serde_json::to_string(&API::Custom("mine".to_string()));
+    assert_eq!(json.unwrap(), "\"mine\"");
+    let api: API = ::serde_json::from_str("\"image\"").unwrap();
+    assert_eq!(api, API::Image);
+}
+
+#[test]
+fn test_page_type() {
+    assert_eq!(PageType::from("video"), PageType::Video);
+    assert_eq!(PageType::from("recipe").to_string(), "recipe");
+    let page_type: PageType = ::serde_json::from_str("\"list\"").unwrap();
+    assert_eq!(page_type, PageType::List);
+}
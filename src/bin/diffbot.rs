@@ -0,0 +1,225 @@
+//! Command-line access to the Diffbot API.
+//!
+//! The token is read from the `DIFFBOT_TOKEN` environment variable, and
+//! results are printed as JSON on the standard output. `DIFFBOT_BASE_URL`
+//! sends the requests to another server, such as an on-premises one.
+
+extern crate diffbot;
+extern crate serde;
+extern crate serde_json;
+
+use std::env;
+use std::io;
+use std::process;
+
+use diffbot::{API, Diffbot, Error, JobResults};
+use diffbot::models::{self, Article, ExtractResponse, SearchResponse};
+use diffbot::output::{self, Format};
+use serde::Serialize;
+use serde_json::Value;
+
+const USAGE: &'static str = "\
+Usage:
+    diffbot [options] article <url>
+    diffbot analyze <url>
+    diffbot search <collection> <query>
+    diffbot crawl start <name> <api> <seed>...
+    diffbot crawl status|pause|resume|delete|download <name>
+    diffbot bulk start <name> <api> <url>...
+    diffbot bulk status|pause|resume|delete|download <name>
+
+Options:
+    --format <format>   json, jsonl, csv or table. Results are printed as
+                        json by default, and downloads as jsonl.
+    --columns <paths>   The fields of the csv and table formats, separated
+                        by commas, like `title,author.name`.
+
+The token is read from the DIFFBOT_TOKEN environment variable. Set
+DIFFBOT_BASE_URL to send the requests to another server than
+https://api.diffbot.com.";
+
+// A failure of the command, reported on the standard error.
+enum Failure {
+    Usage,
+    Api(Error),
+    Output(String),
+}
+
+impl From<Error> for Failure {
+    fn from(err: Error) -> Failure {
+        Failure::Api(err)
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(err: io::Error) -> Failure {
+        Failure::Output(err.to_string())
+    }
+}
+
+// How results are printed.
+struct Printer {
+    format: Option<Format>,
+    columns: Vec<String>,
+}
+
+impl Printer {
+    // Prints a value in the chosen format, JSON by default.
+    fn print<T: Serialize>(&self, value: &T) -> Result<(), Failure> {
+        let value = try!(serde_json::to_value(value)
+                             .map_err(|err| Failure::Output(err.to_string())));
+        let format = self.format.unwrap_or(Format::Json);
+        let columns: Vec<&str> = self.columns
+                                     .iter()
+                                     .map(String::as_str)
+                                     .collect();
+        let stdout = io::stdout();
+        try!(output::write(stdout.lock(), format, &value, &columns));
+        Ok(())
+    }
+
+    // Prints the results of a job, streaming them as JSON lines by default.
+    fn download(&self, results: JobResults<Value>) -> Result<(), Failure> {
+        if self.format.unwrap_or(Format::JsonLines) != Format::JsonLines {
+            let objects = try!(results.collect::<Result<Vec<_>, _>>());
+            return self.print(&objects);
+        }
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        for object in results {
+            try!(output::write_json_lines(&mut stdout, &try!(object)));
+        }
+        Ok(())
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let code = match run(&args) {
+        Ok(()) => 0,
+        Err(Failure::Usage) => {
+            eprintln!("{}", USAGE);
+            2
+        }
+        Err(Failure::Api(err)) => {
+            eprintln!("diffbot: {}", err);
+            1
+        }
+        Err(Failure::Output(message)) => {
+            eprintln!("diffbot: {}", message);
+            1
+        }
+    };
+    process::exit(code);
+}
+
+fn run(args: &[String]) -> Result<(), Failure> {
+    let mut printer = Printer {
+        format: None,
+        columns: Vec::new(),
+    };
+    let mut args: Vec<&str> = args.iter().map(String::as_str).collect();
+    while args.len() >= 2 && args[0].starts_with("--") {
+        match args[0] {
+            "--format" => printer.format = Some(try!(args[1].parse())),
+            "--columns" => {
+                printer.columns = args[1].split(',')
+                                         .map(|column| column.to_string())
+                                         .collect()
+            }
+            _ => return Err(Failure::Usage),
+        }
+        args.drain(..2);
+    }
+    let command = match args.first() {
+        Some(&"help") | Some(&"--help") | Some(&"-h") | None => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        Some(&command) => command,
+    };
+    let client = try!(client());
+
+    match (command, &args[1..]) {
+        ("article", &[url]) => {
+            let result = try!(client.call(API::Article, url));
+            let response: ExtractResponse<Article> =
+                try!(models::parse(result));
+            printer.print(&response.objects)
+        }
+        ("analyze", &[url]) => {
+            let result = try!(client.call(API::Analyze, url));
+            let response: ExtractResponse<Value> =
+                try!(models::parse(result));
+            printer.print(&response.objects)
+        }
+        ("search", &[col, query]) => {
+            let result = try!(client.search(col, query));
+            let response: SearchResponse = try!(models::parse(result));
+            printer.print(&response)
+        }
+        ("crawl", args) | ("bulk", args) => {
+            job(&client, &printer, command, args)
+        }
+        _ => Err(Failure::Usage),
+    }
+}
+
+// Runs a crawl or bulk subcommand.
+fn job(client: &Diffbot, printer: &Printer, kind: &str, args: &[&str])
+       -> Result<(), Failure> {
+    let (action, name, rest) = match args.split_first() {
+        Some((action, rest)) if !rest.is_empty() => {
+            (*action, rest[0], &rest[1..])
+        }
+        _ => return Err(Failure::Usage),
+    };
+    if action == "start" {
+        if rest.len() < 2 {
+            return Err(Failure::Usage);
+        }
+        let api: API = try!(rest[0].parse());
+        let result = if kind == "crawl" {
+            client.crawl(name, api, &rest[1..])
+        } else {
+            client.bulk(name, api, &rest[1..])
+        };
+        return printer.print(&try!(result));
+    }
+    if !rest.is_empty() {
+        return Err(Failure::Usage);
+    }
+
+    let result = match (kind, action) {
+        ("crawl", "status") => client.get_crawl(name),
+        ("crawl", "pause") => client.pause_crawl(name),
+        ("crawl", "resume") => client.resume_crawl(name),
+        ("crawl", "delete") => client.delete_crawl(name),
+        ("crawl", "download") => {
+            return printer.download(client.crawl_results(name))
+        }
+        ("bulk", "status") => client.get_bulk(name),
+        ("bulk", "pause") => client.pause_bulk(name),
+        ("bulk", "resume") => client.resume_bulk(name),
+        ("bulk", "delete") => client.delete_bulk(name),
+        ("bulk", "download") => {
+            return printer.download(client.bulk_results(name))
+        }
+        _ => return Err(Failure::Usage),
+    };
+    printer.print(&try!(result))
+}
+
+fn client() -> Result<Diffbot, Failure> {
+    let client = match env::var("DIFFBOT_TOKEN") {
+        Ok(token) => Diffbot::v3(token),
+        Err(_) => {
+            let message = "DIFFBOT_TOKEN is not set".to_string();
+            return Err(Failure::Output(message));
+        }
+    };
+    match env::var("DIFFBOT_BASE_URL") {
+        Ok(base_url) => Ok(try!(client.with_base_url(&base_url))),
+        Err(_) => Ok(client),
+    }
+}
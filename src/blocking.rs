@@ -0,0 +1,24 @@
+//! The synchronous client, under a path that will not change.
+//!
+//! Every call of the client blocks the current thread until the response is
+//! read, so `blocking::Diffbot` is the same type as `Diffbot`. Scripts and
+//! applications without an async runtime can import it from here: should the
+//! root client ever become asynchronous, this module will keep the current
+//! signatures, driving the asynchronous core internally.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate diffbot;
+//! use diffbot::API;
+//! use diffbot::blocking::Diffbot;
+//!
+//! # fn main() {
+//! let diffbot = Diffbot::v3("insert_your_token_here");
+//! # println!("{:?}",
+//! diffbot.call(API::Article, "http://www.diffbot.com")
+//! # );
+//! # }
+//! ```
+
+pub use {Diffbot, DiffbotApi, DiffbotResult, PreparedRequest, Request};
@@ -0,0 +1,124 @@
+//! Failing fast while the API is down.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use Error;
+
+/// Stops calling the API after repeated failures, for a while.
+///
+/// After `threshold` consecutive network errors or server errors (5xx
+/// statuses), the breaker opens: calls fail at once with
+/// `Error::CircuitOpen`, without reaching the API, for the `cool_down`
+/// period. Then a single call is let through as a probe; if it succeeds the
+/// breaker closes again, otherwise it stays open for another period.
+///
+/// Clones share the same state, so a breaker can be shared by several
+/// clients, and watched with `is_open`.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # use std::time::Duration;
+/// # fn main() {
+/// let breaker = CircuitBreaker::new(5, Duration::from_secs(30));
+/// let diffbot = Diffbot::v3("token").with_circuit_breaker(breaker.clone());
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cool_down: Duration,
+    state: Arc<Mutex<BreakerState>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BreakerState {
+    // Counting the consecutive failures.
+    Closed(u32),
+    // Failing fast until the given time.
+    Open(Instant),
+    // A probe call is running.
+    HalfOpen,
+}
+
+impl CircuitBreaker {
+    /// Returns a breaker opening after `threshold` consecutive failures,
+    /// for `cool_down`.
+    pub fn new(threshold: u32, cool_down: Duration) -> Self {
+        CircuitBreaker {
+            threshold: threshold.max(1),
+            cool_down: cool_down,
+            state: Arc::new(Mutex::new(BreakerState::Closed(0))),
+        }
+    }
+
+    /// Returns `true` if calls currently fail without reaching the API.
+    pub fn is_open(&self) -> bool {
+        match *self.state.lock().unwrap() {
+            BreakerState::Closed(_) => false,
+            BreakerState::Open(until) => until > Instant::now(),
+            BreakerState::HalfOpen => true,
+        }
+    }
+
+    // Lets a call through, or fails if the breaker is open.
+    pub(crate) fn acquire(&self) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            BreakerState::Closed(_) => Ok(()),
+            BreakerState::Open(until) if until <= Instant::now() => {
+                *state = BreakerState::HalfOpen;
+                Ok(())
+            }
+            BreakerState::Open(until) => {
+                let left = until - Instant::now();
+                let message = format!("too many failures, retrying in {}s",
+                                      left.as_secs() + 1);
+                Err(Error::CircuitOpen(message))
+            }
+            BreakerState::HalfOpen => {
+                let message = "too many failures, probing the API";
+                Err(Error::CircuitOpen(message.to_string()))
+            }
+        }
+    }
+
+    // Records the outcome of a call let through by `acquire`.
+    pub(crate) fn record(&self, success: bool) {
+        let mut state = self.state.lock().unwrap();
+        *state = match (*state, success) {
+            (_, true) => BreakerState::Closed(0),
+            (BreakerState::Closed(failures), false)
+                if failures + 1 < self.threshold => {
+                BreakerState::Closed(failures + 1)
+            }
+            (_, false) => BreakerState::Open(Instant::now() + self.cool_down),
+        };
+    }
+}
+
+#[test]
+fn test_circuit_breaker() {
+    let breaker = CircuitBreaker::new(2, Duration::from_millis(50));
+    breaker.acquire().unwrap();
+    breaker.record(false);
+    assert!(!breaker.is_open());
+    breaker.acquire().unwrap();
+    breaker.record(false);
+    assert!(breaker.is_open());
+    match breaker.clone().acquire() {
+        Err(Error::CircuitOpen(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    // After the cool-down, a single probe goes through.
+    ::std::thread::sleep(Duration::from_millis(60));
+    breaker.acquire().unwrap();
+    assert!(breaker.acquire().is_err());
+    breaker.record(true);
+    assert!(!breaker.is_open());
+    breaker.acquire().unwrap();
+}
@@ -0,0 +1,158 @@
+//! Caching of successful API responses.
+//!
+//! A cache is set on the client with `Diffbot::with_cache`, and each call can
+//! choose how to use it with `Request::cache`. Only successful `GET` calls to
+//! the extraction APIs and entity lookups with `Diffbot::kg_entity` are
+//! cached; job creation, posted bodies, the account, job and search
+//! endpoints and other Knowledge Graph queries always go to the API.
+//! Entries are keyed by URL without the token, like fixtures, so calls that
+//! forward headers or credentials to a site are not cached either.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+/// A store for response bodies.
+///
+/// Implement it to cache responses on disk or in a shared store. The client
+/// only keeps successful responses, so `get` can return any stored body as
+/// is.
+pub trait Cache: Send + Sync {
+    /// Returns the body stored for `key`, if any.
+    fn get(&self, key: &str) -> Option<Vec<u8>>;
+
+    /// Stores the body of a response.
+    fn put(&self, key: &str, body: &[u8]);
+}
+
+/// How a call uses the client's cache.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// Use a cached response if there is one, otherwise call the API and
+    /// store the response. This is the default.
+    Use,
+    /// Ignore the cache entirely.
+    Bypass,
+    /// Always call the API, and store the response.
+    Refresh,
+    /// Use a cached response if there is one, otherwise call the API without
+    /// storing the response.
+    ReadOnly,
+}
+
+impl CachePolicy {
+    pub(crate) fn reads(&self) -> bool {
+        *self == CachePolicy::Use || *self == CachePolicy::ReadOnly
+    }
+
+    pub(crate) fn writes(&self) -> bool {
+        *self == CachePolicy::Use || *self == CachePolicy::Refresh
+    }
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        CachePolicy::Use
+    }
+}
+
+/// A cache kept in memory, without expiration.
+#[derive(Default)]
+pub struct MemoryCache {
+    entries: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryCache {
+    /// Returns an empty cache.
+    pub fn new() -> Self {
+        MemoryCache::default()
+    }
+
+    /// Returns the number of cached responses.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Returns `true` if no response is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes every cached response.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+impl Cache for MemoryCache {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    fn put(&self, key: &str, body: &[u8]) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), body.to_vec());
+    }
+}
+
+impl fmt::Debug for MemoryCache {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("MemoryCache")
+           .field("len", &self.len())
+           .finish()
+    }
+}
+
+#[test]
+fn test_cache_policies() {
+    use std::sync::Arc;
+
+    use API;
+    use stub::{StubResponse, StubServer};
+
+    let server = StubServer::start();
+    for title in &["first", "second", "third"] {
+        let body = format!(r#"{{"objects": [{{"title": "{}"}}]}}"#, title);
+        server.push(StubResponse::json(body));
+    }
+    let cache = Arc::new(MemoryCache::new());
+    let diffbot = server.client("token").with_cache(cache.clone());
+    let title = |policy| {
+        let result = diffbot.request(API::Article, "http://a.com")
+                            .cache(policy)
+                            .send()
+                            .unwrap();
+        result["objects"][0]["title"].as_str().unwrap().to_string()
+    };
+
+    assert_eq!(title(CachePolicy::ReadOnly), "first");
+    assert!(cache.is_empty());
+    assert_eq!(title(CachePolicy::Use), "second");
+    assert_eq!(title(CachePolicy::Use), "second");
+    assert_eq!(title(CachePolicy::Bypass), "third");
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn test_cache_extraction_only() {
+    use std::sync::Arc;
+
+    use API;
+    use stub::{StubResponse, StubServer};
+
+    let server = StubServer::start();
+    for _ in 0..4 {
+        server.push(StubResponse::json(r#"{"objects": [], "plan": "p"}"#));
+    }
+    let cache = Arc::new(MemoryCache::new());
+    let diffbot = server.client("token").with_cache(cache.clone());
+    diffbot.account().unwrap();
+    diffbot.account().unwrap();
+    assert!(cache.is_empty());
+    diffbot.call(API::Article, "http://a.com").unwrap();
+    diffbot.call(API::Article, "http://a.com").unwrap();
+    assert_eq!(cache.len(), 1);
+    assert_eq!(server.requests().len(), 3);
+}
@@ -0,0 +1,205 @@
+//! A trait over the client, so code using it can be tested without network.
+
+use {API, Diffbot, DiffbotResult};
+#[cfg(feature = "jobs")]
+use {Error, models};
+
+/// The calls offered by `Diffbot`, as a trait.
+///
+/// Application code can depend on `&dyn DiffbotApi` (or a generic
+/// `T: DiffbotApi`) instead of `Diffbot`, and use a fake implementation in
+/// its tests. URLs and options are taken as plain strings so the trait can be
+/// used as a trait object; URL validation is still done by `Diffbot`.
+///
+/// Only the methods ending in `_with_options`, `list_crawls`,
+/// `list_bulk_jobs`, `get_bulk` and `get_crawl` need to be implemented; the
+/// others forward to them.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// fn title(client: &dyn DiffbotApi, url: &str) -> Option<String> {
+///     let result = match client.call(API::Article, url) {
+///         Ok(result) => result,
+///         Err(_) => return None,
+///     };
+///     result.get("objects")
+///           .and_then(|objects| objects.get(0))
+///           .and_then(|object| object.get("title"))
+///           .and_then(|title| title.as_str())
+///           .map(|title| title.to_string())
+/// }
+/// # fn main() {
+/// # let diffbot = Diffbot::v3("token");
+/// # println!("{:?}", title(&diffbot, "http://diffbot.com"));
+/// # }
+/// ```
+pub trait DiffbotApi {
+    /// Makes an API call without extra options.
+    fn call(&self, api: API, target_url: &str) -> DiffbotResult {
+        self.call_with_options(api, target_url, &[])
+    }
+
+    /// Makes an API call. See `Diffbot::call_with_options`.
+    fn call_with_options(&self, api: API, target_url: &str,
+                         options: &[(&str, &str)])
+                         -> DiffbotResult;
+
+    /// Posts an entire html body to the API, without extra options.
+    fn post_body(&self, api: API, target_url: &str, body: &[u8])
+                 -> DiffbotResult {
+        self.post_body_with_options(api, target_url, body, &[])
+    }
+
+    /// Posts an entire html body to the API. See
+    /// `Diffbot::post_body_with_options`.
+    fn post_body_with_options(&self, api: API, target_url: &str,
+                              body: &[u8], options: &[(&str, &str)])
+                              -> DiffbotResult;
+
+    /// Runs a search in a diffbot collection without extra options.
+    #[cfg(feature = "search")]
+    fn search(&self, col: &str, query: &str) -> DiffbotResult {
+        self.search_with_options(col, query, &[])
+    }
+
+    /// Runs a search in a diffbot collection.
+    #[cfg(feature = "search")]
+    fn search_with_options(&self, col: &str, query: &str,
+                           options: &[(&str, &str)])
+                           -> DiffbotResult;
+
+    /// Runs a search in the collection of a crawl or bulk job.
+    #[cfg(feature = "search")]
+    fn search_job(&self, name: &str, query: &str, options: &[(&str, &str)])
+                  -> DiffbotResult {
+        self.search_with_options(name, query, options)
+    }
+
+    /// Lists the existing crawls.
+    #[cfg(feature = "jobs")]
+    fn list_crawls(&self) -> Result<Vec<models::Job>, Error>;
+
+    /// Lists the existing crawls selected by `filter`.
+    #[cfg(feature = "jobs")]
+    fn list_crawls_matching(&self, filter: &models::JobFilter)
+                            -> Result<Vec<models::Job>, Error> {
+        let jobs = try!(self.list_crawls());
+        Ok(jobs.into_iter().filter(|job| filter.matches(job)).collect())
+    }
+
+    /// Starts a bulk job.
+    #[cfg(feature = "jobs")]
+    fn bulk(&self, name: &str, api: API, urls: &[&str]) -> DiffbotResult {
+        self.bulk_with_options(name, api, urls, &[])
+    }
+
+    /// Starts a bulk job with extra options.
+    #[cfg(feature = "jobs")]
+    fn bulk_with_options(&self, name: &str, api: API, urls: &[&str],
+                         options: &[(&str, &str)])
+                         -> DiffbotResult;
+
+    /// Lists the existing bulk jobs.
+    #[cfg(feature = "jobs")]
+    fn list_bulk_jobs(&self) -> Result<Vec<models::Job>, Error>;
+
+    /// Lists the existing bulk jobs selected by `filter`.
+    #[cfg(feature = "jobs")]
+    fn list_bulk_jobs_matching(&self, filter: &models::JobFilter)
+                               -> Result<Vec<models::Job>, Error> {
+        let jobs = try!(self.list_bulk_jobs());
+        Ok(jobs.into_iter().filter(|job| filter.matches(job)).collect())
+    }
+
+    /// Retrieves the result from a bulk job.
+    #[cfg(feature = "jobs")]
+    fn get_bulk(&self, name: &str) -> DiffbotResult;
+
+    /// Starts a crawl job.
+    #[cfg(feature = "jobs")]
+    fn crawl(&self, name: &str, api: API, seeds: &[&str]) -> DiffbotResult {
+        self.crawl_with_options(name, api, seeds, &[])
+    }
+
+    /// Starts a crawl job with extra options.
+    #[cfg(feature = "jobs")]
+    fn crawl_with_options(&self, name: &str, api: API, seeds: &[&str],
+                          options: &[(&str, &str)])
+                          -> DiffbotResult;
+
+    /// Retrieves the result from a crawl job.
+    #[cfg(feature = "jobs")]
+    fn get_crawl(&self, name: &str) -> DiffbotResult;
+}
+
+impl DiffbotApi for Diffbot {
+    fn call_with_options(&self, api: API, target_url: &str,
+                         options: &[(&str, &str)])
+                         -> DiffbotResult {
+        Diffbot::call_with_options(self, api, target_url, options)
+    }
+
+    fn post_body_with_options(&self, api: API, target_url: &str,
+                              body: &[u8], options: &[(&str, &str)])
+                              -> DiffbotResult {
+        Diffbot::post_body_with_options(self, api, target_url, body, options)
+    }
+
+    #[cfg(feature = "search")]
+    fn search_with_options(&self, col: &str, query: &str,
+                           options: &[(&str, &str)])
+                           -> DiffbotResult {
+        Diffbot::search_with_options(self, col, query, options)
+    }
+
+    #[cfg(feature = "search")]
+    fn search_job(&self, name: &str, query: &str, options: &[(&str, &str)])
+                  -> DiffbotResult {
+        Diffbot::search_job(self, name, query, options)
+    }
+
+    #[cfg(feature = "jobs")]
+    fn list_crawls(&self) -> Result<Vec<models::Job>, Error> {
+        Diffbot::list_crawls(self)
+    }
+
+    #[cfg(feature = "jobs")]
+    fn bulk_with_options(&self, name: &str, api: API, urls: &[&str],
+                         options: &[(&str, &str)])
+                         -> DiffbotResult {
+        Diffbot::bulk_with_options(self, name, api, urls, options)
+    }
+
+    #[cfg(feature = "jobs")]
+    fn list_bulk_jobs(&self) -> Result<Vec<models::Job>, Error> {
+        Diffbot::list_bulk_jobs(self)
+    }
+
+    #[cfg(feature = "jobs")]
+    fn get_bulk(&self, name: &str) -> DiffbotResult {
+        Diffbot::get_bulk(self, name)
+    }
+
+    #[cfg(feature = "jobs")]
+    fn crawl_with_options(&self, name: &str, api: API, seeds: &[&str],
+                          options: &[(&str, &str)])
+                          -> DiffbotResult {
+        Diffbot::crawl_with_options(self, name, api, seeds, options)
+    }
+
+    #[cfg(feature = "jobs")]
+    fn get_crawl(&self, name: &str) -> DiffbotResult {
+        Diffbot::get_crawl(self, name)
+    }
+}
+
+#[test]
+fn test_diffbot_api_is_object_safe() {
+    let diffbot = Diffbot::v3("token");
+    let client: &dyn DiffbotApi = &diffbot;
+    // Invalid URLs are rejected before anything is sent.
+    assert!(client.call(API::Article, "not a url").is_err());
+}
@@ -0,0 +1,282 @@
+//! Cookies forwarded to target sites across calls.
+//!
+//! A `CookieJar` set with `Diffbot::with_cookie_jar` remembers the cookies
+//! of each site, and every call to a page of that site asks Diffbot to send
+//! them, in the `X-Forward-Cookie` header. This is enough to extract pages
+//! behind a simple session login.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+/// Cookies shared by the calls to the same sites.
+///
+/// The jar is filled with the `Set-Cookie` headers of a login response, or
+/// with session cookies obtained elsewhere, and can be shared between
+/// clients: clones use the same cookies.
+///
+/// Only the `Domain`, `Path`, `Secure` and `Max-Age` attributes are
+/// honored. Other cookies are kept until the jar is cleared.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # fn main() {
+/// let jar = CookieJar::new();
+/// jar.store("https://intranet.example.com/login",
+///           &["session=s3cr3t; Path=/; Secure"]);
+/// let diffbot = Diffbot::v3("token").with_cookie_jar(jar);
+/// # println!("{:?}",
+/// diffbot.call(API::Article, "https://intranet.example.com/news/1")
+/// # );
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct CookieJar {
+    cookies: Arc<Mutex<Vec<Cookie>>>,
+}
+
+// A cookie, with the scope it was set for.
+#[derive(Clone, Debug)]
+struct Cookie {
+    name: String,
+    value: String,
+    domain: String,
+    host_only: bool,
+    path: String,
+    secure: bool,
+    expires: Option<Instant>,
+}
+
+impl fmt::Debug for CookieJar {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        // Cookie values are often credentials, so only names are shown.
+        let cookies = self.cookies.lock().unwrap();
+        let names: Vec<String> = cookies.iter()
+                                        .map(|cookie| {
+                                            format!("{}@{}",
+                                                    cookie.name,
+                                                    cookie.domain)
+                                        })
+                                        .collect();
+        fmt.debug_struct("CookieJar").field("cookies", &names).finish()
+    }
+}
+
+impl CookieJar {
+    /// Returns an empty jar.
+    pub fn new() -> Self {
+        CookieJar::default()
+    }
+
+    /// Stores the cookies set by the response to a request for `url`.
+    ///
+    /// `set_cookies` are the values of the `Set-Cookie` headers of the
+    /// response. Invalid values, and cookies for another site, are ignored.
+    /// A cookie with a `Max-Age` of zero or less removes the stored one.
+    pub fn store<S: AsRef<str>>(&self, url: &str, set_cookies: &[S]) {
+        let url = match Url::parse(url) {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let now = Instant::now();
+        let mut cookies = self.cookies.lock().unwrap();
+        for set_cookie in set_cookies {
+            let parsed = parse_set_cookie(&url, set_cookie.as_ref(), now);
+            if let Some((cookie, expired)) = parsed {
+                cookies.retain(|old| {
+                    old.name != cookie.name || old.domain != cookie.domain ||
+                    old.path != cookie.path
+                });
+                if !expired {
+                    cookies.push(cookie);
+                }
+            }
+        }
+    }
+
+    /// Adds a cookie for every page of the host of `url`.
+    ///
+    /// Use this for session cookies copied from a browser.
+    pub fn add(&self, url: &str, name: &str, value: &str) {
+        self.store(url, &[format!("{}={}; Path=/", name, value)]);
+    }
+
+    /// Returns the `Cookie` header to send with a request for `url`, if any
+    /// cookie applies to it.
+    pub fn header_for(&self, url: &Url) -> Option<String> {
+        let host = match url.host_str() {
+            Some(host) => host.to_lowercase(),
+            None => return None,
+        };
+        let now = Instant::now();
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|cookie| cookie.expires.map_or(true, |at| at > now));
+
+        let mut matching: Vec<&Cookie> =
+            cookies.iter()
+                   .filter(|cookie| cookie.matches(url, &host))
+                   .collect();
+        if matching.is_empty() {
+            return None;
+        }
+        // More specific paths first, as browsers do.
+        matching.sort_by(|a, b| b.path.len().cmp(&a.path.len()));
+        let pairs: Vec<String> = matching.iter()
+                                         .map(|cookie| {
+                                             format!("{}={}",
+                                                     cookie.name,
+                                                     cookie.value)
+                                         })
+                                         .collect();
+        Some(pairs.join("; "))
+    }
+
+    /// Returns the number of stored cookies.
+    pub fn len(&self) -> usize {
+        self.cookies.lock().unwrap().len()
+    }
+
+    /// Returns `true` if no cookie is stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes every cookie.
+    pub fn clear(&self) {
+        self.cookies.lock().unwrap().clear();
+    }
+}
+
+impl Cookie {
+    fn matches(&self, url: &Url, host: &str) -> bool {
+        let domain_matches = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(host, &self.domain)
+        };
+        domain_matches && path_match(url.path(), &self.path) &&
+        (!self.secure || url.scheme() == "https")
+    }
+}
+
+// Parses a `Set-Cookie` value received for `url`, and returns the cookie
+// and whether it is already expired.
+fn parse_set_cookie(url: &Url, set_cookie: &str, now: Instant)
+                    -> Option<(Cookie, bool)> {
+    let host = match url.host_str() {
+        Some(host) => host.to_lowercase(),
+        None => return None,
+    };
+    let mut parts = set_cookie.split(';');
+    let pair = parts.next().unwrap_or("");
+    let eq = match pair.find('=') {
+        Some(eq) => eq,
+        None => return None,
+    };
+    let name = pair[..eq].trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: pair[eq + 1..].trim().to_string(),
+        domain: host.clone(),
+        host_only: true,
+        path: default_path(url),
+        secure: false,
+        expires: None,
+    };
+    let mut expired = false;
+    for attribute in parts {
+        let (key, value) = match attribute.find('=') {
+            Some(eq) => (attribute[..eq].trim(), attribute[eq + 1..].trim()),
+            None => (attribute.trim(), ""),
+        };
+        match &*key.to_lowercase() {
+            "domain" if !value.is_empty() => {
+                let domain = value.trim_start_matches('.').to_lowercase();
+                if !domain_match(&host, &domain) {
+                    return None;
+                }
+                cookie.domain = domain;
+                cookie.host_only = false;
+            }
+            "path" if value.starts_with('/') => {
+                cookie.path = value.to_string();
+            }
+            "secure" => cookie.secure = true,
+            "max-age" => {
+                match value.parse::<i64>() {
+                    Ok(age) if age > 0 => {
+                        cookie.expires =
+                            Some(now + Duration::from_secs(age as u64));
+                    }
+                    Ok(_) => expired = true,
+                    Err(_) => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    Some((cookie, expired))
+}
+
+// Returns `true` if `host` is `domain` or one of its subdomains.
+pub(crate) fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain ||
+    (host.ends_with(domain) &&
+     host[..host.len() - domain.len()].ends_with('.'))
+}
+
+// Returns `true` if the cookie path `cookie_path` covers `path`.
+fn path_match(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path ||
+    (path.starts_with(cookie_path) &&
+     (cookie_path.ends_with('/') ||
+      path[cookie_path.len()..].starts_with('/')))
+}
+
+// Returns the directory of the URL path, used when a cookie has no `Path`.
+fn default_path(url: &Url) -> String {
+    let path = url.path();
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(slash) => path[..slash].to_string(),
+    }
+}
+
+#[test]
+fn test_cookie_jar() {
+    let jar = CookieJar::new();
+    jar.store("https://www.example.com/account/login",
+              &["session=abc; Path=/; Secure; HttpOnly",
+                "prefs=dark",
+                "tracking=1; Domain=example.com; Path=/",
+                "foreign=1; Domain=other.com",
+                "invalid"]);
+    assert_eq!(jar.len(), 3);
+
+    let url = |url: &str| Url::parse(url).unwrap();
+    assert_eq!(jar.header_for(&url("https://www.example.com/account/me")),
+               Some("prefs=dark; session=abc; tracking=1".to_string()));
+    assert_eq!(jar.header_for(&url("http://www.example.com/news")),
+               Some("tracking=1".to_string()));
+    assert_eq!(jar.header_for(&url("https://shop.example.com/")),
+               Some("tracking=1".to_string()));
+    assert_eq!(jar.header_for(&url("https://badexample.com/")), None);
+
+    jar.store("https://www.example.com/", &["session=; Path=/; Max-Age=0"]);
+    assert_eq!(jar.header_for(&url("https://www.example.com/")),
+               Some("tracking=1".to_string()));
+
+    jar.add("https://intranet.example.com/", "sid", "42");
+    assert_eq!(jar.header_for(&url("https://intranet.example.com/a/b")),
+               Some("tracking=1; sid=42".to_string()));
+    assert!(!format!("{:?}", jar).contains("42"));
+}
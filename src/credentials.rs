@@ -0,0 +1,100 @@
+//! Basic authentication with target sites.
+
+use std::fmt;
+
+const BASE64: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
+                                abcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A user name and password for a password-protected target site.
+///
+/// Diffbot sends them to the site with HTTP basic authentication, in the
+/// forwarded `Authorization` header. Set them for one call with
+/// `Request::basic_auth`, or for every page of a domain with
+/// `Diffbot::with_site_credentials`.
+///
+/// The `Debug` output does not contain the password.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # fn main() {
+/// let staging = Credentials::new("preview", "hunter2");
+/// let diffbot = Diffbot::v3("token")
+///                   .with_site_credentials("staging.example.com", staging);
+/// # println!("{:?}",
+/// diffbot.call(API::Article, "https://staging.example.com/blog/1")
+/// # );
+/// # }
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    user: String,
+    password: String,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Credentials")
+           .field("user", &self.user)
+           .field("password", &"…")
+           .finish()
+    }
+}
+
+impl Credentials {
+    /// Returns credentials for the given user.
+    pub fn new<U, P>(user: U, password: P) -> Self
+        where U: Into<String>,
+              P: Into<String>
+    {
+        Credentials {
+            user: user.into(),
+            password: password.into(),
+        }
+    }
+
+    /// Returns the user name.
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
+    // Returns the value of the `Authorization` header.
+    pub(crate) fn header(&self) -> String {
+        let pair = format!("{}:{}", self.user, self.password);
+        format!("Basic {}", base64(pair.as_bytes()))
+    }
+}
+
+// Encodes bytes in standard base64, with padding.
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0],
+                 chunk.get(1).cloned().unwrap_or(0),
+                 chunk.get(2).cloned().unwrap_or(0)];
+        let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(n >> (18 - 6 * i)) & 0x3f] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[test]
+fn test_credentials_header() {
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"f"), "Zg==");
+    assert_eq!(base64(b"fo"), "Zm8=");
+    assert_eq!(base64(b"foo"), "Zm9v");
+    assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+
+    let credentials = Credentials::new("Aladdin", "open sesame");
+    assert_eq!(credentials.header(), "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==");
+    assert!(!format!("{:?}", credentials).contains("sesame"));
+}
@@ -0,0 +1,227 @@
+//! Comparison of the results of two crawl rounds.
+//!
+//! The objects of each set are matched by their page URL, or their document
+//! ID when they have no `pageUrl`. Objects only in the new set are added,
+//! objects only in the old set are removed, and matched objects that differ
+//! are changed, with the list of the fields that differ. Nested objects are
+//! compared field by field, and named by their path like `author.name`;
+//! arrays and other values are compared as a whole.
+//!
+//! Keep the results of a round, with `Diffbot::crawl_results` or a
+//! downloaded file, to compare them with the next one.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate diffbot;
+//! # extern crate serde_json;
+//! use diffbot::diff;
+//! use serde_json::Value;
+//!
+//! # fn main() {
+//! let old: Vec<Value> = serde_json::from_str(
+//!     r#"[{"pageUrl": "http://a.com/1", "title": "One", "timestamp": 1},
+//!         {"pageUrl": "http://a.com/2", "title": "Two", "timestamp": 1}]"#)
+//!     .unwrap();
+//! let new: Vec<Value> = serde_json::from_str(
+//!     r#"[{"pageUrl": "http://a.com/1", "title": "Une", "timestamp": 2},
+//!         {"pageUrl": "http://a.com/3", "title": "Three", "timestamp": 2}]"#)
+//!     .unwrap();
+//!
+//! let changes = diff::compare(old, new, &["timestamp"]);
+//! assert_eq!(changes.added.len(), 1);
+//! assert_eq!(changes.removed.len(), 1);
+//! assert_eq!(changes.changed[0].fields[0].path, "title");
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+
+/// The differences between two sets of results.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResultsDiff {
+    /// Objects of the new set without a match in the old one.
+    pub added: Vec<Value>,
+    /// Objects of the old set without a match in the new one.
+    pub removed: Vec<Value>,
+    /// Matched objects that differ.
+    pub changed: Vec<ChangedObject>,
+    /// Number of objects that had neither a `pageUrl` nor a `docId`, and
+    /// were left out of the comparison.
+    pub unmatched: usize,
+}
+
+/// An object found in both sets, with different fields.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangedObject {
+    /// The page URL or document ID the objects were matched by.
+    pub key: String,
+    /// The object of the old set.
+    pub old: Value,
+    /// The object of the new set.
+    pub new: Value,
+    /// The fields that differ, sorted by path.
+    pub fields: Vec<FieldChange>,
+}
+
+/// A field that differs between two versions of an object.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldChange {
+    /// The path of the field, like `title` or `author.name`.
+    pub path: String,
+    /// The old value, or `None` if the field was added.
+    pub old: Option<Value>,
+    /// The new value, or `None` if the field was removed.
+    pub new: Option<Value>,
+}
+
+impl ResultsDiff {
+    /// Returns `true` if both sets hold the same objects.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() &&
+        self.changed.is_empty()
+    }
+}
+
+/// Returns the key an object is matched by: its `pageUrl`, or else its
+/// `docId`.
+pub fn object_key(object: &Value) -> Option<String> {
+    ["pageUrl", "docId"]
+        .iter()
+        .filter_map(|field| object.get(field))
+        .filter_map(|value| match *value {
+            Value::String(ref key) => Some(key.clone()),
+            Value::Number(ref key) => Some(key.to_string()),
+            _ => None,
+        })
+        .next()
+}
+
+/// Compares the results of an old and a new round.
+///
+/// Fields whose path is in `ignored`, such as timestamps that change at
+/// every round, are not compared. When several objects of a set have the
+/// same key, the last one is used.
+pub fn compare<I, J>(old: I, new: J, ignored: &[&str]) -> ResultsDiff
+    where I: IntoIterator<Item = Value>,
+          J: IntoIterator<Item = Value>
+{
+    let mut diff = ResultsDiff::default();
+    let old = index(old, &mut diff.unmatched);
+    let mut new = index(new, &mut diff.unmatched);
+
+    for (key, old) in old {
+        let new = match new.remove(&key) {
+            Some(new) => new,
+            None => {
+                diff.removed.push(old);
+                continue;
+            }
+        };
+        let mut fields = Vec::new();
+        compare_values("", Some(&old), Some(&new), ignored, &mut fields);
+        if !fields.is_empty() {
+            diff.changed.push(ChangedObject {
+                key: key,
+                old: old,
+                new: new,
+                fields: fields,
+            });
+        }
+    }
+    diff.added.extend(new.into_iter().map(|(_, object)| object));
+    diff
+}
+
+// Maps objects by key, counting those without one.
+fn index<I>(objects: I, unmatched: &mut usize) -> BTreeMap<String, Value>
+    where I: IntoIterator<Item = Value>
+{
+    let mut index = BTreeMap::new();
+    for object in objects {
+        match object_key(&object) {
+            Some(key) => {
+                index.insert(key, object);
+            }
+            None => *unmatched += 1,
+        }
+    }
+    index
+}
+
+// Adds the differences between two values at `path` to `changes`,
+// recursing into objects.
+fn compare_values(path: &str, old: Option<&Value>, new: Option<&Value>,
+                  ignored: &[&str], changes: &mut Vec<FieldChange>) {
+    if ignored.contains(&path) {
+        return;
+    }
+    match (old, new) {
+        (Some(&Value::Object(ref old)), Some(&Value::Object(ref new))) => {
+            compare_objects(path, old, new, ignored, changes)
+        }
+        (old, new) if old != new => {
+            changes.push(FieldChange {
+                path: path.to_string(),
+                old: old.cloned(),
+                new: new.cloned(),
+            })
+        }
+        _ => {}
+    }
+}
+
+fn compare_objects(path: &str, old: &Map<String, Value>,
+                   new: &Map<String, Value>, ignored: &[&str],
+                   changes: &mut Vec<FieldChange>) {
+    let mut names: Vec<&String> = old.keys().chain(new.keys()).collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        let field = if path.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", path, name)
+        };
+        compare_values(&field, old.get(name), new.get(name), ignored, changes);
+    }
+}
+
+#[test]
+fn test_compare() {
+    let parse = |json: &str| -> Vec<Value> {
+        ::serde_json::from_str(json).unwrap()
+    };
+    let old = parse(r#"[{"pageUrl": "http://a.com/1", "title": "One",
+                         "author": {"name": "A", "link": "http://a.com"},
+                         "tags": ["x"]},
+                        {"docId": 2, "title": "Two"},
+                        {"title": "No key"}]"#);
+    let new = parse(r#"[{"pageUrl": "http://a.com/1", "title": "One",
+                         "author": {"name": "B", "link": "http://a.com"},
+                         "tags": ["x", "y"], "date": "today"},
+                        {"docId": 2, "title": "Two"},
+                        {"docId": 3, "title": "Three"}]"#);
+
+    let diff = compare(old.clone(), new, &["date"]);
+    assert_eq!(diff.added.len(), 1);
+    assert_eq!(diff.added[0]["docId"], 3);
+    assert!(diff.removed.is_empty());
+    assert_eq!(diff.unmatched, 1);
+    assert_eq!(diff.changed.len(), 1);
+    let changed = &diff.changed[0];
+    assert_eq!(changed.key, "http://a.com/1");
+    let paths: Vec<&str> = changed.fields
+                                  .iter()
+                                  .map(|field| field.path.as_str())
+                                  .collect();
+    assert_eq!(paths, vec!["author.name", "tags"]);
+    assert_eq!(changed.fields[0].old, Some(Value::from("A")));
+    assert_eq!(changed.fields[0].new, Some(Value::from("B")));
+
+    let diff = compare(old.clone(), Vec::new(), &[]);
+    assert_eq!(diff.removed.len(), 2);
+    assert!(compare(old.clone(), old, &[]).is_empty());
+}
@@ -0,0 +1,235 @@
+//! The servers of the Diffbot services.
+
+use url::Url;
+
+use Error;
+use api;
+
+/// The servers a client sends its calls to, for each Diffbot service.
+///
+/// The services live on different hosts: the extraction APIs, crawl and
+/// bulk jobs and search on `api.diffbot.com`, the Knowledge Graph on
+/// `kg.diffbot.com` and the Natural Language API on `nl.diffbot.com`.
+/// Start from a profile, `saas`, `eu` or `on_premises`, change the services
+/// that live elsewhere, and select the result with `Diffbot::with_endpoints`.
+///
+/// Each URL may use plain HTTP and any port, and have a path prefix. The
+/// setters fail with `Error::InvalidUrl` if a URL is not an `http` or
+/// `https` URL with a host.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # fn main() {
+/// // Extraction on premises, the Knowledge Graph from Diffbot.
+/// let endpoints = Endpoints::on_premises("http://diffbot.internal:8080")
+///                     .unwrap()
+///                     .with_kg_url("https://kg.diffbot.com")
+///                     .unwrap();
+/// let diffbot = Diffbot::v3("token").with_endpoints(endpoints);
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Endpoints {
+    pub(crate) extract: Url,
+    pub(crate) jobs: Url,
+    pub(crate) search: Url,
+    pub(crate) kg: Url,
+    pub(crate) nl: Url,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Endpoints::saas()
+    }
+}
+
+impl Endpoints {
+    /// Returns the servers hosted by Diffbot, used by default.
+    pub fn saas() -> Self {
+        Endpoints::hosted(api::DEFAULT_BASE_URL,
+                          api::DEFAULT_KG_URL,
+                          api::DEFAULT_NL_URL)
+    }
+
+    /// Returns the servers hosted by Diffbot in the European Union, for
+    /// accounts whose data must stay there.
+    ///
+    /// The services live on `api.eu.diffbot.com`, `kg.eu.diffbot.com` and
+    /// `nl.eu.diffbot.com`. If your contract gives other hosts, start from
+    /// this profile and set them.
+    pub fn eu() -> Self {
+        Endpoints::hosted(api::EU_BASE_URL, api::EU_KG_URL, api::EU_NL_URL)
+    }
+
+    // Returns the servers of a region hosted by Diffbot.
+    fn hosted(base_url: &str, kg_url: &str, nl_url: &str) -> Self {
+        let base_url = Url::parse(base_url).unwrap();
+        Endpoints {
+            extract: base_url.clone(),
+            jobs: base_url.clone(),
+            search: base_url,
+            kg: Url::parse(kg_url).unwrap(),
+            nl: Url::parse(nl_url).unwrap(),
+        }
+    }
+
+    /// Returns a single server for every service, such as an on-premises
+    /// deployment behind a load balancer.
+    ///
+    /// With `http://diffbot.internal:8080/api`, Article calls go to
+    /// `http://diffbot.internal:8080/api/v3/article` and Knowledge Graph
+    /// queries to `http://diffbot.internal:8080/api/kg/v3/dql`.
+    pub fn on_premises(base_url: &str) -> Result<Self, Error> {
+        let base_url = try!(parse_base_url(base_url));
+        Ok(Endpoints {
+            extract: base_url.clone(),
+            jobs: base_url.clone(),
+            search: base_url.clone(),
+            kg: base_url.clone(),
+            nl: base_url,
+        })
+    }
+
+    /// Sets the server of the extraction APIs, such as Article or Analyze.
+    ///
+    /// Crawl and bulk jobs also ask Diffbot to process their pages with
+    /// this server.
+    pub fn with_extract_url(mut self, url: &str) -> Result<Self, Error> {
+        self.extract = try!(parse_base_url(url));
+        Ok(self)
+    }
+
+    /// Sets the server of crawl and bulk jobs.
+    pub fn with_jobs_url(mut self, url: &str) -> Result<Self, Error> {
+        self.jobs = try!(parse_base_url(url));
+        Ok(self)
+    }
+
+    /// Sets the server of the Search API.
+    pub fn with_search_url(mut self, url: &str) -> Result<Self, Error> {
+        self.search = try!(parse_base_url(url));
+        Ok(self)
+    }
+
+    /// Sets the server of the Knowledge Graph.
+    pub fn with_kg_url(mut self, url: &str) -> Result<Self, Error> {
+        self.kg = try!(parse_base_url(url));
+        Ok(self)
+    }
+
+    /// Sets the server of the Natural Language API.
+    pub fn with_nl_url(mut self, url: &str) -> Result<Self, Error> {
+        self.nl = try!(parse_base_url(url));
+        Ok(self)
+    }
+
+    /// Returns the server of the extraction APIs.
+    pub fn extract_url(&self) -> &str {
+        self.extract.as_str()
+    }
+
+    /// Returns the server of crawl and bulk jobs.
+    pub fn jobs_url(&self) -> &str {
+        self.jobs.as_str()
+    }
+
+    /// Returns the server of the Search API.
+    pub fn search_url(&self) -> &str {
+        self.search.as_str()
+    }
+
+    /// Returns the server of the Knowledge Graph.
+    pub fn kg_url(&self) -> &str {
+        self.kg.as_str()
+    }
+
+    /// Returns the server of the Natural Language API.
+    pub fn nl_url(&self) -> &str {
+        self.nl.as_str()
+    }
+
+    // Returns `true` if `url` is on the server of one of the services: same
+    // scheme, host and port, under the path of the service.
+    pub(crate) fn contains(&self, url: &Url) -> bool {
+        [&self.extract, &self.jobs, &self.search, &self.kg, &self.nl]
+            .iter()
+            .any(|base_url| {
+                base_url.scheme() == url.scheme() &&
+                base_url.host_str() == url.host_str() &&
+                base_url.port_or_known_default() ==
+                url.port_or_known_default() &&
+                url.path().starts_with(base_url.path())
+            })
+    }
+
+    // Returns the server of an endpoint of the main API, such as `article`,
+    // `crawl/data` or `search`.
+    pub(crate) fn for_api(&self, api: &str) -> &Url {
+        match api.split('/').next() {
+            Some("crawl") | Some("bulk") => &self.jobs,
+            Some("search") => &self.search,
+            _ => &self.extract,
+        }
+    }
+}
+
+// Parses the root URL of a service, adding the trailing slash that URLs are
+// joined to.
+fn parse_base_url(base_url: &str) -> Result<Url, Error> {
+    let mut url = try!(Url::parse(base_url).map_err(|err| {
+        Error::InvalidUrl(format!("{}: {}", base_url, err))
+    }));
+    if url.scheme() != "http" && url.scheme() != "https" || !url.has_host() {
+        return Err(Error::InvalidUrl(format!("{} is not an HTTP URL", url)));
+    }
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    Ok(url)
+}
+
+#[test]
+fn test_endpoints() {
+    let endpoints = Endpoints::on_premises("http://diffbot.internal:8080/api")
+                        .unwrap()
+                        .with_search_url("https://search.example.com")
+                        .unwrap();
+    assert_eq!(endpoints.extract_url(), "http://diffbot.internal:8080/api/");
+    assert_eq!(endpoints.kg_url(), "http://diffbot.internal:8080/api/");
+    assert_eq!(endpoints.for_api("crawl/data").as_str(),
+               "http://diffbot.internal:8080/api/");
+    assert_eq!(endpoints.for_api("search").as_str(),
+               "https://search.example.com/");
+    assert_eq!(Endpoints::default().for_api("article").as_str(),
+               api::DEFAULT_BASE_URL);
+    let url = |url: &str| Url::parse(url).unwrap();
+    assert!(endpoints.contains(&url("https://search.example.com/v3/search")));
+    assert!(!endpoints.contains(&url("http://diffbot.internal:8080/img.png")));
+    assert!(!endpoints.contains(&url("http://diffbot.internal/api/v3/")));
+    assert!(!endpoints.contains(&url("http://diffbot.internal:8080/apiv3")));
+    assert!(endpoints.contains(&url("http://DIFFBOT.internal:8080/api/v3")));
+
+    let saas = Endpoints::saas();
+    assert!(saas.contains(&url("https://api.diffbot.com:443/v3/article")));
+    assert!(saas.contains(&url("https://kg.diffbot.com/kg/v3/dql")));
+    assert!(!saas.contains(&url("https://api.diffbot.com.evil/v3/")));
+    assert!(!saas.contains(&url("http://api.diffbot.com/v3/article")));
+    assert!(!saas.contains(&url("https://user@api.diffbot.com.evil/")));
+
+    let eu = Endpoints::eu();
+    assert_eq!(eu.for_api("bulk").as_str(), "https://api.eu.diffbot.com/");
+    assert_eq!(eu.kg_url(), "https://kg.eu.diffbot.com/");
+    assert!(!eu.contains(&url("https://api.diffbot.com/v3/article")));
+
+    for invalid in &["diffbot.internal", "ftp://diffbot.internal/",
+                     "unix:/run/diffbot.sock"] {
+        match Endpoints::on_premises(invalid) {
+            Err(Error::InvalidUrl(_)) => {}
+            other => panic!("{} accepted: {:?}", invalid, other.is_ok()),
+        }
+    }
+}
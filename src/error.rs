@@ -0,0 +1,807 @@
+//! Errors returned by the client.
+
+use std::borrow::Cow;
+use std::error;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use reqwest;
+use serde_json;
+use url::Url;
+
+use API;
+use redact;
+
+/// Error occuring during a call.
+///
+/// The `Debug` and `Display` output never contain the API token.
+/// Lower-level errors are available through `std::error::Error::source`.
+///
+/// New variants may be added in future versions.
+#[non_exhaustive]
+pub enum Error {
+    /// The API returned an error.
+    Api(ApiError),
+    /// An error occured when decoding JSON from the API.
+    Json(JsonError),
+    /// An error occured with the network.
+    Io(io::Error),
+    /// An HTTP error occured with the webserver.
+    Http(TransportError),
+    /// A target URL could not be used.
+    InvalidUrl(String),
+    /// An API name could not be used.
+    InvalidApi(String),
+    /// A job setting could not be used.
+    InvalidSetting(String),
+    /// A header to forward to the target site could not be used.
+    InvalidHeader(String),
+    /// The API version of the client does not offer the requested endpoint.
+    Unsupported(String),
+    /// A webhook request did not carry the expected secret.
+    WebhookRejected(String),
+    /// A job did not finish before the deadline.
+    JobTimeout(String),
+    /// A job could use more calls than are left in the plan this month.
+    QuotaExceeded(String),
+    /// The API failed repeatedly, and calls are suspended for a while; see
+    /// `CircuitBreaker`.
+    CircuitOpen(String),
+    /// A response body was longer than the limit set with
+    /// `Diffbot::with_max_response_size`.
+    ResponseTooLarge(String),
+    /// A file downloaded as an image had another content type; see
+    /// `images::download`.
+    NotAnImage(String),
+}
+
+impl Error {
+    /// Returns how long the API asked us to wait before trying again.
+    ///
+    /// Only set on throttling errors that came with a `Retry-After` header.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match *self {
+            Error::Api(ref err) => err.retry_after(),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the same call may succeed when tried again later.
+    ///
+    /// This covers throttling, server-side errors and network failures.
+    pub fn is_retryable(&self) -> bool {
+        match *self {
+            Error::Api(_) => self.is_throttled() || self.code() >= 500,
+            Error::Http(_) | Error::Io(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the call was rejected because of rate limiting.
+    pub fn is_throttled(&self) -> bool {
+        self.code() == 429
+    }
+
+    /// Returns `true` if the token is missing, invalid or not allowed to
+    /// make this call.
+    pub fn is_auth(&self) -> bool {
+        let code = self.code();
+        code == 401 || code == 403
+    }
+
+    /// Returns `true` if the requested resource (page, job, ...) does not
+    /// exist.
+    pub fn is_not_found(&self) -> bool {
+        self.code() == 404
+    }
+
+    // Returns the API error code, falling back to the HTTP status.
+    fn code(&self) -> u32 {
+        match *self {
+            Error::Api(ref err) if err.code != 0 => err.code,
+            _ => self.details().map_or(0, |details| details.status as u32),
+        }
+    }
+
+    /// Returns the call that produced this error, if known.
+    ///
+    /// Available for errors returned by the API and invalid responses.
+    pub fn context(&self) -> Option<&CallContext> {
+        match *self {
+            Error::Api(ref err) => err.context.as_ref(),
+            Error::Json(ref err) => err.context.as_ref(),
+            Error::Http(ref err) => err.context.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn with_context(self, context: CallContext) -> Self {
+        match self {
+            Error::Api(mut err) => {
+                err.context = Some(context);
+                Error::Api(err)
+            }
+            Error::Json(mut err) => {
+                err.context = Some(context);
+                Error::Json(err)
+            }
+            Error::Http(mut err) => {
+                err.context = Some(context);
+                Error::Http(err)
+            }
+            err => err,
+        }
+    }
+
+    /// Returns the HTTP response that caused this error, if any.
+    ///
+    /// Available for API errors and invalid JSON responses.
+    pub fn details(&self) -> Option<&ErrorDetails> {
+        match *self {
+            Error::Api(ref err) => err.details(),
+            Error::Json(ref err) => err.details(),
+            _ => None,
+        }
+    }
+}
+
+/// Describes the call that produced an error.
+///
+/// Useful to tell which page failed when running many calls.
+#[derive(Clone, Debug)]
+pub struct CallContext {
+    api: Option<API>,
+    endpoint: String,
+    target_url: Option<Url>,
+    request_id: Option<String>,
+}
+
+impl CallContext {
+    pub(crate) fn new(api: Option<API>, endpoint: &Url,
+                      target_url: Option<Url>)
+                      -> Self {
+        CallContext {
+            api: api,
+            endpoint: redact::redact_url(endpoint),
+            target_url: target_url,
+            request_id: None,
+        }
+    }
+
+    pub(crate) fn with_request_id(mut self, request_id: String) -> Self {
+        self.request_id = Some(request_id);
+        self
+    }
+
+    /// Returns the API that was called, for extraction calls.
+    pub fn api(&self) -> Option<&API> {
+        self.api.as_ref()
+    }
+
+    /// Returns the Diffbot URL that was called, with the token masked.
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Returns the URL of the page being processed, for extraction calls.
+    pub fn target_url(&self) -> Option<&Url> {
+        self.target_url.as_ref()
+    }
+
+    /// Returns the identifier of the call, sent in the `X-Request-Id`
+    /// header.
+    ///
+    /// See `Request::request_id`.
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_ref().map(String::as_str)
+    }
+}
+
+impl fmt::Display for CallContext {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!(match (&self.api, &self.target_url) {
+            (&Some(ref api), &Some(ref target_url)) => {
+                write!(fmt, "{} call for {}", api, target_url)
+            }
+            _ => write!(fmt, "call to {}", self.endpoint),
+        });
+        match self.request_id {
+            Some(ref request_id) => write!(fmt, ", request {}", request_id),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The category of a `TransportError`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TransportErrorKind {
+    /// The connection to the API could not be established or was lost.
+    Connection,
+    /// The API redirected too many times, or in a loop.
+    Redirect,
+    /// Any other failure of the HTTP client.
+    Other,
+}
+
+/// A failure to exchange HTTP messages with the API.
+///
+/// The underlying HTTP library is not exposed; a lower-level error may be
+/// available as a `std::error::Error` through `source`. The errors of the
+/// HTTP libraries themselves are not kept as the source: their messages may
+/// contain the request URL, and so the token.
+pub struct TransportError {
+    kind: TransportErrorKind,
+    message: String,
+    source: Option<Box<dyn error::Error + Send + Sync>>,
+    context: Option<CallContext>,
+}
+
+impl TransportError {
+    /// Returns a new error of the given kind.
+    pub fn new<S: Into<String>>(kind: TransportErrorKind, message: S) -> Self {
+        TransportError {
+            kind: kind,
+            message: redact::redact_text(&message.into()),
+            source: None,
+            context: None,
+        }
+    }
+
+    /// Sets the lower-level error that caused this one.
+    pub fn with_source<E>(mut self, source: E) -> Self
+        where E: Into<Box<dyn error::Error + Send + Sync>>
+    {
+        self.source = Some(source.into());
+        self
+    }
+
+    pub(crate) fn from_reqwest(err: reqwest::Error) -> Self {
+        let kind = if err.is_redirect() {
+            TransportErrorKind::Redirect
+        } else if err.is_http() {
+            TransportErrorKind::Connection
+        } else {
+            TransportErrorKind::Other
+        };
+        TransportError::new(kind, err.to_string())
+    }
+
+    /// Returns the category of this error.
+    pub fn kind(&self) -> TransportErrorKind {
+        self.kind
+    }
+
+    /// Returns a description of the error, with the API token masked.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the call that failed.
+    pub fn context(&self) -> Option<&CallContext> {
+        self.context.as_ref()
+    }
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!(fmt.write_str(&self.message));
+        match self.context {
+            Some(ref context) => write!(fmt, " ({})", context),
+            None => Ok(()),
+        }
+    }
+}
+
+impl error::Error for TransportError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self.source {
+            Some(ref err) => Some(&**err),
+            None => None,
+        }
+    }
+}
+
+impl fmt::Debug for TransportError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("TransportError")
+           .field("kind", &self.kind)
+           .field("message", &self.message)
+           .field("context", &self.context)
+           .finish()
+    }
+}
+
+/// The HTTP response behind a failed call.
+#[derive(Clone)]
+pub struct ErrorDetails {
+    status: u16,
+    body: Vec<u8>,
+}
+
+impl ErrorDetails {
+    pub(crate) fn new(status: u16, body: Vec<u8>) -> Self {
+        ErrorDetails {
+            status: status,
+            body: body,
+        }
+    }
+
+    /// Returns the HTTP status code of the response.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the raw body of the response.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns the body of the response as text.
+    ///
+    /// Invalid UTF-8 sequences are replaced.
+    pub fn body_text<'a>(&'a self) -> Cow<'a, str> {
+        String::from_utf8_lossy(&self.body)
+    }
+}
+
+impl fmt::Debug for ErrorDetails {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("ErrorDetails")
+           .field("status", &self.status)
+           .field("body", &self.body_text())
+           .finish()
+    }
+}
+
+// Maximum number of characters of the body kept in `JsonError::raw_text`.
+const RAW_TEXT_LIMIT: usize = 200;
+
+/// An error decoding the JSON returned by the API.
+///
+/// Diffbot sometimes answers with a plain text or HTML error page; the start
+/// of that page is kept in `raw_text` and shown in the error message.
+#[derive(Debug)]
+pub struct JsonError {
+    err: serde_json::error::Error,
+    details: Option<ErrorDetails>,
+    raw_text: Option<String>,
+    context: Option<CallContext>,
+}
+
+impl JsonError {
+    pub(crate) fn new(err: serde_json::error::Error) -> Self {
+        JsonError {
+            err: err,
+            details: None,
+            raw_text: None,
+            context: None,
+        }
+    }
+
+    pub(crate) fn with_details(mut self, details: ErrorDetails) -> Self {
+        self.raw_text = raw_text(&details.body_text());
+        self.details = Some(details);
+        self
+    }
+
+    /// Returns the response that could not be decoded, if any.
+    pub fn details(&self) -> Option<&ErrorDetails> {
+        self.details.as_ref()
+    }
+
+    /// Returns the start of the body that could not be decoded, if any.
+    ///
+    /// The text is trimmed and truncated to 200 characters; the full body is
+    /// available from `details`.
+    pub fn raw_text(&self) -> Option<&str> {
+        self.raw_text.as_ref().map(|text| text.as_str())
+    }
+}
+
+// Returns the trimmed and truncated text of a body, or `None` if it is
+// blank.
+fn raw_text(body: &str) -> Option<String> {
+    let body = body.trim();
+    if body.is_empty() {
+        return None;
+    }
+    let mut text: String = body.chars().take(RAW_TEXT_LIMIT).collect();
+    if text.len() < body.len() {
+        text.push('…');
+    }
+    Some(redact::redact_text(&text))
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!(match self.details {
+            Some(ref details) => {
+                write!(fmt, "invalid JSON in HTTP {} response", details.status)
+            }
+            None => write!(fmt, "invalid JSON"),
+        });
+        if let Some(ref context) = self.context {
+            try!(write!(fmt, " ({})", context));
+        }
+        match self.raw_text {
+            Some(ref text) => write!(fmt, ": {:?}", text),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The category of an `ApiError`.
+///
+/// Server-side (`5xx`) errors are told apart using the error message, as
+/// Diffbot reports most failures to process a page with the same code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ApiErrorKind {
+    /// Too many calls were made; see `ApiError::retry_after`.
+    Throttled,
+    /// The token is missing, invalid, or not allowed to make this call.
+    Unauthorized,
+    /// The requested resource does not exist.
+    NotFound,
+    /// The call itself was invalid (bad parameter, unknown API, ...).
+    InvalidRequest,
+    /// Diffbot timed out while fetching or processing the target page.
+    TargetTimeout,
+    /// Diffbot could not download the target page.
+    TargetUnreachable,
+    /// Diffbot fetched the page but failed to process it.
+    Processing,
+    /// Any other error.
+    Other,
+}
+
+/// An error reported by the Diffbot API.
+#[derive(Clone, Debug)]
+pub struct ApiError {
+    code: u32,
+    message: String,
+    retry_after: Option<Duration>,
+    details: Option<ErrorDetails>,
+    context: Option<CallContext>,
+}
+
+impl ApiError {
+    pub(crate) fn new<S: Into<String>>(code: u32, message: S) -> Self {
+        ApiError {
+            code: code,
+            message: message.into(),
+            retry_after: None,
+            details: None,
+            context: None,
+        }
+    }
+
+    pub(crate) fn with_retry_after(mut self, retry_after: Option<Duration>)
+                                   -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    pub(crate) fn with_details(mut self, details: ErrorDetails) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Returns the `errorCode` sent by the API, or `0` if none was given.
+    pub fn code(&self) -> u32 {
+        self.code
+    }
+
+    /// Returns the error message sent by the API.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns how long to wait before trying again, if the API said so.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+
+    /// Returns the HTTP response carrying the error.
+    pub fn details(&self) -> Option<&ErrorDetails> {
+        self.details.as_ref()
+    }
+
+    /// Returns the call that failed.
+    pub fn context(&self) -> Option<&CallContext> {
+        self.context.as_ref()
+    }
+
+    /// Returns `true` if the call was rejected because of rate limiting.
+    pub fn is_throttled(&self) -> bool {
+        self.code == 429
+    }
+
+    /// Returns the category of this error.
+    pub fn kind(&self) -> ApiErrorKind {
+        match self.code {
+            429 => ApiErrorKind::Throttled,
+            401 | 403 => ApiErrorKind::Unauthorized,
+            404 => ApiErrorKind::NotFound,
+            code if code >= 400 && code < 500 => ApiErrorKind::InvalidRequest,
+            code if code >= 500 && code < 600 => {
+                server_error_kind(&self.message)
+            }
+            _ => ApiErrorKind::Other,
+        }
+    }
+}
+
+// Classifies a 5xx error from its message.
+fn server_error_kind(message: &str) -> ApiErrorKind {
+    let message = message.to_lowercase();
+    let mentions = |words: &[&str]| words.iter().any(|w| message.contains(w));
+
+    if mentions(&["timed out", "timeout"]) {
+        ApiErrorKind::TargetTimeout
+    } else if mentions(&["could not download",
+                         "unable to download",
+                         "could not fetch",
+                         "unable to fetch",
+                         "could not resolve",
+                         "unknownhost",
+                         "connection refused",
+                         "unreachable"]) {
+        ApiErrorKind::TargetUnreachable
+    } else {
+        ApiErrorKind::Processing
+    }
+}
+
+impl error::Error for JsonError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.err)
+    }
+}
+
+impl error::Error for ApiError {}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(fmt, "API error {}: {}", self.code, self.message));
+        match self.context {
+            Some(ref context) => write!(fmt, " ({})", context),
+            None => Ok(()),
+        }
+    }
+}
+
+impl From<serde_json::error::Error> for Error {
+    fn from(err: serde_json::error::Error) -> Self {
+        Error::Json(JsonError::new(err))
+    }
+}
+
+impl From<TransportError> for Error {
+    fn from(err: TransportError) -> Self {
+        Error::Http(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Api(ref err) => err.source(),
+            Error::Json(ref err) => err.source(),
+            Error::Io(ref err) => Some(err),
+            Error::Http(ref err) => err.source(),
+            Error::InvalidUrl(_) => None,
+            Error::InvalidApi(_) => None,
+            Error::InvalidSetting(_) => None,
+            Error::InvalidHeader(_) => None,
+            Error::Unsupported(_) => None,
+            Error::WebhookRejected(_) => None,
+            Error::JobTimeout(_) => None,
+            Error::QuotaExceeded(_) => None,
+            Error::CircuitOpen(_) => None,
+            Error::ResponseTooLarge(_) => None,
+            Error::NotAnImage(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Api(ref err) => fmt::Display::fmt(err, fmt),
+            Error::Json(ref err) => fmt::Display::fmt(err, fmt),
+            Error::Io(_) => fmt.write_str("I/O error"),
+            Error::Http(ref err) => fmt::Display::fmt(err, fmt),
+            Error::InvalidUrl(ref msg) => {
+                write!(fmt, "invalid target URL: {}", msg)
+            }
+            Error::InvalidApi(ref msg) => fmt.write_str(msg),
+            Error::InvalidSetting(ref msg) => {
+                write!(fmt, "invalid setting: {}", msg)
+            }
+            Error::InvalidHeader(ref msg) => {
+                write!(fmt, "invalid header: {}", msg)
+            }
+            Error::Unsupported(ref msg) => {
+                write!(fmt, "unsupported call: {}", msg)
+            }
+            Error::WebhookRejected(ref msg) => {
+                write!(fmt, "webhook request rejected: {}", msg)
+            }
+            Error::JobTimeout(ref msg) => {
+                write!(fmt, "job did not finish in time: {}", msg)
+            }
+            Error::QuotaExceeded(ref msg) => {
+                write!(fmt, "quota exceeded: {}", msg)
+            }
+            Error::CircuitOpen(ref msg) => {
+                write!(fmt, "calls suspended: {}", msg)
+            }
+            Error::ResponseTooLarge(ref msg) => {
+                write!(fmt, "response too large: {}", msg)
+            }
+            Error::NotAnImage(ref msg) => write!(fmt, "not an image: {}", msg),
+        }
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Api(ref err) => fmt.debug_tuple("Api").field(err).finish(),
+            Error::Json(ref err) => {
+                fmt.debug_tuple("Json").field(err).finish()
+            }
+            Error::Io(ref err) => fmt.debug_tuple("Io").field(err).finish(),
+            Error::Http(ref err) => {
+                fmt.debug_tuple("Http").field(err).finish()
+            }
+            Error::InvalidUrl(ref msg) => {
+                fmt.debug_tuple("InvalidUrl").field(msg).finish()
+            }
+            Error::InvalidApi(ref msg) => {
+                fmt.debug_tuple("InvalidApi").field(msg).finish()
+            }
+            Error::InvalidSetting(ref msg) => {
+                fmt.debug_tuple("InvalidSetting").field(msg).finish()
+            }
+            Error::InvalidHeader(ref msg) => {
+                fmt.debug_tuple("InvalidHeader").field(msg).finish()
+            }
+            Error::Unsupported(ref msg) => {
+                fmt.debug_tuple("Unsupported").field(msg).finish()
+            }
+            Error::WebhookRejected(ref msg) => {
+                fmt.debug_tuple("WebhookRejected").field(msg).finish()
+            }
+            Error::JobTimeout(ref msg) => {
+                fmt.debug_tuple("JobTimeout").field(msg).finish()
+            }
+            Error::QuotaExceeded(ref msg) => {
+                fmt.debug_tuple("QuotaExceeded").field(msg).finish()
+            }
+            Error::CircuitOpen(ref msg) => {
+                fmt.debug_tuple("CircuitOpen").field(msg).finish()
+            }
+            Error::ResponseTooLarge(ref msg) => {
+                fmt.debug_tuple("ResponseTooLarge").field(msg).finish()
+            }
+            Error::NotAnImage(ref msg) => {
+                fmt.debug_tuple("NotAnImage").field(msg).finish()
+            }
+        }
+    }
+}
+
+#[test]
+fn test_error_classification() {
+    let throttled = Error::Api(ApiError::new(429, "Too many requests"));
+    assert!(throttled.is_throttled());
+    assert!(throttled.is_retryable());
+
+    let auth = Error::Api(ApiError::new(401, "Not authorized API token."));
+    assert!(auth.is_auth());
+    assert!(!auth.is_retryable());
+
+    let server = Error::Api(ApiError::new(500, "Internal error"));
+    assert!(server.is_retryable());
+    assert!(!server.is_not_found());
+
+    // Without an errorCode, the HTTP status is used
+    let not_found = Error::Api(ApiError::new(0, "Invalid response")
+                                   .with_details(ErrorDetails::new(404,
+                                                                   vec![])));
+    assert!(not_found.is_not_found());
+
+    assert!(!Error::InvalidUrl("nope".to_string()).is_retryable());
+}
+
+#[test]
+fn test_error_context() {
+    let endpoint = Url::parse("https://api.diffbot.com/v3/article\
+                               ?token=0123456789abcdef")
+                       .unwrap();
+    let target = Url::parse("http://example.com/page").unwrap();
+    let err = Error::Api(ApiError::new(500, "Could not download page"))
+                  .with_context(CallContext::new(Some(API::Article),
+                                                 &endpoint,
+                                                 Some(target)));
+
+    assert_eq!(err.to_string(),
+               "API error 500: Could not download page \
+                (article call for http://example.com/page)");
+    let context = err.context().unwrap();
+    assert_eq!(context.endpoint(),
+               "https://api.diffbot.com/v3/article?token=012…cdef");
+    assert_eq!(context.request_id(), None);
+
+    let err = Error::Api(ApiError::new(500, "Could not download page"))
+                  .with_context(CallContext::new(None, &endpoint, None)
+                                    .with_request_id("42".to_string()));
+    assert_eq!(err.to_string(),
+               "API error 500: Could not download page \
+                (call to https://api.diffbot.com/v3/article?token=012…cdef, \
+                request 42)");
+}
+
+#[test]
+fn test_transport_error_redacts_token() {
+    let err = TransportError::new(TransportErrorKind::Connection,
+                                  "connection refused: \
+                                   https://api.diffbot.com/v3/article\
+                                   ?token=0123456789abcdef&url=x");
+    let err = Error::from(err);
+    assert!(!err.to_string().contains("0123456789abcdef"));
+    assert!(!format!("{:?}", err).contains("0123456789abcdef"));
+}
+
+#[test]
+fn test_error_source_chain() {
+    use std::error::Error as StdError;
+
+    let err = ::serde_json::from_str::<::serde_json::Value>("<html>")
+                  .unwrap_err();
+    let err = Error::from(err);
+    assert_eq!(err.to_string(), "invalid JSON");
+    assert!(err.source().is_some());
+
+    let err = Error::from(io::Error::new(io::ErrorKind::Other, "reset"));
+    assert_eq!(err.source().unwrap().to_string(), "reset");
+}
+
+#[test]
+fn test_api_error_kind() {
+    let kind = |code, message| ApiError::new(code, message).kind();
+    assert_eq!(kind(500, "Request timed out"), ApiErrorKind::TargetTimeout);
+    assert_eq!(kind(500, "Could not download page (404)"),
+               ApiErrorKind::TargetUnreachable);
+    assert_eq!(kind(500, "Error processing the page"),
+               ApiErrorKind::Processing);
+    assert_eq!(kind(401, "Not authorized API token."),
+               ApiErrorKind::Unauthorized);
+    assert_eq!(kind(429, "Too many requests"), ApiErrorKind::Throttled);
+    assert_eq!(kind(0, "Invalid response"), ApiErrorKind::Other);
+}
+
+#[test]
+fn test_json_error_raw_text() {
+    let body = b"<html>Bad Gateway</html>".to_vec();
+    let err = ::serde_json::from_slice::<::serde_json::Value>(&body)
+                  .unwrap_err();
+    let err = JsonError::new(err).with_details(ErrorDetails::new(502, body));
+    assert_eq!(err.raw_text(), Some("<html>Bad Gateway</html>"));
+    assert_eq!(err.to_string(),
+               "invalid JSON in HTTP 502 response: \
+                \"<html>Bad Gateway</html>\"");
+
+    let long = "x".repeat(500);
+    assert_eq!(raw_text(&long).unwrap().chars().count(), RAW_TEXT_LIMIT + 1);
+}
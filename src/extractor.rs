@@ -0,0 +1,203 @@
+//! Extraction of many pages with a pool of workers.
+
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::Duration;
+
+use {API, Diffbot, DiffbotResult, RetryPolicy};
+
+/// Runs an API on a list of pages, a few calls at a time.
+///
+/// URLs are read from the input as the workers need them, so the input can
+/// be a long iterator, such as the lines of a file. Each result is handed to
+/// the sink on the calling thread, in the order the calls complete; a failed
+/// call does not stop the others.
+///
+/// Failed calls are retried with the retry policy of the client, or the one
+/// set with `retries`.
+///
+/// # Example
+///
+/// ```no_run
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # use std::time::Duration;
+/// # fn main() {
+/// let diffbot = Diffbot::v3("insert_your_token_here");
+/// let urls = vec!["http://www.diffbot.com", "http://www.diffbot.com/about"];
+/// let report = Extractor::new(&diffbot, API::Article)
+///                  .concurrency(8)
+///                  .retries(RetryPolicy::new(3))
+///                  .rate_limit(5, Duration::from_secs(1))
+///                  .run(urls, |url, result| match result {
+///                      Ok(article) => println!("{}: {:?}", url, article),
+///                      Err(err) => println!("{}: {}", url, err),
+///                  });
+/// println!("{} pages extracted", report.succeeded);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Extractor {
+    client: Diffbot,
+    api: API,
+    options: Vec<(String, String)>,
+    concurrency: usize,
+}
+
+/// The outcome of `Extractor::run`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExtractionReport {
+    /// Number of pages extracted.
+    pub succeeded: usize,
+    /// Number of calls which failed, after retries.
+    pub failed: usize,
+}
+
+impl Extractor {
+    /// Returns an extractor calling `api` with `client`, 4 calls at a time.
+    pub fn new(client: &Diffbot, api: API) -> Self {
+        Extractor {
+            client: client.clone(),
+            api: api,
+            options: Vec::new(),
+            concurrency: 4,
+        }
+    }
+
+    /// Sets how many calls can run at the same time.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Retries failed calls according to `policy`.
+    pub fn retries(mut self, policy: RetryPolicy) -> Self {
+        self.client = self.client.with_retries(policy);
+        self
+    }
+
+    /// Starts at most `calls` calls per `period`, across all workers.
+    ///
+    /// See `Diffbot::with_rate_limit`.
+    pub fn rate_limit(mut self, calls: u32, period: Duration) -> Self {
+        self.client = self.client.with_rate_limit(calls, period);
+        self
+    }
+
+    /// Sets options sent with every call, as with
+    /// `Diffbot::call_with_options`.
+    pub fn options<K, V>(mut self, options: &[(K, V)]) -> Self
+        where K: ToString,
+              V: ToString
+    {
+        self.options = options.iter()
+                              .map(|&(ref key, ref value)| {
+                                  (key.to_string(), value.to_string())
+                              })
+                              .collect();
+        self
+    }
+
+    /// Extracts every page of `urls`, and passes each URL with its result
+    /// to `sink`.
+    ///
+    /// Returns once every call has completed.
+    pub fn run<I, F>(&self, urls: I, mut sink: F) -> ExtractionReport
+        where I: IntoIterator,
+              I::Item: ToString,
+              F: FnMut(String, DiffbotResult)
+    {
+        let (url_sender, url_receiver) = mpsc::sync_channel(self.concurrency);
+        let (result_sender, result_receiver) = mpsc::channel();
+        let url_receiver = Arc::new(Mutex::new(url_receiver));
+        let mut workers = Vec::new();
+        for _ in 0..self.concurrency {
+            let extractor = self.clone();
+            let urls = url_receiver.clone();
+            let results = result_sender.clone();
+            workers.push(thread::spawn(move || {
+                extractor.extract_next(&urls, &results)
+            }));
+        }
+        drop(result_sender);
+
+        let mut report = ExtractionReport::default();
+        for url in urls {
+            while let Ok((url, result)) = result_receiver.try_recv() {
+                report.record(&mut sink, url, result);
+            }
+            if url_sender.send(url.to_string()).is_err() {
+                break;
+            }
+        }
+        drop(url_sender);
+        for (url, result) in result_receiver {
+            report.record(&mut sink, url, result);
+        }
+        for worker in workers {
+            let _ = worker.join();
+        }
+        report
+    }
+
+    // Extracts the URLs received by this worker, until the input is done.
+    fn extract_next(&self, urls: &Mutex<mpsc::Receiver<String>>,
+                    results: &mpsc::Sender<(String, DiffbotResult)>) {
+        loop {
+            let url = match urls.lock().unwrap().recv() {
+                Ok(url) => url,
+                Err(_) => return,
+            };
+            let result = self.client
+                             .call_with_options(self.api.clone(),
+                                                &*url,
+                                                &self.options);
+            if results.send((url, result)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+impl ExtractionReport {
+    fn record<F>(&mut self, sink: &mut F, url: String, result: DiffbotResult)
+        where F: FnMut(String, DiffbotResult)
+    {
+        if result.is_ok() {
+            self.succeeded += 1;
+        } else {
+            self.failed += 1;
+        }
+        sink(url, result);
+    }
+}
+
+#[test]
+fn test_extractor() {
+    use stub::{StubResponse, StubServer};
+
+    let server = StubServer::start();
+    for _ in 0..5 {
+        server.push(StubResponse::json(r#"{"objects": []}"#));
+    }
+    let diffbot = server.client("insert_your_token_here");
+    let urls = (0..5).map(|i| format!("http://diffbot.com/{}", i))
+                     .chain(Some("not a url".to_string()));
+    let mut seen = Vec::new();
+    let report = Extractor::new(&diffbot, API::Article)
+                     .concurrency(3)
+                     .options(&[("fields", "title")])
+                     .run(urls, |url, _| seen.push(url));
+
+    assert_eq!(report,
+               ExtractionReport {
+                   succeeded: 5,
+                   failed: 1,
+               });
+    seen.sort();
+    assert_eq!(seen.len(), 6);
+    assert_eq!(seen[0], "http://diffbot.com/0");
+    let requests = server.requests();
+    assert_eq!(requests.len(), 5);
+    assert!(requests.iter().all(|r| r.query("fields") == Some("title")));
+}
@@ -0,0 +1,114 @@
+//! Selection of optional fields returned by the API.
+
+use std::fmt;
+
+/// A list of optional fields to return, possibly with nested subfields.
+///
+/// Renders to the syntax expected by the `fields` parameter, such as
+/// `links,meta,images(url,naturalHeight)`.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # fn main() {
+/// let fields = Fields::new()
+///                  .field("links")
+///                  .nested("images", Fields::new().field("url")
+///                                                 .field("naturalHeight"));
+/// assert_eq!(fields.to_string(), "links,images(url,naturalHeight)");
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Fields {
+    entries: Vec<Field>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Field {
+    All,
+    Name(String),
+    Nested(String, Fields),
+}
+
+impl Fields {
+    /// Returns an empty selection.
+    pub fn new() -> Self {
+        Fields::default()
+    }
+
+    /// Returns a selection of every available field (`*`).
+    pub fn all() -> Self {
+        Fields { entries: vec![Field::All] }
+    }
+
+    /// Adds a field to the selection.
+    pub fn field<S: Into<String>>(mut self, name: S) -> Self {
+        self.entries.push(Field::Name(name.into()));
+        self
+    }
+
+    /// Adds a field with a selection of its own subfields.
+    ///
+    /// Use `Fields::all()` as `subfields` to get every subfield.
+    pub fn nested<S: Into<String>>(mut self, name: S, subfields: Fields)
+                                   -> Self {
+        self.entries.push(Field::Nested(name.into(), subfields));
+        self
+    }
+
+    /// Adds every field from `other` to this selection.
+    pub fn extend(mut self, other: Fields) -> Self {
+        self.entries.extend(other.entries);
+        self
+    }
+
+    /// Returns `true` if no field was selected.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<'a, S: AsRef<str>> From<&'a [S]> for Fields {
+    fn from(names: &'a [S]) -> Self {
+        names.iter().fold(Fields::new(), |fields, name| {
+            fields.field(name.as_ref())
+        })
+    }
+}
+
+impl fmt::Display for Fields {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i > 0 {
+                try!(fmt.write_str(","));
+            }
+            try!(match *entry {
+                Field::All => fmt.write_str("*"),
+                Field::Name(ref name) => fmt.write_str(name),
+                Field::Nested(ref name, ref subfields) => {
+                    write!(fmt, "{}({})", name, subfields)
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_nested_fields() {
+    let fields = Fields::new()
+                     .field("links")
+                     .nested("images", Fields::all())
+                     .nested("videos",
+                             Fields::new().field("url").field("duration"));
+    assert_eq!(fields.to_string(),
+               "links,images(*),videos(url,duration)");
+}
+
+#[test]
+fn test_fields_from_slice() {
+    let fields = Fields::from(&["links", "meta"][..]);
+    assert_eq!(fields.to_string(), "links,meta");
+}
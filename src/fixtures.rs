@@ -0,0 +1,189 @@
+//! Recording and replaying of API responses.
+//!
+//! In record mode, every response received is written to a fixture file in a
+//! directory. In replay mode, responses are read from those files instead of
+//! the network, and a request without a fixture fails. Fixtures are keyed by
+//! method, URL, headers and body, with the token removed, so they can be
+//! recorded with a real token and replayed with any other. Credentials sent
+//! in headers are masked, and never written to the files.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde_json::{self, Map, Value};
+
+use Error;
+use http::{HttpRequest, HttpResponse};
+
+/// Whether fixtures are written or read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FixtureMode {
+    /// Send requests to the API and save the responses.
+    Record,
+    /// Serve responses from saved fixtures, without network access.
+    Replay,
+}
+
+/// A directory of recorded responses.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # fn main() {
+/// let diffbot = Diffbot::v3("token")
+///                   .with_fixtures(Fixtures::replay("tests/fixtures"));
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Fixtures {
+    dir: PathBuf,
+    mode: FixtureMode,
+}
+
+impl Fixtures {
+    /// Records responses in `dir`, which is created if needed.
+    pub fn record<P: Into<PathBuf>>(dir: P) -> Self {
+        Fixtures {
+            dir: dir.into(),
+            mode: FixtureMode::Record,
+        }
+    }
+
+    /// Replays responses recorded in `dir`.
+    pub fn replay<P: Into<PathBuf>>(dir: P) -> Self {
+        Fixtures {
+            dir: dir.into(),
+            mode: FixtureMode::Replay,
+        }
+    }
+
+    /// Picks the mode from the `DIFFBOT_FIXTURES` environment variable.
+    ///
+    /// Returns `None` unless it is set to `record` or `replay`.
+    pub fn from_env<P: Into<PathBuf>>(dir: P) -> Option<Self> {
+        match ::std::env::var("DIFFBOT_FIXTURES") {
+            Ok(ref mode) if mode == "record" => Some(Fixtures::record(dir)),
+            Ok(ref mode) if mode == "replay" => Some(Fixtures::replay(dir)),
+            _ => None,
+        }
+    }
+
+    /// Returns the fixture directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the mode.
+    pub fn mode(&self) -> FixtureMode {
+        self.mode
+    }
+
+    // Reads the recorded response for a request.
+    pub(crate) fn load(&self, request: &HttpRequest)
+                       -> Result<HttpResponse, Error> {
+        let key = request.key();
+        let path = self.path(&key);
+        let mut text = String::new();
+        match File::open(&path) {
+            Ok(mut file) => try!(file.read_to_string(&mut text)),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(missing_fixture(&key));
+            }
+            Err(err) => return Err(Error::Io(err)),
+        };
+
+        let fixture: Map<String, Value> = try!(serde_json::from_str(&text));
+        if fixture.get("request").and_then(Value::as_str) != Some(&key) {
+            return Err(missing_fixture(&key));
+        }
+        let status = fixture.get("status").and_then(Value::as_u64);
+        let body = fixture.get("body").and_then(Value::as_str);
+        match (status, body) {
+            (Some(status), Some(body)) => {
+                Ok(HttpResponse {
+                    status: status as u16,
+                    retry_after: fixture.get("retryAfter")
+                                        .and_then(Value::as_u64)
+                                        .map(Duration::from_secs),
+                    headers: Vec::new(),
+                    body: body.as_bytes().to_vec(),
+                    elapsed: None,
+                })
+            }
+            _ => {
+                let message = format!("invalid fixture {}", path.display());
+                Err(Error::Io(io::Error::new(io::ErrorKind::InvalidData,
+                                             message)))
+            }
+        }
+    }
+
+    // Writes the response to a request.
+    pub(crate) fn save(&self, request: &HttpRequest,
+                       response: &HttpResponse)
+                       -> Result<(), Error> {
+        let key = request.key();
+        let mut fixture = Map::new();
+        fixture.insert("request".to_string(), Value::String(key.clone()));
+        fixture.insert("status".to_string(), Value::from(response.status));
+        if let Some(retry_after) = response.retry_after {
+            fixture.insert("retryAfter".to_string(),
+                           Value::from(retry_after.as_secs()));
+        }
+        let body = String::from_utf8_lossy(&response.body).into_owned();
+        fixture.insert("body".to_string(), Value::String(body));
+
+        try!(fs::create_dir_all(&self.dir));
+        let mut file = try!(File::create(self.path(&key)));
+        try!(serde_json::to_writer_pretty(&mut file, &fixture));
+        try!(file.write_all(b"\n"));
+        Ok(())
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", fnv1a(key.as_bytes())))
+    }
+}
+
+fn missing_fixture(key: &str) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::NotFound,
+                             format!("no fixture for {}", key)))
+}
+
+// 64-bit FNV-1a, used for file names as it is stable across releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[test]
+fn test_fixture_round_trip() {
+    use url::Url;
+
+    let dir = ::std::env::temp_dir()
+                  .join(format!("diffbot-fixtures-{}", ::std::process::id()));
+    let request = HttpRequest::get(Url::parse("https://api.diffbot.com/v3/\
+                                               article?token=x")
+                                       .unwrap());
+    let response = HttpResponse {
+        status: 200,
+        retry_after: None,
+        headers: Vec::new(),
+        body: br#"{"objects":[]}"#.to_vec(),
+        elapsed: None,
+    };
+
+    let replay = Fixtures::replay(&dir);
+    assert!(replay.load(&request).is_err());
+    Fixtures::record(&dir).save(&request, &response).unwrap();
+    let loaded = replay.load(&request).unwrap();
+    assert_eq!(loaded.status, 200);
+    assert_eq!(loaded.body, response.body);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,249 @@
+//! Description of the HTTP requests sent to the API.
+
+#[cfg(feature = "gzip")]
+use std::io::Write;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "gzip")]
+use flate2::Compression;
+#[cfg(feature = "gzip")]
+use flate2::write::GzEncoder;
+use reqwest::{Method, Url};
+use reqwest::header::Encoding;
+use reqwest::mime::{Mime, SubLevel, TopLevel};
+use url::form_urlencoded;
+
+use {API, CachePolicy, CallContext, USER_AGENT};
+use redact;
+#[cfg(feature = "gzip")]
+use Error;
+
+// A request to the Diffbot API.
+//
+// Kept separate from the underlying HTTP client so it can be sent again when
+// retrying.
+#[derive(Clone)]
+pub(crate) struct HttpRequest {
+    pub method: Method,
+    pub url: Url,
+    pub content_type: Option<Mime>,
+    pub body: Option<Vec<u8>>,
+    pub content_encoding: Option<Encoding>,
+    pub headers: Vec<(String, String)>,
+    pub request_id: String,
+    pub api: Option<API>,
+    pub target_url: Option<Url>,
+    pub cache: CachePolicy,
+}
+
+impl HttpRequest {
+    pub fn get(url: Url) -> Self {
+        HttpRequest {
+            method: Method::Get,
+            url: url,
+            content_type: None,
+            body: None,
+            content_encoding: None,
+            headers: Vec::new(),
+            request_id: new_request_id(),
+            api: None,
+            target_url: None,
+            cache: CachePolicy::Bypass,
+        }
+    }
+
+    pub fn post(url: Url, content_type: Mime, body: Vec<u8>) -> Self {
+        HttpRequest {
+            method: Method::Post,
+            url: url,
+            content_type: Some(content_type),
+            body: Some(body),
+            content_encoding: None,
+            headers: Vec::new(),
+            request_id: new_request_id(),
+            api: None,
+            target_url: None,
+            cache: CachePolicy::Use,
+        }
+    }
+
+    // Compresses the body with gzip.
+    #[cfg(feature = "gzip")]
+    pub fn gzip(mut self) -> Result<Self, Error> {
+        if let Some(body) = self.body.take() {
+            let mut encoder = GzEncoder::new(Vec::new(),
+                                             Compression::default());
+            try!(encoder.write_all(&body));
+            self.body = Some(try!(encoder.finish()));
+            self.content_encoding = Some(Encoding::Gzip);
+        }
+        Ok(self)
+    }
+
+    // Records which page this request is about, for error reporting.
+    //
+    // Only these extraction calls use the cache by default: the other
+    // endpoints answer about the account, jobs or the Knowledge Graph,
+    // whose data changes between calls. Entity lookups opt in with
+    // `with_cache`.
+    pub fn with_target(mut self, api: API, target_url: Url) -> Self {
+        self.api = Some(api);
+        self.target_url = Some(target_url);
+        self.cache = CachePolicy::Use;
+        self
+    }
+
+    // Sets how the request uses the cache of the client.
+    pub fn with_cache(mut self, cache: CachePolicy) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    // Returns every header sent with the request, the fixed ones first.
+    pub fn all_headers(&self) -> Vec<(String, String)> {
+        let mut headers = vec![("User-Agent".to_string(),
+                                USER_AGENT.to_string()),
+                               ("X-Request-Id".to_string(),
+                                self.request_id.clone())];
+        if let Some(ref content_type) = self.content_type {
+            headers.push(("Content-Type".to_string(),
+                          content_type.to_string()));
+        }
+        if let Some(ref encoding) = self.content_encoding {
+            headers.push(("Content-Encoding".to_string(),
+                          encoding.to_string()));
+        }
+        headers.extend(self.headers.iter().cloned());
+        headers
+    }
+
+    pub fn context(&self) -> CallContext {
+        CallContext::new(self.api.clone(), &self.url, self.target_url.clone())
+            .with_request_id(self.request_id.clone())
+    }
+
+    // Returns `true` if the request carries credentials in a header, such
+    // as those of a site.
+    pub fn has_credentials(&self) -> bool {
+        self.headers
+            .iter()
+            .any(|&(ref name, _)| redact::is_secret_header(name))
+    }
+
+    // Describes the request without its token or other secret parameters,
+    // e.g. `GET https://...`.
+    // Credentials sent in headers are masked.
+    //
+    // Used to match requests with recorded or cached responses.
+    pub fn key(&self) -> String {
+        let mut url = self.url.clone();
+        let query = without_secrets(url.query().unwrap_or(""));
+        url.set_query(if query.is_empty() {
+            None
+        } else {
+            Some(&query)
+        });
+
+        let mut key = format!("{} {}", self.method, url);
+        for &(ref name, ref value) in &redact::redact_headers(&self.headers) {
+            key.push_str(&format!("\n{}: {}", name, value));
+        }
+        if let Some(ref body) = self.body {
+            key.push('\n');
+            if self.is_form() {
+                let body = String::from_utf8_lossy(body);
+                key.push_str(&without_secrets(&body));
+            } else {
+                key.push_str(&String::from_utf8_lossy(body));
+            }
+        }
+        key
+    }
+
+    // Returns the body as it can be shown, with the secrets of a form, such
+    // as the token of crawl and bulk calls, masked.
+    pub(crate) fn redacted_body(&self) -> Option<String> {
+        self.body.as_ref().map(|body| {
+            let body = String::from_utf8_lossy(body);
+            if self.is_form() {
+                redact::redact_text(&body)
+            } else {
+                body.into_owned()
+            }
+        })
+    }
+
+    // Returns `true` if the body is a URL-encoded form.
+    fn is_form(&self) -> bool {
+        let form = Mime(TopLevel::Application,
+                        SubLevel::WwwFormUrlEncoded,
+                        vec![]);
+        self.content_type.as_ref() == Some(&form)
+    }
+}
+
+// Returns a new identifier for a call, unique enough to find it in logs.
+pub(crate) fn new_request_id() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)
+                               .unwrap_or(Duration::from_secs(0));
+    let time = now.as_secs() as u32 ^ now.subsec_nanos();
+    let count = COUNTER.fetch_add(1, Ordering::SeqCst);
+    format!("{:08x}{:04x}{:04x}",
+            time,
+            process::id() & 0xffff,
+            count & 0xffff)
+}
+
+// Removes the secret parameters, such as `token`, from an urlencoded
+// string.
+fn without_secrets(query: &str) -> String {
+    let pairs = form_urlencoded::parse(query.as_bytes())
+                    .filter(|&(ref key, _)| !redact::is_secret_param(key));
+    form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(pairs)
+        .finish()
+}
+
+// A response from the Diffbot API, read in full.
+pub(crate) struct HttpResponse {
+    pub status: u16,
+    pub retry_after: Option<Duration>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    // Time spent sending the request and reading the response, when it came
+    // from the network.
+    pub elapsed: Option<Duration>,
+}
+
+#[test]
+fn test_key_ignores_token() {
+    use url::Url;
+
+    let url = "https://api.diffbot.com/v3/article\
+               ?token={}&url=http%3A%2F%2Fa.com";
+    let first = Url::parse(&url.replace("{}", "first")).unwrap();
+    let second = Url::parse(&url.replace("{}", "second")).unwrap();
+    let key = HttpRequest::get(first).key();
+    assert_eq!(key,
+               "GET https://api.diffbot.com/v3/article\
+                ?url=http%3A%2F%2Fa.com");
+    assert_eq!(key, HttpRequest::get(second).key());
+
+    let proxied = Url::parse("https://api.diffbot.com/v3/article?\
+                              proxyAuth=user%3Asecret&proxy=1.2.3.4")
+                      .unwrap();
+    assert_eq!(HttpRequest::get(proxied).key(),
+               "GET https://api.diffbot.com/v3/article?proxy=1.2.3.4");
+}
+
+#[test]
+fn test_request_ids() {
+    let first = new_request_id();
+    let second = new_request_id();
+    assert_eq!(first.len(), 16);
+    assert_ne!(first, second);
+}
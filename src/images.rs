@@ -0,0 +1,249 @@
+//! Download of the images found by the extraction APIs.
+//!
+//! ```no_run
+//! # extern crate diffbot;
+//! # use diffbot::*;
+//! use std::path::Path;
+//! use diffbot::images;
+//! use diffbot::models::{self, Article, ExtractResponse};
+//!
+//! # fn main() {
+//! # let diffbot = Diffbot::v3("token");
+//! let response: ExtractResponse<Article> =
+//!     diffbot.call(API::Article, "http://diffbot.com")
+//!            .and_then(models::parse)
+//!            .unwrap();
+//! for article in &response.objects {
+//!     let paths = images::download_all(&diffbot, &article.images,
+//!                                      Path::new("images"), 4);
+//!     println!("{:?}", paths);
+//! }
+//! # }
+//! ```
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use {Diffbot, Error, IntoTargetUrl};
+use models::Image;
+
+// Longest file name kept from an image URL, without the extension.
+const MAX_STEM: usize = 100;
+
+/// Downloads an image into `dest_dir`, and returns the path of the file.
+///
+/// The file is named after the last segment of the image URL, with the
+/// extension of its content type: the extension in the URL may not match
+/// what the server sends. An existing file is never overwritten: a number
+/// is added to the name instead.
+///
+/// Fails with `Error::InvalidUrl` if the image has no valid URL, and with
+/// `Error::NotAnImage` if the server does not send an image.
+pub fn download(client: &Diffbot, image: &Image, dest_dir: &Path)
+                -> Result<PathBuf, Error> {
+    let url = match image.url {
+        Some(ref url) => try!(url.as_str().into_target_url()),
+        None => {
+            return Err(Error::InvalidUrl("the image has no URL".to_string()))
+        }
+    };
+    let (content_type, mut body) = try!(client.download(&url));
+    let extension = match content_type {
+        Some(ref content_type) if is_image(content_type) => {
+            extension(content_type)
+        }
+        other => {
+            let message = format!("{} ({})",
+                                  url,
+                                  other.as_ref()
+                                       .map_or("no content type",
+                                               String::as_str));
+            return Err(Error::NotAnImage(message));
+        }
+    };
+
+    let name = url.path_segments()
+                  .and_then(|segments| segments.last())
+                  .unwrap_or("");
+    let (path, mut file) = try!(create_file(dest_dir, name, extension));
+    if let Err(err) = io::copy(&mut body, &mut file) {
+        let _ = ::std::fs::remove_file(&path);
+        return Err(Error::Io(err));
+    }
+    Ok(path)
+}
+
+/// Downloads images into `dest_dir`, `concurrency` at a time.
+///
+/// Returns the result of each download, in the order of `images`; a failed
+/// download does not stop the others. See `download`.
+pub fn download_all(client: &Diffbot, images: &[Image], dest_dir: &Path,
+                    concurrency: usize)
+                    -> Vec<Result<PathBuf, Error>> {
+    let images = Arc::new(images.to_vec());
+    let next = Arc::new(AtomicUsize::new(0));
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let mut workers = Vec::new();
+    for _ in 0..concurrency.max(1).min(images.len()) {
+        let client = client.clone();
+        let images = images.clone();
+        let dest_dir = dest_dir.to_path_buf();
+        let next = next.clone();
+        let results = results.clone();
+        workers.push(thread::spawn(move || {
+            download_next(&client, &images, &dest_dir, &next, &results)
+        }));
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    let mut results = match results.lock() {
+        Ok(mut results) => results.split_off(0),
+        Err(poisoned) => poisoned.into_inner().split_off(0),
+    };
+    results.sort_by_key(|&(index, _)| index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+// Downloads the next image not taken by another worker, until none is left.
+fn download_next(client: &Diffbot, images: &[Image], dest_dir: &Path,
+                 next: &AtomicUsize,
+                 results: &Mutex<Vec<(usize, Result<PathBuf, Error>)>>) {
+    loop {
+        let index = next.fetch_add(1, Ordering::SeqCst);
+        let image = match images.get(index) {
+            Some(image) => image,
+            None => return,
+        };
+        let result = download(client, image, dest_dir);
+        match results.lock() {
+            Ok(mut results) => results.push((index, result)),
+            Err(poisoned) => poisoned.into_inner().push((index, result)),
+        }
+    }
+}
+
+// Returns `true` if a content type is that of an image.
+fn is_image(content_type: &str) -> bool {
+    content_type.get(..6)
+                .map_or(false, |prefix| prefix.eq_ignore_ascii_case("image/"))
+}
+
+// Returns the usual file extension of an image content type.
+fn extension(content_type: &str) -> &'static str {
+    let mime = content_type.split(';').next().unwrap_or("").trim();
+    match &*mime.to_lowercase() {
+        "image/jpeg" | "image/jpg" | "image/pjpeg" => "jpg",
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        "image/bmp" => "bmp",
+        "image/tiff" => "tiff",
+        "image/avif" => "avif",
+        "image/x-icon" | "image/vnd.microsoft.icon" => "ico",
+        _ => "img",
+    }
+}
+
+// Creates a new file for an image, named after the last segment of its URL
+// without its extension.
+fn create_file(dest_dir: &Path, name: &str, extension: &str)
+               -> Result<(PathBuf, File), Error> {
+    let name: String = name.chars().map(safe_char).collect();
+    let name = name.trim_matches('.');
+    let stem = match name.rfind('.') {
+        Some(dot) => &name[..dot],
+        None => name,
+    };
+    let stem: String = if stem.is_empty() {
+        "image".to_string()
+    } else {
+        stem.chars().take(MAX_STEM).collect()
+    };
+
+    let mut path = dest_dir.join(format!("{}.{}", stem, extension));
+    let mut attempt = 0;
+    loop {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(Error::Io(err)),
+        }
+        attempt += 1;
+        path = dest_dir.join(format!("{}-{}.{}", stem, attempt, extension));
+    }
+}
+
+// Replaces the characters that may not be safe in a file name.
+fn safe_char(c: char) -> char {
+    match c {
+        '-' | '_' | '.' => c,
+        c if c.is_alphanumeric() => c,
+        _ => '_',
+    }
+}
+
+// Reads a whole file, for tests.
+#[cfg(test)]
+fn read(path: &Path) -> Vec<u8> {
+    use std::io::Read;
+
+    let mut content = Vec::new();
+    File::open(path).unwrap().read_to_end(&mut content).unwrap();
+    content
+}
+
+#[test]
+fn test_download_images() {
+    use stub::{StubResponse, StubServer};
+
+    let dir = ::std::env::temp_dir().join(format!("diffbot-images-{}",
+                                                  ::std::process::id()));
+    ::std::fs::create_dir_all(&dir).unwrap();
+    let server = StubServer::start();
+    let image = |path: &str| {
+        Image {
+            url: Some(server.url().join(path).unwrap().to_string()),
+            ..Image::default()
+        }
+    };
+
+    server.push(StubResponse::new(200, "png").header("Content-Type",
+                                                     "image/png"));
+    server.push(StubResponse::new(200, "png").header("Content-Type",
+                                                     "image/png"));
+    server.push(StubResponse::new(200, "<html>").header("Content-Type",
+                                                        "text/html"));
+    server.push(StubResponse::new(200, "jpeg").header("Content-Type",
+                                                      "IMAGE/JPEG"));
+    let client = server.client("token");
+    let first = download(&client, &image("/img/logo"), &dir).unwrap();
+    assert_eq!(first.file_name().unwrap(), "logo.png");
+    assert_eq!(read(&first), b"png");
+    let second = download(&client, &image("/logo.jpg"), &dir).unwrap();
+    assert_eq!(second.file_name().unwrap(), "logo-1.png");
+    let err = download(&client, &image("/page"), &dir).unwrap_err();
+    match err {
+        Error::NotAnImage(ref msg) => assert!(msg.ends_with("(text/html)")),
+        other => panic!("unexpected error: {:?}", other),
+    }
+    let third = download(&client, &image("/photo"), &dir).unwrap();
+    assert_eq!(third.file_name().unwrap(), "photo.jpg");
+
+    server.push(StubResponse::new(200, "a").header("Content-Type",
+                                                   "image/gif"));
+    let results = download_all(&client,
+                               &[image("/a.gif"), Image::default()],
+                               &dir,
+                               4);
+    assert_eq!(results[0].as_ref().unwrap().file_name().unwrap(), "a.gif");
+    assert!(results[1].is_err());
+
+    ::std::fs::remove_dir_all(&dir).unwrap();
+}
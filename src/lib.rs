@@ -12,11 +12,15 @@
 //!
 //! fn main() {
 //!     let client = Diffbot::v3("insert_your_token_here");
-//!     match client.call(API::Analyze, "http://www.diffbot.com") {
+//!     match client.request(API::Article, "http://www.diffbot.com")
+//!                 .field("links")
+//!                 .paging(false)
+//!                 .send() {
 //!         Ok(result) =>
 //!             println!("{:?}", result),
-//!         Err(Error::Api(code, msg)) =>
-//!             println!("API returned error {}: {}", code, msg),
+//!         Err(Error::Api(err)) =>
+//!             println!("API returned error {}: {}",
+//!                      err.code(), err.message()),
 //!         Err(err) =>
 //!             println!("Other error: {:?}", err),
 //!     };
@@ -24,126 +28,234 @@
 //! ```
 
 extern crate url;
+#[cfg(feature = "chrono")]
+extern crate chrono;
+#[cfg(feature = "curl")]
+extern crate curl;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+#[cfg(feature = "stream")]
+extern crate futures;
 extern crate reqwest;
 extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate serde_json;
-
-use reqwest::header::{ContentType, UserAgent};
+#[cfg(feature = "ureq")]
+extern crate ureq;
+
+mod api;
+mod breaker;
+mod cache;
+mod client;
+mod cookies;
+mod credentials;
+mod endpoints;
+mod error;
+mod extractor;
+mod fields;
+mod fixtures;
+mod http;
+mod limit;
+mod options;
+#[cfg(feature = "jobs")]
+mod quota;
+mod redact;
+mod request;
+mod response;
+mod retry;
+#[cfg(feature = "jobs")]
+mod settings;
+mod stream;
+#[cfg(any(test, feature = "test-util"))]
+mod stub;
+mod target;
+mod transport;
+
+pub mod blocking;
+pub mod diff;
+pub mod images;
+pub mod models;
+#[cfg(feature = "kg")]
+pub mod nl;
+#[cfg(feature = "jobs")]
+pub mod notifications;
+pub mod output;
+pub mod testing;
+pub mod text;
+
+pub use api::{PageType, TokenStatus, API};
+pub use breaker::CircuitBreaker;
+pub use cache::{Cache, CachePolicy, MemoryCache};
+pub use client::DiffbotApi;
+pub use cookies::CookieJar;
+pub use credentials::Credentials;
+pub use endpoints::Endpoints;
+pub use error::{ApiError, ApiErrorKind, CallContext, Error, ErrorDetails,
+                JsonError, TransportError, TransportErrorKind};
+pub use extractor::{ExtractionReport, Extractor};
+pub use fields::Fields;
+pub use fixtures::{FixtureMode, Fixtures};
+pub use limit::RateBudget;
+pub use options::{AnalyzeOptions, ApiOptions, ArticleOptions,
+                  DiscussionOptions, ImageOptions, ProductOptions,
+                  VideoOptions};
+#[cfg(feature = "jobs")]
+pub use quota::QuotaShortfall;
+pub use request::{PreparedRequest, Request};
+pub use response::ResponseMeta;
+pub use retry::RetryPolicy;
+#[cfg(feature = "jobs")]
+pub use retry::{JobProgress, PollPolicy};
+#[cfg(feature = "jobs")]
+pub use settings::{BulkLimits, BulkPart, BulkSettings, CrawlSettings,
+                   Schedule, UrlPatterns};
+pub use stream::{DataFormat, JsonStream};
+#[cfg(feature = "kg")]
+pub use stream::KgResults;
+#[cfg(feature = "jobs")]
+pub use stream::JobResults;
+#[cfg(feature = "search")]
+pub use stream::SearchResults;
+pub use target::{IntoTargetUrl, RejectedUrl, UrlValidator, ValidatedUrls};
+pub use transport::{Transport, TransportResponse};
+#[cfg(feature = "curl")]
+pub use transport::CurlTransport;
+#[cfg(feature = "ureq")]
+pub use transport::{DnsCache, UreqTransport};
+
+use reqwest::header::{ContentEncoding, ContentType, UserAgent};
+#[cfg(feature = "gzip")]
+use reqwest::header::Encoding;
 use reqwest::mime::{Mime, TopLevel, SubLevel};
 
-use std::error::{self, Error as StdError};
-use std::io;
+#[cfg(feature = "gzip")]
+use flate2::Compression;
+#[cfg(feature = "gzip")]
+use flate2::read::GzEncoder;
+#[cfg(feature = "kg")]
+use std::collections::HashMap;
 use std::fmt;
+#[cfg(any(feature = "jobs", feature = "kg"))]
+use std::io::Cursor;
+use std::io::{self, Read};
+use std::str;
+use std::sync::{Arc, mpsc};
+#[cfg(feature = "kg")]
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use http::{HttpRequest, HttpResponse};
+use limit::RateLimiter;
+#[cfg(any(feature = "jobs", feature = "kg", feature = "search"))]
+use serde::de::DeserializeOwned;
+#[cfg(all(feature = "jobs", feature = "kg"))]
+use serde::Serialize;
+
+// The `User-Agent` of the requests sent to the API.
+pub(crate) const USER_AGENT: &'static str = "diffbot/rust";
 
 fn user_agent() -> UserAgent {
-    UserAgent("diffbot/rust".to_owned())
-}
-
-/// One of the possible diffbot API.
-///
-/// See [the diffbot documentation](https://www.diffbot.com/dev/docs/).
-pub enum API {
-    /// The analyze API automatically detects the page type.
-    Analyze,
-    /// The article API for news article.
-    Article,
-    /// The product API for products in online shops.
-    Product,
-    /// The discussion API for forums.
-    Discussion,
-    /// The image API for image-central pages.
-    Image,
-    /// The video API for video pages (youtube, ...).
-    Video,
-    /// Custom-built API with a specific name
-    Custom(String),
-}
-
-impl API {
-    fn get_str(&self) -> &str {
-        match *self {
-            API::Analyze => "analyze",
-            API::Article => "article",
-            API::Product => "product",
-            API::Discussion => "discussion",
-            API::Image => "image",
-            API::Video => "video",
-            API::Custom(ref name) => name.as_ref(),
-        }
-    }
-
-    fn get_url_string(&self, version: u8) -> String {
-        get_api_url_string(self.get_str(), version)
-    }
-
-    fn get_url(&self, version: u8) -> reqwest::Url {
-        get_api_url(self.get_str(), version)
-    }
-}
-
-fn get_api_url_string(api: &str, version: u8) -> String {
-    format!("https://api.diffbot.com/v{}/{}", version, api)
-}
-
-fn get_api_url(api: &str, version: u8) -> reqwest::Url {
-    reqwest::Url::parse(&get_api_url_string(api, version)).unwrap()
+    UserAgent(USER_AGENT.to_owned())
 }
 
-
-
-
-/// Error occuring during a call.
-#[derive(Debug)]
-pub enum Error {
-    /// The API returned an error.
-    Api(u32, String),
-    /// An error occured when decoding JSON from the API.
-    Json(serde_json::error::Error),
-    /// An error occured with the network.
-    Io(io::Error),
-    // TODO: don't expose reqwest
-    /// An HTTP error occured with the webserver.
-    Http(reqwest::Error),
+// Converts a response of the built-in HTTP client.
+fn from_reqwest(response: reqwest::Response) -> TransportResponse {
+    let headers = response.headers()
+                          .iter()
+                          .map(|header| {
+                              (header.name().to_string(),
+                               header.value_string())
+                          })
+                          .collect();
+    TransportResponse::new(response.status().to_u16(), headers, response)
 }
 
-impl From<serde_json::error::Error> for Error {
-    fn from(err: serde_json::error::Error) -> Self {
-        Error::Json(err)
-    }
+// Returns the delay asked by the `Retry-After` header of a response. Only
+// delays given in seconds are understood.
+fn retry_after(response: &TransportResponse) -> Option<Duration> {
+    response.header("Retry-After")
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_secs)
 }
 
-impl From<reqwest::Error> for Error {
-    fn from(err: reqwest::Error) -> Self {
-        Error::Http(err)
+// Returns `true` if the outcome of a call shows that the API is up: only
+// network failures and server errors count against the circuit breaker.
+fn is_healthy(status: Result<u16, &Error>) -> bool {
+    match status {
+        Ok(status) => status < 500,
+        Err(&Error::Http(_)) | Err(&Error::Io(_)) => false,
+        Err(_) => true,
     }
 }
 
-impl error::Error for Error {
-    fn description(&self) -> &str {
-        match *self {
-            Error::Api(_, ref msg) => msg,
-            Error::Json(ref err) => err.description(),
-            Error::Io(ref err) => err.description(),
-            Error::Http(ref err) => err.description(),
+// Reads the status and body of a response, failing if the body is longer
+// than `max_size` bytes.
+fn read_response(response: TransportResponse, max_size: Option<u64>)
+                 -> Result<HttpResponse, Error> {
+    let retry_after = retry_after(&response);
+    let status = response.status();
+    let headers = response.headers().to_vec();
+    let mut body = Vec::new();
+    match max_size {
+        Some(max_size) => {
+            let too_large = || {
+                let message = format!("response body longer than {} bytes",
+                                      max_size);
+                Error::ResponseTooLarge(message)
+            };
+            let length = response.header("Content-Length")
+                                 .and_then(|value| value.trim().parse().ok());
+            if length.map_or(false, |length: u64| length > max_size) {
+                return Err(too_large());
+            }
+            try!(response.into_body()
+                         .take(max_size + 1)
+                         .read_to_end(&mut body));
+            if body.len() as u64 > max_size {
+                return Err(too_large());
+            }
         }
-    }
-
-    fn cause(&self) -> Option<&error::Error> {
-        match *self {
-            Error::Api(_, _) => None,
-            Error::Json(ref err) => Some(err),
-            Error::Io(ref err) => Some(err),
-            Error::Http(ref err) => Some(err),
+        None => {
+            try!(response.into_body().read_to_end(&mut body));
         }
     }
+
+    Ok(HttpResponse {
+        status: status,
+        retry_after: retry_after,
+        headers: headers,
+        body: body,
+        elapsed: None,
+    })
 }
 
-impl fmt::Display for Error {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.write_str(self.description())
-    }
+// Returns the value of the first option named `key`.
+#[cfg(feature = "jobs")]
+fn find_option<'a, S: AsRef<str>>(options: &'a [(S, S)], key: &str)
+                                  -> Option<&'a str> {
+    options.iter()
+           .find(|&&(ref k, _)| k.as_ref() == key)
+           .map(|&(_, ref value)| value.as_ref())
 }
 
+// Returns the number of calls a bulk job on `urls` URLs makes over all its
+// rounds, or `None` if it repeats without limit.
+#[cfg(feature = "jobs")]
+fn bulk_calls<S: AsRef<str>>(urls: usize, options: &[(S, S)]) -> Option<u64> {
+    let repeats = find_option(options, "repeat")
+                      .and_then(|repeat| repeat.parse::<f64>().ok())
+                      .map_or(false, |repeat| repeat > 0.0);
+    if !repeats {
+        return Some(urls as u64);
+    }
+    find_option(options, "maxRounds")
+        .and_then(|rounds| rounds.parse::<u64>().ok())
+        .filter(|&rounds| rounds > 0)
+        .map(|rounds| urls as u64 * rounds)
+}
 
 /// Result from a call.
 pub type DiffbotResult = Result<serde_json::map::Map<String, serde_json::Value>, Error>;
@@ -161,23 +273,434 @@ pub type DiffbotResult = Result<serde_json::map::Map<String, serde_json::Value>,
 /// # println!("{:?}", result);
 /// # }
 /// ```
+///
+/// The client is cheap to clone and can be shared between threads: clones
+/// use the same connection pool.
+///
+/// Host names are resolved with the system resolver when a connection is
+/// opened, and pooled connections are reused, so sharing one client keeps
+/// DNS lookups to a minimum. The built-in client cannot use another
+/// resolver: with the `ureq` feature, `with_resolver` switches to
+/// `UreqTransport` and looks host names up with your own resolver, or with
+/// a `DnsCache`. With the `curl` feature, `CurlTransport::with_resolve` pins
+/// the addresses of the API servers.
+#[derive(Clone)]
 pub struct Diffbot {
-    token: String,
+    token: Arc<String>,
     version: u8,
 
-    client: reqwest::Client,
+    client: Arc<reqwest::Client>,
+    retry_policy: Option<RetryPolicy>,
+    retry_posts: bool,
+    rate_limiter: RateLimiter,
+    breaker: Option<CircuitBreaker>,
+    hedge_delay: Option<Duration>,
+    transport: Option<Arc<dyn Transport>>,
+    max_response_size: Option<u64>,
+    fixtures: Option<Fixtures>,
+    cache: Option<Arc<dyn Cache>>,
+    cookies: Option<CookieJar>,
+    site_credentials: Vec<(String, Credentials)>,
+    default_options: Vec<(String, String)>,
+    endpoints: Endpoints,
+    headers: Vec<(String, String)>,
+    gzip_bodies: bool,
+    #[cfg(feature = "jobs")]
+    quota_check: Option<quota::QuotaCheck>,
+}
+
+impl fmt::Debug for Diffbot {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Diffbot")
+           .field("token", &redact::redact_token(&self.token))
+           .field("version", &self.version)
+           .field("endpoints", &self.endpoints)
+           .field("headers",
+                  &self.headers
+                       .iter()
+                       .map(|&(ref name, _)| name.as_str())
+                       .collect::<Vec<_>>())
+           .field("retry_policy", &self.retry_policy)
+           .field("retry_posts", &self.retry_posts)
+           .field("rate_limiter", &self.rate_limiter)
+           .field("breaker", &self.breaker)
+           .field("hedge_delay", &self.hedge_delay)
+           .field("transport", &self.transport.is_some())
+           .field("max_response_size", &self.max_response_size)
+           .field("fixtures", &self.fixtures)
+           .field("cache", &self.cache.is_some())
+           .field("cookies", &self.cookies)
+           .field("site_credentials", &self.site_credentials)
+           .field("default_options", &self.default_options)
+           .field("gzip_bodies", &self.gzip_bodies)
+           .finish()
+    }
 }
 
 impl Diffbot {
     /// Returns a Diffbot client that uses the given token and version.
     ///
-    /// Valid versions: `1`, `2`, `3`.
+    /// Valid versions: `1`, `2`, `3`. Calls to an endpoint the version does
+    /// not offer, such as crawls before version 3, fail with
+    /// `Error::Unsupported` without reaching the API.
     pub fn new<S: ToString>(token: S, version: u8) -> Self {
         Diffbot {
-            token: token.to_string(),
+            token: Arc::new(token.to_string()),
             version: version,
-            client: reqwest::Client::new().unwrap(),
+            client: Arc::new(reqwest::Client::new().unwrap()),
+            retry_policy: None,
+            retry_posts: false,
+            rate_limiter: RateLimiter::new(),
+            breaker: None,
+            hedge_delay: None,
+            transport: None,
+            max_response_size: None,
+            fixtures: None,
+            cache: None,
+            cookies: None,
+            site_credentials: Vec::new(),
+            default_options: Vec::new(),
+            endpoints: Endpoints::saas(),
+            headers: Vec::new(),
+            gzip_bodies: false,
+            #[cfg(feature = "jobs")]
+            quota_check: None,
+        }
+    }
+
+    /// Retries failed calls according to the given policy.
+    ///
+    /// By default, failed calls are not retried. Only GET calls are retried
+    /// unless `retrying_posts` is used: see there why.
+    pub fn with_retries(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Returns a clone of the client which also retries POST calls.
+    ///
+    /// A POST call (`post_body`, `bulk`, `crawl`, job updates, ...) may have
+    /// been processed by the API even though it failed on the way back, for
+    /// instance after a timeout. Retrying it then repeats the work: the
+    /// page is extracted and billed twice, or a job is submitted twice. Use
+    /// this only for the calls where that is acceptable.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// let diffbot = Diffbot::v3("token").with_retries(RetryPolicy::new(3));
+    /// let body = b"<html>...</html>";
+    /// # println!("{:?}",
+    /// diffbot.retrying_posts()
+    ///        .post_body(API::Article, "http://my.website.com", body)
+    /// # );
+    /// # }
+    /// ```
+    pub fn retrying_posts(&self) -> Self {
+        let mut client = self.clone();
+        client.retry_posts = true;
+        client
+    }
+
+    /// Sends at most `calls` calls per `period` to the API, waiting as
+    /// needed.
+    ///
+    /// The limit adapts to the API: after a call is refused as too many, the
+    /// client slows down and waits for the delay requested by the API, then
+    /// gradually returns to the configured rate. Clones share the limit.
+    ///
+    /// By default, calls are sent as soon as they are made.
+    pub fn with_rate_limit(mut self, calls: u32, period: Duration) -> Self {
+        self.rate_limiter = RateLimiter::with_rate(calls, period);
+        self
+    }
+
+    /// Suspends calls after repeated failures, according to `breaker`.
+    ///
+    /// By default, calls are always sent. See `CircuitBreaker`.
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = Some(breaker);
+        self
+    }
+
+    /// Sends a second, identical request when a GET call gets no response
+    /// within `delay`, and uses whichever response comes first.
+    ///
+    /// This cuts the long tail of extraction latency, at the cost of extra
+    /// calls. Only GET calls, which have no side effects, are hedged. The
+    /// slower request is not interrupted: it completes in the background,
+    /// and its response is dropped without being counted by the circuit
+    /// breaker or the rate limiter.
+    ///
+    /// By default, calls are sent once (plus retries).
+    pub fn with_hedging(mut self, delay: Duration) -> Self {
+        self.hedge_delay = Some(delay);
+        self
+    }
+
+    /// Sends the requests of the client with `transport`, instead of the
+    /// built-in HTTP client.
+    ///
+    /// Bodies posted with `post_reader` are read in full before being
+    /// passed to the transport. See `Transport`.
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Looks the API servers up with `resolver`, such as a `DnsCache`,
+    /// instead of the system resolver on each new connection.
+    ///
+    /// The built-in HTTP client cannot take a resolver, so the requests are
+    /// sent with a `UreqTransport`, replacing any transport set before.
+    /// Requires the `ureq` feature.
+    #[cfg(feature = "ureq")]
+    pub fn with_resolver<R>(self, resolver: R) -> Self
+        where R: ureq::Resolver + 'static
+    {
+        self.with_transport(Arc::new(UreqTransport::from_resolver(resolver)))
+    }
+
+    /// Fails calls whose response body is longer than `max_size` bytes,
+    /// with `Error::ResponseTooLarge`, instead of reading it in memory.
+    ///
+    /// Methods which stream job results, such as `crawl_data` and
+    /// `get_crawl_data`, do not hold the whole body and are not limited.
+    /// By default, bodies of any size are read.
+    pub fn with_max_response_size(mut self, max_size: u64) -> Self {
+        self.max_response_size = Some(max_size);
+        self
+    }
+
+    /// Returns what is known of the rate limits of the token, from the
+    /// responses received so far.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// let diffbot = Diffbot::v3("token");
+    /// let budget = diffbot.rate_budget();
+    /// if budget.is_exhausted() {
+    ///     println!("{} calls refused, waiting", budget.throttled());
+    /// }
+    /// # }
+    /// ```
+    pub fn rate_budget(&self) -> RateBudget {
+        self.rate_limiter.budget()
+    }
+
+    /// Sends the requests to another server than `https://api.diffbot.com`,
+    /// such as an on-premises deployment or a local stub.
+    ///
+    /// The URL may use plain HTTP and any port, and have a path prefix:
+    /// with `http://diffbot.internal:8080/api`, Article calls go to
+    /// `http://diffbot.internal:8080/api/v3/article`. The Knowledge Graph
+    /// and Natural Language calls go to the same server; use
+    /// `with_endpoints` when the services live on different servers. Fails
+    /// if the URL is not an `http` or `https` URL with a host.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// let diffbot = Diffbot::v3("token")
+    ///                   .with_base_url("http://diffbot.internal:8080/api")
+    ///                   .unwrap();
+    /// # }
+    /// ```
+    pub fn with_base_url(self, base_url: &str) -> Result<Self, Error> {
+        Endpoints::on_premises(base_url)
+            .map(|endpoints| self.with_endpoints(endpoints))
+    }
+
+    /// Sends the calls of each service to the given servers.
+    ///
+    /// See `Endpoints`.
+    pub fn with_endpoints(mut self, endpoints: Endpoints) -> Self {
+        self.endpoints = endpoints;
+        self
+    }
+
+    /// Sends a header with every request to the API, replacing any previous
+    /// value for the same name.
+    ///
+    /// Use it to authenticate with a gateway in front of Diffbot; the token
+    /// is still sent as a query parameter. The header is only sent to the
+    /// servers of `with_endpoints`, not when downloading other files such
+    /// as images. It is not part of the requests matched with fixtures or
+    /// the cache, and its value is left out of the `Debug` output. Fails
+    /// with `Error::InvalidHeader` if `name` is not a valid header name, or
+    /// if `value` contains a line break.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// let diffbot = Diffbot::v3("token")
+    ///                   .with_base_url("https://gateway.internal/diffbot")
+    ///                   .unwrap()
+    ///                   .with_header("Authorization", "Bearer 0123456789")
+    ///                   .unwrap();
+    /// # }
+    /// ```
+    pub fn with_header(mut self, name: &str, value: &str)
+                       -> Result<Self, Error> {
+        try!(request::check_header(name, value));
+        self.headers.retain(|&(ref n, _)| !n.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+        Ok(self)
+    }
+
+    /// Caches successful responses to extraction calls and entity lookups
+    /// in `cache`.
+    ///
+    /// Calls use the cache according to their `CachePolicy`; see
+    /// `Request::cache`. Calls about the account, jobs, searches and other
+    /// Knowledge Graph queries are never cached, as their answers change.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// use std::sync::Arc;
+    ///
+    /// # fn main() {
+    /// let diffbot = Diffbot::v3("token")
+    ///                   .with_cache(Arc::new(MemoryCache::new()));
+    /// # println!("{:?}",
+    /// diffbot.request(API::Article, "http://diffbot.com")
+    ///        .cache(CachePolicy::Refresh)
+    ///        .send()
+    /// # );
+    /// # }
+    /// ```
+    pub fn with_cache(mut self, cache: Arc<dyn Cache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Adds `options` to every extraction call.
+    ///
+    /// An option given to a call replaces the default with the same key.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// let diffbot = Diffbot::v3("token")
+    ///                   .with_default_options(&[("discussion", "false"),
+    ///                                           ("timeout", "30000")]);
+    /// # println!("{:?}",
+    /// diffbot.request(API::Article, "http://diffbot.com")
+    ///        .timeout_ms(60_000)
+    ///        .send()
+    /// # );
+    /// # }
+    /// ```
+    pub fn with_default_options<K, V>(mut self, options: &[(K, V)]) -> Self
+        where K: ToString,
+              V: ToString
+    {
+        for &(ref key, ref value) in options {
+            let key = key.to_string();
+            self.default_options.retain(|&(ref k, _)| *k != key);
+            self.default_options.push((key, value.to_string()));
         }
+        self
+    }
+
+    /// Forwards the cookies of `jar` to the target sites.
+    ///
+    /// Each call sends the cookies that apply to its target URL, unless it
+    /// sets a `Cookie` header itself with `Request::forward_header`. See
+    /// `CookieJar`.
+    pub fn with_cookie_jar(mut self, jar: CookieJar) -> Self {
+        self.cookies = Some(jar);
+        self
+    }
+
+    /// Authenticates with the pages of `domain`, and of its subdomains,
+    /// using `credentials`.
+    ///
+    /// Calls to these pages ask Diffbot to use HTTP basic authentication,
+    /// unless they set their own credentials with `Request::basic_auth`.
+    /// When several domains match, the most specific one is used.
+    pub fn with_site_credentials(mut self, domain: &str,
+                                 credentials: Credentials)
+                                 -> Self {
+        let domain = domain.trim_start_matches('.').to_lowercase();
+        self.site_credentials.retain(|&(ref d, _)| *d != domain);
+        self.site_credentials.push((domain, credentials));
+        self
+    }
+
+    /// Records responses to, or replays them from, a fixture directory.
+    ///
+    /// See `Fixtures` for details.
+    pub fn with_fixtures(mut self, fixtures: Fixtures) -> Self {
+        self.fixtures = Some(fixtures);
+        self
+    }
+
+    /// Compresses posted bodies with gzip.
+    ///
+    /// This shortens the upload of large pages over slow connections, at the
+    /// cost of some CPU time. Requires the `gzip` feature.
+    #[cfg(feature = "gzip")]
+    pub fn with_gzip_bodies(mut self) -> Self {
+        self.gzip_bodies = true;
+        self
+    }
+
+    /// Checks the quota of the account before starting a crawl or bulk job.
+    ///
+    /// The calls left this month are read with `account`, bypassing the
+    /// cache. A job that could use more fails with `Error::QuotaExceeded`
+    /// without being started. A bulk job makes one call per URL in each
+    /// round, up to `maxRounds` rounds if it repeats; a crawl makes at most
+    /// `maxToProcess` calls. Jobs without these limits always fail the
+    /// check.
+    ///
+    /// Plans without a monthly limit pass the check.
+    #[cfg(feature = "jobs")]
+    pub fn with_quota_check(mut self) -> Self {
+        self.quota_check = Some(quota::QuotaCheck::Fail);
+        self
+    }
+
+    /// Checks the quota like `with_quota_check`, but only calls `warn` for
+    /// a job that could exceed it, and starts the job anyway.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// let diffbot = Diffbot::v3("token").with_quota_warning(|shortfall| {
+    ///     println!("warning: {}", shortfall);
+    /// });
+    /// # }
+    /// ```
+    #[cfg(feature = "jobs")]
+    pub fn with_quota_warning<F>(mut self, warn: F) -> Self
+        where F: Fn(&QuotaShortfall) + Send + Sync + 'static
+    {
+        self.quota_check = Some(quota::QuotaCheck::Warn(Arc::new(warn)));
+        self
     }
 
     /// Convenient method to use a v1 client.
@@ -195,20 +718,60 @@ impl Diffbot {
         Diffbot::new(token, 3)
     }
 
+    /// Starts building an API call.
+    ///
+    /// Options are added to the returned `Request` with chained method
+    /// calls, and the call is made with `Request::send`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// # println!("{:?}",
+    /// diffbot.request(API::Article, "http://diffbot.com")
+    ///        .paging(false)
+    ///        .send()
+    /// # );
+    /// # }
+    /// ```
+    pub fn request<'a, U: IntoTargetUrl>(&'a self, api: API, target_url: U)
+                                         -> Request<'a> {
+        Request::new(self, api, target_url.into_target_url())
+    }
+
+    /// Builds an API call without sending it.
+    ///
+    /// The returned request can be inspected and changed before being sent
+    /// with `PreparedRequest::send`. Use `request` and
+    /// `Request::prepare` to set options first.
+    pub fn prepare_request<U>(&self, api: API, target_url: U)
+                              -> Result<PreparedRequest, Error>
+        where U: IntoTargetUrl
+    {
+        self.request(api, target_url).prepare()
+    }
+
     /// Makes an API call without extra options.
     ///
     /// Just calls `call_with_options` with an empty option list.
-    pub fn call(&self, api: API, target_url: &str) -> DiffbotResult {
-        self.call_with_options::<String>(api, target_url, &[])
+    pub fn call<U: IntoTargetUrl>(&self, api: API, target_url: U)
+                                  -> DiffbotResult {
+        self.call_with_options::<U, String>(api, target_url, &[])
     }
 
     /// Makes an API call
     ///
     /// Runs `target_url` through the diffbot endpoint specified by `api`.
+    /// `target_url` can be a `&str`, a `String` or a `url::Url`.
     /// Add each (key,value) pair in `options` to the query string.
     /// Read the [diffbot documentation](https://www.diffbot.com/dev/docs/)
     /// for information on supported values.
     ///
+    /// `request` offers the same options through dedicated methods.
+    ///
     /// # Example
     ///
     /// ```
@@ -223,24 +786,49 @@ impl Diffbot {
     /// # );
     /// # }
     /// ```
-    pub fn call_with_options<S: ToString>(&self, api: API, target_url: &str,
-                                          options: &[(S, S)])
-                                          -> DiffbotResult {
-        let url = self.prepare_url(api, target_url, options);
+    pub fn call_with_options<U, S>(&self, api: API, target_url: U,
+                                   options: &[(S, S)])
+                                   -> DiffbotResult
+        where U: IntoTargetUrl,
+              S: ToString
+    {
+        let target_url = try!(target_url.into_target_url());
+        let url = try!(self.prepare_url(&api, &target_url, options));
+        let request = HttpRequest::get(url).with_target(api, target_url);
+        self.execute(&self.forward_site_headers(request))
+    }
 
-        let builder = self.client.get(url).header(user_agent());
-        Diffbot::process_request(builder)
+    /// Makes an API call and returns the JSON response without parsing it.
+    ///
+    /// This avoids building a `serde_json::Value` when the response is only
+    /// stored or forwarded. Use `request(...).send_raw()` to set options.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// if let Ok(json) = diffbot.call_raw_json(API::Article,
+    ///                                         "http://diffbot.com") {
+    ///     println!("{}", String::from_utf8_lossy(&json));
+    /// }
+    /// # }
+    /// ```
+    pub fn call_raw_json<U: IntoTargetUrl>(&self, api: API, target_url: U)
+                                           -> Result<Vec<u8>, Error> {
+        self.request(api, target_url).send_raw()
     }
 
-    /// List existing crawls.
-    pub fn list_crawls(&self) -> DiffbotResult {
-        let mut url = self.get_api_url("crawl");
-        url.query_pairs_mut().append_pair("token", &self.token);
-        let builder = self.client.get(url).header(user_agent());
-        Diffbot::process_request(builder)
+    // Sends a GET request to the given diffbot URL
+    #[cfg(any(feature = "jobs", feature = "search"))]
+    fn get(&self, url: reqwest::Url) -> DiffbotResult {
+        self.execute(&HttpRequest::get(url))
     }
 
     // Things in common between crawl and bulk
+    #[cfg(any(feature = "jobs", feature = "search"))]
     fn do_crawl_bulk<S: AsRef<str>>(&self, api: &str,
                                     main_options: Vec<(&str, &str)>,
                                     extra_options: &[(S, S)])
@@ -251,17 +839,12 @@ impl Diffbot {
         serializer.extend_pairs(extra_options);
         let body = serializer.finish();
 
-        let url = self.get_api_url(api);
+        let url = try!(self.get_api_url(api));
 
-        let content_type = reqwest::header::ContentType(Mime(TopLevel::Application,
-                                            SubLevel::WwwFormUrlEncoded,
-                                            vec![]));
-        let builder = self.client
-                          .post(url)
-                          .body(body.as_bytes())
-                          .header(content_type)
-                          .header(user_agent());
-        Diffbot::process_request(builder)
+        let content_type = Mime(TopLevel::Application,
+                                SubLevel::WwwFormUrlEncoded,
+                                vec![]);
+        self.execute(&HttpRequest::post(url, content_type, body.into_bytes()))
     }
 
     /// Post an entire html body to the API, without extra options.
@@ -286,9 +869,10 @@ impl Diffbot {
     /// # } );
     /// # }
     /// ```
-    pub fn post_body(&self, api: API, target_url: &str, body: &[u8])
-                     -> DiffbotResult {
-        self.post_body_with_options::<String>(api, target_url, body, &[])
+    pub fn post_body<U: IntoTargetUrl>(&self, api: API, target_url: U,
+                                       body: &[u8])
+                                       -> DiffbotResult {
+        self.post_body_with_options::<U, String>(api, target_url, body, &[])
     }
 
     /// Posti an entire html body to the API.
@@ -297,21 +881,105 @@ impl Diffbot {
     ///
     /// `target_url` here is the URL the page would have.
     /// It doesn't have to be accessible, but will be used when resolving links.
-    pub fn post_body_with_options<S: ToString>(&self, api: API,
-                                               target_url: &str, body: &[u8],
-                                               options: &[(S, S)])
-                                               -> DiffbotResult {
-        let url = self.prepare_url(api, target_url, options);
+    pub fn post_body_with_options<U, S>(&self, api: API, target_url: U,
+                                        body: &[u8], options: &[(S, S)])
+                                        -> DiffbotResult
+        where U: IntoTargetUrl,
+              S: ToString
+    {
+        let target_url = try!(target_url.into_target_url());
+        let url = try!(self.prepare_url(&api, &target_url, options));
+
+        let content_type = Mime(TopLevel::Text, SubLevel::Html, vec![]);
+        let request = HttpRequest::post(url, content_type, body.to_vec());
+        #[cfg(feature = "gzip")]
+        let request = if self.gzip_bodies {
+            try!(request.gzip())
+        } else {
+            request
+        };
+        self.execute(&self.forward_site_headers(request.with_target(api,
+                                                               target_url)))
+    }
+
+    /// Posts an html body read from `reader`, without extra options.
+    ///
+    /// See `post_reader_with_options`.
+    pub fn post_reader<U, R>(&self, api: API, target_url: U, reader: R)
+                             -> DiffbotResult
+        where U: IntoTargetUrl,
+              R: Read + Send + 'static
+    {
+        self.post_reader_with_options::<U, R, String>(api,
+                                                      target_url,
+                                                      reader,
+                                                      &[])
+    }
+
+    /// Posts an html body read from `reader`, without buffering it.
+    ///
+    /// The body is sent with chunked transfer encoding as it is read, so
+    /// large documents can be posted in bounded memory. As it can only be
+    /// read once, the call is not retried. When fixtures are set, the body
+    /// is read into memory first so the call can be recorded or replayed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// let page = std::fs::File::open("page.html").unwrap();
+    /// # println!("{:?}",
+    /// diffbot.post_reader_with_options(API::Article,
+    ///                                  "http://my.website.com",
+    ///                                  page,
+    ///                                  &[("fields", "links")])
+    /// # );
+    /// # }
+    /// ```
+    pub fn post_reader_with_options<U, R, S>(&self, api: API, target_url: U,
+                                             mut reader: R,
+                                             options: &[(S, S)])
+                                             -> DiffbotResult
+        where U: IntoTargetUrl,
+              R: Read + Send + 'static,
+              S: ToString
+    {
+        if self.fixtures.is_some() || self.transport.is_some() {
+            let mut body = Vec::new();
+            try!(reader.read_to_end(&mut body));
+            return self.post_body_with_options(api, target_url, &body,
+                                               options);
+        }
 
-        let content_type = ContentType(Mime(TopLevel::Text,
-                                            SubLevel::Html,
-                                            vec![]));
-        let builder = self.client
-                          .post(url)
-                          .body(body)
-                          .header(content_type)
-                          .header(user_agent());
-        Diffbot::process_request(builder)
+        let target_url = try!(target_url.into_target_url());
+        let url = try!(self.prepare_url(&api, &target_url, options));
+        let content_type = Mime(TopLevel::Text, SubLevel::Html, vec![]);
+
+        let request = HttpRequest::post(url, content_type, Vec::new())
+                          .with_target(api, target_url);
+        let mut request = self.forward_site_headers(request);
+        request.body = None;
+        #[cfg(feature = "gzip")]
+        let body = if self.gzip_bodies {
+            request.content_encoding = Some(Encoding::Gzip);
+            let reader = GzEncoder::new(reader, Compression::default());
+            reqwest::Body::new(reader)
+        } else {
+            reqwest::Body::new(reader)
+        };
+        #[cfg(not(feature = "gzip"))]
+        let body = reqwest::Body::new(reader);
+        self.send_guarded(|| {
+                              self.send_request_with_body(&request,
+                                                          Some(body))
+                                  .map(from_reqwest)
+                          },
+                          None)
+            .and_then(response::parse_response)
+            .map_err(|err| err.with_context(request.context()))
     }
 
     /// Run a search in a diffbot collection without extra options.
@@ -330,6 +998,7 @@ impl Diffbot {
     /// # );
     /// # }
     /// ```
+    #[cfg(feature = "search")]
     pub fn search(&self, col: &str, query: &str) -> DiffbotResult {
         self.search_with_options::<String>(col, query, &[])
     }
@@ -337,231 +1006,2965 @@ impl Diffbot {
     /// Run a search in a diffbot collection.
     ///
     /// Use `col` = `GLOBAL-INDEX` for the global search collection.
+    #[cfg(feature = "search")]
     pub fn search_with_options<S: ToString>(&self, col: &str, query: &str,
                                             options: &[(S, S)])
                                             -> DiffbotResult {
-        let url = self.prepare_search_url(col, query, options);
-
-        let builder = self.client.get(url).header(user_agent());
-        Diffbot::process_request(builder)
+        let url = try!(self.prepare_search_url(col, query, options));
+        self.get(url)
     }
 
-    fn get_api_url(&self, api: &str) -> reqwest::Url {
-        get_api_url(api, self.version)
+    /// Runs a search in the collection of a crawl or bulk job.
+    ///
+    /// Every job indexes the objects it extracts in a collection of the
+    /// same name, which can be searched like `GLOBAL-INDEX`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// let result = diffbot.search_job("my_crawl",
+    ///                                 "type:article sortby:date",
+    ///                                 &[("num", "50")]);
+    /// # }
+    /// ```
+    #[cfg(feature = "search")]
+    pub fn search_job<S: ToString>(&self, name: &str, query: &str,
+                                   options: &[(S, S)])
+                                   -> DiffbotResult {
+        self.search_with_options(name, query, options)
     }
 
-    // Process a request and analyze the result
-    fn process_request(builder: reqwest::RequestBuilder) -> DiffbotResult {
-        let mut result = try!(builder.send());
+    /// Iterates over every object matching a search, page by page.
+    ///
+    /// Each object comes with its search metadata, as a `SearchHit`.
+    #[cfg(feature = "search")]
+    pub fn search_results<'a, T>(&'a self, col: &str, query: &str)
+                                 -> SearchResults<'a, T>
+        where T: DeserializeOwned
+    {
+        SearchResults::new(self, col, query)
+    }
 
-        let json_result = match try!(serde_json::from_reader(&mut result)) {
-            serde_json::Value::Object(obj) => obj,
-            _ => return Err(Error::Api(0, "Invalid response".to_string())),
-        };
+    /// Removes a document from the search collection of a crawl or bulk
+    /// job.
+    ///
+    /// `doc_id` is the `docId` of the object, as found in search results.
+    /// The job and the rest of its collection are left untouched.
+    #[cfg(feature = "search")]
+    pub fn delete_from_collection(&self, col: &str, doc_id: &str)
+                                  -> DiffbotResult {
+        self.do_crawl_bulk::<&str>("search",
+                                   vec![("token", &self.token),
+                                        ("col", col),
+                                        ("docId", doc_id),
+                                        ("delete", "1")],
+                                   &[])
+    }
 
-        if json_result.contains_key("error") {
-            let error_code = json_result.get("errorCode")
-                                        .and_then(|c| c.as_u64())
-                                        .unwrap_or(0u64);
-            let error = json_result["error"].as_str().unwrap_or("");
-            return Err(Error::Api(error_code as u32, error.to_string()));
-        }
+    /// Removes every document from the search collection of a crawl or bulk
+    /// job, without deleting the job.
+    #[cfg(feature = "search")]
+    pub fn purge_collection(&self, col: &str) -> DiffbotResult {
+        self.do_crawl_bulk::<&str>("search",
+                                   vec![("token", &self.token),
+                                        ("col", col),
+                                        ("purge", "1")],
+                                   &[])
+    }
 
-        Ok(json_result)
+    /// Returns the plan and usage of the account of the token.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// let account = diffbot.account().unwrap();
+    /// println!("{:?} calls left this month", account.remaining_calls());
+    /// # }
+    /// ```
+    pub fn account(&self) -> Result<models::Account, Error> {
+        let mut url = try!(self.get_api_url("account"));
+        url.query_pairs_mut().append_pair("token", &self.token);
+        // Quota checks need the current numbers, never cached ones.
+        let request = HttpRequest::get(url).with_cache(CachePolicy::Bypass);
+        self.execute(&request).and_then(models::parse)
     }
 
-    fn prepare_search_url<S: ToString>(&self, col: &str, query: &str,
+    /// Checks that the token is accepted by the API.
+    ///
+    /// Makes a single call to the account API, whatever the version of the
+    /// client, without retries and bypassing the cache. Returns an error
+    /// only when the API could not be reached, or gave another answer.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// let diffbot = Diffbot::v3("insert_your_token_here");
+    /// match diffbot.validate_token().unwrap() {
+    ///     TokenStatus::Valid => println!("ready"),
+    ///     TokenStatus::Unauthorized => println!("check your token"),
+    ///     TokenStatus::Throttled => println!("slow down"),
+    /// }
+    /// # }
+    /// ```
+    pub fn validate_token(&self) -> Result<TokenStatus, Error> {
+        let mut url = try!(api::get_api_url(self.endpoints.for_api("account"),
+                                          "account",
+                                          3));
+        url.query_pairs_mut().append_pair("token", &self.token);
+        let request = HttpRequest::get(url);
+        match self.fetch(&request).and_then(response::parse_response) {
+            Ok(_) => Ok(TokenStatus::Valid),
+            Err(ref err) if err.is_auth() => Ok(TokenStatus::Unauthorized),
+            Err(ref err) if err.is_throttled() => Ok(TokenStatus::Throttled),
+            Err(err) => Err(err.with_context(request.context())),
+        }
+    }
+
+    // Adds the cookies and credentials for the target page to a request,
+    // unless it already sets them
+    pub(crate) fn forward_site_headers(&self, mut request: HttpRequest)
+                                       -> HttpRequest {
+        let target_url = match request.target_url {
+            Some(ref target_url) => target_url.clone(),
+            None => return request,
+        };
+        let host = target_url.host_str().unwrap_or("").to_lowercase();
+        let cookie = self.cookies
+                         .as_ref()
+                         .and_then(|jar| jar.header_for(&target_url));
+        let authorization = self.site_credentials
+                                .iter()
+                                .filter(|&&(ref domain, _)| {
+                                    cookies::domain_match(&host, domain)
+                                })
+                                .max_by_key(|&&(ref domain, _)| domain.len())
+                                .map(|&(_, ref credentials)| {
+                                    credentials.header()
+                                });
+        let headers = vec![("X-Forward-Cookie", cookie),
+                           ("X-Forward-Authorization", authorization)];
+        for (name, value) in headers {
+            let is_set = request.headers.iter().any(|&(ref n, _)| {
+                n.eq_ignore_ascii_case(name)
+            });
+            if let (Some(value), false) = (value, is_set) {
+                request.headers.push((name.to_string(), value));
+            }
+        }
+        request
+    }
+
+    // Returns the URL of an endpoint, if the API version offers it
+    fn get_api_url(&self, api: &str) -> Result<reqwest::Url, Error> {
+        api::get_api_url(self.endpoints.for_api(api), api, self.version)
+    }
+
+    // Sends a request, retrying it according to the retry policy
+    fn execute(&self, request: &HttpRequest) -> DiffbotResult {
+        self.execute_with(request, response::parse_response)
+    }
+
+    // Sends a request with retries, interpreting the response with `parse`
+    fn execute_with<T, F>(&self, request: &HttpRequest, parse: F)
+                          -> Result<T, Error>
+        where F: Fn(HttpResponse) -> Result<T, Error>
+    {
+        let retry_policy = if request.method == reqwest::Method::Get ||
+                              self.retry_posts {
+            self.retry_policy.as_ref()
+        } else {
+            None
+        };
+        let mut attempt = 0;
+        loop {
+            let err = match self.send_once(request, &parse) {
+                Ok(result) => return Ok(result),
+                Err(err) => err,
+            };
+            let delay = retry_policy.and_then(|policy| {
+                                        policy.delay(attempt, &err)
+                                    });
+            match delay {
+                Some(delay) => thread::sleep(delay),
+                None => return Err(err.with_context(request.context())),
+            }
+            attempt += 1;
+        }
+    }
+
+    // Sends a request once and analyze the result, going through the cache
+    fn send_once<T, F>(&self, request: &HttpRequest, parse: &F)
+                       -> Result<T, Error>
+        where F: Fn(HttpResponse) -> Result<T, Error>
+    {
+        // Responses to calls with credentials may differ for each of them,
+        // which are not part of the key.
+        let cache = match self.cache {
+            Some(ref cache) if request.method == reqwest::Method::Get &&
+                               !request.has_credentials() => cache,
+            _ => return self.fetch(request).and_then(parse),
+        };
+
+        let key = request.key();
+        if request.cache.reads() {
+            if let Some(body) = cache.get(&key) {
+                return parse(HttpResponse {
+                    status: 200,
+                    retry_after: None,
+                    headers: Vec::new(),
+                    body: body,
+                    elapsed: None,
+                });
+            }
+        }
+
+        let response = try!(self.fetch(request));
+        let body = if request.cache.writes() && response.status == 200 {
+            Some(response.body.clone())
+        } else {
+            None
+        };
+        let result = parse(response);
+        if let (true, Some(body)) = (result.is_ok(), body) {
+            cache.put(&key, &body);
+        }
+        result
+    }
+
+    // Gets the response to a request, from the API or from fixtures
+    fn fetch(&self, request: &HttpRequest) -> Result<HttpResponse, Error> {
+        let response = match self.fixtures {
+            Some(ref fixtures) if fixtures.mode() == FixtureMode::Replay => {
+                try!(fixtures.load(request))
+            }
+            Some(ref fixtures) => {
+                let response = try!(self.send_http(request));
+                try!(fixtures.save(request, &response));
+                response
+            }
+            None => try!(self.send_http(request)),
+        };
+        Ok(response)
+    }
+
+    // Sends a request once and returns a reader over the body of a
+    // successful response, without buffering it.
+    #[cfg(any(feature = "jobs", feature = "kg"))]
+    fn open(&self, request: &HttpRequest)
+            -> Result<Box<dyn Read + Send>, Error> {
+        let response = if self.fixtures.is_some() {
+            try!(self.fetch(request))
+        } else {
+            let response = try!(self.send_streamed(request));
+            if response.status() == 200 {
+                return Ok(response.into_body());
+            }
+            try!(read_response(response, self.max_response_size))
+        };
+        if response.status == 200 {
+            Ok(Box::new(Cursor::new(response.body)))
+        } else {
+            let err = response::parse_error(response);
+            Err(err.with_context(request.context()))
+        }
+    }
+
+    // Downloads a file that is not part of the API, returning its content
+    // type and body
+    pub(crate) fn download(&self, url: &reqwest::Url)
+                           -> Result<(Option<String>, Box<dyn Read + Send>),
+                                     Error> {
+        let request = HttpRequest::get(url.clone());
+        let response = try!(self.send_streamed(&request));
+        let content_type = response.header("Content-Type")
+                                   .map(|value| value.trim().to_string());
+        if response.status() == 200 {
+            return Ok((content_type, response.into_body()));
+        }
+        let response = try!(read_response(response, self.max_response_size));
+        Err(response::parse_error(response).with_context(request.context()))
+    }
+
+    // Sends a request over the network and reads the response, hedging GET
+    // calls if asked to
+    fn send_http(&self, request: &HttpRequest)
+                 -> Result<HttpResponse, Error> {
+        match self.hedge_delay {
+            Some(delay) if request.method == reqwest::Method::Get => {
+                self.send_hedged(request, delay)
+            }
+            _ => self.send_http_once(request),
+        }
+    }
+
+    // Sends a request, and a copy of it if no response came within `delay`.
+    // Returns the first successful response, or the last error. The other
+    // attempt is not cancelled, but once a response is returned its outcome
+    // is not recorded by the circuit breaker and the rate limiter.
+    fn send_hedged(&self, request: &HttpRequest, delay: Duration)
+                   -> Result<HttpResponse, Error> {
+        let (sender, receiver) = mpsc::channel();
+        let settled = Arc::new(AtomicBool::new(false));
+        let spawn = |sender: mpsc::Sender<Result<HttpResponse, Error>>| {
+            let client = self.clone();
+            let request = request.clone();
+            let settled = settled.clone();
+            thread::spawn(move || {
+                let send = || client.send_request(&request);
+                let _ = sender.send(client.send_guarded(send, Some(&settled)));
+            });
+        };
+
+        spawn(sender.clone());
+        if let Ok(result) = receiver.recv_timeout(delay) {
+            return result;
+        }
+        spawn(sender);
+        let stopped = io::Error::new(io::ErrorKind::Other,
+                                     "request thread stopped");
+        let mut result = Err(Error::Io(stopped));
+        for _ in 0..2 {
+            result = match receiver.recv() {
+                Ok(result) => result,
+                Err(_) => break,
+            };
+            if result.is_ok() {
+                break;
+            }
+        }
+        settled.store(true, Ordering::SeqCst);
+        result
+    }
+
+    // Sends a request over the network once and reads the response
+    fn send_http_once(&self, request: &HttpRequest)
+                      -> Result<HttpResponse, Error> {
+        self.send_guarded(|| self.send_request(request), None)
+    }
+
+    // Sends a request with `send`, through the circuit breaker and the rate
+    // limiter. The outcome is not recorded if `settled` is set by then.
+    fn send_guarded<F>(&self, send: F, settled: Option<&AtomicBool>)
+                       -> Result<HttpResponse, Error>
+        where F: FnOnce() -> Result<TransportResponse, Error>
+    {
+        if let Some(ref breaker) = self.breaker {
+            try!(breaker.acquire());
+        }
+        self.rate_limiter.wait();
+        let start = Instant::now();
+        let response = send().and_then(|response| {
+            read_response(response, self.max_response_size)
+        });
+        let late = settled.map_or(false, |settled| {
+            settled.load(Ordering::SeqCst)
+        });
+        if let (Some(breaker), false) = (self.breaker.as_ref(), late) {
+            let status = response.as_ref().map(|response| response.status);
+            breaker.record(is_healthy(status));
+        }
+        let mut response = try!(response);
+        response.elapsed = Some(start.elapsed());
+        if !late {
+            self.rate_limiter.observe(&response);
+        }
+        Ok(response)
+    }
+
+    // Sends a request through the circuit breaker and the rate limiter like
+    // `send_guarded`, but returns the response before its body is read.
+    fn send_streamed(&self, request: &HttpRequest)
+                     -> Result<TransportResponse, Error> {
+        if let Some(ref breaker) = self.breaker {
+            try!(breaker.acquire());
+        }
+        self.rate_limiter.wait();
+        let response = self.send_request(request);
+        if let Some(ref breaker) = self.breaker {
+            let status = response.as_ref().map(TransportResponse::status);
+            breaker.record(is_healthy(status));
+        }
+        let response = try!(response);
+        self.rate_limiter.observe(&HttpResponse {
+            status: response.status(),
+            retry_after: retry_after(&response),
+            headers: response.headers().to_vec(),
+            body: Vec::new(),
+            elapsed: None,
+        });
+        Ok(response)
+    }
+
+    // Sends a request over the network, with the custom transport if any
+    fn send_request(&self, request: &HttpRequest)
+                    -> Result<TransportResponse, Error> {
+        if let Some(ref transport) = self.transport {
+            let mut request = request.clone();
+            request.headers.extend(self.headers_for(&request.url)
+                                       .iter()
+                                       .cloned());
+            return transport.send(&PreparedRequest::new(request));
+        }
+        let body = request.body.clone().map(reqwest::Body::from);
+        self.send_request_with_body(request, body).map(from_reqwest)
+    }
+
+    // Returns the headers set with `with_header`, if `url` is on the
+    // servers of the API.
+    fn headers_for(&self, url: &reqwest::Url) -> &[(String, String)] {
+        if self.endpoints.contains(url) {
+            &self.headers
+        } else {
+            &[]
+        }
+    }
+
+    // Sends a request over the network, with a body given separately
+    fn send_request_with_body(&self, request: &HttpRequest,
+                              body: Option<reqwest::Body>)
+                              -> Result<reqwest::Response, Error> {
+        let mut builder = self.client
+                              .request(request.method.clone(),
+                                       request.url.clone())
+                              .header(user_agent());
+        if let Some(ref content_type) = request.content_type {
+            builder = builder.header(ContentType(content_type.clone()));
+        }
+        if let Some(ref encoding) = request.content_encoding {
+            builder = builder.header(ContentEncoding(vec![encoding.clone()]));
+        }
+        let mut headers = reqwest::header::Headers::new();
+        headers.set_raw("X-Request-Id",
+                        vec![request.request_id.clone().into_bytes()]);
+        let gateway = self.headers_for(&request.url);
+        for &(ref name, ref value) in request.headers.iter().chain(gateway) {
+            let value = vec![value.clone().into_bytes()];
+            headers.set_raw(name.clone(), value);
+        }
+        builder = builder.headers(headers);
+        if let Some(body) = body {
+            builder = builder.body(body);
+        }
+
+        builder.send().map_err(|err| TransportError::from_reqwest(err).into())
+    }
+
+    #[cfg(feature = "search")]
+    fn prepare_search_url<S: ToString>(&self, col: &str, query: &str,
                                        options: &[(S, S)])
-                                       -> reqwest::Url {
+                                       -> Result<reqwest::Url, Error> {
         let mut params = Vec::<(String, String)>::new();
-        params.push(("token".to_string(), self.token.clone()));
+        params.push(("token".to_string(), self.token.to_string()));
         params.push(("col".to_string(), col.to_string()));
         params.push(("query".to_string(), query.to_string()));
         for &(ref key, ref value) in options.iter() {
             params.push((key.to_string(), value.to_string()));
         }
 
-        // We control the URL, it should always be valid.
-        let mut url = self.get_api_url("search");
+        let mut url = try!(self.get_api_url("search"));
         url.query_pairs_mut().extend_pairs(&params);
 
-        url
+        Ok(url)
     }
 
     // Returns the diffbot URL for the given call
-    fn prepare_url<S: ToString>(&self, api: API, target_url: &str,
+    fn prepare_url<S: ToString>(&self, api: &API, target_url: &reqwest::Url,
                                 options: &[(S, S)])
-                                -> reqwest::Url {
+                                -> Result<reqwest::Url, Error> {
 
         let mut params = Vec::<(String, String)>::new();
-        params.push(("token".to_string(), self.token.clone()));
+        params.push(("token".to_string(), self.token.to_string()));
         params.push(("url".to_string(), target_url.to_string()));
         for &(ref key, ref value) in options.iter() {
             params.push((key.to_string(), value.to_string()));
         }
+        for &(ref key, ref value) in &self.default_options {
+            if !params.iter().any(|&(ref k, _)| k == key) {
+                params.push((key.clone(), value.clone()));
+            }
+        }
 
         // We control the URL, it should always be valid.
-        let mut url = api.get_url(self.version);
+        let mut url = try!(api.get_url(&self.endpoints.extract, self.version));
         url.query_pairs_mut().extend_pairs(&params);
 
-        url
+        Ok(url)
+    }
+}
+
+#[cfg(feature = "kg")]
+impl Diffbot {
+    /// Runs a DQL query of the Knowledge Graph.
+    ///
+    /// The matching entities are in the `data` array of the result.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// let result = diffbot.kg_query("type:Organization name:\"Diffbot\"")
+    ///                     .unwrap();
+    /// println!("{} organizations", result["hits"]);
+    /// # }
+    /// ```
+    pub fn kg_query(&self, query: &str) -> DiffbotResult {
+        self.kg_query_with_options::<&str>(query, &[])
+    }
+
+    /// Runs a DQL query of the Knowledge Graph with extra options, such as
+    /// `size` or `from`.
+    pub fn kg_query_with_options<S: ToString>(&self, query: &str,
+                                              options: &[(S, S)])
+                                              -> DiffbotResult {
+        self.execute(&self.kg_request(query, options))
+    }
+
+    // Returns the request running a DQL query.
+    fn kg_request<S: ToString>(&self, query: &str, options: &[(S, S)])
+                               -> HttpRequest {
+        let mut url = api::get_kg_url(&self.endpoints.kg, "dql");
+        url.query_pairs_mut()
+           .append_pair("token", &self.token)
+           .append_pair("type", "query")
+           .append_pair("query", query)
+           .extend_pairs(options.iter().map(|&(ref key, ref value)| {
+               (key.to_string(), value.to_string())
+           }));
+        HttpRequest::get(url)
+    }
+
+    /// Iterates over every entity matching a DQL query, page by page.
+    ///
+    /// Large result sets are requested a page at a time as the iteration
+    /// goes; the entities are returned without their score.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # extern crate serde_json;
+    /// # use diffbot::*;
+    /// use serde_json::Value;
+    ///
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// let query = "type:Organization location.country.name:\"France\"";
+    /// for entity in diffbot.kg_query_iter::<Value>(query).page_size(200) {
+    ///     println!("{}", entity.unwrap()["name"]);
+    /// }
+    /// # }
+    /// ```
+    pub fn kg_query_iter<'a, T>(&'a self, query: &str) -> KgResults<'a, T>
+        where T: DeserializeOwned
+    {
+        KgResults::new(self, query)
+    }
+
+    /// Returns an asynchronous stream of the entities matching a DQL query.
+    ///
+    /// The pages are downloaded by `kg_query_iter` on a background thread,
+    /// which stays at most a few entities ahead of the consumer and stops
+    /// when the stream is dropped. Requires the `stream` feature.
+    #[cfg(feature = "stream")]
+    pub fn kg_query_stream(&self, query: &str)
+        -> impl futures::Stream<Item = Result<serde_json::Value, Error>> {
+        stream::spawn_kg_results(self.clone(), query)
+    }
+
+    /// Looks up an entity of the Knowledge Graph by its URI or ID.
+    ///
+    /// Takes the URIs found in extracted objects, such as the `uri` of
+    /// article tags (`https://diffbot.com/entity/CZs2UmAw8NyW1-z3Y9TZ5Pw`),
+    /// or a bare ID. Returns `None` if there is no such entity.
+    ///
+    /// Unlike other queries, lookups go through the cache of the client, if
+    /// set: an entity changes little from one call to the next.
+    pub fn kg_entity(&self, uri: &str)
+                     -> Result<Option<serde_json::Value>, Error> {
+        let id = uri.trim_end_matches('/').rsplit('/').next().unwrap_or(uri);
+        let query = format!("id:{}", id);
+        let request = self.kg_request(&query, &[("size", "1")])
+                          .with_cache(CachePolicy::Use);
+        let mut result = try!(self.execute(&request));
+        let item = match result.remove("data") {
+            Some(serde_json::Value::Array(data)) => data.into_iter().next(),
+            _ => None,
+        };
+        // Entities come with their score, unless asked otherwise.
+        Ok(item.map(|mut item| match item.get_mut("entity") {
+            Some(entity) => entity.take(),
+            None => item,
+        }))
+    }
+
+    /// Returns the tags of an article with the Knowledge Graph entity of
+    /// each, looked up with `kg_entity`.
+    ///
+    /// Each entity is looked up once, even if several tags share its URI;
+    /// tags without a URI, or whose entity is not found, have no entity.
+    /// Lookups go through the cache and rate limit of the client, if set.
+    /// Fails with the first failed lookup.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// use diffbot::models::{self, Article, ExtractResponse};
+    ///
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// let response: ExtractResponse<Article> =
+    ///     diffbot.call(API::Article, "http://blog.diffbot.com")
+    ///            .and_then(models::parse)
+    ///            .unwrap();
+    /// for tag in diffbot.tags_with_entities(&response.objects[0]).unwrap() {
+    ///     if let Some(entity) = tag.entity {
+    ///         println!("{:?}: {}", tag.tag.label, entity["description"]);
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn tags_with_entities(&self, article: &models::Article)
+                              -> Result<Vec<models::EnrichedTag>, Error> {
+        self.tags_with_entities_concurrently(article, 1)
+    }
+
+    /// Returns the tags of an article with their entity like
+    /// `tags_with_entities`, with up to `concurrency` lookups at the same
+    /// time. After a failed lookup, no new lookup is started.
+    pub fn tags_with_entities_concurrently(&self, article: &models::Article,
+                                           concurrency: usize)
+                                           -> Result<Vec<models::EnrichedTag>,
+                                                     Error> {
+        let mut uris: Vec<String> = article.tags
+                                           .iter()
+                                           .filter_map(|tag| tag.uri.clone())
+                                           .collect();
+        uris.sort();
+        uris.dedup();
+
+        let queue = Arc::new(Mutex::new(uris.into_iter()));
+        let failed = Arc::new(AtomicBool::new(false));
+        let workers: Vec<_> = (0..concurrency.max(1))
+            .map(|_| {
+                let client = self.clone();
+                let queue = queue.clone();
+                let failed = failed.clone();
+                thread::spawn(move || {
+                    let mut found = HashMap::new();
+                    loop {
+                        if failed.load(Ordering::SeqCst) {
+                            return Ok(found);
+                        }
+                        let uri = match queue.lock().unwrap().next() {
+                            Some(uri) => uri,
+                            None => return Ok(found),
+                        };
+                        match client.kg_entity(&uri) {
+                            Ok(entity) => found.insert(uri, entity),
+                            Err(err) => {
+                                failed.store(true, Ordering::SeqCst);
+                                return Err(err);
+                            }
+                        };
+                    }
+                })
+            })
+            .collect();
+
+        let mut entities = HashMap::new();
+        let mut failure = None;
+        for worker in workers {
+            match worker.join().unwrap() {
+                Ok(found) => entities.extend(found),
+                Err(err) => failure = failure.or(Some(err)),
+            }
+        }
+        if let Some(err) = failure {
+            return Err(err);
+        }
+        Ok(article.tags
+                  .iter()
+                  .map(|tag| {
+                      let entity = tag.uri
+                                      .as_ref()
+                                      .and_then(|uri| entities.get(uri))
+                                      .and_then(Option::clone);
+                      models::EnrichedTag {
+                          tag: tag.clone(),
+                          entity: entity,
+                      }
+                  })
+                  .collect())
+    }
+
+    /// Counts the entities matching a DQL query for each value of `field`.
+    ///
+    /// Sends `query` with a `facet:` clause for the field, such as
+    /// `industries` or `location.country.name`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// let industries = diffbot.kg_facet("type:Organization", "industries")
+    ///                         .unwrap();
+    /// for bucket in industries.buckets {
+    ///     println!("{}: {}", bucket.value, bucket.count);
+    /// }
+    /// # }
+    /// ```
+    pub fn kg_facet(&self, query: &str, field: &str)
+                    -> Result<models::FacetResult, Error> {
+        let query = format!("{} facet:{}", query, field);
+        self.kg_query(&query).and_then(models::parse)
+    }
+
+    /// Exports every entity matching a DQL query, in one download.
+    ///
+    /// Unlike `kg_query_iter`, the results are not paged: the body is
+    /// returned as it arrives, so exports of millions of entities can be
+    /// written to a file or processed in bounded memory. JSON exports hold
+    /// one entity per line, and can be parsed with `JsonStream::lines`. The
+    /// request is not retried, and does not go through the cache.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # use std::fs::File;
+    /// # use std::io;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// let mut csv = diffbot.kg_export("type:Organization industries:Banks",
+    ///                                 DataFormat::Csv)
+    ///                      .unwrap();
+    /// let mut file = File::create("banks.csv").unwrap();
+    /// io::copy(&mut csv, &mut file).unwrap();
+    /// # }
+    /// ```
+    pub fn kg_export(&self, query: &str, format: DataFormat)
+                     -> Result<Box<dyn Read + Send>, Error> {
+        self.kg_export_with_options::<&str>(query, format, &[])
+    }
+
+    /// Exports the entities matching a DQL query with extra options, such
+    /// as `exportspec` to choose the columns of a CSV export, or `size` to
+    /// limit the number of entities.
+    pub fn kg_export_with_options<S: ToString>(&self, query: &str,
+                                               format: DataFormat,
+                                               options: &[(S, S)])
+                                               -> Result<Box<dyn Read + Send>,
+                                                         Error> {
+        let options: Vec<(String, String)> =
+            options.iter()
+                   .map(|&(ref key, ref value)| {
+                       (key.to_string(), value.to_string())
+                   })
+                   .collect();
+        let mut url = api::get_kg_url(&self.endpoints.kg, "dql");
+        url.query_pairs_mut()
+           .append_pair("token", &self.token)
+           .append_pair("type", "query")
+           .append_pair("query", query)
+           .append_pair("format", match format {
+               DataFormat::Json => "jsonl",
+               DataFormat::Csv => "csv",
+           });
+        if !options.iter().any(|&(ref key, _)| key == "size") {
+            url.query_pairs_mut().append_pair("size", "-1");
+        }
+        url.query_pairs_mut().extend_pairs(options);
+        self.open(&HttpRequest::get(url))
+    }
+
+    /// Exports the entities matching a DQL query, parsed one at a time as
+    /// the download arrives.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # use diffbot::models::Person;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// let query = "type:Person employments.employer.name:Diffbot";
+    /// for person in diffbot.kg_export_entities::<Person>(query).unwrap() {
+    ///     println!("{:?}", person.unwrap().name);
+    /// }
+    /// # }
+    /// ```
+    pub fn kg_export_entities<T>(&self, query: &str)
+                                 -> Result<JsonStream<T>, Error>
+        where T: DeserializeOwned
+    {
+        let reader = try!(self.kg_export(query, DataFormat::Json));
+        Ok(JsonStream::lines(reader))
+    }
+
+    /// Finds the entities, sentiment and facts of a text with the Natural
+    /// Language API.
+    ///
+    /// See the `nl` module.
+    pub fn nl_analyze(&self, text: &str, options: &nl::NlOptions)
+                      -> Result<nl::Analysis, Error> {
+        let body = try!(serde_json::to_vec(&options.document(text)));
+        let mut url = self.endpoints.nl.join("v1/").unwrap();
+        url.query_pairs_mut()
+           .append_pair("token", &self.token)
+           .extend_pairs(options.query_pairs());
+        let content_type = Mime(TopLevel::Application, SubLevel::Json, vec![]);
+        let request = HttpRequest::post(url, content_type, body);
+        self.execute(&request).and_then(models::parse)
+    }
+}
+
+#[cfg(all(feature = "jobs", feature = "kg"))]
+impl Diffbot {
+    /// Matches a batch of records to entities of the Knowledge Graph, and
+    /// returns the outcome of each record.
+    ///
+    /// Each record is a partial entity, such as
+    /// `{"type": "Organization", "name": "Diffbot"}`. The batch is submitted
+    /// as a bulk Enhance job, whose status is checked according to `policy`
+    /// until it completes; then the matches are downloaded. The rows are
+    /// returned in the order of the records.
+    ///
+    /// Fails with `Error::JobTimeout` if the job is still running when the
+    /// policy times out: its results can still be downloaded later with
+    /// `enhance_bulk_results`, using the ID in the error message.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # #[macro_use] extern crate serde_json;
+    /// # use diffbot::*;
+    /// # use diffbot::models::EnhanceOutcome;
+    /// # use std::time::Duration;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// let records = vec![json!({"type": "Organization", "name": "Diffbot"}),
+    ///                    json!({"type": "Person", "name": "Mike Tung"})];
+    /// let policy = PollPolicy {
+    ///     interval: Duration::from_secs(10),
+    ///     timeout: Duration::from_secs(600),
+    /// };
+    /// for row in diffbot.enhance_bulk(&records, &policy).unwrap() {
+    ///     if let EnhanceOutcome::Matched { entity, .. } = row.outcome {
+    ///         println!("{}: {}", row.index, entity["name"]);
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn enhance_bulk<R: Serialize>(&self, records: &[R],
+                                      policy: &PollPolicy)
+                                      -> Result<Vec<models::EnhanceRow>,
+                                                Error> {
+        let id = try!(self.start_enhance_bulk(records));
+        let deadline = Instant::now() + policy.timeout;
+        loop {
+            let status = try!(self.enhance_bulk_status(&id));
+            if status.is_failed() {
+                let message = format!("bulk Enhance job {} failed", id);
+                return Err(Error::Api(ApiError::new(500, message)));
+            }
+            if status.is_finished() {
+                return self.enhance_bulk_results(&id);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                let message = format!("bulk Enhance job {} still running \
+                                       after {:?}",
+                                      id,
+                                      policy.timeout);
+                return Err(Error::JobTimeout(message));
+            }
+            thread::sleep(std::cmp::min(policy.interval, deadline - now));
+        }
+    }
+
+    /// Submits a batch of records as a bulk Enhance job, and returns the ID
+    /// of the job.
+    ///
+    /// See `enhance_bulk`, which also waits for the matches.
+    pub fn start_enhance_bulk<R: Serialize>(&self, records: &[R])
+                                            -> Result<String, Error> {
+        let body = try!(serde_json::to_vec(records));
+        let mut url = api::get_kg_url(&self.endpoints.kg, "enhance/bulk");
+        url.query_pairs_mut().append_pair("token", &self.token);
+        let content_type = Mime(TopLevel::Application, SubLevel::Json, vec![]);
+        let result = try!(self.execute(&HttpRequest::post(url,
+                                                          content_type,
+                                                          body)));
+        let id = ["job_id", "jobId", "bulkjobId"]
+                     .iter()
+                     .filter_map(|key| result.get(*key))
+                     .filter_map(|id| id.as_str())
+                     .next();
+        match id {
+            Some(id) => Ok(id.to_string()),
+            None => {
+                let message = "no job ID in the bulk Enhance response";
+                Err(Error::Api(ApiError::new(0, message)))
+            }
+        }
+    }
+
+    /// Returns the status of a bulk Enhance job.
+    pub fn enhance_bulk_status(&self, id: &str)
+                               -> Result<models::EnhanceBulkStatus, Error> {
+        let url = self.enhance_bulk_url(id, true);
+        // The status changes between polls, so it is never cached.
+        let request = HttpRequest::get(url).with_cache(CachePolicy::Bypass);
+        self.execute(&request).and_then(models::parse)
+    }
+
+    /// Downloads the outcome of each record of a completed bulk Enhance
+    /// job.
+    pub fn enhance_bulk_results(&self, id: &str)
+                                -> Result<Vec<models::EnhanceRow>, Error> {
+        let url = self.enhance_bulk_url(id, false);
+        let rows = try!(self.execute_with(&HttpRequest::get(url),
+                                          response::parse_array_response));
+        let mut rows: Vec<_> = rows.into_iter()
+                                   .enumerate()
+                                   .map(|(index, row)| {
+                                       models::EnhanceRow::from_value(index,
+                                                                      row)
+                                   })
+                                   .collect();
+        rows.sort_by_key(|row| row.index);
+        Ok(rows)
+    }
+
+    // Returns the URL of the results or the status of a bulk Enhance job
+    fn enhance_bulk_url(&self, id: &str, status: bool) -> reqwest::Url {
+        let mut url = api::get_kg_url(&self.endpoints.kg, "enhance/bulk");
+        {
+            let mut segments = url.path_segments_mut().unwrap();
+            segments.push(id);
+            if status {
+                segments.push("status");
+            }
+        }
+        url.query_pairs_mut().append_pair("token", &self.token);
+        url
+    }
+}
+
+#[cfg(feature = "jobs")]
+impl Diffbot {
+    /// Lists the existing crawls.
+    pub fn list_crawls(&self) -> Result<Vec<models::Job>, Error> {
+        self.list_jobs("crawl")
+    }
+
+    /// Lists the existing crawls selected by `filter`.
+    ///
+    /// The filter is applied by the client, to the full list of crawls.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # use diffbot::models::JobFilter;
+    /// # use std::time::Duration;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// let week = Duration::from_secs(7 * 24 * 3600);
+    /// let filter = JobFilter::new().failed().older_than(week);
+    /// for job in diffbot.list_crawls_matching(&filter).unwrap() {
+    ///     println!("{:?}", job.name);
+    /// }
+    /// # }
+    /// ```
+    pub fn list_crawls_matching(&self, filter: &models::JobFilter)
+                                -> Result<Vec<models::Job>, Error> {
+        let jobs = try!(self.list_crawls());
+        Ok(jobs.into_iter().filter(|job| filter.matches(job)).collect())
+    }
+
+    /// Lists the existing bulk jobs.
+    pub fn list_bulk_jobs(&self) -> Result<Vec<models::Job>, Error> {
+        self.list_jobs("bulk")
+    }
+
+    /// Lists the existing bulk jobs selected by `filter`.
+    ///
+    /// The filter is applied by the client, to the full list of bulk jobs.
+    /// See `list_crawls_matching`.
+    pub fn list_bulk_jobs_matching(&self, filter: &models::JobFilter)
+                                   -> Result<Vec<models::Job>, Error> {
+        let jobs = try!(self.list_bulk_jobs());
+        Ok(jobs.into_iter().filter(|job| filter.matches(job)).collect())
+    }
+
+    /// Starts a bulk job.
+    ///
+    /// Starts a bulk job called `name` on the given url list, using `api_url` on each.
+    pub fn bulk<U: IntoTargetUrl + Clone>(&self, name: &str, api: API,
+                                          urls: &[U])
+                                          -> DiffbotResult {
+        self.bulk_with_options::<U, &str>(name, api, urls, &[])
+    }
+
+    /// Starts a bulk job with extra options.
+    ///
+    /// Give `options` a list of (key, value) pairs.
+    /// Each URL is validated before the job is submitted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// # println!("{:?}",
+    /// diffbot.bulk_with_options("my_bulk_job", API::Analyze,
+    ///                          &["http://my.first.page.com",
+    ///                            "https://my.second.page.com"],
+    ///                          &[("repeat", "7.0"),
+    ///                            ("notifyEmail", "me@example.com")])
+    /// # );
+    /// # }
+    /// ```
+    pub fn bulk_with_options<U, S>(&self, name: &str, api: API, urls: &[U],
+                                   options: &[(S, S)])
+                                   -> DiffbotResult
+        where U: IntoTargetUrl + Clone,
+              S: AsRef<str>
+    {
+        let joined = try!(target::join_target_urls(urls));
+        let api_url = try!(api.get_url_string(&self.endpoints.extract,
+                                              self.version));
+        try!(self.check_quota(name, bulk_calls(urls.len(), options)));
+
+        self.do_crawl_bulk("bulk",
+                           vec![("name", name),
+                                ("token", &self.token),
+                                ("apiUrl", &api_url),
+                                ("urls", &joined)],
+                           options)
+    }
+
+    /// Starts bulk jobs on the URLs read from `reader`, one per line.
+    ///
+    /// Blank lines and lines starting with `#` are skipped. Every URL is
+    /// validated before anything is submitted; an invalid one fails with
+    /// `Error::InvalidUrl` giving its line number, and so does a list
+    /// without any URL. Long lists are split into several jobs with the
+    /// default `BulkLimits`, like `bulk_chunked`, and `options` are given
+    /// to each job.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # use std::fs::File;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// let urls = File::open("urls.txt").unwrap();
+    /// diffbot.bulk_from_reader("my_bulk_job", API::Article, urls,
+    ///                          &[("notifyEmail", "me@example.com")])
+    ///        .unwrap();
+    /// # }
+    /// ```
+    pub fn bulk_from_reader<R, S>(&self, name: &str, api: API, reader: R,
+                                  options: &[(S, S)])
+                                  -> Result<Vec<BulkPart>, Error>
+        where R: Read,
+              S: AsRef<str>
+    {
+        let urls = try!(target::read_target_urls(reader));
+        self.bulk_chunked(name, api, &urls, &BulkLimits::default(), options)
+    }
+
+    /// Starts bulk jobs on a URL list too large for a single job.
+    ///
+    /// Every URL is validated first. If the list fits in `limits`, a single
+    /// job called `name` is started; otherwise the list is split into jobs
+    /// called `name-part1`, `name-part2`, and so on, each given `options`.
+    /// Returns the jobs that were started. If starting one fails, the error
+    /// is returned and the jobs started before it keep running. An empty
+    /// list fails with `Error::InvalidUrl`.
+    pub fn bulk_chunked<U, S>(&self, name: &str, api: API, urls: &[U],
+                              limits: &BulkLimits, options: &[(S, S)])
+                              -> Result<Vec<BulkPart>, Error>
+        where U: IntoTargetUrl + Clone,
+              S: AsRef<str>
+    {
+        if urls.is_empty() {
+            let message = format!("no URL to submit to bulk job {:?}", name);
+            return Err(Error::InvalidUrl(message));
+        }
+        let mut parsed = Vec::with_capacity(urls.len());
+        for url in urls {
+            parsed.push(try!(url.clone().into_target_url()));
+        }
+        let chunks = target::chunk_target_urls(&parsed,
+                                               limits.max_urls,
+                                               limits.max_body_bytes);
+        let api_url = try!(api.get_url_string(&self.endpoints.extract,
+                                              self.version));
+        try!(self.check_quota(name, bulk_calls(parsed.len(), options)));
+
+        let mut parts = Vec::with_capacity(chunks.len());
+        for (index, chunk) in chunks.iter().enumerate() {
+            let part_name = if chunks.len() == 1 {
+                name.to_string()
+            } else {
+                format!("{}-part{}", name, index + 1)
+            };
+            let params = vec![("name", part_name.as_str()),
+                              ("token", &self.token),
+                              ("apiUrl", &api_url),
+                              ("urls", chunk)];
+            let response = try!(self.do_crawl_bulk("bulk", params, options));
+            parts.push(BulkPart {
+                name: part_name,
+                urls: chunk.split(' ').count(),
+                response: response,
+            });
+        }
+        Ok(parts)
+    }
+
+    /// Retrieves the result from a bulk job
+    pub fn get_bulk(&self, name: &str) -> DiffbotResult {
+        self.do_crawl_bulk::<&str>("bulk",
+                                   vec![("token", &self.token),
+                                        ("name", name),
+                                        ("format", "json")],
+                                   &[])
+    }
+
+    /// Waits for a bulk job to finish, like `wait_for_crawl`.
+    pub fn wait_for_bulk(&self, name: &str, policy: &PollPolicy)
+                         -> Result<models::Job, Error> {
+        self.wait_for_job("bulk", name, policy, &mut |_| {})
+    }
+
+    /// Pauses a bulk job.
+    pub fn pause_bulk(&self, name: &str) -> DiffbotResult {
+        self.control_job("bulk", name, "pause", "1")
+    }
+
+    /// Resumes a paused bulk job.
+    pub fn resume_bulk(&self, name: &str) -> DiffbotResult {
+        self.control_job("bulk", name, "pause", "0")
+    }
+
+    /// Deletes a bulk job and its results.
+    pub fn delete_bulk(&self, name: &str) -> DiffbotResult {
+        self.control_job("bulk", name, "delete", "1")
+    }
+
+    // Sets a single parameter of an existing crawl or bulk job
+    fn control_job(&self, api: &str, name: &str, key: &str, value: &str)
+                   -> DiffbotResult {
+        self.do_crawl_bulk::<&str>(api,
+                                   vec![("token", &self.token),
+                                        ("name", name),
+                                        (key, value)],
+                                   &[])
+    }
+
+    // Checks that a job making at most `calls` calls fits in the quota,
+    // when asked to with `with_quota_check` or `with_quota_warning`
+    fn check_quota(&self, name: &str, calls: Option<u64>)
+                   -> Result<(), Error> {
+        let check = match self.quota_check {
+            Some(ref check) => check,
+            None => return Ok(()),
+        };
+        let remaining = match try!(self.account()).remaining_calls() {
+            Some(remaining) => remaining,
+            None => return Ok(()),
+        };
+        if calls.map_or(false, |calls| calls <= remaining) {
+            return Ok(());
+        }
+        let shortfall = QuotaShortfall::new(name, calls, remaining);
+        match *check {
+            quota::QuotaCheck::Fail => {
+                Err(Error::QuotaExceeded(shortfall.to_string()))
+            }
+            quota::QuotaCheck::Warn(ref warn) => {
+                warn(&shortfall);
+                Ok(())
+            }
+        }
+    }
+
+    // Lists the crawl or bulk jobs of the token
+    fn list_jobs(&self, api: &str) -> Result<Vec<models::Job>, Error> {
+        let mut url = try!(self.get_api_url(api));
+        url.query_pairs_mut().append_pair("token", &self.token);
+        let response: models::JobsResponse =
+            try!(self.get(url).and_then(models::parse));
+        Ok(response.jobs)
+    }
+
+    // Returns the status of the crawl or bulk job with the given name
+    pub(crate) fn find_job(&self, api: &str, name: &str)
+                           -> Result<models::Job, Error> {
+        let result = self.do_crawl_bulk::<&str>(api,
+                                                vec![("token", &self.token),
+                                                     ("name", name),
+                                                     ("format", "json")],
+                                                &[]);
+        let status: models::JobsResponse =
+            try!(result.and_then(models::parse));
+        let job = status.jobs
+                        .into_iter()
+                        .find(|job| job.name.as_ref().map(String::as_str) ==
+                                    Some(name));
+        match job {
+            Some(job) => Ok(job),
+            None => {
+                let message = format!("No {} named {:?}", api, name);
+                Err(Error::Api(ApiError::new(404, message)))
+            }
+        }
+    }
+
+    // Polls a crawl or bulk job until it finishes or the policy times out
+    fn wait_for_job(&self, api: &str, name: &str, policy: &PollPolicy,
+                    observer: &mut dyn FnMut(&JobProgress))
+                    -> Result<models::Job, Error> {
+        let start = Instant::now();
+        let deadline = start + policy.timeout;
+        let mut first = None;
+        loop {
+            let job = try!(self.find_job(api, name));
+            let progress =
+                JobProgress::new(&job, start.elapsed(), first.as_ref());
+            observer(&progress);
+            if first.is_none() {
+                first = Some(progress);
+            }
+            if job.job_status.as_ref().map_or(false, |s| s.is_finished()) {
+                return Ok(job);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                let message = format!("{} {:?} still running after {:?}",
+                                      api,
+                                      name,
+                                      policy.timeout);
+                return Err(Error::JobTimeout(message));
+            }
+            thread::sleep(std::cmp::min(policy.interval, deadline - now));
+        }
+    }
+
+    /// Downloads the objects extracted by a bulk job.
+    ///
+    /// The objects are parsed one at a time as the response arrives, so
+    /// results of any size can be processed in bounded memory. The request
+    /// is not retried, and does not go through the cache.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # use diffbot::models::Article;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// for article in diffbot.bulk_data::<Article>("my_bulk_job").unwrap() {
+    ///     println!("{:?}", article.unwrap().title);
+    /// }
+    /// # }
+    /// ```
+    pub fn bulk_data<T: DeserializeOwned>(&self, name: &str)
+                                          -> Result<JsonStream<T>, Error> {
+        self.job_data("bulk", name, &[])
+    }
+
+    /// Downloads `num` objects extracted by a bulk job, skipping the first
+    /// `offset` ones.
+    ///
+    /// The body is returned as it arrives, in the requested format; JSON
+    /// data can be parsed with `JsonStream::array`. The request is not
+    /// retried, and does not go through the cache.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # use std::io;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// let mut csv = diffbot.get_bulk_data("my_bulk_job", 0, 1000,
+    ///                                     DataFormat::Csv)
+    ///                      .unwrap();
+    /// io::copy(&mut csv, &mut io::stdout()).unwrap();
+    /// # }
+    /// ```
+    pub fn get_bulk_data(&self, name: &str, offset: u64, num: u64,
+                         format: DataFormat)
+                         -> Result<Box<dyn Read + Send>, Error> {
+        self.job_data_slice("bulk", name, offset, num, format)
+    }
+
+    /// Iterates over the objects extracted by a bulk job, page by page.
+    ///
+    /// Pages are downloaded as the iteration reaches them, so only one page
+    /// is held in memory at a time. See `JobResults` for the page size.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # use diffbot::models::Article;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// for article in diffbot.bulk_results::<Article>("my_bulk_job") {
+    ///     println!("{:?}", article.unwrap().title);
+    /// }
+    /// # }
+    /// ```
+    pub fn bulk_results<'a, T>(&'a self, name: &str) -> JobResults<'a, T>
+        where T: DeserializeOwned
+    {
+        JobResults::new(self, "bulk", name)
+    }
+
+    // Opens the data of a crawl or bulk job
+    pub(crate) fn job_data<T>(&self, api: &str, name: &str,
+                              options: &[(&str, &str)])
+                              -> Result<JsonStream<T>, Error>
+        where T: DeserializeOwned
+    {
+        let reader = try!(self.open_job_data(api, name, DataFormat::Json,
+                                             options));
+        Ok(JsonStream::array(reader))
+    }
+
+    // Opens the data of a crawl or bulk job in the given format
+    fn open_job_data(&self, api: &str, name: &str, format: DataFormat,
+                     options: &[(&str, &str)])
+                     -> Result<Box<dyn Read + Send>, Error> {
+        let mut url = try!(self.get_api_url(&format!("{}/data", api)));
+        url.query_pairs_mut()
+           .append_pair("token", &self.token)
+           .append_pair("name", name)
+           .append_pair("format", &format.to_string())
+           .extend_pairs(options);
+        self.open(&HttpRequest::get(url))
+    }
+
+    // Opens a slice of the data of a crawl or bulk job
+    fn job_data_slice(&self, api: &str, name: &str, offset: u64, num: u64,
+                      format: DataFormat)
+                      -> Result<Box<dyn Read + Send>, Error> {
+        self.open_job_data(api,
+                           name,
+                           format,
+                           &[("offset", &offset.to_string()),
+                             ("num", &num.to_string())])
+    }
+
+    /// Starts a crawl job.
+    pub fn crawl<U: IntoTargetUrl + Clone>(&self, name: &str, api: API,
+                                           seeds: &[U])
+                                           -> DiffbotResult {
+
+        self.crawl_with_options::<U, &str>(name, api, seeds, &[])
+    }
+
+    /// Starts a crawl job with extra options.
+    ///
+    /// Give `options` a list of (key, value) pairs.
+    /// Each seed URL is validated before the job is submitted.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// # println!("{:?}",
+    /// diffbot.crawl_with_options("my_crawl_job", API::Analyze,
+    ///                            &["http://my.first.page.com",
+    ///                              "https://my.second.page.com"],
+    ///                            &[("repeat", "7.0"),
+    ///                              ("maxHops", "3")])
+    /// # );
+    /// # }
+    /// ```
+    pub fn crawl_with_options<U, S>(&self, name: &str, api: API, seeds: &[U],
+                                    options: &[(S, S)])
+                                    -> DiffbotResult
+        where U: IntoTargetUrl + Clone,
+              S: AsRef<str>
+    {
+
+        let api_url = try!(api.get_url_string(&self.endpoints.extract,
+                                              self.version));
+        let joined = try!(target::join_target_urls(seeds));
+        let max_to_process = find_option(options, "maxToProcess")
+                                 .and_then(|value| value.parse().ok());
+        try!(self.check_quota(name, max_to_process));
+
+        self.do_crawl_bulk("crawl",
+                           vec![("name", name),
+                                ("token", &self.token),
+                                ("apiUrl", &api_url),
+                                ("seeds", &joined)],
+                           options)
+    }
+
+    /// Retrieves the result from a crawl job.
+    pub fn get_crawl(&self, name: &str) -> DiffbotResult {
+        // TODO: specify `num` parameter
+        self.do_crawl_bulk::<&str>("crawl",
+                                   vec![("token", &self.token),
+                                        ("name", name),
+                                        ("format", "json")],
+                                   &[])
+    }
+
+    /// Adds seed URLs to an existing crawl job.
+    ///
+    /// The current seeds are read from the status of the job, which is then
+    /// updated with both lists. Seeds already in the job are not repeated,
+    /// and its other settings are left unchanged.
+    pub fn add_crawl_seeds<U>(&self, name: &str, seeds: &[U]) -> DiffbotResult
+        where U: IntoTargetUrl + Clone
+    {
+        let added = try!(target::join_target_urls(seeds));
+        let job = try!(self.find_job("crawl", name));
+        let current = job.seeds.unwrap_or_default();
+
+        let mut all: Vec<&str> = current.split_whitespace().collect();
+        for seed in added.split_whitespace() {
+            if !all.contains(&seed) {
+                all.push(seed);
+            }
+        }
+        self.do_crawl_bulk::<&str>("crawl",
+                                   vec![("token", &self.token),
+                                        ("name", name),
+                                        ("seeds", &all.join(" "))],
+                                   &[])
+    }
+
+    /// Waits for a crawl job to finish, checking its status as often as the
+    /// policy says.
+    ///
+    /// Returns the last status of the job once it completed, reached one of
+    /// its limits or failed; see `models::JobStatus::is_failed` to tell
+    /// them apart. Fails with `Error::JobTimeout` if the job is still
+    /// running after the policy timeout.
+    pub fn wait_for_crawl(&self, name: &str, policy: &PollPolicy)
+                          -> Result<models::Job, Error> {
+        self.wait_for_job("crawl", name, policy, &mut |_| {})
+    }
+
+    /// Waits for a crawl job to finish like `wait_for_crawl`, calling
+    /// `observer` with the progress of the job after each status check.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # use std::time::Duration;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// let policy = PollPolicy {
+    ///     interval: Duration::from_secs(30),
+    ///     timeout: Duration::from_secs(3600),
+    /// };
+    /// let report = |progress: &JobProgress| {
+    ///     println!("{} pages crawled ({:?} per second)",
+    ///              progress.pages_crawled,
+    ///              progress.crawl_rate);
+    /// };
+    /// diffbot.wait_for_crawl_with_progress("my_crawl_job", &policy, report)
+    ///        .unwrap();
+    /// # }
+    /// ```
+    pub fn wait_for_crawl_with_progress<F>(&self, name: &str,
+                                           policy: &PollPolicy,
+                                           mut observer: F)
+                                           -> Result<models::Job, Error>
+        where F: FnMut(&JobProgress)
+    {
+        self.wait_for_job("crawl", name, policy, &mut observer)
+    }
+
+    /// Pauses a crawl job.
+    pub fn pause_crawl(&self, name: &str) -> DiffbotResult {
+        self.control_job("crawl", name, "pause", "1")
+    }
+
+    /// Resumes a paused crawl job.
+    pub fn resume_crawl(&self, name: &str) -> DiffbotResult {
+        self.control_job("crawl", name, "pause", "0")
     }
 
-    /// Starts a bulk job.
+    /// Deletes a crawl job and its results.
+    pub fn delete_crawl(&self, name: &str) -> DiffbotResult {
+        self.control_job("crawl", name, "delete", "1")
+    }
+
+    /// Returns an asynchronous stream of the objects extracted by a crawl
+    /// job.
     ///
-    /// Starts a bulk job called `name` on the given url list, using `api_url` on each.
-    pub fn bulk<S: AsRef<str> + ::std::borrow::Borrow<str>>
-        (&self, name: &str, api: API, urls: &[S])
-         -> DiffbotResult {
-        self.bulk_with_options(name, api, urls, &[])
+    /// The pages are downloaded by `crawl_results` on a background thread,
+    /// which stays at most a few objects ahead of the consumer and stops
+    /// when the stream is dropped. Requires the `stream` feature.
+    #[cfg(feature = "stream")]
+    pub fn crawl_results_stream(&self, name: &str)
+        -> impl futures::Stream<Item = Result<serde_json::Value, Error>> {
+        stream::spawn_results(self.clone(), "crawl", name)
     }
 
-    /// Starts a bulk job with extra options.
+    /// Returns a future resolving to the status of a crawl job once it
+    /// finishes, like `wait_for_crawl`.
     ///
-    /// Give `options` a list of (key, value) pairs.
+    /// The status is checked on a background thread, first after five
+    /// seconds and then backing off up to every five minutes; the thread
+    /// stops when the future is dropped. Throttling, server errors and
+    /// network failures do not end the wait: the status is checked again
+    /// later. There is no deadline: combine the future with a timer to give
+    /// up. Requires the `stream` feature.
     ///
     /// # Example
     ///
-    /// ```
+    /// ```no_run
     /// # extern crate diffbot;
+    /// # extern crate futures;
     /// # use diffbot::*;
     /// # fn main() {
     /// # let diffbot = Diffbot::v3("token");
-    /// # println!("{:?}",
-    /// diffbot.bulk_with_options("my_bulk_job", API::Analyze,
-    ///                          &["http://my.first.page.com",
-    ///                            "https://my.second.page.com"],
-    ///                          &[("repeat", "7.0"),
-    ///                            ("notifyEmail", "me@example.com")])
-    /// # );
+    /// let completion = diffbot.crawl_completion("my_crawl_job");
+    /// let job = futures::executor::block_on(completion).unwrap();
+    /// println!("{:?}", job.job_status);
     /// # }
     /// ```
-    pub fn bulk_with_options<S: AsRef<str> + ::std::borrow::Borrow<str>>
-        (&self, name: &str, api: API, urls: &[S], options: &[(S, S)])
-         -> DiffbotResult {
-        let joined = urls.join(" ");
-        let api_url = api.get_url_string(self.version);
+    #[cfg(feature = "stream")]
+    pub fn crawl_completion(&self, name: &str)
+        -> impl futures::Future<Output = Result<models::Job, Error>> {
+        stream::spawn_completion(self.clone(), "crawl", name)
+    }
 
-        self.do_crawl_bulk("bulk",
-                           vec![("name", name),
-                                ("token", &self.token),
-                                ("apiUrl", &api_url),
-                                ("urls", &joined)],
-                           options)
+    /// Downloads the objects extracted by a crawl job.
+    ///
+    /// Like `bulk_data`, the objects are parsed one at a time as the
+    /// response arrives.
+    pub fn crawl_data<T: DeserializeOwned>(&self, name: &str)
+                                           -> Result<JsonStream<T>, Error> {
+        self.job_data("crawl", name, &[])
     }
 
-    /// Retrieves the result from a bulk job
-    pub fn get_bulk(&self, name: &str) -> DiffbotResult {
-        self.do_crawl_bulk::<&str>("bulk",
-                                   vec![("token", &self.token),
-                                        ("name", name),
-                                        ("format", "json")],
-                                   &[])
+    /// Downloads `num` objects extracted by a crawl job, skipping the first
+    /// `offset` ones, like `get_bulk_data`.
+    pub fn get_crawl_data(&self, name: &str, offset: u64, num: u64,
+                          format: DataFormat)
+                          -> Result<Box<dyn Read + Send>, Error> {
+        self.job_data_slice("crawl", name, offset, num, format)
     }
 
-    /// Starts a crawl job.
-    pub fn crawl<S: AsRef<str> + ::std::borrow::Borrow<str>>
-        (&self, name: &str, api: API, seeds: &[S])
-         -> DiffbotResult {
+    /// Iterates over the objects extracted by a crawl job, page by page.
+    ///
+    /// Like `bulk_results`, only one page is held in memory at a time.
+    pub fn crawl_results<'a, T>(&'a self, name: &str) -> JobResults<'a, T>
+        where T: DeserializeOwned
+    {
+        JobResults::new(self, "crawl", name)
+    }
 
-        self.crawl_with_options(name, api, seeds, &[])
+    /// Iterates over the objects of a crawl indexed at or after `since`,
+    /// in milliseconds since the epoch.
+    ///
+    /// The objects are found with the Search API in the collection of the
+    /// crawl, so only the new results of a repeating crawl are downloaded.
+    /// Keep the largest `SearchHit::timestamp` seen to start the next
+    /// download from there. Requires the `search` feature.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # extern crate diffbot;
+    /// # extern crate serde_json;
+    /// # use diffbot::*;
+    /// use serde_json::Value;
+    ///
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// # let last_run = 0;
+    /// let hits = diffbot.crawl_results_since::<Value>("my_crawl", last_run);
+    /// for hit in hits {
+    ///     let hit = hit.unwrap();
+    ///     println!("{:?}: {}", hit.timestamp, hit.object);
+    /// }
+    /// # }
+    /// ```
+    #[cfg(feature = "search")]
+    pub fn crawl_results_since<'a, T>(&'a self, name: &str, since: u64)
+                                      -> SearchResults<'a, T>
+        where T: DeserializeOwned
+    {
+        self.search_results(name, &format!("min:timestamp:{}", since))
     }
 
-    /// Starts a crawl job with extra options.
+    /// Iterates over the objects of a given type extracted by a crawl or
+    /// bulk job.
     ///
-    /// Give `options` a list of (key, value) pairs.
+    /// Only the objects of that type are downloaded, with the Search API in
+    /// the collection of the job: use it to get the products of a crawl
+    /// processed by Analyze, for example. Requires the `search` feature.
     ///
     /// # Example
     ///
     /// ```no_run
     /// # extern crate diffbot;
     /// # use diffbot::*;
+    /// use diffbot::models::Product;
+    ///
     /// # fn main() {
     /// # let diffbot = Diffbot::v3("token");
-    /// # println!("{:?}",
-    /// diffbot.crawl_with_options("my_crawl_job", API::Analyze,
-    ///                            &["http://my.first.page.com",
-    ///                              "https://my.second.page.com"],
-    ///                            &[("repeat", "7.0"),
-    ///                              ("maxHops", "3")])
-    /// # );
+    /// let hits = diffbot.job_results_by_type::<Product>("my_crawl",
+    ///                                                   PageType::Product);
+    /// for hit in hits {
+    ///     println!("{:?}", hit.unwrap().title);
+    /// }
     /// # }
     /// ```
-    pub fn crawl_with_options<S: AsRef<str> + ::std::borrow::Borrow<str>>
-        (&self, name: &str, api: API, seeds: &[S], options: &[(S, S)])
-         -> DiffbotResult {
+    #[cfg(feature = "search")]
+    pub fn job_results_by_type<'a, T>(&'a self, name: &str,
+                                      page_type: PageType)
+                                      -> SearchResults<'a, T>
+        where T: DeserializeOwned
+    {
+        self.search_results(name, "").page_type(page_type)
+    }
+}
 
-        let api_url = api.get_url_string(self.version);
-        let joined = seeds.join(" ");
+#[cfg(test)]
+use stub::{StubResponse, StubServer};
 
-        self.do_crawl_bulk("crawl",
-                           vec![("name", name),
-                                ("token", &self.token),
-                                ("apiUrl", &api_url),
-                                ("seeds", &joined)],
-                           options)
-    }
+#[cfg(feature = "search")]
+#[test]
+fn test_search() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    let diffbot = server.client("insert_your_token_here");
+    diffbot.search("GLOBAL-INDEX", "diffbot").unwrap();
 
-    /// Retrieves the result from a crawl job.
-    pub fn get_crawl(&self, name: &str) -> DiffbotResult {
-        // TODO: specify `num` parameter
-        self.do_crawl_bulk::<&str>("crawl",
-                                   vec![("token", &self.token),
-                                        ("name", name),
-                                        ("format", "json")],
-                                   &[])
+    let request = &server.requests()[0];
+    assert_eq!(request.path(), "/v3/search");
+    assert_eq!(request.query("token"), Some("insert_your_token_here"));
+    assert_eq!(request.query("col"), Some("GLOBAL-INDEX"));
+    assert_eq!(request.query("query"), Some("diffbot"));
+}
+
+#[cfg(all(feature = "jobs", feature = "kg"))]
+#[test]
+fn test_enhance_bulk() {
+    use models::EnhanceOutcome;
+
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"job_id": "abc123"}"#));
+    server.push(StubResponse::json(r#"{"status": "PROCESSING"}"#));
+    server.push(StubResponse::json(r#"{"status": "COMPLETE"}"#));
+    server.push(StubResponse::json(r#"[
+        {"request_ctx": {"query_ctx": {"index": 1}}, "data": []},
+        {"request_ctx": {"query_ctx": {"index": 0}},
+         "data": [{"score": 0.9, "entity": {"name": "Diffbot"}}]},
+        {"errors": [{"message": "Invalid record"}]}]"#));
+    let diffbot = server.client("insert_your_token_here")
+                        .with_cache(Arc::new(MemoryCache::new()));
+
+    let records = [serde_json::json!({"type": "Organization", "name": "D"}),
+                   serde_json::json!({"type": "Person"}),
+                   serde_json::json!({})];
+    let policy = PollPolicy {
+        interval: Duration::from_millis(1),
+        timeout: Duration::from_secs(5),
+    };
+    let rows = diffbot.enhance_bulk(&records, &policy).unwrap();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0].entity().unwrap()["name"], "Diffbot");
+    match rows[0].outcome {
+        EnhanceOutcome::Matched { score, .. } => assert_eq!(score, Some(0.9)),
+        ref other => panic!("unexpected outcome: {:?}", other),
     }
+    assert_eq!(rows[1].outcome, EnhanceOutcome::NoMatch);
+    assert_eq!(rows[2].outcome,
+               EnhanceOutcome::Failed("Invalid record".to_string()));
+
+    let requests = server.requests();
+    assert_eq!(requests[0].method(), "POST");
+    assert_eq!(requests[0].path(), "/kg/v3/enhance/bulk");
+    assert_eq!(requests[0].header("Content-Type"), Some("application/json"));
+    let body: serde_json::Value = serde_json::from_slice(requests[0].body())
+                                      .unwrap();
+    assert_eq!(body[0]["name"], "D");
+    assert_eq!(requests[1].path(), "/kg/v3/enhance/bulk/abc123/status");
+    assert_eq!(requests[3].path(), "/kg/v3/enhance/bulk/abc123");
+}
+
+#[cfg(feature = "kg")]
+#[test]
+fn test_nl_analyze() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"sentiment": 0.4, "entities": [
+        {"name": "Diffbot", "salience": 0.9, "sentiment": 0.5,
+         "allTypes": [{"name": "organization"}],
+         "mentions": [{"text": "Diffbot", "beginOffset": 0,
+                       "endOffset": 7}]}],
+        "facts": [{"humanReadable": "Diffbot is in Menlo Park",
+                   "property": {"name": "location"}}]}"#));
+    let diffbot = server.client("insert_your_token_here");
+    let options = nl::NlOptions::new().entities().sentiment().facts();
+    let analysis = diffbot.nl_analyze("Diffbot is in Menlo Park.", &options)
+                          .unwrap();
+    assert_eq!(analysis.sentiment, Some(0.4));
+    assert_eq!(analysis.entities[0].salience, Some(0.9));
+    assert_eq!(analysis.entities[0].mentions[0].end_offset, Some(7));
+    assert_eq!(analysis.facts[0].property.as_ref().unwrap().name,
+               Some("location".to_string()));
+
+    let request = &server.requests()[0];
+    assert_eq!(request.method(), "POST");
+    assert_eq!(request.path(), "/v1/");
+    assert_eq!(request.query("fields"), Some("entities,sentiment,facts"));
+    let body: serde_json::Value = serde_json::from_slice(request.body())
+                                      .unwrap();
+    assert_eq!(body["content"], "Diffbot is in Menlo Park.");
 }
 
+#[cfg(feature = "kg")]
+#[test]
+fn test_tags_with_entities() {
+    use models::{Article, Tag};
+
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"hits": 1, "data": [
+        {"entity": {"id": "E1", "name": "Diffbot"}}]}"#));
+    server.push(StubResponse::json(r#"{"hits": 0, "data": []}"#));
+    let diffbot = server.client("insert_your_token_here");
+    let tag = |label: &str, uri: Option<&str>| {
+        Tag {
+            label: Some(label.to_string()),
+            uri: uri.map(str::to_string),
+            ..Tag::default()
+        }
+    };
+    let article = Article {
+        tags: vec![tag("Diffbot", Some("https://diffbot.com/entity/E1")),
+                   tag("Bot", None),
+                   tag("Diffbot Inc", Some("https://diffbot.com/entity/E1")),
+                   tag("Gone", Some("https://diffbot.com/entity/E2"))],
+        ..Article::default()
+    };
+
+    let tags = diffbot.tags_with_entities(&article).unwrap();
+    assert_eq!(tags.len(), 4);
+    assert_eq!(tags[0].entity.as_ref().unwrap()["name"], "Diffbot");
+    assert!(tags[1].entity.is_none());
+    assert_eq!(tags[2].entity, tags[0].entity);
+    assert!(tags[3].entity.is_none());
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].query("query"), Some("id:E1"));
+    assert_eq!(requests[1].query("query"), Some("id:E2"));
+
+    // Entities already looked up come from the cache.
+    server.push(StubResponse::json(r#"{"hits": 1, "data": [
+        {"entity": {"id": "E1", "name": "Diffbot"}}]}"#));
+    server.push(StubResponse::json(r#"{"hits": 0, "data": []}"#));
+    let cached = diffbot.clone().with_cache(Arc::new(MemoryCache::new()));
+    cached.tags_with_entities(&article).unwrap();
+    let tags = cached.tags_with_entities(&article).unwrap();
+    assert_eq!(tags[0].entity.as_ref().unwrap()["name"], "Diffbot");
+    assert_eq!(server.requests().len(), 4);
+
+    // The lookup still running finishes, but no other one starts.
+    let denied = r#"{"errorCode": 401, "error": "Not authorized"}"#;
+    server.push(StubResponse::new(401, denied)
+                    .delay(Duration::from_millis(100)));
+    server.push(StubResponse::json(r#"{"hits": 0, "data": []}"#)
+                    .delay(Duration::from_millis(300)));
+    let article = Article {
+        tags: vec![tag("A", Some("https://diffbot.com/entity/A")),
+                   tag("B", Some("https://diffbot.com/entity/B")),
+                   tag("C", Some("https://diffbot.com/entity/C"))],
+        ..Article::default()
+    };
+    let sent = server.requests().len();
+    let err = diffbot.tags_with_entities_concurrently(&article, 2)
+                     .unwrap_err();
+    assert!(err.is_auth());
+    let requests = server.requests();
+    assert!(requests.len() <= sent + 2);
+    assert!(requests.iter().all(|r| r.query("query") != Some("id:C")));
+}
 
+#[cfg(feature = "kg")]
 #[test]
-fn test_search() {
-    let diffbot = Diffbot::v3("insert_your_token_here");
-    println!("{:?}", diffbot.search("GLOBAL-INDEX", "diffbot"));
+fn test_kg_facet() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"hits": 10, "data": [
+        {"value": "Software Companies", "count": 7},
+        {"value": "Banks", "count": 3}]}"#));
+    let diffbot = server.client("insert_your_token_here");
+    let facets = diffbot.kg_facet("type:Organization", "industries")
+                        .unwrap();
+    assert_eq!(facets.hits, Some(10));
+    assert_eq!(facets.count("Banks"), 3);
+
+    let request = &server.requests()[0];
+    assert_eq!(request.path(), "/kg/v3/dql");
+    assert_eq!(request.query("type"), Some("query"));
+    assert_eq!(request.query("query"),
+               Some("type:Organization facet:industries"));
+}
+
+#[cfg(feature = "kg")]
+#[test]
+fn test_kg_export() {
+    let server = StubServer::start();
+    server.push(StubResponse::new(200, "name,id\nDiffbot,C1\n"));
+    server.push(StubResponse::new(200,
+                                  "{\"name\": \"A\"}\n\n{\"name\": \"B\"}\n"));
+    let diffbot = server.client("insert_your_token_here");
+
+    let mut csv = String::new();
+    diffbot.kg_export_with_options("type:Organization", DataFormat::Csv,
+                                   &[("exportspec", "name;id")])
+           .unwrap()
+           .read_to_string(&mut csv)
+           .unwrap();
+    assert_eq!(csv, "name,id\nDiffbot,C1\n");
+    let names: Vec<String> =
+        diffbot.kg_export_entities::<models::Person>("type:Person")
+               .unwrap()
+               .map(|person| person.unwrap().name.unwrap())
+               .collect();
+    assert_eq!(names, vec!["A", "B"]);
+
+    let requests = server.requests();
+    assert_eq!(requests[0].path(), "/kg/v3/dql");
+    assert_eq!(requests[0].query("format"), Some("csv"));
+    assert_eq!(requests[0].query("size"), Some("-1"));
+    assert_eq!(requests[0].query("exportspec"), Some("name;id"));
+    assert_eq!(requests[1].query("format"), Some("jsonl"));
+    assert_eq!(requests[1].query("query"), Some("type:Person"));
+}
+
+#[cfg(feature = "search")]
+#[test]
+fn test_search_job() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    let diffbot = server.client("insert_your_token_here");
+    diffbot.search_job("my_crawl", "type:product", &[("num", "5")]).unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(request.path(), "/v3/search");
+    assert_eq!(request.query("col"), Some("my_crawl"));
+    assert_eq!(request.query("query"), Some("type:product"));
+    assert_eq!(request.query("num"), Some("5"));
 }
 
+#[cfg(feature = "search")]
 #[test]
 fn test_search_with_options() {
-    let diffbot = Diffbot::v3("insert_your_token_here");
-    println!("{:?}",
-             diffbot.search_with_options("GLOBAL-INDEX",
-                                         "site:techcrunch.com sortby:date",
-                                         &[("num", "2")]));
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    let diffbot = server.client("insert_your_token_here");
+    diffbot.search_with_options("GLOBAL-INDEX",
+                                "site:techcrunch.com sortby:date",
+                                &[("num", "2")])
+           .unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(request.query("query"),
+               Some("site:techcrunch.com sortby:date"));
+    assert_eq!(request.query("num"), Some("2"));
 }
 
 #[test]
 fn test_call() {
-    // Use `cargo test -- --nocapture` to see the output
-    let diffbot = Diffbot::v3("insert_your_token_here");
-    println!("{:?}", diffbot.call(API::Analyze, "http://diffbot.com"));
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": [{"type": "article"}]}"#));
+    let diffbot = server.client("insert_your_token_here");
+    let result = diffbot.call(API::Analyze, "http://diffbot.com").unwrap();
+    assert_eq!(result["objects"][0]["type"], "article");
+
+    let request = &server.requests()[0];
+    assert_eq!(request.method(), "GET");
+    assert_eq!(request.path(), "/v3/analyze");
+    assert_eq!(request.query("url"), Some("http://diffbot.com/"));
 }
 
 #[test]
 fn test_call_with_options() {
-    // Use `cargo test -- --nocapture` to see the output
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    let diffbot = server.client("insert_your_token_here");
+    diffbot.call_with_options(API::Analyze,
+                              "http://diffbot.com",
+                              &[("fields", "links,meta")])
+           .unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(request.query("fields"), Some("links,meta"));
+}
+
+#[test]
+fn test_call_api_error() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"errorCode": 401,
+                                       "error": "Not authorized."}"#));
+    let diffbot = server.client("insert_your_token_here");
+    match diffbot.call(API::Article, "http://diffbot.com") {
+        Err(Error::Api(err)) => {
+            assert_eq!(err.code(), 401);
+            assert_eq!(err.message(), "Not authorized.");
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn test_rate_budget() {
+    let server = StubServer::start();
+    server.push(StubResponse::json("{}").header("X-RateLimit-Limit", "50")
+                                        .header("X-RateLimit-Remaining", "7"));
+    server.push(StubResponse::new(429, "").header("Retry-After", "0"));
+    let diffbot = server.client("insert_your_token_here")
+                        .with_rate_limit(100, Duration::from_secs(1));
+    diffbot.call(API::Article, "http://diffbot.com").unwrap();
+    assert!(diffbot.call(API::Article, "http://diffbot.com").is_err());
+
+    let budget = diffbot.clone().rate_budget();
+    assert_eq!(budget.limit(), Some(50));
+    assert_eq!(budget.remaining(), Some(6));
+    assert_eq!(budget.throttled(), 1);
+    assert_eq!(budget.interval(), Some(Duration::from_millis(100)));
+}
+
+#[test]
+fn test_circuit_breaker() {
+    let server = StubServer::start();
+    server.push(StubResponse::new(502, ""));
+    server.push(StubResponse::new(503, ""));
+    let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+    let diffbot = server.client("insert_your_token_here")
+                        .with_circuit_breaker(breaker.clone());
+    for _ in 0..2 {
+        assert!(diffbot.call(API::Article, "http://diffbot.com").is_err());
+    }
+    assert!(breaker.is_open());
+    match diffbot.call(API::Article, "http://diffbot.com") {
+        Err(Error::CircuitOpen(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    let image = reqwest::Url::parse("http://diffbot.com/a.png").unwrap();
+    match diffbot.download(&image) {
+        Err(Error::CircuitOpen(_)) => {}
+        other => panic!("unexpected result: {:?}", other.map(|r| r.0)),
+    }
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[test]
+fn test_hedging() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"title": "slow"}"#)
+                    .delay(Duration::from_millis(500)));
+    server.push(StubResponse::json(r#"{"title": "fast"}"#));
+    let diffbot = server.client("insert_your_token_here")
+                        .with_hedging(Duration::from_millis(50));
+
+    let result = diffbot.call(API::Article, "http://diffbot.com").unwrap();
+    assert_eq!(result["title"], "fast");
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].query("url"), requests[1].query("url"));
+
+    // The slower attempt fails after the other one won: it is ignored.
+    server.push(StubResponse::new(503, "")
+                    .delay(Duration::from_millis(200)));
+    server.push(StubResponse::json(r#"{"title": "fast"}"#));
+    let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+    let diffbot = diffbot.with_circuit_breaker(breaker.clone());
+    diffbot.call(API::Article, "http://diffbot.com").unwrap();
+    thread::sleep(Duration::from_millis(400));
+    assert_eq!(server.requests().len(), 4);
+    assert!(!breaker.is_open());
+}
+
+#[test]
+fn test_base_url() {
+    let diffbot = Diffbot::v3("insert_your_token_here")
+                      .with_base_url("http://diffbot.internal:8080/api")
+                      .unwrap();
+    let request = diffbot.prepare_request(API::Article, "http://a.com")
+                         .unwrap();
+    let url = request.url().as_str();
+    assert!(url.starts_with("http://diffbot.internal:8080/api/v3/article?"));
+    assert_eq!(diffbot.endpoints.kg.join("kg/v3/dql").unwrap().as_str(),
+               "http://diffbot.internal:8080/api/kg/v3/dql");
+    assert!(Diffbot::v3("token").with_base_url("diffbot.internal").is_err());
+}
+
+#[test]
+fn test_gateway_header() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    let diffbot = server.client("insert_your_token_here")
+                        .with_header("Authorization", "Bearer secret")
+                        .unwrap()
+                        .with_header("authorization", "Bearer gateway")
+                        .unwrap();
+
+    diffbot.call(API::Article, "http://diffbot.com").unwrap();
+    let request = &server.requests()[0];
+    assert_eq!(request.header("Authorization"), Some("Bearer gateway"));
+    assert_eq!(request.query("token"), Some("insert_your_token_here"));
+    let image = reqwest::Url::parse("http://images.example.com/a.png")
+                    .unwrap();
+    assert!(diffbot.headers_for(&image).is_empty());
+    assert!(!format!("{:?}", diffbot).contains("Bearer"));
+    assert!(Diffbot::v3("token").with_header("Bad:Name", "x").is_err());
+}
+
+#[test]
+fn test_transport() {
+    use std::sync::Mutex;
+
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl Transport for Recorder {
+        fn send(&self, request: &PreparedRequest)
+                -> Result<TransportResponse, Error> {
+            let user_agent = request.headers()[0].1.clone();
+            let line = format!("{} {}", request, user_agent);
+            self.0.lock().unwrap().push(line);
+            let body = r#"{"errorCode": 429, "error": "Too many requests"}"#;
+            let body = ::std::io::Cursor::new(body.as_bytes().to_vec());
+            Ok(TransportResponse::new(429,
+                                      vec![("retry-after".to_string(),
+                                            "7".to_string())],
+                                      body))
+        }
+    }
+
+    let recorder = Arc::new(Recorder(Mutex::new(Vec::new())));
+    let diffbot = Diffbot::v3("insert_your_token_here")
+                      .with_transport(recorder.clone());
+    let err = diffbot.call(API::Article, "http://diffbot.com").unwrap_err();
+    assert_eq!(err.retry_after(), Some(Duration::from_secs(7)));
+    let sent = recorder.0.lock().unwrap();
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0].starts_with("GET https://api.diffbot.com/v3/article?"));
+    assert!(sent[0].ends_with(USER_AGENT));
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_transport_masks_form_token() {
+    use std::sync::Mutex;
+
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl Transport for Recorder {
+        fn send(&self, request: &PreparedRequest)
+                -> Result<TransportResponse, Error> {
+            let line = format!("{}\n{}", request, request.to_curl());
+            self.0.lock().unwrap().push(line);
+            let body = r#"{"response": "Successfully added urls"}"#;
+            let body = ::std::io::Cursor::new(body.as_bytes().to_vec());
+            Ok(TransportResponse::new(200, Vec::new(), body))
+        }
+    }
+
+    let recorder = Arc::new(Recorder(Mutex::new(Vec::new())));
+    let diffbot = Diffbot::v3("0123456789abcdef")
+                      .with_transport(recorder.clone());
+    diffbot.bulk("my_bulk", API::Article, &["http://a.com"]).unwrap();
+    let sent = recorder.0.lock().unwrap();
+    assert!(sent[0].starts_with("POST "));
+    assert!(sent[0].contains("token=012…cdef"));
+    assert!(!sent[0].contains("0123456789abcdef"));
+}
+
+// Sends a call and a failing post through `transport` to a stub server.
+#[cfg(all(test, any(feature = "curl", feature = "ureq")))]
+fn check_transport(transport: Arc<dyn Transport>) {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"title": "sent"}"#)
+                    .header("X-Test", "yes"));
+    server.push(StubResponse::new(404,
+                                  r#"{"errorCode": 404, "error": "Gone"}"#));
+    let diffbot = server.client("insert_your_token_here")
+                        .with_transport(transport);
+
+    let result = diffbot.call(API::Article, "http://diffbot.com").unwrap();
+    assert_eq!(result["title"], "sent");
+    let body = b"<html></html>";
+    let err = diffbot.post_body(API::Article, "http://a.com", body)
+                     .unwrap_err();
+    assert!(err.is_not_found());
+
+    let requests = server.requests();
+    assert_eq!(requests[0].method(), "GET");
+    assert_eq!(requests[0].header("User-Agent"), Some(USER_AGENT));
+    assert_eq!(requests[1].method(), "POST");
+    assert_eq!(requests[1].body(), body);
+}
+
+#[cfg(feature = "curl")]
+#[test]
+fn test_curl_transport() {
+    check_transport(Arc::new(CurlTransport::new()));
+}
+
+#[cfg(feature = "ureq")]
+#[test]
+fn test_ureq_transport() {
+    check_transport(Arc::new(UreqTransport::new()));
+}
+
+// Calls the API at `diffbot.test` through `transport`, which must resolve
+// that name to the stub server.
+#[cfg(all(test, any(feature = "curl", feature = "ureq")))]
+fn check_resolve<F>(transport: F)
+    where F: FnOnce(u16) -> Arc<dyn Transport>
+{
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"title": "resolved"}"#));
+    let port = server.url().port().unwrap();
+    let diffbot = Diffbot::v3("insert_your_token_here")
+                      .with_base_url(&format!("http://diffbot.test:{}", port))
+                      .unwrap()
+                      .with_transport(transport(port));
+    let result = diffbot.call(API::Article, "http://diffbot.com").unwrap();
+    assert_eq!(result["title"], "resolved");
+    assert_eq!(server.requests()[0].header("Host"),
+               Some(format!("diffbot.test:{}", port).as_str()));
+}
+
+#[cfg(feature = "curl")]
+#[test]
+fn test_curl_resolve() {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    check_resolve(|port| {
+        let localhost = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        Arc::new(CurlTransport::new().with_resolve("diffbot.test",
+                                                   port,
+                                                   &[localhost]))
+    });
+}
+
+#[cfg(feature = "ureq")]
+#[test]
+fn test_dns_cache() {
+    use ureq::Resolver;
+
+    let dns = DnsCache::new(Duration::from_secs(60));
+    let first = dns.resolve("localhost:80").unwrap();
+    assert!(!first.is_empty());
+    assert_eq!(dns.clone().resolve("localhost:80").unwrap(), first);
+
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"title": "cached"}"#));
+    let port = server.url().port().unwrap();
+    let diffbot = Diffbot::v3("insert_your_token_here")
+                      .with_base_url(&format!("http://localhost:{}", port))
+                      .unwrap()
+                      .with_resolver(dns);
+    let result = diffbot.call(API::Article, "http://diffbot.com").unwrap();
+    assert_eq!(result["title"], "cached");
+}
+
+#[cfg(feature = "ureq")]
+#[test]
+fn test_ureq_resolve() {
+    use std::net::SocketAddr;
+
+    check_resolve(|port| {
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+        Arc::new(UreqTransport::from_resolver(move |netloc: &str| {
+            assert_eq!(netloc, format!("diffbot.test:{}", port));
+            Ok(vec![addr])
+        }))
+    });
+}
+
+#[test]
+fn test_post_retries() {
+    let server = StubServer::start();
+    let unavailable = r#"{"errorCode": 503, "error": "Unavailable"}"#;
+    server.push(StubResponse::new(503, unavailable));
+    server.push(StubResponse::new(503, unavailable));
+    server.push(StubResponse::json("{}"));
+    let policy = RetryPolicy::new(1).initial_backoff(Duration::from_millis(1));
+    let diffbot = server.client("insert_your_token_here").with_retries(policy);
+    let body = b"<html></html>";
+
+    assert!(diffbot.post_body(API::Article, "http://a.com", body).is_err());
+    assert_eq!(server.requests().len(), 1);
+    diffbot.retrying_posts()
+           .post_body(API::Article, "http://a.com", body)
+           .unwrap();
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn test_max_response_size() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"title": "short"}"#));
+    server.push(StubResponse::json(format!(r#"{{"text": "{}"}}"#,
+                                           "x".repeat(100))));
+    let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+    let diffbot = server.client("insert_your_token_here")
+                        .with_max_response_size(64)
+                        .with_circuit_breaker(breaker.clone());
+
+    diffbot.call(API::Article, "http://diffbot.com").unwrap();
+    match diffbot.call(API::Article, "http://diffbot.com") {
+        Err(Error::ResponseTooLarge(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    // The API answered: a response too large is not an outage.
+    assert!(!breaker.is_open());
+}
+
+#[test]
+fn test_request() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    let diffbot = server.client("insert_your_token_here");
+    diffbot.request(API::Article, "http://diffbot.com")
+           .field("links")
+           .paging(false)
+           .timeout_ms(30_000)
+           .send()
+           .unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(request.path(), "/v3/article");
+    assert_eq!(request.query("fields"), Some("links"));
+    assert_eq!(request.query("paging"), Some("false"));
+    assert_eq!(request.query("timeout"), Some("30000"));
+}
+
+#[test]
+fn test_request_rendering() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    let diffbot = server.client("token");
+    diffbot.request(API::Article, "http://diffbot.com")
+           .render(false)
+           .send()
+           .unwrap();
+    diffbot.request(API::Article, "http://diffbot.com")
+           .render(false)
+           .render(true)
+           .evaluate("document.querySelector('.more').click();")
+           .send()
+           .unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].query("norender"), Some("true"));
+    assert_eq!(requests[0].header("X-Evaluate"), None);
+    assert_eq!(requests[1].query("norender"), None);
+    assert_eq!(requests[1].header("X-Evaluate"),
+               Some("document.querySelector('.more').click();"));
+}
+
+#[test]
+fn test_request_proxy() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    let diffbot = server.client("token");
+    diffbot.request(API::Article, "http://diffbot.com")
+           .proxy("1.2.3.4:8080")
+           .proxy_auth("user", "secret")
+           .send()
+           .unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].query("proxy"), Some("1.2.3.4:8080"));
+    assert_eq!(requests[0].query("proxyAuth"), Some("user:secret"));
+}
+
+#[test]
+fn test_forward_header() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    let diffbot = server.client("token");
+    diffbot.request(API::Article, "http://diffbot.com")
+           .forward_header("X-Api-Key", "secret")
+           .send()
+           .unwrap();
+    assert_eq!(server.requests()[0].header("X-Forward-X-Api-Key"),
+               Some("secret"));
+    let prepared = diffbot.request(API::Article, "http://diffbot.com")
+                          .forward_header("X-Api-Key", "secret")
+                          .prepare()
+                          .unwrap();
+    assert!(!prepared.to_string().contains("secret"));
+    assert!(!prepared.to_curl().contains("secret"));
+
+    let err = diffbot.request(API::Article, "http://diffbot.com")
+                     .forward_header("Bad Name", "x")
+                     .send()
+                     .unwrap_err();
+    assert_eq!(err.to_string(),
+               "invalid header: \"X-Forward-Bad Name\" is not a valid name");
+    let err = diffbot.request(API::Article, "http://diffbot.com")
+                     .forward_header("Key", "a\r\nInjected: b")
+                     .send()
+                     .unwrap_err();
+    assert!(err.to_string().contains("line break"));
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn test_cookie_jar_forwarding() {
+    let server = StubServer::start();
+    for _ in 0..3 {
+        server.push(StubResponse::json(r#"{"objects": []}"#));
+    }
+    let jar = CookieJar::new();
+    jar.store("https://intranet.example.com/login",
+              &["session=abc; Path=/"]);
+    let diffbot = server.client("token").with_cookie_jar(jar.clone());
+    diffbot.call(API::Article, "https://intranet.example.com/news")
+           .unwrap();
+    diffbot.call(API::Article, "https://www.example.com/").unwrap();
+    diffbot.request(API::Article, "https://intranet.example.com/news")
+           .forward_header("Cookie", "session=override")
+           .send()
+           .unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].header("X-Forward-Cookie"), Some("session=abc"));
+    assert_eq!(requests[1].header("X-Forward-Cookie"), None);
+    assert_eq!(requests[2].header("X-Forward-Cookie"),
+               Some("session=override"));
+
+    let prepared = diffbot.request(API::Article,
+                                   "https://intranet.example.com/news")
+                          .prepare()
+                          .unwrap();
+    for shown in &[prepared.to_string(),
+                   prepared.to_curl(),
+                   format!("{:?}", prepared)] {
+        assert!(shown.contains("X-Forward-Cookie: …") ||
+                shown.contains("\"X-Forward-Cookie\", \"…\""),
+                "{}",
+                shown);
+        assert!(!shown.contains("session=abc"), "{}", shown);
+    }
+}
+
+#[test]
+fn test_site_credentials() {
+    let server = StubServer::start();
+    for _ in 0..4 {
+        server.push(StubResponse::json(r#"{"objects": []}"#));
+    }
+    let diffbot = server.client("token")
+                        .with_site_credentials("example.com",
+                                               Credentials::new("a", "b"))
+                        .with_site_credentials("staging.example.com",
+                                               Credentials::new("c", "d"));
+    diffbot.call(API::Article, "https://www.example.com/").unwrap();
+    diffbot.call(API::Article, "https://staging.example.com/").unwrap();
+    diffbot.call(API::Article, "https://other.com/").unwrap();
+    diffbot.request(API::Article, "https://www.example.com/")
+           .basic_auth(&Credentials::new("e", "f"))
+           .send()
+           .unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].header("X-Forward-Authorization"),
+               Some("Basic YTpi"));
+    assert_eq!(requests[1].header("X-Forward-Authorization"),
+               Some("Basic Yzpk"));
+    assert_eq!(requests[2].header("X-Forward-Authorization"), None);
+    assert_eq!(requests[3].header("X-Forward-Authorization"),
+               Some("Basic ZTpm"));
+    assert!(!format!("{:?}", diffbot).contains("\"b\""));
+}
+
+#[test]
+fn test_site_credentials_masked() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    let dir = std::env::temp_dir()
+                  .join(format!("diffbot-secrets-{}", std::process::id()));
+    let diffbot = server.client("token")
+                        .with_site_credentials("example.com",
+                                               Credentials::new("a", "b"))
+                        .with_fixtures(Fixtures::record(&dir));
+    diffbot.call(API::Article, "https://example.com/").unwrap();
+    assert_eq!(server.requests()[0].header("X-Forward-Authorization"),
+               Some("Basic YTpi"));
+    for entry in std::fs::read_dir(&dir).unwrap() {
+        let fixture = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+        assert!(fixture.contains("X-Forward-Authorization: …"));
+        assert!(!fixture.contains("YTpi"), "{}", fixture);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let request = diffbot.request(API::Article, "https://example.com/")
+                         .basic_auth(&Credentials::new("e", "f"));
+    assert!(!format!("{:?}", request).contains("ZTpm"));
+    let prepared = request.prepare().unwrap();
+    assert_eq!(prepared.headers()[2],
+               ("X-Forward-Authorization".to_string(),
+                "Basic ZTpm".to_string()));
+    for shown in &[prepared.to_string(),
+                   prepared.to_curl(),
+                   format!("{:?}", prepared)] {
+        assert!(!shown.contains("ZTpm"), "{}", shown);
+    }
+}
+
+#[test]
+fn test_default_options() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    let diffbot = server.client("token")
+                        .with_default_options(&[("discussion", "false"),
+                                                ("timeout", "30000")]);
+    diffbot.call(API::Article, "http://diffbot.com").unwrap();
+    diffbot.request(API::Article, "http://diffbot.com")
+           .timeout_ms(60_000)
+           .send()
+           .unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].query("discussion"), Some("false"));
+    assert_eq!(requests[0].query("timeout"), Some("30000"));
+    assert_eq!(requests[1].query("discussion"), Some("false"));
+    assert_eq!(requests[1].query("timeout"), Some("60000"));
+}
+
+#[test]
+fn test_request_id() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    server.push(StubResponse::json(r#"{"error": "Not found",
+                                       "errorCode": 404}"#));
+    let diffbot = server.client("token");
+    let request = diffbot.request(API::Article, "http://diffbot.com");
+    let id = request.id().to_string();
+    request.send().unwrap();
+    let err = diffbot.request(API::Article, "http://diffbot.com/missing")
+                     .request_id("job-7/page-3")
+                     .send()
+                     .unwrap_err();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].header("X-Request-Id"), Some(id.as_str()));
+    assert_eq!(requests[1].header("X-Request-Id"), Some("job-7/page-3"));
+    assert_eq!(err.context().unwrap().request_id(), Some("job-7/page-3"));
+    assert!(err.to_string().ends_with(", request job-7/page-3)"));
+}
+
+#[test]
+fn test_prepare_request() {
+    let diffbot = Diffbot::v3("0123456789abcdef");
+    let prepared = diffbot.request(API::Article, "http://diffbot.com/a'b")
+                          .paging(false)
+                          .evaluate("click('more')")
+                          .forward_header("X-Api-Key", "secret")
+                          .request_id("42")
+                          .prepare()
+                          .unwrap();
+    assert_eq!(prepared.method(), "GET");
+    assert_eq!(prepared.url().as_str(),
+               "https://api.diffbot.com/v3/article?token=0123456789abcdef\
+                &url=http%3A%2F%2Fdiffbot.com%2Fa%27b&paging=false");
+    assert_eq!(prepared.body(), None);
+    assert_eq!(prepared.to_string(),
+               "GET https://api.diffbot.com/v3/article?token=012…cdef\
+                &url=http%3A%2F%2Fdiffbot.com%2Fa%27b&paging=false\n\
+                User-Agent: diffbot/rust\n\
+                X-Request-Id: 42\n\
+                X-Evaluate: click('more')\n\
+                X-Forward-X-Api-Key: …");
+    assert_eq!(prepared.to_curl(),
+               "curl -X GET 'https://api.diffbot.com/v3/article\
+                ?token=012…cdef&url=http%3A%2F%2Fdiffbot.com%2Fa%27b\
+                &paging=false' \
+                -H 'User-Agent: diffbot/rust' -H 'X-Request-Id: 42' \
+                -H 'X-Evaluate: click('\\''more'\\'')' \
+                -H 'X-Forward-X-Api-Key: …'");
+    let debug = format!("{:?}", prepared);
+    assert!(!debug.contains("0123456789abcdef"));
+    assert!(!debug.contains("secret"));
+}
+
+#[test]
+fn test_send_prepared_request() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    let diffbot = server.client("token");
+    let mut prepared = diffbot.request(API::Article, "http://diffbot.com")
+                              .paging(false)
+                              .prepare()
+                              .unwrap();
+    prepared.set_query("discussion", "false").remove_query("paging");
+    prepared.set_header("X-Forward-Accept-Language", "fr").unwrap();
+    assert_eq!(prepared.query("discussion"), Some("false".to_string()));
+    assert!(prepared.set_header("Bad Name", "x").is_err());
+    prepared.send(&diffbot).unwrap();
+    prepared.remove_header("X-Forward-Accept-Language");
+    prepared.send(&diffbot).unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].query("discussion"), Some("false"));
+    assert_eq!(requests[0].query("paging"), None);
+    assert_eq!(requests[0].header("X-Forward-Accept-Language"), Some("fr"));
+    assert_eq!(requests[1].header("X-Forward-Accept-Language"), None);
+    assert_eq!(requests[0].header("X-Request-Id"),
+               requests[1].header("X-Request-Id"));
+}
+
+#[test]
+fn test_send_with_meta() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#)
+                    .header("X-Diffbot-Node", "n42"));
+    let diffbot = server.client("token");
+    let request = diffbot.request(API::Article, "http://diffbot.com");
+    let id = request.id().to_string();
+    let (result, meta) = request.send_with_meta().unwrap();
+    assert!(result.contains_key("objects"));
+    assert_eq!(meta.status(), 200);
+    assert_eq!(meta.header("x-diffbot-node"), Some("n42"));
+    assert_eq!(meta.request_id(), id);
+    assert!(meta.elapsed().unwrap() <= meta.total_time());
+}
+
+#[test]
+fn test_debug_redacts_token() {
+    let diffbot = Diffbot::v3("0123456789abcdef");
+    let debug = format!("{:?}", diffbot.request(API::Article, "http://a.com"));
+    assert!(!debug.contains("0123456789abcdef"));
+}
+
+#[test]
+fn test_client_is_shareable() {
+    fn assert_shareable<T: Clone + Send + Sync>() {}
+    assert_shareable::<Diffbot>();
+
     let diffbot = Diffbot::v3("insert_your_token_here");
-    println!("{:?}",
-             diffbot.call_with_options(API::Analyze,
-                                       "http://diffbot.com",
-                                       &[("fields", "links,meta")]));
+    let clone = diffbot.clone();
+    let debug = ::std::thread::spawn(move || format!("{:?}", clone))
+                    .join()
+                    .unwrap();
+    assert!(debug.starts_with("Diffbot {"));
+    assert!(!debug.contains("insert_your_token_here"));
 }
 
+#[cfg(feature = "jobs")]
 #[test]
 fn test_crawl() {
-    let diffbot = Diffbot::v3("insert_your_token_here");
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"response": "Successfully added"}"#));
+    server.push(StubResponse::json(r#"{"jobs": []}"#));
+    let diffbot = server.client("insert_your_token_here");
+
+    diffbot.crawl("crawl", API::Analyze, &["http://mysite.com"]).unwrap();
+    diffbot.list_crawls().unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].method(), "POST");
+    assert_eq!(requests[0].path(), "/v3/crawl");
+    assert_eq!(requests[0].form("name"), Some("crawl".to_string()));
+    assert_eq!(requests[0].form("seeds"),
+               Some("http://mysite.com/".to_string()));
+    assert_eq!(requests[0].form("apiUrl"),
+               Some(format!("{}v3/analyze", server.url())));
+    assert_eq!(requests[1].method(), "GET");
+    assert_eq!(requests[1].query("token"), Some("insert_your_token_here"));
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_list_bulk_jobs() {
+    use models::JobFilter;
+
+    let jobs = r#"{"jobs": [
+        {"name": "shop-1", "type": "bulk", "jobStatus": {"status": 10}},
+        {"name": "shop-2", "type": "bulk", "jobStatus": {"status": 9}},
+        {"name": "news-1", "type": "bulk", "jobStatus": {"status": 10}}]}"#;
+    let server = StubServer::start();
+    server.push(StubResponse::json(jobs));
+    server.push(StubResponse::json(jobs));
+    let diffbot = server.client("insert_your_token_here");
+
+    assert_eq!(diffbot.list_bulk_jobs().unwrap().len(), 3);
+    let filter = JobFilter::new().failed().name_prefix("shop");
+    let failed = diffbot.list_bulk_jobs_matching(&filter).unwrap();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].name, Some("shop-1".to_string()));
+
+    let requests = server.requests();
+    assert_eq!(requests[0].path(), "/v3/bulk");
+    assert_eq!(requests[0].query("token"), Some("insert_your_token_here"));
+}
+
+#[test]
+fn test_validate_token() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"planCalls": 100}"#));
+    server.push(StubResponse::new(401,
+                                  r#"{"errorCode": 401,
+                                      "error": "Not authorized."}"#));
+    server.push(StubResponse::new(429,
+                                  r#"{"errorCode": 429,
+                                      "error": "Too many requests."}"#));
+    server.push(StubResponse::new(500,
+                                  r#"{"errorCode": 500,
+                                      "error": "Internal error."}"#));
+    let diffbot = server.client("insert_your_token_here")
+                        .with_retries(RetryPolicy::new(3));
+
+    assert_eq!(diffbot.validate_token().unwrap(), TokenStatus::Valid);
+    assert_eq!(diffbot.validate_token().unwrap(), TokenStatus::Unauthorized);
+    assert_eq!(diffbot.validate_token().unwrap(), TokenStatus::Throttled);
+    assert!(diffbot.validate_token().is_err());
+    let requests = server.requests();
+    assert_eq!(requests.len(), 4);
+    assert_eq!(requests[0].path(), "/v3/account");
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_quota_check() {
+    use std::sync::Mutex;
+
+    let account = r#"{"planCalls": 100,
+                      "apiCalls": [{"date": "2017-10-01", "calls": 98}]}"#;
+    let server = StubServer::start();
+    server.push(StubResponse::json(account));
+    server.push(StubResponse::json(account));
+    server.push(StubResponse::json(account));
+    server.push(StubResponse::json(r#"{"response": "Successfully added"}"#));
+    for _ in 0..2 {
+        server.push(StubResponse::json(account));
+        server.push(StubResponse::json(r#"{"response": "Added"}"#));
+    }
+    let diffbot = server.client("insert_your_token_here")
+                        .with_cache(Arc::new(MemoryCache::new()))
+                        .with_quota_check();
+
+    let urls = ["http://a.com", "http://b.com", "http://c.com"];
+    match diffbot.bulk("bulk", API::Article, &urls) {
+        Err(Error::QuotaExceeded(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    let seeds = ["http://a.com"];
+    assert!(diffbot.crawl_with_options("crawl",
+                                       API::Article,
+                                       &seeds,
+                                       &[("maxHops", "1")])
+                   .is_err());
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let seen = warnings.clone();
+    let diffbot = diffbot.with_quota_warning(move |shortfall| {
+        seen.lock().unwrap().push(shortfall.to_string());
+    });
+    diffbot.bulk("bulk", API::Article, &urls).unwrap();
+    diffbot.bulk_with_options("bulk", API::Article, &urls[..1],
+                              &[("repeat", "1"), ("maxRounds", "3")])
+           .unwrap();
+    diffbot.bulk_with_options("bulk", API::Article, &urls[..1],
+                              &[("repeat", "1")])
+           .unwrap();
+    assert_eq!(*warnings.lock().unwrap(),
+               vec!["job \"bulk\" can make 3 calls, 2 left this month",
+                    "job \"bulk\" can make 3 calls, 2 left this month",
+                    "job \"bulk\" has no limit on its calls, 2 calls left \
+                     this month"]);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 8);
+    assert_eq!(requests[0].path(), "/v3/account");
+    assert_eq!(requests[3].path(), "/v3/bulk");
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_control_job() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"response": "Successfully paused"}"#));
+    server.push(StubResponse::json(r#"{"response": "Successfully deleted"}"#));
+    let diffbot = server.client("token");
+    diffbot.pause_crawl("my_crawl").unwrap();
+    diffbot.delete_bulk("my_bulk").unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].path(), "/v3/crawl");
+    assert_eq!(requests[0].form("name"), Some("my_crawl".to_string()));
+    assert_eq!(requests[0].form("pause"), Some("1".to_string()));
+    assert_eq!(requests[1].path(), "/v3/bulk");
+    assert_eq!(requests[1].form("delete"), Some("1".to_string()));
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_add_crawl_seeds() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"jobs": [{"name": "news",
+        "seeds": "http://a.com/ http://b.com/"}]}"#));
+    server.push(StubResponse::json(r#"{"response": "Successfully updated"}"#));
+    let diffbot = server.client("token");
+    diffbot.add_crawl_seeds("news", &["http://b.com", "http://c.com"])
+           .unwrap();
+
+    let update = &server.requests()[1];
+    assert_eq!(update.form("name"), Some("news".to_string()));
+    assert_eq!(update.form("seeds"),
+               Some("http://a.com/ http://b.com/ http://c.com/".to_string()));
+    assert_eq!(update.form("apiUrl"), None);
+
+    server.push(StubResponse::json(r#"{"jobs": []}"#));
+    let err = diffbot.add_crawl_seeds("missing", &["http://c.com"])
+                     .unwrap_err();
+    assert!(err.is_not_found());
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_wait_for_crawl() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"jobs": [{"name": "news",
+        "jobStatus": {"status": 7, "message": "Job is in progress."}}]}"#));
+    server.push(StubResponse::json(r#"{"jobs": [{"name": "news",
+        "objectsFound": 3, "jobStatus": {"status": 9,
+        "message": "Job has completed and no repeat is scheduled."}}]}"#));
+    let diffbot = server.client("token");
+    let policy = PollPolicy {
+        interval: Duration::from_millis(1),
+        timeout: Duration::from_secs(10),
+    };
+    let mut reports = Vec::new();
+    let job = diffbot.wait_for_crawl_with_progress("news", &policy, |p| {
+                         reports.push(p.clone())
+                     })
+                     .unwrap();
+    assert_eq!(job.job_status.unwrap().status, 9);
+    assert_eq!(server.requests().len(), 2);
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[1].objects_found, 3);
+    assert!(reports[0].crawl_rate.is_none());
+
+    server.push(StubResponse::json(r#"{"jobs": [{"name": "news",
+        "jobStatus": {"status": 7, "message": "Job is in progress."}}]}"#));
+    let policy = PollPolicy {
+        interval: Duration::from_millis(1),
+        timeout: Duration::from_millis(0),
+    };
+    match diffbot.wait_for_crawl("news", &policy) {
+        Err(Error::JobTimeout(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[cfg(feature = "search")]
+#[test]
+fn test_delete_from_collection() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"response": "Deleted"}"#));
+    let diffbot = server.client("token");
+    diffbot.delete_from_collection("my_crawl", "12345").unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(request.method(), "POST");
+    assert_eq!(request.path(), "/v3/search");
+    assert_eq!(request.form("col"), Some("my_crawl".to_string()));
+    assert_eq!(request.form("docId"), Some("12345".to_string()));
+    assert_eq!(request.form("delete"), Some("1".to_string()));
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_crawl_data() {
+    use models::Article;
+
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"[{"title": "a"}, {"title": "b"}]"#));
+    server.push(StubResponse::new(404, r#"{"error": "No such job"}"#));
+    let diffbot = server.client("token");
+
+    let titles: Vec<_> = diffbot.crawl_data::<Article>("my_crawl")
+                                .unwrap()
+                                .map(|article| article.unwrap().title)
+                                .collect();
+    assert_eq!(titles, vec![Some("a".to_string()), Some("b".to_string())]);
+    let request = &server.requests()[0];
+    assert_eq!(request.path(), "/v3/crawl/data");
+    assert_eq!(request.query("name"), Some("my_crawl"));
+
+    let err = diffbot.bulk_data::<Article>("missing").err().unwrap();
+    assert!(err.is_not_found());
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_bulk_from_reader() {
+    let server = StubServer::start();
+    let added = r#"{"response": "Successfully added urls for spidering."}"#;
+    server.push(StubResponse::json(added));
+    let diffbot = server.client("token");
+
+    let list = "http://a.com/\n# skipped\nhttp://b.com/\n";
+    diffbot.bulk_from_reader("my_bulk", API::Article, list.as_bytes(),
+                             &[("notifyEmail", "me@example.com")])
+           .unwrap();
+    let request = &server.requests()[0];
+    assert_eq!(request.form("urls"),
+               Some("http://a.com/ http://b.com/".to_string()));
+    assert_eq!(request.form("notifyEmail"),
+               Some("me@example.com".to_string()));
+
+    let err = diffbot.bulk_from_reader::<_, &str>("my_bulk", API::Article,
+                                                  &b"a.com"[..], &[])
+                     .unwrap_err();
+    assert!(err.to_string().contains("line 1"));
+    let err = diffbot.bulk_from_reader::<_, &str>("my_bulk", API::Article,
+                                                  &b"# none\n"[..], &[])
+                     .unwrap_err();
+    assert!(err.to_string().contains("no URL"));
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_bulk_chunked() {
+    let server = StubServer::start();
+    let added = r#"{"response": "Successfully added urls for spidering."}"#;
+    server.push(StubResponse::json(added));
+    server.push(StubResponse::json(added));
+    let diffbot = server.client("token");
+
+    let urls = ["http://a.com/", "http://b.com/", "http://c.com/"];
+    let limits = BulkLimits::new().max_urls(2);
+    let parts = diffbot.bulk_chunked("my_bulk", API::Article, &urls, &limits,
+                                     &[("repeat", "7.0")])
+                       .unwrap();
+    assert_eq!(parts.len(), 2);
+    assert_eq!(parts[1].name, "my_bulk-part2");
+    assert_eq!(parts[1].urls, 1);
+    let requests = server.requests();
+    assert_eq!(requests[0].form("name"), Some("my_bulk-part1".to_string()));
+    assert_eq!(requests[1].form("urls"), Some("http://c.com/".to_string()));
+    assert_eq!(requests[1].form("repeat"), Some("7.0".to_string()));
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_get_bulk_data() {
+    let server = StubServer::start();
+    server.push(StubResponse::new(200, "title\na\n"));
+    let diffbot = server.client("token");
+
+    let mut body = String::new();
+    diffbot.get_bulk_data("my_bulk", 20, 10, DataFormat::Csv)
+           .unwrap()
+           .read_to_string(&mut body)
+           .unwrap();
+    assert_eq!(body, "title\na\n");
+    let request = &server.requests()[0];
+    assert_eq!(request.path(), "/v3/bulk/data");
+    assert_eq!(request.query("format"), Some("csv"));
+    assert_eq!(request.query("offset"), Some("20"));
+    assert_eq!(request.query("num"), Some("10"));
+}
 
-    println!("{:?}", diffbot.crawl("crawl", API::Analyze, &["http://mysite.com"]));
+#[test]
+fn test_post_reader() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    let diffbot = server.client("token");
+    let page = ::std::io::Cursor::new(b"<html>streamed</html>".to_vec());
+    diffbot.post_reader(API::Article, "http://a.com", page).unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(request.method(), "POST");
+    assert_eq!(request.body(), b"<html>streamed</html>");
+}
 
-    println!("{:?}", diffbot.list_crawls());
+#[cfg(feature = "gzip")]
+#[test]
+fn test_post_gzip() {
+    use flate2::read::GzDecoder;
+
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    let diffbot = server.client("token").with_gzip_bodies();
+    diffbot.post_body(API::Article, "http://a.com", b"<html>a</html>")
+           .unwrap();
+    let page = ::std::io::Cursor::new(b"<html>b</html>".to_vec());
+    diffbot.post_reader(API::Article, "http://a.com", page).unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].header("Transfer-Encoding"), Some("chunked"));
+    let pages = ["<html>a</html>", "<html>b</html>"];
+    for (request, page) in requests.iter().zip(&pages) {
+        assert_eq!(request.header("Content-Encoding"), Some("gzip"));
+        let mut body = String::new();
+        GzDecoder::new(request.body()).read_to_string(&mut body).unwrap();
+        assert_eq!(&body, page);
+    }
 }
 
 #[test]
 fn test_post() {
-    // Use `cargo test -- --nocapture` to see the output
-    let diffbot = Diffbot::v3("insert_your_token_here");
-    let res = diffbot.post_body(API::Article,
-                                "http://my.website.com",
-                                br#"
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    let diffbot = server.client("insert_your_token_here");
+    let body = br#"
 <html>
     <head>
         <title>My Website</title>
@@ -571,28 +3974,76 @@ fn test_post() {
         <p>This is a fake website, yet we will analyze its content.
            Isn't it interesting?</p>
     </body>
-</html>"#);
+</html>"#;
+    diffbot.post_body(API::Article, "http://my.website.com", body).unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(request.method(), "POST");
+    assert_eq!(request.header("Content-Type"), Some("text/html"));
+    assert_eq!(request.query("url"), Some("http://my.website.com/"));
+    assert_eq!(request.body(), &body[..]);
+}
 
-    println!("{:?}", res);
+// Client for the `real_test` tests.
+//
+// Set `DIFFBOT_FIXTURES=record` to save the responses in `fixtures/`, and
+// `DIFFBOT_FIXTURES=replay` to run the tests offline from them.
+#[cfg(feature = "real_test")]
+fn real_client() -> Diffbot {
+    let diffbot = Diffbot::v3(option_env!("TOKEN").unwrap_or("replay"));
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures");
+    match Fixtures::from_env(dir) {
+        Some(fixtures) => diffbot.with_fixtures(fixtures),
+        None => diffbot,
+    }
 }
 
 #[test]
 #[cfg(feature = "real_test")]
 fn test_real_search() {
-    let diffbot = Diffbot::v3(env!("TOKEN"));
+    let diffbot = real_client();
     diffbot.search("GLOBAL-INDEX", "diffbot").unwrap();
 }
 
 #[test]
 #[cfg(feature = "real_test")]
 fn test_real_analyze() {
-    let diffbot = Diffbot::v3(env!("TOKEN"));
+    let diffbot = real_client();
     diffbot.call(API::Analyze, "http://diffbot.com").unwrap();
 }
 
 #[test]
 #[cfg(feature = "real_test")]
 fn test_real_crawl_list() {
-    let diffbot = Diffbot::v3(env!("TOKEN"));
+    let diffbot = real_client();
     diffbot.list_crawls().unwrap();
 }
+
+#[test]
+fn test_follow_pages() {
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": [{"type": "article",
+        "text": "one", "extra": 1, "images": [{"url": "http://a.com/1.png"}],
+        "nextPages": ["http://a.com/2", "http://a.com/3"]}]}"#));
+    server.push(StubResponse::json(r#"{"objects": [{"type": "article",
+        "text": "two", "images": [{"url": "http://a.com/1.png"},
+                                  {"url": "http://a.com/2.png"}],
+        "nextPages": ["http://a.com/2", "http://a.com/3"]}]}"#));
+    server.push(StubResponse::json(r#"{"objects": [{"type": "article",
+                                                    "text": "three"}]}"#));
+    let diffbot = server.client("token");
+
+    let result = diffbot.request(API::Article, "http://a.com/1")
+                        .follow_pages(10)
+                        .send()
+                        .unwrap();
+    let article = &result["objects"][0];
+    assert_eq!(article["text"], "one\n\ntwo\n\nthree");
+    assert_eq!(article["images"].as_array().unwrap().len(), 2);
+    assert_eq!(article["numPages"], 3);
+    assert_eq!(article["extra"], 1);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[1].query("url"), Some("http://a.com/2"));
+    assert_eq!(requests[2].query("paging"), Some("false"));
+}
@@ -0,0 +1,255 @@
+//! Client-side limits on the rate of calls, and tracking of the API's own.
+
+use std::cmp;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use http::HttpResponse;
+
+// The bounds of the delay between two calls, once the API has refused some.
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What the client knows of the rate limits of its token.
+///
+/// Built from the responses received so far: `429 Too Many Requests`
+/// statuses, `Retry-After` headers and the `X-RateLimit-Limit`,
+/// `X-RateLimit-Remaining` and `X-RateLimit-Reset` headers, when the API
+/// sends them. Returned by `Diffbot::rate_budget`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RateBudget {
+    throttled: u64,
+    limit: Option<u64>,
+    remaining: Option<u64>,
+    reset: Option<Instant>,
+    paused_until: Option<Instant>,
+    interval: Option<Duration>,
+}
+
+impl RateBudget {
+    /// Returns how many calls were refused as too many.
+    pub fn throttled(&self) -> u64 {
+        self.throttled
+    }
+
+    /// Returns how many calls are allowed per window, if the API said so.
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// Returns an estimate of the calls left in the current window.
+    ///
+    /// This is the last `X-RateLimit-Remaining` value received, minus the
+    /// calls answered since without that header.
+    pub fn remaining(&self) -> Option<u64> {
+        self.remaining
+    }
+
+    /// Returns when the current window ends, if the API said so.
+    pub fn reset(&self) -> Option<Instant> {
+        self.reset
+    }
+
+    /// Returns until when the API asked not to be called, with the
+    /// `Retry-After` header of the last refused call.
+    pub fn paused_until(&self) -> Option<Instant> {
+        self.paused_until
+    }
+
+    /// Returns the current delay between two calls of the client.
+    ///
+    /// `None` without a limit set with `Diffbot::with_rate_limit`. The delay
+    /// grows after each refused call, and shrinks back to the configured
+    /// rate after successful ones.
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    /// Returns `true` if no call should succeed before the window ends or
+    /// the requested pause is over.
+    pub fn is_exhausted(&self) -> bool {
+        let now = Instant::now();
+        self.paused_until.map_or(false, |at| at > now) ||
+        (self.remaining == Some(0) && self.reset.map_or(true, |at| at > now))
+    }
+
+    // Returns the earliest time a call is expected to succeed.
+    fn available_at(&self) -> Option<Instant> {
+        let exhausted = if self.remaining == Some(0) {
+            self.reset
+        } else {
+            None
+        };
+        cmp::max(self.paused_until, exhausted)
+    }
+
+    // Starts a new window once the previous one has ended.
+    fn refresh(&mut self, now: Instant) {
+        if self.reset.map_or(false, |at| at <= now) {
+            self.remaining = self.limit;
+            self.reset = None;
+        }
+    }
+}
+
+// Spaces the calls of a client and tracks its rate budget. Clones share the
+// same schedule and budget.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RateLimiter {
+    base: Option<Duration>,
+    state: Arc<Mutex<LimiterState>>,
+}
+
+#[derive(Debug, Default)]
+struct LimiterState {
+    budget: RateBudget,
+    next: Option<Instant>,
+}
+
+impl RateLimiter {
+    // Only tracks the budget, without delaying calls.
+    pub fn new() -> Self {
+        RateLimiter::default()
+    }
+
+    // Allows at most `calls` calls per `period`.
+    pub fn with_rate(calls: u32, period: Duration) -> Self {
+        let interval = period / calls.max(1);
+        let limiter = RateLimiter {
+            base: Some(interval),
+            state: Arc::default(),
+        };
+        limiter.state.lock().unwrap().budget.interval = Some(interval);
+        limiter
+    }
+
+    // Blocks until the next call is allowed, and reserves its slot.
+    pub fn wait(&self) {
+        if self.base.is_none() {
+            return;
+        }
+        let delay = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            state.budget.refresh(now);
+            let slot = [state.next, state.budget.available_at()]
+                           .iter()
+                           .filter_map(|&at| at)
+                           .fold(now, cmp::max);
+            let interval = state.budget.interval.unwrap_or_default();
+            state.next = Some(slot + interval);
+            slot - now
+        };
+        if delay > Duration::from_secs(0) {
+            thread::sleep(delay);
+        }
+    }
+
+    // Updates the budget with a response of the API.
+    pub fn observe(&self, response: &HttpResponse) {
+        let header = |name: &str| {
+            response.headers
+                    .iter()
+                    .find(|&&(ref n, _)| n.eq_ignore_ascii_case(name))
+                    .and_then(|&(_, ref value)| value.trim().parse().ok())
+        };
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let budget = &mut state.budget;
+        budget.refresh(now);
+
+        if let Some(limit) = header("X-RateLimit-Limit") {
+            budget.limit = Some(limit);
+        }
+        match header("X-RateLimit-Remaining") {
+            Some(remaining) => budget.remaining = Some(remaining),
+            None => {
+                budget.remaining = budget.remaining
+                                         .map(|n| n.saturating_sub(1));
+            }
+        }
+        if let Some(reset) = header("X-RateLimit-Reset") {
+            budget.reset = Some(now + Duration::from_secs(reset));
+        }
+
+        if response.status == 429 {
+            budget.throttled += 1;
+            budget.paused_until = response.retry_after
+                                          .map(|delay| now + delay);
+            budget.interval = self.base.map(|base| {
+                let interval = budget.interval.unwrap_or(base) * 2;
+                cmp::min(cmp::max(interval, MIN_BACKOFF), MAX_INTERVAL)
+            });
+        } else if let (Some(base), Some(interval)) = (self.base,
+                                                      budget.interval) {
+            budget.interval = Some(cmp::max(base, interval - interval / 8));
+        }
+    }
+
+    // Returns a snapshot of the budget.
+    pub fn budget(&self) -> RateBudget {
+        let mut state = self.state.lock().unwrap();
+        state.budget.refresh(Instant::now());
+        state.budget.clone()
+    }
+}
+
+#[test]
+fn test_rate_limiter() {
+    let limiter = RateLimiter::with_rate(10, Duration::from_secs(1));
+    let start = Instant::now();
+    for _ in 0..3 {
+        limiter.clone().wait();
+    }
+    // The first call is immediate, the next two wait 100ms each.
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+#[test]
+fn test_rate_budget() {
+    let response = |status: u16, headers: &[(&str, &str)]| {
+        HttpResponse {
+            status: status,
+            retry_after: if status == 429 {
+                Some(Duration::from_secs(30))
+            } else {
+                None
+            },
+            headers: headers.iter()
+                            .map(|&(n, v)| (n.to_string(), v.to_string()))
+                            .collect(),
+            body: Vec::new(),
+            elapsed: None,
+        }
+    };
+
+    let limiter = RateLimiter::with_rate(10, Duration::from_secs(1));
+    limiter.observe(&response(200,
+                              &[("X-RateLimit-Limit", "100"),
+                                ("x-ratelimit-remaining", "2"),
+                                ("X-RateLimit-Reset", "60")]));
+    limiter.observe(&response(200, &[]));
+    let budget = limiter.budget();
+    assert_eq!(budget.limit(), Some(100));
+    assert_eq!(budget.remaining(), Some(1));
+    assert!(!budget.is_exhausted());
+    assert_eq!(budget.interval(), Some(Duration::from_millis(100)));
+
+    limiter.observe(&response(429, &[]));
+    let budget = limiter.budget();
+    assert_eq!(budget.throttled(), 1);
+    assert_eq!(budget.remaining(), Some(0));
+    assert!(budget.is_exhausted());
+    assert!(budget.paused_until().is_some());
+    assert_eq!(budget.interval(), Some(Duration::from_millis(200)));
+
+    limiter.observe(&response(200, &[]));
+    assert_eq!(limiter.budget().interval(), Some(Duration::from_millis(175)));
+
+    // Without a limit, the budget is tracked but calls are not delayed.
+    let unlimited = RateLimiter::new();
+    unlimited.observe(&response(429, &[]));
+    assert_eq!(unlimited.budget().throttled(), 1);
+    assert_eq!(unlimited.budget().interval(), None);
+}
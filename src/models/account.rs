@@ -0,0 +1,57 @@
+//! Plan and usage of an account.
+
+/// The response of the Account API.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Account {
+    /// The name of the account holder.
+    pub name: Option<String>,
+    /// The email address of the account.
+    pub email: Option<String>,
+    /// The name of the plan.
+    pub plan: Option<String>,
+    /// The number of calls included in the plan each month.
+    pub plan_calls: Option<u64>,
+    /// `"active"`, or the reason the account is disabled.
+    pub status: Option<String>,
+    /// The calls made each day, oldest first.
+    #[serde(default)]
+    pub api_calls: Vec<DailyCalls>,
+}
+
+/// The calls made with an account on one day.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct DailyCalls {
+    /// The day, as `YYYY-MM-DD`.
+    pub date: String,
+    /// The number of calls.
+    #[serde(default)]
+    pub calls: u64,
+    /// The number of credits used, when calls cost more than one.
+    pub credits: Option<u64>,
+}
+
+impl Account {
+    /// Returns the number of calls made in the current month.
+    ///
+    /// The current month is the one of the most recent day listed by the
+    /// API.
+    pub fn calls_this_month(&self) -> u64 {
+        let month = match self.api_calls.iter().map(|day| &day.date).max() {
+            Some(date) => date.get(..7).unwrap_or(date),
+            None => return 0,
+        };
+        self.api_calls
+            .iter()
+            .filter(|day| day.date.starts_with(month))
+            .map(|day| day.calls)
+            .sum()
+    }
+
+    /// Returns the number of calls left in the plan this month, if the
+    /// plan has a limit.
+    pub fn remaining_calls(&self) -> Option<u64> {
+        let used = self.calls_this_month();
+        self.plan_calls.map(|plan_calls| plan_calls.saturating_sub(used))
+    }
+}
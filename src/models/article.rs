@@ -0,0 +1,178 @@
+//! Objects returned by the Article API.
+
+use serde_json::Value;
+
+use models::Discussion;
+
+/// An article, as returned by the Article and Analyze APIs.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Article {
+    /// The object type, `"article"`.
+    #[serde(rename = "type")]
+    pub object_type: Option<String>,
+    /// The title of the article.
+    pub title: Option<String>,
+    /// The plain text of the article.
+    pub text: Option<String>,
+    /// The normalized HTML of the article.
+    pub html: Option<String>,
+    /// The publication date, as an RFC 1123 string.
+    pub date: Option<String>,
+    /// The estimated publication date, when none is shown on the page.
+    pub estimated_date: Option<String>,
+    /// The author of the article.
+    pub author: Option<String>,
+    /// The URL of the author's page.
+    pub author_url: Option<String>,
+    /// The name of the site.
+    pub site_name: Option<String>,
+    /// The country of the publisher.
+    pub publisher_country: Option<String>,
+    /// The region of the publisher.
+    pub publisher_region: Option<String>,
+    /// The language of the article, as an ISO 639-1 code.
+    pub human_language: Option<String>,
+    /// The sentiment of the article, from -1 to 1.
+    pub sentiment: Option<f64>,
+    /// The URL of the page.
+    pub page_url: Option<String>,
+    /// The URL of the page after redirections.
+    pub resolved_page_url: Option<String>,
+    /// The URL of the site's favicon.
+    pub icon: Option<String>,
+    /// The unique identifier of this object.
+    pub diffbot_uri: Option<String>,
+    /// The number of pages the article was concatenated from.
+    pub num_pages: Option<u32>,
+    /// The URL of the next page of the article.
+    pub next_page: Option<String>,
+    /// The URLs of the following pages of the article.
+    #[serde(default)]
+    pub next_pages: Vec<String>,
+    /// The entities the article is about.
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    /// The images of the article.
+    #[serde(default)]
+    pub images: Vec<Image>,
+    /// The navigation path of the page.
+    #[serde(default)]
+    pub breadcrumb: Vec<Breadcrumb>,
+    /// The comments, if the `discussion` option was enabled.
+    pub discussion: Option<Discussion>,
+}
+
+impl Article {
+    /// Appends the following page of a multi-page article.
+    ///
+    /// The text and HTML of the page are concatenated to those of the
+    /// article, and its images not already in the article are added.
+    pub fn append_page(&mut self, page: &Article) {
+        append(&mut self.text, &page.text, "\n\n");
+        append(&mut self.html, &page.html, "\n");
+        for image in &page.images {
+            if !self.images.iter().any(|known| known.url == image.url) {
+                self.images.push(image.clone());
+            }
+        }
+        self.num_pages = Some(self.num_pages.unwrap_or(1) +
+                              page.num_pages.unwrap_or(1));
+    }
+
+    /// Returns the `n` most relevant tags, by decreasing score.
+    pub fn top_tags(&self, n: usize) -> Vec<&Tag> {
+        super::top_by(&self.tags, n, |tag| tag.score)
+    }
+
+    /// Returns the tag of the given entity, ignoring case.
+    pub fn tag(&self, label: &str) -> Option<&Tag> {
+        self.tags.iter().find(|tag| {
+            tag.label.as_ref().map_or(false, |l| l.eq_ignore_ascii_case(label))
+        })
+    }
+}
+
+// Appends `more` to `text`, with a separator if both are set.
+fn append(text: &mut Option<String>, more: &Option<String>, separator: &str) {
+    match (text.as_mut(), more.as_ref()) {
+        (Some(text), Some(more)) => {
+            text.push_str(separator);
+            text.push_str(more);
+        }
+        (None, Some(more)) => *text = Some(more.clone()),
+        _ => {}
+    }
+}
+
+/// An entity an article is about.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    /// The name of the entity.
+    pub label: Option<String>,
+    /// How many times the entity is mentioned.
+    pub count: Option<u32>,
+    /// How relevant the entity is, from 0 to 1.
+    pub score: Option<f64>,
+    /// The sentiment towards the entity, from -1 to 1.
+    pub sentiment: Option<f64>,
+    /// The Knowledge Graph URI of the entity.
+    pub uri: Option<String>,
+    /// The types of the entity.
+    #[serde(default)]
+    pub rdf_types: Vec<String>,
+}
+
+impl Tag {
+    /// Returns `true` if the article is favorable to the entity.
+    pub fn is_positive(&self) -> bool {
+        self.sentiment.map_or(false, |sentiment| sentiment > 0.0)
+    }
+
+    /// Returns `true` if the article is unfavorable to the entity.
+    pub fn is_negative(&self) -> bool {
+        self.sentiment.map_or(false, |sentiment| sentiment < 0.0)
+    }
+}
+
+/// A tag with the Knowledge Graph entity it refers to, as returned by
+/// `Diffbot::tags_with_entities`.
+#[derive(Clone, Debug)]
+pub struct EnrichedTag {
+    /// The tag.
+    pub tag: Tag,
+    /// The entity, if the tag has a URI and the entity was found.
+    pub entity: Option<Value>,
+}
+
+/// An image found in a page.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Image {
+    /// The URL of the image.
+    pub url: Option<String>,
+    /// The title or caption of the image.
+    pub title: Option<String>,
+    /// The displayed width, in pixels.
+    pub width: Option<u32>,
+    /// The displayed height, in pixels.
+    pub height: Option<u32>,
+    /// The actual width of the image file, in pixels.
+    pub natural_width: Option<u32>,
+    /// The actual height of the image file, in pixels.
+    pub natural_height: Option<u32>,
+    /// Whether this is the main image of the page.
+    pub primary: Option<bool>,
+    /// The unique identifier of this image.
+    pub diffbot_uri: Option<String>,
+}
+
+/// A link of a page's navigation path.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Breadcrumb {
+    /// The text of the link.
+    pub name: Option<String>,
+    /// The target of the link.
+    pub link: Option<String>,
+}
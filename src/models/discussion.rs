@@ -0,0 +1,209 @@
+//! Objects returned by the Discussion API.
+
+use std::collections::{HashMap, HashSet};
+use std::vec;
+
+use models::{Image, Tag};
+
+/// A discussion thread, such as comments, forum posts or reviews.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Discussion {
+    /// The object type, `"discussion"`.
+    #[serde(rename = "type")]
+    pub object_type: Option<String>,
+    /// The title of the thread.
+    pub title: Option<String>,
+    /// The URL of the page.
+    pub page_url: Option<String>,
+    /// The service hosting the discussion, such as `"Disqus"`.
+    pub provider: Option<String>,
+    /// The URL of the thread's RSS feed.
+    pub rss_url: Option<String>,
+    /// The language of the thread, as an ISO 639-1 code.
+    pub human_language: Option<String>,
+    /// The number of pages of the thread.
+    pub num_pages: Option<u32>,
+    /// The number of posts.
+    pub num_posts: Option<u32>,
+    /// The number of distinct authors.
+    pub num_participants: Option<u32>,
+    /// The URLs of the following pages of the thread.
+    #[serde(default)]
+    pub next_pages: Vec<String>,
+    /// The entities the thread is about.
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    /// The posts, in page order.
+    #[serde(default)]
+    pub posts: Vec<Post>,
+}
+
+/// A post of a discussion.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Post {
+    /// The position of the post in the thread.
+    pub id: Option<u32>,
+    /// The `id` of the post this one replies to.
+    pub parent_id: Option<u32>,
+    /// The author of the post.
+    pub author: Option<String>,
+    /// The URL of the author's page.
+    pub author_url: Option<String>,
+    /// The date of the post, as an RFC 1123 string.
+    pub date: Option<String>,
+    /// The plain text of the post.
+    pub text: Option<String>,
+    /// The normalized HTML of the post.
+    pub html: Option<String>,
+    /// The score of the post (likes, upvotes, ...).
+    pub votes: Option<i64>,
+    /// The language of the post, as an ISO 639-1 code.
+    pub human_language: Option<String>,
+    /// The URL of the page containing the post.
+    pub page_url: Option<String>,
+    /// The unique identifier of this post.
+    pub diffbot_uri: Option<String>,
+    /// The images of the post.
+    #[serde(default)]
+    pub images: Vec<Image>,
+}
+
+/// A post of a discussion, with the replies to it.
+#[derive(Clone, Debug)]
+pub struct Thread<'a> {
+    /// The post.
+    pub post: &'a Post,
+    /// The replies to the post, in page order.
+    pub replies: Vec<Thread<'a>>,
+}
+
+impl<'a> Thread<'a> {
+    /// Returns the number of posts in this thread, including its first post.
+    pub fn len(&self) -> usize {
+        1 + self.replies.iter().map(Thread::len).sum::<usize>()
+    }
+}
+
+impl Discussion {
+    /// Returns the posts arranged in reply trees, using their `parent_id`.
+    ///
+    /// Posts that reply to no post, or to a post missing from the page,
+    /// start a new thread. Threads and replies are in page order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate diffbot;
+    /// # use diffbot::models::Thread;
+    /// # fn print_thread(thread: &Thread, depth: usize) {
+    /// #     println!("{:width$}{:?}", "", thread.post.text, width = depth);
+    /// #     for reply in &thread.replies { print_thread(reply, depth + 2) }
+    /// # }
+    /// # fn main() {
+    /// # let discussion = diffbot::models::Discussion::default();
+    /// for thread in discussion.threads() {
+    ///     print_thread(&thread, 0);
+    /// }
+    /// # }
+    /// ```
+    pub fn threads<'a>(&'a self) -> Vec<Thread<'a>> {
+        let ids: HashSet<u32> = self.posts
+                                    .iter()
+                                    .filter_map(|post| post.id)
+                                    .collect();
+        let mut replies: HashMap<u32, Vec<usize>> = HashMap::new();
+        let mut roots = Vec::new();
+        for (index, post) in self.posts.iter().enumerate() {
+            match post.parent_id {
+                Some(parent) if ids.contains(&parent) &&
+                                post.id != Some(parent) => {
+                    replies.entry(parent).or_insert_with(Vec::new).push(index)
+                }
+                _ => roots.push(index),
+            }
+        }
+
+        let mut seen = vec![false; self.posts.len()];
+        let mut threads = Vec::new();
+        for index in roots {
+            threads.push(thread(&self.posts, index, &replies, &mut seen));
+        }
+        // Posts replying to each other in a loop are not reached from a
+        // root; each loop starts a thread.
+        for index in 0..self.posts.len() {
+            if !seen[index] {
+                threads.push(thread(&self.posts, index, &replies, &mut seen));
+            }
+        }
+        threads
+    }
+
+    /// Iterates over the posts from the oldest to the most recent.
+    ///
+    /// Posts whose date is missing or cannot be parsed come last, in page
+    /// order.
+    pub fn chronological(&self) -> vec::IntoIter<&Post> {
+        let mut posts: Vec<&Post> = self.posts.iter().collect();
+        posts.sort_by_key(|post| {
+            let date = post.date.as_ref().and_then(|date| parse_date(date));
+            (date.is_none(), date)
+        });
+        posts.into_iter()
+    }
+}
+
+// Builds the thread starting at a post, skipping the posts already seen.
+fn thread<'a>(posts: &'a [Post], index: usize,
+              replies: &HashMap<u32, Vec<usize>>, seen: &mut [bool])
+              -> Thread<'a> {
+    seen[index] = true;
+    let post = &posts[index];
+    let mut thread = Thread {
+        post: post,
+        replies: Vec::new(),
+    };
+    if let Some(children) = post.id.and_then(|id| replies.get(&id)) {
+        for &child in children {
+            if !seen[child] {
+                thread.replies
+                      .push(self::thread(posts, child, replies, seen));
+            }
+        }
+    }
+    thread
+}
+
+// Parses an RFC 1123 date such as `Mon, 02 Oct 2017 10:15:00 GMT` into a
+// sortable tuple.
+fn parse_date(date: &str) -> Option<(u32, u32, u32, u32, u32, u32)> {
+    const MONTHS: [&'static str; 12] = ["jan", "feb", "mar", "apr", "may",
+                                        "jun", "jul", "aug", "sep", "oct",
+                                        "nov", "dec"];
+    let date = date.splitn(2, ',').last().unwrap_or("");
+    let parts: Vec<&str> = date.split_whitespace().collect();
+    if parts.len() < 4 {
+        return None;
+    }
+    let day = parts[0].parse().ok();
+    let month = parts[1].to_lowercase();
+    let month = MONTHS.iter()
+                      .position(|name| month.starts_with(name))
+                      .map(|month| month as u32 + 1);
+    let year = parts[2].parse().ok();
+    let time: Vec<u32> = parts[3].split(':')
+                                 .map(|part| part.parse().ok())
+                                 .collect::<Option<_>>()
+                                 .unwrap_or_default();
+    if time.len() < 2 {
+        return None;
+    }
+    let second = time.get(2).cloned().unwrap_or(0);
+    match (year, month, day) {
+        (Some(year), Some(month), Some(day)) => {
+            Some((year, month, day, time[0], time[1], second))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,111 @@
+//! Results of bulk Enhance jobs of the Knowledge Graph.
+
+use serde_json::Value;
+
+/// The state of a bulk Enhance job, as returned by
+/// `Diffbot::enhance_bulk_status`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnhanceBulkStatus {
+    /// The state of the job, such as `"PROCESSING"` or `"COMPLETE"`.
+    pub status: Option<String>,
+    /// The number of records submitted.
+    pub total_count: Option<u64>,
+    /// The number of records processed so far.
+    pub complete_count: Option<u64>,
+}
+
+impl EnhanceBulkStatus {
+    /// Returns `true` if the job stopped, whether it completed or failed.
+    pub fn is_finished(&self) -> bool {
+        match self.status.as_ref().map(|s| s.to_uppercase()) {
+            Some(ref status) => {
+                status == "COMPLETE" || status == "COMPLETED" ||
+                status == "DONE" || self.is_failed()
+            }
+            None => false,
+        }
+    }
+
+    /// Returns `true` if the job failed.
+    pub fn is_failed(&self) -> bool {
+        match self.status.as_ref().map(|s| s.to_uppercase()) {
+            Some(ref status) => status == "FAILED" || status == "ERROR",
+            None => false,
+        }
+    }
+}
+
+/// The outcome of one record of a bulk Enhance job.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnhanceRow {
+    /// The position of the record in the submitted batch.
+    pub index: usize,
+    /// Whether and how the record was matched.
+    pub outcome: EnhanceOutcome,
+}
+
+/// Whether a record of a bulk Enhance job matched an entity.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EnhanceOutcome {
+    /// The best matching entity, with its score.
+    Matched {
+        /// The entity of the Knowledge Graph.
+        entity: Value,
+        /// How closely the entity matches the record, if given.
+        score: Option<f64>,
+    },
+    /// No entity matched the record.
+    NoMatch,
+    /// The record could not be processed.
+    Failed(String),
+}
+
+impl EnhanceRow {
+    // Interprets the result of the record at `index`: the `data` it matched,
+    // or the `errors` it raised.
+    pub(crate) fn from_value(index: usize, mut row: Value) -> Self {
+        let index = row.pointer("/request_ctx/query_ctx/index")
+                       .and_then(Value::as_u64)
+                       .map_or(index, |index| index as usize);
+        let error = row.get("errors")
+                       .and_then(|errors| errors.get(0))
+                       .map(|error| {
+                           error.get("message")
+                                .and_then(Value::as_str)
+                                .map_or_else(|| error.to_string(),
+                                             str::to_string)
+                       });
+        let best = match row.get_mut("data") {
+            Some(&mut Value::Array(ref mut data)) if !data.is_empty() => {
+                Some(data.swap_remove(0))
+            }
+            _ => None,
+        };
+        let outcome = match (best, error) {
+            (Some(mut best), _) => {
+                EnhanceOutcome::Matched {
+                    score: best.get("score").and_then(Value::as_f64),
+                    entity: match best.get_mut("entity") {
+                        Some(entity) => entity.take(),
+                        None => best,
+                    },
+                }
+            }
+            (None, Some(error)) => EnhanceOutcome::Failed(error),
+            (None, None) => EnhanceOutcome::NoMatch,
+        };
+        EnhanceRow {
+            index: index,
+            outcome: outcome,
+        }
+    }
+
+    /// Returns the matched entity, if any.
+    pub fn entity(&self) -> Option<&Value> {
+        match self.outcome {
+            EnhanceOutcome::Matched { ref entity, .. } => Some(entity),
+            _ => None,
+        }
+    }
+}
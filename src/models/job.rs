@@ -0,0 +1,237 @@
+//! Status of crawl and bulk jobs.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The response of the Crawl and Bulk APIs, listing one or more jobs.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct JobsResponse {
+    /// A message about the action performed, if any.
+    pub response: Option<String>,
+    /// The jobs.
+    #[serde(default)]
+    pub jobs: Vec<Job>,
+}
+
+/// The settings and progress of a crawl or bulk job.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    /// The name of the job.
+    pub name: Option<String>,
+    /// `"crawl"` or `"bulk"`.
+    #[serde(rename = "type")]
+    pub job_type: Option<String>,
+    /// The current state of the job.
+    pub job_status: Option<JobStatus>,
+    /// When the job was created, in seconds since the epoch.
+    #[serde(rename = "jobCreationTimeUTC")]
+    pub job_creation_time_utc: Option<u64>,
+    /// When the job completed, in seconds since the epoch.
+    #[serde(rename = "jobCompletionTimeUTC")]
+    pub job_completion_time_utc: Option<u64>,
+    /// The URL of the API processing the pages.
+    pub api_url: Option<String>,
+    /// The seed URLs of a crawl, separated by spaces.
+    pub seeds: Option<String>,
+    /// The number of objects extracted.
+    pub objects_found: Option<u64>,
+    /// The number of URLs found.
+    pub urls_harvested: Option<u64>,
+    /// The number of pages the crawler tried to fetch.
+    pub page_crawl_attempts: Option<u64>,
+    /// The number of pages fetched.
+    pub page_crawl_successes: Option<u64>,
+    /// The number of pages fetched in the current round.
+    pub page_crawl_successes_this_round: Option<u64>,
+    /// The number of pages the API tried to process.
+    pub page_process_attempts: Option<u64>,
+    /// The number of pages processed.
+    pub page_process_successes: Option<u64>,
+    /// The number of pages processed in the current round.
+    pub page_process_successes_this_round: Option<u64>,
+    /// The maximum number of rounds, for repeating jobs.
+    pub max_rounds: Option<i64>,
+    /// The number of rounds completed.
+    pub rounds_completed: Option<u64>,
+    /// The number of days between rounds.
+    pub repeat: Option<f64>,
+    /// The URL to download the results as JSON.
+    pub download_json: Option<String>,
+    /// The URL to download the list of URLs as CSV.
+    pub download_urls: Option<String>,
+    /// The email address notified when the job completes.
+    pub notify_email: Option<String>,
+    /// The URL called when the job completes.
+    pub notify_webhook: Option<String>,
+}
+
+/// The state of a job.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct JobStatus {
+    /// The status code; see the Crawl API documentation.
+    pub status: u32,
+    /// A description of the status.
+    pub message: Option<String>,
+}
+
+impl JobStatus {
+    /// Returns `true` if the job stopped: it completed, reached one of its
+    /// limits, or failed.
+    ///
+    /// A repeating job waiting for its next round is not finished.
+    pub fn is_finished(&self) -> bool {
+        match self.status {
+            1 | 2 | 3 | 9 => true,
+            _ => self.is_failed(),
+        }
+    }
+
+    /// Returns `true` if the job stopped without crawling anything, because
+    /// no URL was given or no seed could be fetched.
+    pub fn is_failed(&self) -> bool {
+        self.status == 5 || self.status == 10
+    }
+}
+
+/// Selects jobs by status, name and age.
+///
+/// Applied to the jobs listed by `Diffbot::list_crawls_matching` and
+/// `Diffbot::list_bulk_jobs_matching`; a job must pass every criterion set.
+/// Jobs missing the field a criterion looks at do not match it.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::models::JobFilter;
+/// # use std::time::Duration;
+/// # fn main() {
+/// // Failed jobs created more than a week ago.
+/// let filter = JobFilter::new()
+///                  .failed()
+///                  .older_than(Duration::from_secs(7 * 24 * 3600));
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct JobFilter {
+    statuses: Vec<u32>,
+    failed: bool,
+    name_prefix: Option<String>,
+    older_than: Option<Duration>,
+    newer_than: Option<Duration>,
+}
+
+impl JobFilter {
+    /// Returns a filter matching every job.
+    pub fn new() -> Self {
+        JobFilter::default()
+    }
+
+    /// Matches jobs with the given status code.
+    ///
+    /// Can be called several times to match any of the codes.
+    pub fn status(mut self, code: u32) -> Self {
+        self.statuses.push(code);
+        self
+    }
+
+    /// Matches the jobs which failed, as told by `JobStatus::is_failed`.
+    pub fn failed(mut self) -> Self {
+        self.failed = true;
+        self
+    }
+
+    /// Matches jobs whose name starts with `prefix`.
+    pub fn name_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.name_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Matches jobs created more than `age` ago.
+    pub fn older_than(mut self, age: Duration) -> Self {
+        self.older_than = Some(age);
+        self
+    }
+
+    /// Matches jobs created less than `age` ago.
+    pub fn newer_than(mut self, age: Duration) -> Self {
+        self.newer_than = Some(age);
+        self
+    }
+
+    /// Returns `true` if `job` passes every criterion of the filter.
+    pub fn matches(&self, job: &Job) -> bool {
+        let now = SystemTime::now()
+                      .duration_since(UNIX_EPOCH)
+                      .unwrap_or_default();
+        self.matches_at(job, now)
+    }
+
+    // Checks a job against the filter, `now` being the time since the epoch.
+    fn matches_at(&self, job: &Job, now: Duration) -> bool {
+        let status = job.job_status.as_ref();
+        if !self.statuses.is_empty() &&
+           !status.map_or(false, |s| self.statuses.contains(&s.status)) {
+            return false;
+        }
+        if self.failed && !status.map_or(false, JobStatus::is_failed) {
+            return false;
+        }
+        if let Some(ref prefix) = self.name_prefix {
+            let name = job.name.as_ref().map_or("", String::as_str);
+            if job.name.is_none() || !name.starts_with(prefix.as_str()) {
+                return false;
+            }
+        }
+        if self.older_than.is_none() && self.newer_than.is_none() {
+            return true;
+        }
+        let age = match job.job_creation_time_utc {
+            Some(created) => now.checked_sub(Duration::from_secs(created))
+                                .unwrap_or_default(),
+            None => return false,
+        };
+        self.older_than.map_or(true, |min| age > min) &&
+        self.newer_than.map_or(true, |max| age < max)
+    }
+}
+
+#[test]
+fn test_job_filter() {
+    let job = |name: &str, status: u32, created: u64| {
+        Job {
+            name: Some(name.to_string()),
+            job_status: Some(JobStatus {
+                status: status,
+                message: None,
+            }),
+            job_creation_time_utc: Some(created),
+            ..Job::default()
+        }
+    };
+    let day = 24 * 3600;
+    let now = Duration::from_secs(100 * day);
+    let jobs = vec![job("news-1", 10, 90 * day),
+                    job("news-2", 9, 90 * day),
+                    job("news-3", 5, 99 * day),
+                    job("blog-1", 5, 80 * day),
+                    Job::default()];
+    let names = |filter: JobFilter| {
+        jobs.iter()
+            .filter(|job| filter.matches_at(job, now))
+            .map(|job| job.name.clone().unwrap_or_default())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(names(JobFilter::new()).len(), 5);
+    assert_eq!(names(JobFilter::new().status(9).status(5)),
+               vec!["news-2", "news-3", "blog-1"]);
+    assert_eq!(names(JobFilter::new()
+                         .failed()
+                         .name_prefix("news")
+                         .older_than(Duration::from_secs(7 * day))),
+               vec!["news-1"]);
+    let two_days = Duration::from_secs(2 * day);
+    assert_eq!(names(JobFilter::new().newer_than(two_days)),
+               vec!["news-3"]);
+}
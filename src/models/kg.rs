@@ -0,0 +1,37 @@
+//! Results of Knowledge Graph queries.
+
+use serde_json::Value;
+
+/// The result of a facet query of the Knowledge Graph: the number of
+/// matching entities for each value of a field.
+///
+/// Returned by `Diffbot::kg_facet`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct FacetResult {
+    /// The number of entities matching the query.
+    pub hits: Option<u64>,
+    /// The values of the field, most frequent first.
+    #[serde(rename = "data", default)]
+    pub buckets: Vec<FacetBucket>,
+}
+
+/// A value of a faceted field, with its number of entities.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct FacetBucket {
+    /// The value, usually a string.
+    pub value: Value,
+    /// The number of matching entities with this value.
+    #[serde(default)]
+    pub count: u64,
+}
+
+impl FacetResult {
+    /// Returns the number of entities with the given value, or 0 if the
+    /// value is not listed.
+    pub fn count(&self, value: &str) -> u64 {
+        self.buckets
+            .iter()
+            .find(|bucket| bucket.value.as_str() == Some(value))
+            .map_or(0, |bucket| bucket.count)
+    }
+}
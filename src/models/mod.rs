@@ -0,0 +1,347 @@
+//! Typed models of the API responses.
+//!
+//! Calls return a JSON map; convert it to one of these types with `parse`:
+//!
+//! ```
+//! # extern crate diffbot;
+//! # use diffbot::*;
+//! use diffbot::models::{self, Article, ExtractResponse};
+//!
+//! # fn main() {
+//! # let diffbot = Diffbot::v3("token");
+//! let result = diffbot.call(API::Article, "http://diffbot.com")
+//!                     .and_then(models::parse::<ExtractResponse<Article>>);
+//! if let Ok(response) = result {
+//!     for article in response.objects {
+//!         println!("{:?}", article.title);
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! Every field is optional, as Diffbot omits the fields it could not
+//! extract. Fields not modelled here are still available in the raw map.
+//! Sample responses for each endpoint are in the `samples` directory.
+//!
+//! The models of the extraction APIs are always built, even without
+//! default features: `Request::follow_pages` merges the pages of an
+//! article with `Article::append_page`, and the `images`, `text`, `output`
+//! and `diff` modules work on them. They only need `serde`, which the
+//! client uses anyway, so leaving them out would not remove a dependency.
+//! The models of jobs, searches and the Knowledge Graph are behind the
+//! features of their methods.
+
+use serde::de::DeserializeOwned;
+use serde_json::{self, Map, Value};
+
+use {Error, PageType};
+
+mod account;
+mod article;
+mod discussion;
+#[cfg(all(feature = "jobs", feature = "kg"))]
+mod enhance;
+#[cfg(feature = "jobs")]
+mod job;
+#[cfg(feature = "kg")]
+mod kg;
+#[cfg(feature = "kg")]
+mod person;
+mod product;
+#[cfg(feature = "jobs")]
+mod result;
+#[cfg(feature = "search")]
+mod search;
+mod video;
+
+pub use self::account::{Account, DailyCalls};
+pub use self::article::{Article, Breadcrumb, EnrichedTag, Image, Tag};
+pub use self::discussion::{Discussion, Post, Thread};
+#[cfg(all(feature = "jobs", feature = "kg"))]
+pub use self::enhance::{EnhanceBulkStatus, EnhanceOutcome, EnhanceRow};
+#[cfg(feature = "jobs")]
+pub use self::job::{Job, JobFilter, JobStatus, JobsResponse};
+#[cfg(feature = "kg")]
+pub use self::kg::{FacetBucket, FacetResult};
+#[cfg(feature = "kg")]
+pub use self::person::{Education, Employment, EntityRef, KgDate, Location,
+                       Person};
+pub use self::product::{PriceDetails, Product, RateProvider};
+#[cfg(feature = "jobs")]
+pub use self::result::{partition_results, JobObject, PageFailure};
+#[cfg(feature = "search")]
+pub use self::search::{SearchHit, SearchResponse};
+pub use self::video::{Video, VideoMedia};
+
+/// Converts the result of a call into a typed model.
+pub fn parse<T: DeserializeOwned>(result: Map<String, Value>)
+                                  -> Result<T, Error> {
+    serde_json::from_value(Value::Object(result)).map_err(Error::from)
+}
+
+// Returns the `n` items with the highest `key`, highest first. Items
+// without a value come last.
+pub(crate) fn top_by<T, F>(items: &[T], n: usize, key: F) -> Vec<&T>
+    where F: Fn(&T) -> Option<f64>
+{
+    let mut ranked: Vec<&T> = items.iter().collect();
+    ranked.sort_by(|a, b| {
+        let (a, b) = (key(a).unwrap_or(::std::f64::NEG_INFINITY),
+                      key(b).unwrap_or(::std::f64::NEG_INFINITY));
+        b.partial_cmp(&a).unwrap_or(::std::cmp::Ordering::Equal)
+    });
+    ranked.truncate(n);
+    ranked
+}
+
+/// The response of an extraction API (Article, Product, Analyze, ...).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ExtractResponse<T> {
+    /// A summary of the call.
+    pub request: Option<RequestInfo>,
+    /// The type of the page, as detected by the Analyze API.
+    #[serde(rename = "type")]
+    pub page_type: Option<PageType>,
+    /// The language of the page, as an ISO 639-1 code, for Analyze calls.
+    #[serde(rename = "humanLanguage")]
+    pub human_language: Option<String>,
+    /// The extracted objects.
+    #[serde(default = "Vec::new")]
+    pub objects: Vec<T>,
+}
+
+/// The summary of a call, sent back with its response.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestInfo {
+    /// The URL of the processed page.
+    pub page_url: Option<String>,
+    /// The URL of the page after redirections.
+    pub resolved_page_url: Option<String>,
+    /// The API used.
+    pub api: Option<String>,
+    /// The version of the API.
+    pub version: Option<u8>,
+    /// The options given to the call.
+    #[serde(default)]
+    pub options: Vec<String>,
+    /// The optional fields requested.
+    pub fields: Option<String>,
+}
+
+#[cfg(test)]
+fn sample<T: DeserializeOwned>(json: &str) -> T {
+    match serde_json::from_str(json) {
+        Ok(Value::Object(map)) => parse(map).unwrap(),
+        other => panic!("invalid sample: {:?}", other),
+    }
+}
+
+#[test]
+fn test_sample_article() {
+    let response: ExtractResponse<Article> =
+        sample(include_str!("../../samples/article.json"));
+    let request = response.request.unwrap();
+    assert_eq!(request.api.unwrap(), "article");
+    let article = &response.objects[0];
+    assert_eq!(article.title.as_ref().unwrap(),
+               "Diffbot's New Product API Teaches Robots to Shop Online");
+    assert_eq!(article.tags[0].label.as_ref().unwrap(), "Diffbot");
+    assert!(article.images[0].primary.unwrap());
+    let top = article.top_tags(1);
+    assert_eq!(top.len(), 1);
+    assert_eq!(top[0].score, Some(0.92));
+    assert!(article.tags[0].is_positive());
+    assert!(!article.tags[1].is_negative());
+}
+
+#[test]
+fn test_sample_product() {
+    let response: ExtractResponse<Product> =
+        sample(include_str!("../../samples/product.json"));
+    let product = &response.objects[0];
+    assert_eq!(product.offer_price.as_ref().unwrap(), "$17.99");
+    let details = product.offer_price_details.as_ref().unwrap();
+    assert_eq!(details.amount, Some(17.99));
+    assert_eq!(product.specs["weight"], "1.2 pounds");
+
+    assert_eq!(details.currency(), Some("USD"));
+    let rates = |_: &str, to: &str| if to == "EUR" { Some(2.0) } else { None };
+    assert_eq!(product.offer_price_in("EUR", &rates), Some(35.98));
+    assert_eq!(product.offer_price_in("USD", &rates), Some(17.99));
+    assert_eq!(product.offer_price_in("GBP", &rates), None);
+}
+
+#[test]
+fn test_sample_discussion() {
+    let response: ExtractResponse<Discussion> =
+        sample(include_str!("../../samples/discussion.json"));
+    let discussion = &response.objects[0];
+    assert_eq!(discussion.num_posts, Some(3));
+    assert_eq!(discussion.posts.len(), 3);
+    assert_eq!(discussion.posts[1].parent_id, Some(0));
+
+    let threads = discussion.threads();
+    assert_eq!(threads.len(), 1);
+    assert_eq!(threads[0].len(), 3);
+    assert_eq!(threads[0].replies[0].replies[0].post.id, Some(2));
+
+    let mut discussion = discussion.clone();
+    discussion.posts[0].date = Some("Tue, 03 Oct 2017 08:00:00 GMT".into());
+    discussion.posts[1].date = None;
+    let order: Vec<_> = discussion.chronological()
+                                  .map(|post| post.id.unwrap())
+                                  .collect();
+    assert_eq!(order, vec![2, 0, 1]);
+}
+
+#[test]
+fn test_sample_video() {
+    let response: ExtractResponse<Video> =
+        sample(include_str!("../../samples/video.json"));
+    let mut video = response.objects[0].clone();
+    assert_eq!(video.duration, Some(124.5));
+    let best = video.best_media().unwrap();
+    assert_eq!(best.url.unwrap(),
+               "https://cdn.example.com/diffbot-demo-720.mp4");
+
+    video.media.clear();
+    assert_eq!(video.best_media().unwrap().mime.unwrap(), "video/webm");
+    video.media_url = None;
+    let player = video.best_media().unwrap();
+    assert_eq!(player.url.unwrap(),
+               "https://video.example.com/embed/diffbot-demo");
+    assert_eq!(player.mime.unwrap(), "text/html");
+}
+
+#[test]
+fn test_sample_analyze() {
+    let response: ExtractResponse<Value> =
+        sample(include_str!("../../samples/analyze.json"));
+    assert_eq!(response.objects[0]["type"], "article");
+    assert_eq!(response.page_type, Some(PageType::Article));
+    assert_eq!(response.human_language.unwrap(), "en");
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_sample_crawl_status() {
+    let response: JobsResponse =
+        sample(include_str!("../../samples/crawl_status.json"));
+    let job = &response.jobs[0];
+    assert_eq!(job.job_type.as_ref().unwrap(), "crawl");
+    assert_eq!(job.job_status.as_ref().unwrap().status, 9);
+    assert_eq!(job.seeds.as_ref().unwrap(), "https://blog.diffbot.com/");
+}
+
+#[cfg(feature = "kg")]
+#[test]
+fn test_sample_kg_facet() {
+    let facets: FacetResult =
+        sample(include_str!("../../samples/kg_facet.json"));
+    assert_eq!(facets.hits, Some(1520));
+    assert_eq!(facets.buckets.len(), 3);
+    assert_eq!(facets.count("Software Companies"), 812);
+    assert_eq!(facets.count("Unknown"), 0);
+}
+
+#[cfg(feature = "kg")]
+#[test]
+fn test_sample_kg_person() {
+    let person: Person = sample(include_str!("../../samples/kg_person.json"));
+    assert_eq!(person.name.as_ref().unwrap(), "Jane Example");
+    let current = person.current_employments();
+    assert_eq!(current.len(), 1);
+    assert_eq!(current[0].employer.as_ref().unwrap().name.as_ref().unwrap(),
+               "Example Corp");
+    assert!(current[0].has_category("executive"));
+
+    let past = &person.employments[1];
+    assert!(!past.is_current());
+    let from = past.from.as_ref().unwrap();
+    assert_eq!((from.year(), from.month()), (Some(2012), None));
+    let to = past.to.as_ref().unwrap();
+    assert_eq!((to.year(), to.month(), to.day()), (Some(2018), Some(2), None));
+
+    let education = &person.educations[0];
+    assert_eq!(education.to.as_ref().unwrap().day(), Some(15));
+    let location = person.current_location().unwrap();
+    assert_eq!(location.city.as_ref().unwrap().name.as_ref().unwrap(),
+               "Menlo Park");
+}
+
+#[cfg(all(feature = "kg", feature = "chrono"))]
+#[test]
+fn test_kg_person_dates() {
+    use chrono::NaiveDate;
+
+    let person: Person = sample(include_str!("../../samples/kg_person.json"));
+    let past = &person.employments[1];
+    let from = past.from.as_ref().unwrap();
+    assert_eq!(from.first_day(), NaiveDate::from_ymd_opt(2012, 1, 1));
+    assert_eq!(from.last_day(), NaiveDate::from_ymd_opt(2012, 12, 31));
+    let to = past.to.as_ref().unwrap();
+    assert_eq!(to.last_day(), NaiveDate::from_ymd_opt(2018, 2, 28));
+
+    let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    assert!(past.held_on(date(2015, 6, 1)));
+    assert!(past.held_on(date(2018, 2, 28)));
+    assert!(!past.held_on(date(2018, 3, 1)));
+    assert!(!past.held_on(date(2011, 12, 31)));
+    assert!(person.employments[0].held_on(date(2030, 1, 1)));
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_sample_bulk_status() {
+    let response: JobsResponse =
+        sample(include_str!("../../samples/bulk_status.json"));
+    let job = &response.jobs[0];
+    assert_eq!(job.job_type.as_ref().unwrap(), "bulk");
+    assert_eq!(job.page_process_successes, Some(2));
+}
+
+#[test]
+fn test_sample_account() {
+    let account: Account = sample(include_str!("../../samples/account.json"));
+    assert_eq!(account.plan.as_ref().unwrap(), "startup");
+    assert_eq!(account.calls_this_month(), 2000);
+    assert_eq!(account.remaining_calls(), Some(248000));
+}
+
+#[cfg(feature = "search")]
+#[test]
+fn test_sample_search() {
+    let response: SearchResponse =
+        sample(include_str!("../../samples/search.json"));
+    assert_eq!(response.hits, Some(2));
+    assert_eq!(response.objects.len(), 2);
+    let hit = &response.objects[0];
+    assert_eq!(hit.doc_id, Some(1318270562));
+    assert_eq!(hit.score, Some(1.7));
+    assert_eq!(hit.timestamp, Some(1375257600000));
+    assert_eq!(hit.object_type.as_ref().unwrap(), "article");
+    assert_eq!(hit["title"],
+               "Diffbot's New Product API Teaches Robots to Shop Online");
+
+    let articles: SearchResponse<Article> =
+        sample(include_str!("../../samples/search.json"));
+    let article = &articles.objects[0];
+    assert_eq!(article.object_type.as_ref().unwrap(), "article");
+    assert!(article.title.is_some());
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_job_objects() {
+    let json = r#"[
+        {"type": "article", "title": "a", "pageUrl": "http://a.com/"},
+        {"pageUrl": "http://b.com/", "errorCode": 404,
+         "error": "Could not download page (404)"}]"#;
+    let results: Vec<JobObject<Article>> = serde_json::from_str(json).unwrap();
+    assert!(results[1].is_failure());
+    let (articles, failures) = partition_results(results);
+    assert_eq!(articles[0].title.as_ref().unwrap(), "a");
+    assert_eq!(failures[0].error_code, Some(404));
+    assert_eq!(failures[0].page_url.as_ref().unwrap(), "http://b.com/");
+}
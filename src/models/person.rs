@@ -0,0 +1,243 @@
+//! Person entities of the Knowledge Graph.
+
+#[cfg(feature = "chrono")]
+use chrono::NaiveDate;
+
+/// A person of the Knowledge Graph, as returned by
+/// `diffbot.kg_query_iter::<Person>("type:Person ...")`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Person {
+    /// The ID of the entity.
+    pub id: Option<String>,
+    /// The URI of the entity.
+    pub diffbot_uri: Option<String>,
+    /// The full name of the person.
+    pub name: Option<String>,
+    /// A short description, such as their current position.
+    pub summary: Option<String>,
+    /// A longer description of the person.
+    pub description: Option<String>,
+    /// The jobs the person held, current and past.
+    #[serde(default)]
+    pub employments: Vec<Employment>,
+    /// The schools the person attended.
+    #[serde(default)]
+    pub educations: Vec<Education>,
+    /// The places the person lived in.
+    #[serde(default)]
+    pub locations: Vec<Location>,
+}
+
+impl Person {
+    /// Returns the jobs the person currently holds.
+    pub fn current_employments(&self) -> Vec<&Employment> {
+        self.employments
+            .iter()
+            .filter(|employment| employment.is_current())
+            .collect()
+    }
+
+    /// Returns the place the person currently lives in.
+    pub fn current_location(&self) -> Option<&Location> {
+        self.locations
+            .iter()
+            .find(|location| location.is_current == Some(true))
+    }
+}
+
+/// A job held by a person.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Employment {
+    /// The organization the person worked for.
+    pub employer: Option<EntityRef>,
+    /// The title of the position, such as `"Chief Technology Officer"`.
+    pub title: Option<String>,
+    /// The categories of the position, such as `"Executive"` or
+    /// `"Engineering"`.
+    #[serde(default)]
+    pub categories: Vec<EntityRef>,
+    /// When the person started.
+    pub from: Option<KgDate>,
+    /// When the person left, if they did.
+    pub to: Option<KgDate>,
+    /// Whether the person still holds the position.
+    pub is_current: Option<bool>,
+    /// Where the person worked.
+    pub location: Option<Location>,
+}
+
+impl Employment {
+    /// Returns `true` if the person still holds the position: it is marked
+    /// current, or it has no end date.
+    pub fn is_current(&self) -> bool {
+        self.is_current.unwrap_or_else(|| self.to.is_none())
+    }
+
+    /// Returns `true` if one of the categories of the position has the
+    /// given name, ignoring case.
+    pub fn has_category(&self, name: &str) -> bool {
+        self.categories.iter().any(|category| category.is_named(name))
+    }
+
+    /// Returns `true` if the person held the position on `date`.
+    ///
+    /// A position without a start date is assumed to have started before
+    /// `date`, and one without an end date to still be held if it is
+    /// current. Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn held_on(&self, date: NaiveDate) -> bool {
+        let started = self.from
+                          .as_ref()
+                          .and_then(KgDate::first_day)
+                          .map_or(true, |from| from <= date);
+        let ended = match self.to.as_ref().and_then(KgDate::last_day) {
+            Some(to) => to < date,
+            None => !self.is_current(),
+        };
+        started && !ended
+    }
+}
+
+/// A school attended by a person.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Education {
+    /// The school.
+    pub institution: Option<EntityRef>,
+    /// The degree obtained, such as `"Master of Science"`.
+    pub degree: Option<EntityRef>,
+    /// The field of study, such as `"Computer Science"`.
+    pub major: Option<EntityRef>,
+    /// When the person started.
+    pub from: Option<KgDate>,
+    /// When the person graduated or left.
+    pub to: Option<KgDate>,
+}
+
+/// A place, such as where a person lives or works.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    /// The full address.
+    pub address: Option<String>,
+    /// The city.
+    pub city: Option<EntityRef>,
+    /// The state or region.
+    pub region: Option<EntityRef>,
+    /// The country.
+    pub country: Option<EntityRef>,
+    /// The latitude, in degrees.
+    pub latitude: Option<f64>,
+    /// The longitude, in degrees.
+    pub longitude: Option<f64>,
+    /// Whether this is the current location of the person.
+    pub is_current: Option<bool>,
+}
+
+/// A reference to another entity of the Knowledge Graph, such as an
+/// employer or a city.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityRef {
+    /// The name of the entity.
+    pub name: Option<String>,
+    /// The URI of the entity, if it is in the Knowledge Graph.
+    pub diffbot_uri: Option<String>,
+    /// The ID of the entity, if it is in the Knowledge Graph.
+    pub target_diffbot_id: Option<String>,
+}
+
+impl EntityRef {
+    fn is_named(&self, name: &str) -> bool {
+        self.name
+            .as_ref()
+            .map_or(false, |n| n.eq_ignore_ascii_case(name))
+    }
+}
+
+/// A date of the Knowledge Graph, known to the year, month or day.
+///
+/// The API gives it as a string like `d2015-04-XX`, where `XX` marks the
+/// unknown parts, with its precision: 1 for a year, 2 for a month, 3 for a
+/// day.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct KgDate {
+    /// The date as a string, like `d2015-04-XX`.
+    #[serde(rename = "str")]
+    pub value: Option<String>,
+    /// How much of the date is known, from 1 (the year) to 3 (the day).
+    pub precision: Option<u8>,
+    /// The first millisecond of the date, since the epoch.
+    pub timestamp: Option<i64>,
+}
+
+impl KgDate {
+    /// Returns the year, if it is known.
+    pub fn year(&self) -> Option<i32> {
+        self.part(0).map(|year| year as i32)
+    }
+
+    /// Returns the month, from 1 to 12, if it is known.
+    pub fn month(&self) -> Option<u32> {
+        self.part(1).filter(|&month| month >= 1 && month <= 12)
+    }
+
+    /// Returns the day of the month, if it is known.
+    pub fn day(&self) -> Option<u32> {
+        self.part(2).filter(|&day| day >= 1 && day <= 31)
+    }
+
+    /// Returns the first day of the date: the first of January if only the
+    /// year is known, the first of the month if the day is not.
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn first_day(&self) -> Option<NaiveDate> {
+        let month = self.month();
+        let day = month.and(self.day());
+        self.year().and_then(|year| {
+            NaiveDate::from_ymd_opt(year, month.unwrap_or(1), day.unwrap_or(1))
+        })
+    }
+
+    /// Returns the last day of the date: the 31st of December if only the
+    /// year is known, the end of the month if the day is not.
+    ///
+    /// Requires the `chrono` feature.
+    #[cfg(feature = "chrono")]
+    pub fn last_day(&self) -> Option<NaiveDate> {
+        let year = match self.year() {
+            Some(year) => year,
+            None => return None,
+        };
+        match (self.month(), self.day()) {
+            (Some(month), Some(day)) => {
+                NaiveDate::from_ymd_opt(year, month, day)
+            }
+            (Some(12), None) | (None, _) => {
+                NaiveDate::from_ymd_opt(year, 12, 31)
+            }
+            (Some(month), None) => {
+                NaiveDate::from_ymd_opt(year, month + 1, 1)
+                    .and_then(|next| next.pred_opt())
+            }
+        }
+    }
+
+    // Returns the numeric part at `index` of the string, limited by the
+    // precision.
+    fn part(&self, index: usize) -> Option<u32> {
+        let precision = self.precision.map_or(3, |p| p as usize);
+        if index >= precision {
+            return None;
+        }
+        self.value
+            .as_ref()
+            .and_then(|value| {
+                value.trim_start_matches('d').split('-').nth(index)
+            })
+            .and_then(|part| part.parse().ok())
+    }
+}
@@ -0,0 +1,172 @@
+//! Objects returned by the Product API.
+
+use serde_json::{Map, Value};
+
+use models::{Discussion, Image};
+
+/// A product, as returned by the Product and Analyze APIs.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Product {
+    /// The object type, `"product"`.
+    #[serde(rename = "type")]
+    pub object_type: Option<String>,
+    /// The name of the product.
+    pub title: Option<String>,
+    /// The description of the product.
+    pub text: Option<String>,
+    /// The brand of the product.
+    pub brand: Option<String>,
+    /// The offer price, as displayed (e.g. `"$17.99"`).
+    pub offer_price: Option<String>,
+    /// The offer price, parsed.
+    pub offer_price_details: Option<PriceDetails>,
+    /// The regular price, as displayed.
+    pub regular_price: Option<String>,
+    /// The regular price, parsed.
+    pub regular_price_details: Option<PriceDetails>,
+    /// The discount, as displayed.
+    pub save_amount: Option<String>,
+    /// The shipping cost, as displayed.
+    pub shipping_amount: Option<String>,
+    /// The identifier of the product on the site.
+    pub product_id: Option<String>,
+    /// The Universal Product Code.
+    pub upc: Option<String>,
+    /// The Stock Keeping Unit.
+    pub sku: Option<String>,
+    /// The Manufacturer Part Number.
+    pub mpn: Option<String>,
+    /// The ISBN, for books.
+    pub isbn: Option<String>,
+    /// Whether the product is in stock.
+    pub availability: Option<bool>,
+    /// The category of the product.
+    pub category: Option<String>,
+    /// The language of the page, as an ISO 639-1 code.
+    pub human_language: Option<String>,
+    /// The URL of the page.
+    pub page_url: Option<String>,
+    /// The unique identifier of this object.
+    pub diffbot_uri: Option<String>,
+    /// The images of the product.
+    #[serde(default)]
+    pub images: Vec<Image>,
+    /// The specifications of the product, by name.
+    #[serde(default)]
+    pub specs: Map<String, Value>,
+    /// The reviews, if the `discussion` option was enabled.
+    pub discussion: Option<Discussion>,
+}
+
+/// A parsed price.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PriceDetails {
+    /// The amount.
+    pub amount: Option<f64>,
+    /// The currency symbol, such as `"$"`.
+    pub symbol: Option<String>,
+    /// The price, as displayed.
+    pub text: Option<String>,
+}
+
+impl Product {
+    /// Returns the offer price converted to `currency`, an ISO 4217 code
+    /// like `"EUR"`.
+    ///
+    /// Returns `None` if the price or its currency is unknown, or if
+    /// `rates` has no rate for the conversion.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate diffbot;
+    /// # use diffbot::models::{PriceDetails, Product};
+    /// # fn main() {
+    /// let mut product = Product::default();
+    /// product.offer_price_details = Some(PriceDetails {
+    ///     amount: Some(10.0),
+    ///     symbol: Some("$".to_string()),
+    ///     text: Some("$10.00".to_string()),
+    /// });
+    /// let rates = |from: &str, to: &str| match (from, to) {
+    ///     ("USD", "EUR") => Some(0.5),
+    ///     _ => None,
+    /// };
+    /// assert_eq!(product.offer_price_in("EUR", &rates), Some(5.0));
+    /// # }
+    /// ```
+    pub fn offer_price_in<R>(&self, currency: &str, rates: &R) -> Option<f64>
+        where R: RateProvider + ?Sized
+    {
+        self.offer_price_details
+            .as_ref()
+            .and_then(|price| price.convert(currency, rates))
+    }
+}
+
+impl PriceDetails {
+    /// Returns the ISO 4217 code of the currency, guessed from the symbol.
+    ///
+    /// Ambiguous symbols are resolved to the most common currency: `$` is
+    /// read as US dollars and `¥` as yens.
+    pub fn currency(&self) -> Option<&str> {
+        let symbol = match self.symbol {
+            Some(ref symbol) => symbol.trim(),
+            None => return None,
+        };
+        let code = match symbol {
+            "$" | "US$" => "USD",
+            "€" => "EUR",
+            "£" => "GBP",
+            "¥" | "円" => "JPY",
+            "₹" | "Rs" | "Rs." => "INR",
+            "C$" | "CA$" => "CAD",
+            "A$" | "AU$" => "AUD",
+            "R$" => "BRL",
+            "₩" => "KRW",
+            "₽" => "RUB",
+            "CHF" | "Fr." => "CHF",
+            "kr" => "SEK",
+            "zł" => "PLN",
+            code if code.len() == 3 &&
+                    code.chars().all(|c| c.is_ascii_uppercase()) => code,
+            _ => return None,
+        };
+        Some(code)
+    }
+
+    /// Returns the amount converted to `currency`, an ISO 4217 code.
+    pub fn convert<R>(&self, currency: &str, rates: &R) -> Option<f64>
+        where R: RateProvider + ?Sized
+    {
+        let amount = match self.amount {
+            Some(amount) => amount,
+            None => return None,
+        };
+        let from = match self.currency() {
+            Some(from) => from,
+            None => return None,
+        };
+        if from == currency {
+            return Some(amount);
+        }
+        rates.rate(from, currency).map(|rate| amount * rate)
+    }
+}
+
+/// Exchange rates used to compare prices in different currencies.
+///
+/// Implemented for closures taking the source and target currency codes.
+pub trait RateProvider {
+    /// Returns how much one unit of `from` is worth in `to`, if known.
+    fn rate(&self, from: &str, to: &str) -> Option<f64>;
+}
+
+impl<F> RateProvider for F
+    where F: Fn(&str, &str) -> Option<f64>
+{
+    fn rate(&self, from: &str, to: &str) -> Option<f64> {
+        self(from, to)
+    }
+}
@@ -0,0 +1,105 @@
+//! Objects downloaded from crawl and bulk jobs.
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+/// An entry of the results of a crawl or bulk job.
+///
+/// Pages that could not be processed appear in the results as objects with
+/// an `error` field; they are parsed as a `Failure` instead of a `T`.
+///
+/// # Example
+///
+/// ```no_run
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// use diffbot::models::{self, Article, JobObject};
+///
+/// # fn main() {
+/// # let diffbot = Diffbot::v3("token");
+/// let results = diffbot.bulk_results::<JobObject<Article>>("my_bulk_job");
+/// let (articles, failures) =
+///     models::partition_results(results.map(Result::unwrap));
+/// println!("{} articles, {} failures", articles.len(), failures.len());
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub enum JobObject<T = Value> {
+    /// An object extracted from a page.
+    Success(T),
+    /// A page that could not be processed.
+    Failure(PageFailure),
+}
+
+impl<T> JobObject<T> {
+    /// Returns the extracted object, if any.
+    pub fn ok(self) -> Option<T> {
+        match self {
+            JobObject::Success(object) => Some(object),
+            JobObject::Failure(_) => None,
+        }
+    }
+
+    /// Returns `true` if the page could not be processed.
+    pub fn is_failure(&self) -> bool {
+        match *self {
+            JobObject::Success(_) => false,
+            JobObject::Failure(_) => true,
+        }
+    }
+}
+
+/// A page of a job that could not be processed.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageFailure {
+    /// The URL of the page.
+    pub page_url: Option<String>,
+    /// The error message.
+    pub error: String,
+    /// The error code, usually the HTTP status of the page.
+    pub error_code: Option<u32>,
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for JobObject<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D)
+                                         -> Result<Self, D::Error> {
+        let value = try!(Value::deserialize(deserializer));
+        let failed = value.get("error").map_or(false, Value::is_string);
+        if failed {
+            let failure = try!(serde_json::from_value(value)
+                                   .map_err(de::Error::custom));
+            return Ok(JobObject::Failure(failure));
+        }
+        let object = try!(serde_json::from_value(value)
+                              .map_err(de::Error::custom));
+        Ok(JobObject::Success(object))
+    }
+}
+
+impl<T: Serialize> Serialize for JobObject<T> {
+    fn serialize<S: Serializer>(&self, serializer: S)
+                                -> Result<S::Ok, S::Error> {
+        match *self {
+            JobObject::Success(ref object) => object.serialize(serializer),
+            JobObject::Failure(ref failure) => failure.serialize(serializer),
+        }
+    }
+}
+
+/// Splits the results of a job into the extracted objects and the pages
+/// that failed.
+pub fn partition_results<T, I>(results: I) -> (Vec<T>, Vec<PageFailure>)
+    where I: IntoIterator<Item = JobObject<T>>
+{
+    let mut objects = Vec::new();
+    let mut failures = Vec::new();
+    for result in results {
+        match result {
+            JobObject::Success(object) => objects.push(object),
+            JobObject::Failure(failure) => failures.push(failure),
+        }
+    }
+    (objects, failures)
+}
@@ -0,0 +1,115 @@
+//! Objects returned by the Search API.
+
+use std::ops::Deref;
+
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+use models::RequestInfo;
+
+/// The response of the Search API.
+///
+/// `T` is the type of the matching objects; use `Value` when they can be of
+/// several types.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(bound(deserialize = "T: DeserializeOwned",
+              serialize = "T: Serialize"))]
+pub struct SearchResponse<T = Value> {
+    /// A summary of the call.
+    pub request: Option<RequestInfo>,
+    /// The total number of matching objects.
+    pub hits: Option<u64>,
+    /// The number of objects in this response.
+    pub results: Option<u64>,
+    /// The matching objects.
+    #[serde(default = "Vec::new")]
+    pub objects: Vec<SearchHit<T>>,
+}
+
+/// A matching object, with the search metadata that comes with it.
+///
+/// The metadata is parsed from the fields of the object, which are also
+/// parsed as a `T`; the hit dereferences to that object.
+#[derive(Clone, Debug, Default)]
+pub struct SearchHit<T = Value> {
+    /// The relevance of the object to the query.
+    pub score: Option<f64>,
+    /// When the object was indexed, in milliseconds since the epoch.
+    pub timestamp: Option<u64>,
+    /// The identifier of the object in its collection.
+    pub doc_id: Option<u64>,
+    /// The object type, like `"article"` or `"product"`.
+    pub object_type: Option<String>,
+    /// The crawl or bulk job the object comes from.
+    pub job: Option<String>,
+    /// The object.
+    pub object: T,
+}
+
+// The metadata fields of a hit.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HitMetadata {
+    score: Option<f64>,
+    timestamp: Option<u64>,
+    doc_id: Option<u64>,
+    #[serde(rename = "type")]
+    object_type: Option<String>,
+    #[serde(rename = "crawlJob")]
+    job: Option<String>,
+}
+
+impl<T> Deref for SearchHit<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.object
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for SearchHit<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D)
+                                         -> Result<Self, D::Error> {
+        let value = try!(Value::deserialize(deserializer));
+        let metadata: HitMetadata = try!(serde_json::from_value(value.clone())
+                                             .map_err(de::Error::custom));
+        let object = try!(serde_json::from_value(value)
+                              .map_err(de::Error::custom));
+        Ok(SearchHit {
+            score: metadata.score,
+            timestamp: metadata.timestamp,
+            doc_id: metadata.doc_id,
+            object_type: metadata.object_type,
+            job: metadata.job,
+            object: object,
+        })
+    }
+}
+
+// Serializes the object, with the metadata fields it does not have.
+impl<T: Serialize> Serialize for SearchHit<T> {
+    fn serialize<S: Serializer>(&self, serializer: S)
+                                -> Result<S::Ok, S::Error> {
+        let metadata = HitMetadata {
+            score: self.score,
+            timestamp: self.timestamp,
+            doc_id: self.doc_id,
+            object_type: self.object_type.clone(),
+            job: self.job.clone(),
+        };
+        let mut object = try!(serde_json::to_value(&self.object)
+                                  .map_err(ser::Error::custom));
+        let metadata = try!(serde_json::to_value(&metadata)
+                                .map_err(ser::Error::custom));
+        if let (&mut Value::Object(ref mut object),
+                Value::Object(metadata)) = (&mut object, metadata) {
+            for (key, value) in metadata {
+                if !value.is_null() && !object.contains_key(&key) {
+                    object.insert(key, value);
+                }
+            }
+        }
+        object.serialize(serializer)
+    }
+}
@@ -0,0 +1,113 @@
+//! Objects returned by the Video API.
+
+use models::Image;
+
+/// A video, as returned by the Video and Analyze APIs.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Video {
+    /// The object type, `"video"`.
+    #[serde(rename = "type")]
+    pub object_type: Option<String>,
+    /// The title of the video.
+    pub title: Option<String>,
+    /// The description of the video.
+    pub text: Option<String>,
+    /// The publication date, as an RFC 1123 string.
+    pub date: Option<String>,
+    /// The author of the video.
+    pub author: Option<String>,
+    /// The length of the video, in seconds.
+    pub duration: Option<f64>,
+    /// The number of views.
+    pub view_count: Option<u64>,
+    /// The URL of the video file.
+    pub media_url: Option<String>,
+    /// The URL of the embeddable player.
+    pub embed_url: Option<String>,
+    /// The content type of the video file, such as `"video/mp4"`.
+    pub mime: Option<String>,
+    /// The width of the video, in pixels.
+    pub natural_width: Option<u32>,
+    /// The height of the video, in pixels.
+    pub natural_height: Option<u32>,
+    /// Other files of the same video, such as other resolutions.
+    #[serde(default)]
+    pub media: Vec<VideoMedia>,
+    /// The language of the video, as an ISO 639-1 code.
+    pub human_language: Option<String>,
+    /// The URL of the page.
+    pub page_url: Option<String>,
+    /// The unique identifier of this object.
+    pub diffbot_uri: Option<String>,
+    /// The thumbnails of the video.
+    #[serde(default)]
+    pub images: Vec<Image>,
+}
+
+/// A file or player showing a video.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoMedia {
+    /// The URL of the file or player.
+    pub url: Option<String>,
+    /// The content type, such as `"video/mp4"`.
+    pub mime: Option<String>,
+    /// The width of the video, in pixels.
+    pub natural_width: Option<u32>,
+    /// The height of the video, in pixels.
+    pub natural_height: Option<u32>,
+}
+
+impl Video {
+    /// Returns the best way to play the video.
+    ///
+    /// Video files are preferred over the embeddable player, MP4 files over
+    /// other formats, and then the highest resolution. The player is only
+    /// returned when no file is known; its `mime` is `"text/html"`.
+    pub fn best_media(&self) -> Option<VideoMedia> {
+        let main = VideoMedia {
+            url: self.media_url.clone(),
+            mime: self.mime.clone(),
+            natural_width: self.natural_width,
+            natural_height: self.natural_height,
+        };
+        let best = Some(main).into_iter()
+                             .chain(self.media.iter().cloned())
+                             .filter(|media| media.url.is_some())
+                             .max_by_key(|media| {
+                                 (media.is_mp4(), media.pixels())
+                             });
+        best.or_else(|| {
+            self.embed_url.as_ref().map(|url| {
+                VideoMedia {
+                    url: Some(url.clone()),
+                    mime: Some("text/html".to_string()),
+                    natural_width: self.natural_width,
+                    natural_height: self.natural_height,
+                }
+            })
+        })
+    }
+}
+
+impl VideoMedia {
+    // Tells MP4 files from the content type, or else the URL extension.
+    fn is_mp4(&self) -> bool {
+        match (&self.mime, &self.url) {
+            (&Some(ref mime), _) => mime.eq_ignore_ascii_case("video/mp4"),
+            (&None, &Some(ref url)) => {
+                let path = url.split(|c| c == '?' || c == '#')
+                              .next()
+                              .unwrap_or("");
+                path.to_lowercase().ends_with(".mp4")
+            }
+            (&None, &None) => false,
+        }
+    }
+
+    fn pixels(&self) -> u64 {
+        self.natural_width.unwrap_or(0) as u64 *
+        self.natural_height.unwrap_or(0) as u64
+    }
+}
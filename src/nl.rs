@@ -0,0 +1,273 @@
+//! The Natural Language API: entities, sentiment and facts found in text.
+//!
+//! Send text with `Diffbot::nl_analyze`, choosing what to find with
+//! `NlOptions`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! # extern crate diffbot;
+//! # use diffbot::*;
+//! use diffbot::nl::NlOptions;
+//!
+//! # fn main() {
+//! # let diffbot = Diffbot::v3("token");
+//! let options = NlOptions::new().entities().sentiment();
+//! let analysis = diffbot.nl_analyze("Diffbot is based in Menlo Park.",
+//!                                   &options)
+//!                       .unwrap();
+//! for entity in analysis.entities {
+//!     println!("{:?} ({:?})", entity.name, entity.salience);
+//! }
+//! # }
+//! ```
+
+use std::cmp::Ordering;
+
+use models;
+
+/// Options for the Natural Language API.
+#[derive(Clone, Debug, Default)]
+pub struct NlOptions {
+    fields: Vec<&'static str>,
+    lang: Option<String>,
+}
+
+impl NlOptions {
+    /// Returns an empty set of options: the API returns its default
+    /// fields.
+    pub fn new() -> Self {
+        NlOptions::default()
+    }
+
+    /// Requests the entities mentioned in the text.
+    pub fn entities(self) -> Self {
+        self.field("entities")
+    }
+
+    /// Requests the sentiment of the whole text.
+    pub fn sentiment(self) -> Self {
+        self.field("sentiment")
+    }
+
+    /// Requests the facts stated in the text.
+    pub fn facts(self) -> Self {
+        self.field("facts")
+    }
+
+    /// Sets the language of the text, as a code such as `"en"`, instead of
+    /// letting the API detect it.
+    pub fn lang<S: Into<String>>(mut self, lang: S) -> Self {
+        self.lang = Some(lang.into());
+        self
+    }
+
+    fn field(mut self, field: &'static str) -> Self {
+        if !self.fields.contains(&field) {
+            self.fields.push(field);
+        }
+        self
+    }
+
+    // Returns the query parameters for these options.
+    pub(crate) fn query_pairs(&self) -> Vec<(&'static str, String)> {
+        if self.fields.is_empty() {
+            Vec::new()
+        } else {
+            vec![("fields", self.fields.join(","))]
+        }
+    }
+
+    // Returns the document to send for `text`.
+    pub(crate) fn document(&self, text: &str) -> Document {
+        Document {
+            content: text.to_string(),
+            lang: self.lang.clone(),
+            format: "plain text",
+        }
+    }
+}
+
+// The body of a call.
+#[derive(Serialize)]
+pub(crate) struct Document {
+    content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lang: Option<String>,
+    format: &'static str,
+}
+
+/// What the Natural Language API found in a text.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Analysis {
+    /// The entities mentioned in the text.
+    #[serde(default)]
+    pub entities: Vec<Entity>,
+    /// The sentiment of the text, from -1 (negative) to 1 (positive).
+    pub sentiment: Option<f64>,
+    /// The facts stated in the text.
+    #[serde(default)]
+    pub facts: Vec<Fact>,
+    /// The language of the text.
+    pub language: Option<String>,
+}
+
+impl Analysis {
+    /// Returns the `n` most salient entities, by decreasing salience.
+    pub fn top_entities(&self, n: usize) -> Vec<&Entity> {
+        models::top_by(&self.entities, n, |entity| entity.salience)
+    }
+
+    /// Returns the entity with the given name, ignoring case.
+    pub fn entity(&self, name: &str) -> Option<&Entity> {
+        self.entities.iter().find(|entity| {
+            entity.name
+                  .as_ref()
+                  .map_or(false, |n| n.eq_ignore_ascii_case(name))
+        })
+    }
+
+    /// Returns the entities the text is favorable to, most positive first.
+    pub fn positive_entities(&self) -> Vec<&Entity> {
+        let mut entities: Vec<&Entity> =
+            self.entities.iter().filter(|e| e.is_positive()).collect();
+        entities.sort_by(|a, b| {
+            b.sentiment.partial_cmp(&a.sentiment).unwrap_or(Ordering::Equal)
+        });
+        entities
+    }
+
+    /// Returns the entities the text is unfavorable to, most negative
+    /// first.
+    pub fn negative_entities(&self) -> Vec<&Entity> {
+        let mut entities: Vec<&Entity> =
+            self.entities.iter().filter(|e| e.is_negative()).collect();
+        entities.sort_by(|a, b| {
+            a.sentiment.partial_cmp(&b.sentiment).unwrap_or(Ordering::Equal)
+        });
+        entities
+    }
+}
+
+/// An entity mentioned in a text.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entity {
+    /// The name of the entity.
+    pub name: Option<String>,
+    /// The Knowledge Graph URI of the entity, if it is known.
+    pub diffbot_uri: Option<String>,
+    /// How confident the API is that the entity is mentioned, from 0 to 1.
+    pub confidence: Option<f64>,
+    /// How central the entity is to the text, from 0 to 1.
+    pub salience: Option<f64>,
+    /// The sentiment of the text towards the entity, from -1 to 1.
+    pub sentiment: Option<f64>,
+    /// The types of the entity, such as `"organization"`.
+    #[serde(default)]
+    pub all_types: Vec<EntityType>,
+    /// Where the entity is mentioned.
+    #[serde(default)]
+    pub mentions: Vec<Mention>,
+}
+
+impl Entity {
+    /// Returns `true` if the text is favorable to the entity.
+    pub fn is_positive(&self) -> bool {
+        self.sentiment.map_or(false, |sentiment| sentiment > 0.0)
+    }
+
+    /// Returns `true` if the text is unfavorable to the entity.
+    pub fn is_negative(&self) -> bool {
+        self.sentiment.map_or(false, |sentiment| sentiment < 0.0)
+    }
+
+    /// Returns `true` if the entity has the given type, such as
+    /// `"organization"`.
+    pub fn has_type(&self, name: &str) -> bool {
+        self.all_types
+            .iter()
+            .any(|t| t.name.as_ref().map_or(false, |n| n == name))
+    }
+}
+
+/// A type of entity.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityType {
+    /// The name of the type.
+    pub name: Option<String>,
+    /// The Knowledge Graph URI of the type.
+    pub diffbot_uri: Option<String>,
+}
+
+/// A mention of an entity in a text.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Mention {
+    /// The text of the mention.
+    pub text: Option<String>,
+    /// The offset of the first character of the mention.
+    pub begin_offset: Option<u64>,
+    /// The offset after the last character of the mention.
+    pub end_offset: Option<u64>,
+    /// How confident the API is that this refers to the entity.
+    pub confidence: Option<f64>,
+}
+
+/// A fact stated in a text: an entity, a property and its value.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fact {
+    /// The fact as a sentence.
+    pub human_readable: Option<String>,
+    /// The entity the fact is about.
+    pub entity: Option<FactPart>,
+    /// The property, such as `"founded by"`.
+    pub property: Option<FactPart>,
+    /// The value of the property.
+    pub value: Option<FactPart>,
+    /// How confident the API is in the fact, from 0 to 1.
+    pub confidence: Option<f64>,
+}
+
+/// An entity, property or value of a `Fact`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FactPart {
+    /// The name.
+    pub name: Option<String>,
+    /// The Knowledge Graph URI, if it is known.
+    pub diffbot_uri: Option<String>,
+}
+
+#[test]
+fn test_nl_options() {
+    let options = NlOptions::new().entities().facts().entities().lang("fr");
+    assert_eq!(options.query_pairs(),
+               vec![("fields", "entities,facts".to_string())]);
+    let body = ::serde_json::to_value(options.document("Bonjour")).unwrap();
+    assert_eq!(body["content"], "Bonjour");
+    assert_eq!(body["lang"], "fr");
+    assert!(NlOptions::new().query_pairs().is_empty());
+}
+
+#[test]
+fn test_entity_ranking() {
+    let analysis: Analysis = ::serde_json::from_str(r#"{"entities": [
+        {"name": "A", "salience": 0.2, "sentiment": -0.5},
+        {"name": "B", "salience": 0.9, "sentiment": 0.1},
+        {"name": "C", "sentiment": 0.8,
+         "allTypes": [{"name": "organization"}]},
+        {"name": "D", "salience": 0.5, "sentiment": -0.9}]}"#)
+                                 .unwrap();
+    let names = |entities: Vec<&Entity>| {
+        entities.iter()
+                .map(|entity| entity.name.clone().unwrap())
+                .collect::<Vec<_>>()
+    };
+    assert_eq!(names(analysis.top_entities(3)), vec!["B", "D", "A"]);
+    assert_eq!(names(analysis.positive_entities()), vec!["C", "B"]);
+    assert_eq!(names(analysis.negative_entities()), vec!["D", "A"]);
+    assert!(analysis.entity("c").unwrap().has_type("organization"));
+}
@@ -0,0 +1,208 @@
+//! Notifications sent by Diffbot when a job finishes.
+//!
+//! A job created with a `notifyWebhook` URL makes Diffbot POST the status of
+//! the job to that URL at the end of each round. `JobNotification` parses
+//! the body of that request.
+//!
+//! `handle_job_webhook` does this from any web framework. To make sure
+//! requests come from Diffbot, add a secret to the webhook URL and check it
+//! with `WebhookSecret`:
+//!
+//! ```
+//! # extern crate diffbot;
+//! use diffbot::CrawlSettings;
+//! use diffbot::notifications::WebhookSecret;
+//!
+//! # fn main() {
+//! let secret = WebhookSecret::new("s3cr3t");
+//! let url = secret.webhook_url("https://example.com/diffbot").unwrap();
+//! let settings = CrawlSettings::new().notify_webhook(url.as_str());
+//!
+//! // In the handler, with the query string and body of the request:
+//! # let (query, body) = ("secret=s3cr3t", br#"{"name": "news"}"#);
+//! match secret.handle(query, body) {
+//!     Ok(notification) => println!("{:?} is done", notification.name),
+//!     Err(err) => println!("rejected: {}", err),
+//! }
+//! # }
+//! ```
+
+use std::fmt;
+use std::ops::Deref;
+
+use serde_json::{self, Value};
+use url::{form_urlencoded, Url};
+
+use Error;
+use models::{Job, JobsResponse};
+use redact;
+
+/// Parses the body of a webhook request sent by Diffbot.
+///
+/// This does not check where the request comes from; see `WebhookSecret`.
+pub fn handle_job_webhook(body: &[u8]) -> Result<JobNotification, Error> {
+    JobNotification::parse(body)
+}
+
+/// A secret shared with Diffbot through the webhook URL.
+///
+/// The secret is added to the URL as a query parameter, `secret` by
+/// default, and requests without it are rejected.
+#[derive(Clone)]
+pub struct WebhookSecret {
+    param: String,
+    value: String,
+}
+
+impl WebhookSecret {
+    /// Uses `value` as the secret, in the `secret` query parameter.
+    pub fn new<S: Into<String>>(value: S) -> Self {
+        WebhookSecret {
+            param: "secret".to_string(),
+            value: value.into(),
+        }
+    }
+
+    /// Sets the name of the query parameter holding the secret.
+    pub fn param<S: Into<String>>(mut self, param: S) -> Self {
+        self.param = param.into();
+        self
+    }
+
+    /// Returns the webhook URL to give to Diffbot, with the secret added.
+    pub fn webhook_url(&self, url: &str) -> Result<Url, Error> {
+        let mut url = try!(Url::parse(url).map_err(|err| {
+            Error::InvalidUrl(format!("{}: {}", err, url))
+        }));
+        url.query_pairs_mut().append_pair(&self.param, &self.value);
+        Ok(url)
+    }
+
+    /// Checks that a request carries the secret.
+    ///
+    /// `query` is the query string of the request, or its whole URI.
+    pub fn verify(&self, query: &str) -> Result<(), Error> {
+        let query = match query.find('?') {
+            Some(start) => &query[start + 1..],
+            None => query,
+        };
+        let found = form_urlencoded::parse(query.as_bytes())
+                        .any(|(key, value)| {
+                            key == self.param.as_str() &&
+                            constant_time_eq(value.as_bytes(),
+                                             self.value.as_bytes())
+                        });
+        if found {
+            Ok(())
+        } else {
+            let message = format!("missing or wrong `{}` parameter",
+                                  self.param);
+            Err(Error::WebhookRejected(message))
+        }
+    }
+
+    /// Checks that a request carries the secret, then parses its body.
+    pub fn handle(&self, query: &str, body: &[u8])
+                  -> Result<JobNotification, Error> {
+        try!(self.verify(query));
+        handle_job_webhook(body)
+    }
+}
+
+impl fmt::Debug for WebhookSecret {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("WebhookSecret")
+           .field("param", &self.param)
+           .field("value", &redact::redact_token(&self.value))
+           .finish()
+    }
+}
+
+// Compares secrets in a time that does not depend on where they differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() &&
+    a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// The status of a job, as sent to its webhook.
+///
+/// It dereferences to the `Job` it describes.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// use diffbot::notifications::JobNotification;
+///
+/// # fn main() {
+/// let body = br#"{"name": "news", "type": "crawl",
+///                 "jobStatus": {"status": 9, "message": "Done"}}"#;
+/// let notification = JobNotification::parse(body).unwrap();
+/// assert_eq!(notification.name.as_ref().unwrap(), "news");
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct JobNotification {
+    /// The job that finished a round.
+    pub job: Job,
+}
+
+impl JobNotification {
+    /// Parses the body of a webhook request.
+    ///
+    /// Both a single job object and a `{"jobs": [...]}` response, as
+    /// returned by the job APIs, are accepted.
+    pub fn parse(body: &[u8]) -> Result<JobNotification, Error> {
+        let value: Value = try!(serde_json::from_slice(body));
+        let job = if value.get("jobs").is_some() {
+            let response: JobsResponse = try!(serde_json::from_value(value));
+            match response.jobs.into_iter().next() {
+                Some(job) => job,
+                None => Job::default(),
+            }
+        } else {
+            try!(serde_json::from_value(value))
+        };
+        Ok(JobNotification { job: job })
+    }
+}
+
+impl Deref for JobNotification {
+    type Target = Job;
+
+    fn deref(&self) -> &Job {
+        &self.job
+    }
+}
+
+#[test]
+fn test_parse_notification() {
+    let body = include_bytes!("../samples/webhook.json");
+    let notification = JobNotification::parse(body).unwrap();
+    assert_eq!(notification.name.as_ref().unwrap(), "diffbot-blog");
+    assert_eq!(notification.job_status.as_ref().unwrap().status, 9);
+    assert_eq!(notification.objects_found, Some(148));
+
+    let status = include_bytes!("../samples/crawl_status.json");
+    let notification = JobNotification::parse(status).unwrap();
+    assert_eq!(notification.name.as_ref().unwrap(), "diffbot-blog");
+
+    assert!(JobNotification::parse(b"name=diffbot-blog").is_err());
+}
+
+#[test]
+fn test_webhook_secret() {
+    let secret = WebhookSecret::new("a&b").param("key");
+    let url = secret.webhook_url("https://example.com/hook?x=1").unwrap();
+    assert_eq!(url.as_str(), "https://example.com/hook?x=1&key=a%26b");
+
+    let body = br#"{"name": "news"}"#;
+    assert!(secret.handle("/hook?x=1&key=a%26b", body).is_ok());
+    assert!(secret.verify("key=a%26c").is_err());
+    assert!(secret.verify("x=1").is_err());
+    match secret.handle("key=a", body) {
+        Err(Error::WebhookRejected(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert!(!format!("{:?}", secret).contains("a&b"));
+}
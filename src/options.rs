@@ -0,0 +1,420 @@
+//! Typed options for each extraction API.
+//!
+//! Each struct only exposes the parameters documented for its API, so a
+//! misspelled option is a compile error rather than a silently ignored
+//! query parameter. Apply them to a call with `Request::options`.
+
+use std::time::Duration;
+
+use PageType;
+use fields::Fields;
+
+/// A set of options that can be applied to a request.
+pub trait ApiOptions {
+    /// Returns the `(key, value)` query parameters for these options.
+    fn query_pairs(&self) -> Vec<(&'static str, String)>;
+}
+
+// Converts a duration to the milliseconds expected by `timeout` parameters.
+pub(crate) fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + (duration.subsec_nanos() / 1_000_000) as u64
+}
+
+// Appends `key=value` to `pairs` when the option was set.
+pub(crate) fn push<T: ToString>(pairs: &mut Vec<(&'static str, String)>,
+                     key: &'static str, value: &Option<T>) {
+    if let Some(ref value) = *value {
+        pairs.push((key, value.to_string()));
+    }
+}
+
+// Appends the `fields` parameter when any field was set.
+fn push_fields(pairs: &mut Vec<(&'static str, String)>, fields: &Fields) {
+    if !fields.is_empty() {
+        pairs.push(("fields", fields.to_string()));
+    }
+}
+
+/// Options for the Article API.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # fn main() {
+/// # let diffbot = Diffbot::v3("token");
+/// let options = ArticleOptions::new().paging(false).max_tags(5);
+/// # println!("{:?}",
+/// diffbot.request(API::Article, "http://diffbot.com")
+///        .options(&options)
+///        .send()
+/// # );
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ArticleOptions {
+    fields: Fields,
+    paging: Option<bool>,
+    max_tags: Option<u32>,
+    discussion: Option<bool>,
+    timeout: Option<u64>,
+}
+
+impl ArticleOptions {
+    /// Returns an empty set of options.
+    pub fn new() -> Self {
+        ArticleOptions::default()
+    }
+
+    /// Requests an optional field in the response.
+    pub fn field<S: Into<String>>(mut self, field: S) -> Self {
+        self.fields = self.fields.field(field);
+        self
+    }
+
+    /// Requests a selection of optional fields in the response.
+    pub fn fields(mut self, fields: Fields) -> Self {
+        self.fields = self.fields.extend(fields);
+        self
+    }
+
+    /// Enables or disables the concatenation of multi-page articles.
+    pub fn paging(mut self, paging: bool) -> Self {
+        self.paging = Some(paging);
+        self
+    }
+
+    /// Sets the maximum number of tags returned.
+    pub fn max_tags(mut self, max_tags: u32) -> Self {
+        self.max_tags = Some(max_tags);
+        self
+    }
+
+    /// Enables or disables the extraction of comments.
+    pub fn discussion(mut self, discussion: bool) -> Self {
+        self.discussion = Some(discussion);
+        self
+    }
+
+    /// Sets the processing timeout.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.timeout_ms(millis(timeout))
+    }
+
+    /// Sets the processing timeout, in milliseconds.
+    pub fn timeout_ms(mut self, timeout: u64) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl ApiOptions for ArticleOptions {
+    fn query_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = Vec::new();
+        push_fields(&mut pairs, &self.fields);
+        push(&mut pairs, "paging", &self.paging);
+        push(&mut pairs, "maxTags", &self.max_tags);
+        push(&mut pairs, "discussion", &self.discussion);
+        push(&mut pairs, "timeout", &self.timeout);
+        pairs
+    }
+}
+
+/// Options for the Product API.
+#[derive(Clone, Debug, Default)]
+pub struct ProductOptions {
+    fields: Fields,
+    discussion: Option<bool>,
+    timeout: Option<u64>,
+}
+
+impl ProductOptions {
+    /// Returns an empty set of options.
+    pub fn new() -> Self {
+        ProductOptions::default()
+    }
+
+    /// Requests an optional field in the response.
+    pub fn field<S: Into<String>>(mut self, field: S) -> Self {
+        self.fields = self.fields.field(field);
+        self
+    }
+
+    /// Requests a selection of optional fields in the response.
+    pub fn fields(mut self, fields: Fields) -> Self {
+        self.fields = self.fields.extend(fields);
+        self
+    }
+
+    /// Enables or disables the extraction of reviews.
+    pub fn discussion(mut self, discussion: bool) -> Self {
+        self.discussion = Some(discussion);
+        self
+    }
+
+    /// Sets the processing timeout.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.timeout_ms(millis(timeout))
+    }
+
+    /// Sets the processing timeout, in milliseconds.
+    pub fn timeout_ms(mut self, timeout: u64) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl ApiOptions for ProductOptions {
+    fn query_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = Vec::new();
+        push_fields(&mut pairs, &self.fields);
+        push(&mut pairs, "discussion", &self.discussion);
+        push(&mut pairs, "timeout", &self.timeout);
+        pairs
+    }
+}
+
+/// Options for the Analyze API.
+#[derive(Clone, Debug, Default)]
+pub struct AnalyzeOptions {
+    fields: Fields,
+    mode: Option<PageType>,
+    discussion: Option<bool>,
+    timeout: Option<u64>,
+}
+
+impl AnalyzeOptions {
+    /// Returns an empty set of options.
+    pub fn new() -> Self {
+        AnalyzeOptions::default()
+    }
+
+    /// Requests an optional field in the response.
+    pub fn field<S: Into<String>>(mut self, field: S) -> Self {
+        self.fields = self.fields.field(field);
+        self
+    }
+
+    /// Requests a selection of optional fields in the response.
+    pub fn fields(mut self, fields: Fields) -> Self {
+        self.fields = self.fields.extend(fields);
+        self
+    }
+
+    /// Only extracts pages of the given type, like `PageType::Article`.
+    ///
+    /// Pages of other types are still classified, but no object is
+    /// extracted from them. A type name such as `"article"` is also
+    /// accepted.
+    pub fn mode<M: Into<PageType>>(mut self, mode: M) -> Self {
+        self.mode = Some(mode.into());
+        self
+    }
+
+    /// Enables or disables the extraction of comments and reviews.
+    pub fn discussion(mut self, discussion: bool) -> Self {
+        self.discussion = Some(discussion);
+        self
+    }
+
+    /// Sets the processing timeout.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.timeout_ms(millis(timeout))
+    }
+
+    /// Sets the processing timeout, in milliseconds.
+    pub fn timeout_ms(mut self, timeout: u64) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl ApiOptions for AnalyzeOptions {
+    fn query_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = Vec::new();
+        push_fields(&mut pairs, &self.fields);
+        push(&mut pairs, "mode", &self.mode);
+        push(&mut pairs, "discussion", &self.discussion);
+        push(&mut pairs, "timeout", &self.timeout);
+        pairs
+    }
+}
+
+/// Options for the Discussion API.
+#[derive(Clone, Debug, Default)]
+pub struct DiscussionOptions {
+    fields: Fields,
+    max_pages: Option<u32>,
+    timeout: Option<u64>,
+}
+
+impl DiscussionOptions {
+    /// Returns an empty set of options.
+    pub fn new() -> Self {
+        DiscussionOptions::default()
+    }
+
+    /// Requests an optional field in the response.
+    pub fn field<S: Into<String>>(mut self, field: S) -> Self {
+        self.fields = self.fields.field(field);
+        self
+    }
+
+    /// Requests a selection of optional fields in the response.
+    pub fn fields(mut self, fields: Fields) -> Self {
+        self.fields = self.fields.extend(fields);
+        self
+    }
+
+    /// Sets the maximum number of pages of the thread to follow.
+    pub fn max_pages(mut self, max_pages: u32) -> Self {
+        self.max_pages = Some(max_pages);
+        self
+    }
+
+    /// Sets the processing timeout.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.timeout_ms(millis(timeout))
+    }
+
+    /// Sets the processing timeout, in milliseconds.
+    pub fn timeout_ms(mut self, timeout: u64) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl ApiOptions for DiscussionOptions {
+    fn query_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = Vec::new();
+        push_fields(&mut pairs, &self.fields);
+        push(&mut pairs, "maxPages", &self.max_pages);
+        push(&mut pairs, "timeout", &self.timeout);
+        pairs
+    }
+}
+
+/// Options for the Image API.
+#[derive(Clone, Debug, Default)]
+pub struct ImageOptions {
+    fields: Fields,
+    timeout: Option<u64>,
+}
+
+impl ImageOptions {
+    /// Returns an empty set of options.
+    pub fn new() -> Self {
+        ImageOptions::default()
+    }
+
+    /// Requests an optional field in the response.
+    pub fn field<S: Into<String>>(mut self, field: S) -> Self {
+        self.fields = self.fields.field(field);
+        self
+    }
+
+    /// Requests a selection of optional fields in the response.
+    pub fn fields(mut self, fields: Fields) -> Self {
+        self.fields = self.fields.extend(fields);
+        self
+    }
+
+    /// Sets the processing timeout.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.timeout_ms(millis(timeout))
+    }
+
+    /// Sets the processing timeout, in milliseconds.
+    pub fn timeout_ms(mut self, timeout: u64) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl ApiOptions for ImageOptions {
+    fn query_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = Vec::new();
+        push_fields(&mut pairs, &self.fields);
+        push(&mut pairs, "timeout", &self.timeout);
+        pairs
+    }
+}
+
+/// Options for the Video API.
+#[derive(Clone, Debug, Default)]
+pub struct VideoOptions {
+    fields: Fields,
+    timeout: Option<u64>,
+}
+
+impl VideoOptions {
+    /// Returns an empty set of options.
+    pub fn new() -> Self {
+        VideoOptions::default()
+    }
+
+    /// Requests an optional field in the response.
+    pub fn field<S: Into<String>>(mut self, field: S) -> Self {
+        self.fields = self.fields.field(field);
+        self
+    }
+
+    /// Requests a selection of optional fields in the response.
+    pub fn fields(mut self, fields: Fields) -> Self {
+        self.fields = self.fields.extend(fields);
+        self
+    }
+
+    /// Sets the processing timeout.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.timeout_ms(millis(timeout))
+    }
+
+    /// Sets the processing timeout, in milliseconds.
+    pub fn timeout_ms(mut self, timeout: u64) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl ApiOptions for VideoOptions {
+    fn query_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = Vec::new();
+        push_fields(&mut pairs, &self.fields);
+        push(&mut pairs, "timeout", &self.timeout);
+        pairs
+    }
+}
+
+#[test]
+fn test_article_options() {
+    let options = ArticleOptions::new()
+                      .field("links")
+                      .field("meta")
+                      .paging(false)
+                      .max_tags(5);
+    assert_eq!(options.query_pairs(),
+               vec![("fields", "links,meta".to_string()),
+                    ("paging", "false".to_string()),
+                    ("maxTags", "5".to_string())]);
+}
+
+#[test]
+fn test_timeout_millis() {
+    let options = VideoOptions::new().timeout(Duration::from_millis(30_500));
+    assert_eq!(options.query_pairs(), vec![("timeout", "30500".to_string())]);
+}
+
+#[test]
+fn test_empty_options() {
+    assert!(AnalyzeOptions::new().query_pairs().is_empty());
+}
+
+#[test]
+fn test_analyze_mode() {
+    let options = AnalyzeOptions::new().mode(PageType::Product);
+    assert_eq!(options.query_pairs(), vec![("mode", "product".to_string())]);
+    let options = AnalyzeOptions::new().mode("article");
+    assert_eq!(options.query_pairs(), vec![("mode", "article".to_string())]);
+}
@@ -0,0 +1,316 @@
+//! Rendering of results for people and other tools.
+//!
+//! Results can be written as indented JSON, JSON lines, CSV or a plain text
+//! table. The last three write one row per object: the `objects` or `jobs`
+//! of a response, the elements of an array, or else the value itself.
+//! Nested fields are flattened into columns named by their path, like
+//! `tags.0.label`.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate diffbot;
+//! # extern crate serde_json;
+//! use diffbot::output::{self, Format};
+//!
+//! # fn main() {
+//! let result = serde_json::from_str(r#"{"objects": [{"title": "T",
+//!                                       "author": {"name": "A"}}]}"#)
+//!                  .unwrap();
+//! let mut csv = Vec::new();
+//! output::write(&mut csv, Format::Csv, &result, &[]).unwrap();
+//! assert_eq!(String::from_utf8(csv).unwrap(), "author.name,title\nA,T\n");
+//! # }
+//! ```
+
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use serde_json::{self, Value};
+
+use Error;
+
+// Table cells longer than this are truncated.
+const MAX_CELL_WIDTH: usize = 40;
+
+/// An output format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// The whole value as indented JSON.
+    Json,
+    /// One compact JSON object per line.
+    JsonLines,
+    /// Comma-separated values, with a header line.
+    Csv,
+    /// A table aligned with spaces, for reading in a terminal.
+    Table,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(match *self {
+            Format::Json => "json",
+            Format::JsonLines => "jsonl",
+            Format::Csv => "csv",
+            Format::Table => "table",
+        })
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Format, Error> {
+        match name {
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::JsonLines),
+            "csv" => Ok(Format::Csv),
+            "table" => Ok(Format::Table),
+            _ => {
+                let message = format!("unknown output format: {:?}", name);
+                Err(Error::Io(io::Error::new(io::ErrorKind::InvalidInput,
+                                             message)))
+            }
+        }
+    }
+}
+
+/// Writes a value in the given format.
+///
+/// `columns` selects the fields of the CSV and table formats, by path. If it
+/// is empty, every field found in the rows is used, in order of appearance.
+/// It is ignored by the JSON formats.
+pub fn write<W: Write>(out: W, format: Format, value: &Value,
+                       columns: &[&str])
+                       -> io::Result<()> {
+    match format {
+        Format::Json => write_json(out, value),
+        Format::JsonLines => write_json_lines(out, value),
+        Format::Csv => write_csv(out, value, columns),
+        Format::Table => write_table(out, value, columns),
+    }
+}
+
+/// Writes a value as indented JSON.
+pub fn write_json<W: Write>(mut out: W, value: &Value) -> io::Result<()> {
+    try!(serde_json::to_writer_pretty(&mut out, value).map_err(io_error));
+    writeln!(out)
+}
+
+/// Writes each row of a value as a line of compact JSON.
+pub fn write_json_lines<W: Write>(mut out: W, value: &Value)
+                                  -> io::Result<()> {
+    for row in rows(value) {
+        try!(serde_json::to_writer(&mut out, row).map_err(io_error));
+        try!(writeln!(out));
+    }
+    Ok(())
+}
+
+/// Writes the rows of a value as CSV.
+pub fn write_csv<W: Write>(mut out: W, value: &Value, columns: &[&str])
+                           -> io::Result<()> {
+    let (columns, cells) = table(value, columns);
+    try!(write_csv_line(&mut out, &columns));
+    for row in cells {
+        try!(write_csv_line(&mut out, &row));
+    }
+    Ok(())
+}
+
+/// Writes the rows of a value as a table aligned with spaces.
+///
+/// Long cells are truncated, and line breaks are replaced with spaces.
+pub fn write_table<W: Write>(mut out: W, value: &Value, columns: &[&str])
+                             -> io::Result<()> {
+    let (columns, cells) = table(value, columns);
+    let cells: Vec<Vec<String>> = cells.iter()
+                                       .map(|row| {
+                                           row.iter()
+                                              .map(|cell| table_cell(cell))
+                                              .collect()
+                                       })
+                                       .collect();
+    let widths: Vec<usize> = columns.iter()
+                                    .enumerate()
+                                    .map(|(i, column)| {
+                                        cells.iter()
+                                             .map(|row| width(&row[i]))
+                                             .chain(Some(width(column)))
+                                             .max()
+                                             .unwrap_or(0)
+                                    })
+                                    .collect();
+
+    try!(write_table_line(&mut out, &columns, &widths));
+    let rule: Vec<String> = widths.iter()
+                                  .map(|&width| "-".repeat(width))
+                                  .collect();
+    try!(write_table_line(&mut out, &rule, &widths));
+    for row in &cells {
+        try!(write_table_line(&mut out, row, &widths));
+    }
+    Ok(())
+}
+
+/// Returns the rows of a value.
+///
+/// These are the `objects` or `jobs` of an object that has them, the
+/// elements of an array, or else the value itself.
+pub fn rows(value: &Value) -> Vec<&Value> {
+    for key in &["objects", "jobs"] {
+        if let Some(&Value::Array(ref rows)) = value.get(key) {
+            return rows.iter().collect();
+        }
+    }
+    match *value {
+        Value::Array(ref rows) => rows.iter().collect(),
+        ref value => vec![value],
+    }
+}
+
+/// Flattens a value into (path, text) pairs, one per scalar field.
+///
+/// Object keys and array indexes are joined with dots. Strings are kept as
+/// is, `null` becomes an empty string and other scalars their JSON text.
+pub fn flatten(value: &Value) -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    flatten_into(value, String::new(), &mut fields);
+    fields
+}
+
+fn flatten_into(value: &Value, path: String,
+                fields: &mut Vec<(String, String)>) {
+    let child = |key: &str| if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    };
+    match *value {
+        Value::Object(ref map) => {
+            for (key, value) in map {
+                flatten_into(value, child(key), fields);
+            }
+        }
+        Value::Array(ref values) => {
+            for (i, value) in values.iter().enumerate() {
+                flatten_into(value, child(&i.to_string()), fields);
+            }
+        }
+        Value::Null => fields.push((path, String::new())),
+        Value::String(ref text) => fields.push((path, text.clone())),
+        ref scalar => fields.push((path, scalar.to_string())),
+    }
+}
+
+// Returns the column names and the cells of each row.
+fn table(value: &Value, columns: &[&str]) -> (Vec<String>, Vec<Vec<String>>) {
+    let rows: Vec<Vec<(String, String)>> = rows(value).into_iter()
+                                                      .map(flatten)
+                                                      .collect();
+    let mut names: Vec<String> = columns.iter()
+                                        .map(|column| column.to_string())
+                                        .collect();
+    if names.is_empty() {
+        for row in &rows {
+            for &(ref path, _) in row {
+                if !names.contains(path) {
+                    names.push(path.clone());
+                }
+            }
+        }
+    }
+
+    let cells = rows.iter()
+                    .map(|row| {
+                        names.iter()
+                             .map(|name| {
+                                 row.iter()
+                                    .find(|&&(ref path, _)| path == name)
+                                    .map(|&(_, ref text)| text.clone())
+                                    .unwrap_or_default()
+                             })
+                             .collect()
+                    })
+                    .collect();
+    (names, cells)
+}
+
+fn write_csv_line<W: Write>(out: &mut W, cells: &[String])
+                            -> io::Result<()> {
+    for (i, cell) in cells.iter().enumerate() {
+        if i > 0 {
+            try!(out.write_all(b","));
+        }
+        if cell.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+            try!(write!(out, "\"{}\"", cell.replace('"', "\"\"")));
+        } else {
+            try!(out.write_all(cell.as_bytes()));
+        }
+    }
+    out.write_all(b"\n")
+}
+
+fn write_table_line<W: Write>(out: &mut W, cells: &[String],
+                              widths: &[usize])
+                              -> io::Result<()> {
+    let mut line = String::new();
+    for (i, (cell, &width)) in cells.iter().zip(widths).enumerate() {
+        if i > 0 {
+            line.push_str("  ");
+        }
+        line.push_str(cell);
+        if i + 1 < cells.len() {
+            line.push_str(&" ".repeat(width - self::width(cell)));
+        }
+    }
+    writeln!(out, "{}", line)
+}
+
+fn table_cell(text: &str) -> String {
+    let text = text.replace(|c| c == '\n' || c == '\r' || c == '\t', " ");
+    if width(&text) <= MAX_CELL_WIDTH {
+        return text;
+    }
+    let mut cell: String = text.chars().take(MAX_CELL_WIDTH - 1).collect();
+    cell.push('…');
+    cell
+}
+
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+fn io_error(err: serde_json::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+#[test]
+fn test_output_formats() {
+    let value = json(r#"{"objects": [
+        {"title": "a, \"b\"", "n": 1},
+        {"title": "c", "tags": [{"x": null}]}]}"#);
+    let render = |format, columns: &[&str]| {
+        let mut out = Vec::new();
+        write(&mut out, format, &value, columns).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    assert_eq!(render(Format::JsonLines, &[]),
+               "{\"n\":1,\"title\":\"a, \\\"b\\\"\"}\n\
+                {\"tags\":[{\"x\":null}],\"title\":\"c\"}\n");
+    assert_eq!(render(Format::Csv, &[]),
+               "n,title,tags.0.x\n1,\"a, \"\"b\"\"\",\n,c,\n");
+    assert_eq!(render(Format::Csv, &["title", "missing"]),
+               "title,missing\n\"a, \"\"b\"\"\",\nc,\n");
+    assert_eq!(render(Format::Table, &["n", "title"]),
+               "n  title\n-  ------\n1  a, \"b\"\n   c\n");
+    assert_eq!("jsonl".parse::<Format>().unwrap(), Format::JsonLines);
+    assert!("xml".parse::<Format>().is_err());
+}
+
+#[cfg(test)]
+fn json(text: &str) -> Value {
+    serde_json::from_str(text).unwrap()
+}
@@ -0,0 +1,79 @@
+//! Checks of the monthly call quota before starting jobs.
+
+use std::fmt;
+use std::sync::Arc;
+
+/// A job that could use more calls than are left in the plan this month.
+///
+/// Passed to the callback of `Diffbot::with_quota_warning`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuotaShortfall {
+    job: String,
+    calls: Option<u64>,
+    remaining: u64,
+}
+
+impl QuotaShortfall {
+    pub(crate) fn new(job: &str, calls: Option<u64>, remaining: u64)
+                      -> Self {
+        QuotaShortfall {
+            job: job.to_string(),
+            calls: calls,
+            remaining: remaining,
+        }
+    }
+
+    /// Returns the name of the job.
+    pub fn job(&self) -> &str {
+        &self.job
+    }
+
+    /// Returns the most calls the job can make over all its rounds, or
+    /// `None` if it has no limit: a crawl without `maxToProcess`, or a job
+    /// repeating without `maxRounds`.
+    pub fn calls(&self) -> Option<u64> {
+        self.calls
+    }
+
+    /// Returns the number of calls left in the plan this month.
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+}
+
+impl fmt::Display for QuotaShortfall {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.calls {
+            Some(calls) => {
+                write!(fmt,
+                       "job {:?} can make {} calls, {} left this month",
+                       self.job,
+                       calls,
+                       self.remaining)
+            }
+            None => {
+                write!(fmt,
+                       "job {:?} has no limit on its calls, {} calls left \
+                        this month",
+                       self.job,
+                       self.remaining)
+            }
+        }
+    }
+}
+
+// What the client does when a job could exceed the quota.
+#[derive(Clone)]
+pub(crate) enum QuotaCheck {
+    Fail,
+    Warn(Arc<dyn Fn(&QuotaShortfall) + Send + Sync>),
+}
+
+impl fmt::Debug for QuotaCheck {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            QuotaCheck::Fail => fmt.write_str("Fail"),
+            QuotaCheck::Warn(_) => fmt.write_str("Warn"),
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Masking of API tokens and other secrets in debug and error output.
+
+use url::Url;
+
+// Masks a token, keeping only enough of it to tell tokens apart.
+pub(crate) fn redact_token(token: &str) -> String {
+    let chars: Vec<char> = token.chars().collect();
+    if chars.len() < 12 {
+        return "…".to_string();
+    }
+    let head: String = chars[..3].iter().cloned().collect();
+    let tail: String = chars[chars.len() - 4..].iter().cloned().collect();
+    format!("{}…{}", head, tail)
+}
+
+// Returns the URL as a string, with the `token` query parameter masked.
+pub(crate) fn redact_url(url: &Url) -> String {
+    redact_text(url.as_str())
+}
+
+// Masks the value of every secret query parameter, such as `token=`, found
+// in `text`.
+//
+// Used on messages we don't control, such as transport errors, which may
+// contain the full request URL.
+pub(crate) fn redact_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some((pos, param)) = find_secret_param(rest) {
+        let value_start = pos + param.len() + 1;
+        result.push_str(&rest[..value_start]);
+        let value_len = rest[value_start..]
+                            .find(|c: char| {
+                                c == '&' || c == '#' || c.is_whitespace()
+                            })
+                            .unwrap_or(rest.len() - value_start);
+        let value = &rest[value_start..value_start + value_len];
+        if param == "token" {
+            result.push_str(&redact_token(value));
+        } else {
+            result.push_str("…");
+        }
+        rest = &rest[value_start + value_len..];
+    }
+    result.push_str(rest);
+    result
+}
+
+// Headers whose values are credentials, set for a gateway in front of the
+// API.
+const SECRET_HEADERS: &'static [&'static str] = &["Authorization",
+                                                   "Proxy-Authorization",
+                                                   "Cookie"];
+
+// The prefix of the headers Diffbot forwards to the sites it crawls. Their
+// values are all treated as credentials, as custom authentication headers
+// such as API keys cannot be told apart from other headers.
+const FORWARD_PREFIX: &'static str = "X-Forward-";
+
+// Returns `true` if the value of the header `name` is a credential.
+pub(crate) fn is_secret_header(name: &str) -> bool {
+    let forwarded = name.len() > FORWARD_PREFIX.len() &&
+                    name.is_char_boundary(FORWARD_PREFIX.len()) &&
+                    name[..FORWARD_PREFIX.len()]
+                        .eq_ignore_ascii_case(FORWARD_PREFIX);
+    forwarded ||
+    SECRET_HEADERS.iter().any(|secret| secret.eq_ignore_ascii_case(name))
+}
+
+// Returns the value of a header as it can be shown, masked if it is a
+// credential.
+pub(crate) fn redact_header(name: &str, value: &str) -> String {
+    if is_secret_header(name) {
+        "…".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+// Returns the headers as they can be shown, with credentials masked.
+pub(crate) fn redact_headers(headers: &[(String, String)])
+                             -> Vec<(String, String)> {
+    headers.iter()
+           .map(|&(ref name, ref value)| {
+               (name.clone(), redact_header(name, value))
+           })
+           .collect()
+}
+
+// Query parameters whose values are secrets: the API token, and the
+// credentials of the proxy Diffbot crawls through.
+const SECRET_PARAMS: &'static [&'static str] = &["token", "proxyAuth"];
+
+// Returns `true` if the value of the query parameter `name` is a secret.
+pub(crate) fn is_secret_param(name: &str) -> bool {
+    SECRET_PARAMS.contains(&name)
+}
+
+// Finds the next secret parameter, and returns its position and name.
+fn find_secret_param(text: &str) -> Option<(usize, &'static str)> {
+    let mut found = None;
+    for param in SECRET_PARAMS {
+        if let Some(pos) = find_param(text, &format!("{}=", param)) {
+            if found.map_or(true, |(first, _)| pos < first) {
+                found = Some((pos, *param));
+            }
+        }
+    }
+    found
+}
+
+// Finds the next `name=` that starts a query parameter.
+fn find_param(text: &str, param: &str) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(pos) = text[offset..].find(param) {
+        let pos = offset + pos;
+        if pos == 0 || text[..pos].ends_with('?') ||
+           text[..pos].ends_with('&') {
+            return Some(pos);
+        }
+        offset = pos + param.len();
+    }
+    None
+}
+
+#[test]
+fn test_redact_token() {
+    assert_eq!(redact_token("0123456789abcdef"), "012…cdef");
+    assert_eq!(redact_token("short"), "…");
+}
+
+#[test]
+fn test_redact_url() {
+    let url = Url::parse("https://api.diffbot.com/v3/article\
+                          ?token=0123456789abcdef\
+                          &url=http%3A%2F%2Fa.com%2F%3Fmytoken%3Dx")
+                  .unwrap();
+    assert_eq!(redact_url(&url),
+               "https://api.diffbot.com/v3/article?token=012…cdef\
+                &url=http%3A%2F%2Fa.com%2F%3Fmytoken%3Dx");
+}
+
+#[test]
+fn test_redact_proxy_auth() {
+    let url = Url::parse("https://api.diffbot.com/v3/article\
+                          ?token=0123456789abcdef&proxy=1.2.3.4:8080\
+                          &proxyAuth=user:secret&url=a")
+                  .unwrap();
+    assert_eq!(redact_url(&url),
+               "https://api.diffbot.com/v3/article?token=012…cdef\
+                &proxy=1.2.3.4:8080&proxyAuth=…&url=a");
+}
+
+#[test]
+fn test_redact_header() {
+    assert_eq!(redact_header("x-forward-authorization", "Basic dTpw"), "…");
+    assert_eq!(redact_header("Authorization", "Bearer secret"), "…");
+    assert_eq!(redact_header("X-Forward-Cookie", "session=abc"), "…");
+    assert_eq!(redact_header("X-Forward-X-Api-Key", "secret"), "…");
+    assert_eq!(redact_header("X-Evaluate", "it's"), "it's");
+}
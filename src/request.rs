@@ -0,0 +1,637 @@
+//! Fluent builder for a single API call.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serde_json::{self, Map, Value};
+use url::Url;
+
+use {API, CachePolicy, Credentials, Diffbot, DiffbotResult, Error,
+     IntoTargetUrl, ResponseMeta};
+use fields::Fields;
+use http::{self, HttpRequest};
+use models::Article;
+use redact;
+use response;
+use options::{self, ApiOptions};
+
+/// An API call being prepared.
+///
+/// Created by [`Diffbot::request`](../struct.Diffbot.html#method.request).
+/// Options are added with chained calls, and the request is executed with
+/// `send`.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # fn main() {
+/// # let diffbot = Diffbot::v3("token");
+/// # println!("{:?}",
+/// diffbot.request(API::Article, "http://diffbot.com")
+///        .field("links")
+///        .paging(false)
+///        .timeout_ms(30_000)
+///        .send()
+/// # );
+/// # }
+/// ```
+pub struct Request<'a> {
+    client: &'a Diffbot,
+    api: API,
+    target_url: Result<Url, Error>,
+    fields: Fields,
+    options: Vec<(String, String)>,
+    cache: CachePolicy,
+    max_pages: u32,
+    headers: Vec<(String, String)>,
+    request_id: String,
+}
+
+impl<'a> fmt::Debug for Request<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Request")
+           .field("client", self.client)
+           .field("api", &self.api)
+           .field("target_url", &self.target_url)
+           .field("fields", &self.fields)
+           .field("options", &self.options)
+           .field("cache", &self.cache)
+           .field("max_pages", &self.max_pages)
+           .field("headers", &redact::redact_headers(&self.headers))
+           .field("request_id", &self.request_id)
+           .finish()
+    }
+}
+
+impl<'a> Request<'a> {
+    pub(crate) fn new(client: &'a Diffbot, api: API,
+                      target_url: Result<Url, Error>)
+                      -> Self {
+        Request {
+            client: client,
+            api: api,
+            target_url: target_url,
+            fields: Fields::new(),
+            options: Vec::new(),
+            cache: CachePolicy::Use,
+            max_pages: 1,
+            headers: Vec::new(),
+            request_id: http::new_request_id(),
+        }
+    }
+
+    /// Requests an optional field in the response.
+    ///
+    /// Can be called multiple times; all fields are sent together.
+    pub fn field<S: Into<String>>(mut self, field: S) -> Self {
+        self.fields = self.fields.field(field);
+        self
+    }
+
+    /// Requests a selection of optional fields, possibly nested.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// # println!("{:?}",
+    /// diffbot.request(API::Article, "http://diffbot.com")
+    ///        .fields(Fields::new().field("meta")
+    ///                             .nested("images", Fields::all()))
+    ///        .send()
+    /// # );
+    /// # }
+    /// ```
+    pub fn fields(mut self, fields: Fields) -> Self {
+        self.fields = self.fields.extend(fields);
+        self
+    }
+
+    /// Enables or disables the automatic concatenation of multi-page
+    /// articles.
+    pub fn paging(self, paging: bool) -> Self {
+        self.set("paging", paging)
+    }
+
+    /// Follows the next pages of a multi-page article, up to `max_pages`
+    /// pages in total, and merges them into the first object of the
+    /// response.
+    ///
+    /// Each page is requested separately, with `paging` disabled. Their
+    /// text and HTML are concatenated and their images merged, as done by
+    /// `models::Article::append_page`. Only used by Article calls.
+    pub fn follow_pages(mut self, max_pages: u32) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    /// Enables or disables the execution of JavaScript when Diffbot renders
+    /// the page.
+    ///
+    /// Pages are rendered by default; disabling it (the `norender`
+    /// parameter) is faster for pages that do not need scripts.
+    pub fn render(mut self, render: bool) -> Self {
+        if render {
+            self.options.retain(|&(ref key, _)| key != "norender");
+            self
+        } else {
+            self.set("norender", true)
+        }
+    }
+
+    /// Runs a JavaScript snippet on the page before extraction, such as a
+    /// click on a "read more" button.
+    ///
+    /// Sent in the `X-Evaluate` header.
+    pub fn evaluate<S: Into<String>>(self, script: S) -> Self {
+        self.set_header("X-Evaluate", script.into())
+    }
+
+    /// Sets a header Diffbot sends to the target site when fetching the
+    /// page, such as an authentication header.
+    ///
+    /// Sent as `X-Forward-<name>`. The call fails with
+    /// `Error::InvalidHeader` if `name` is not a valid header name, or if
+    /// `value` contains a line break. As it may be a credential, the value
+    /// is masked in debug output and fixtures, and the call is not cached.
+    pub fn forward_header<S: Into<String>>(self, name: &str, value: S)
+                                           -> Self {
+        self.set_header(&format!("X-Forward-{}", name), value.into())
+    }
+
+    /// Authenticates with the target page using HTTP basic authentication.
+    ///
+    /// Overrides the credentials set for its domain with
+    /// `Diffbot::with_site_credentials`.
+    pub fn basic_auth(self, credentials: &Credentials) -> Self {
+        self.forward_header("Authorization", credentials.header())
+    }
+
+    /// Makes Diffbot fetch the page through a proxy, given as `host:port`.
+    pub fn proxy<S: Into<String>>(self, address: S) -> Self {
+        self.set("proxy", address.into())
+    }
+
+    /// Sets the credentials Diffbot uses to authenticate with the proxy.
+    pub fn proxy_auth(self, user: &str, password: &str) -> Self {
+        self.set("proxyAuth", format!("{}:{}", user, password))
+    }
+
+    /// Sets the time Diffbot may spend fetching and processing the page.
+    ///
+    /// Sent as the `timeout` parameter, in milliseconds. This only limits
+    /// the work done by Diffbot, not the network round-trip.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.timeout_ms(options::millis(timeout))
+    }
+
+    /// Sets the time, in milliseconds, Diffbot may spend fetching and
+    /// processing the page.
+    pub fn timeout_ms(self, timeout: u64) -> Self {
+        self.set("timeout", timeout)
+    }
+
+    /// Applies a set of typed options, such as `ArticleOptions`.
+    ///
+    /// Options already set on this request are replaced, except for
+    /// fields which are added to the list.
+    pub fn options<O: ApiOptions>(mut self, options: &O) -> Self {
+        for (key, value) in options.query_pairs() {
+            if key == "fields" {
+                self.fields = self.fields.field(value);
+            } else {
+                self = self.set(key, value);
+            }
+        }
+        self
+    }
+
+    /// Adds a raw `key=value` pair to the query string.
+    ///
+    /// Use this for parameters that don't have a dedicated method.
+    pub fn option<K: ToString, V: ToString>(mut self, key: K, value: V)
+                                            -> Self {
+        self.options.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets the identifier of this call, such as the ID of the job it is
+    /// part of in your own logs.
+    ///
+    /// Sent to Diffbot in the `X-Request-Id` header, and available on
+    /// errors with `CallContext::request_id`. By default, a unique
+    /// identifier is generated; read it with `id` before sending to log
+    /// it alongside the result.
+    pub fn request_id<S: Into<String>>(mut self, request_id: S) -> Self {
+        self.request_id = request_id.into();
+        self
+    }
+
+    /// Returns the identifier of this call.
+    pub fn id(&self) -> &str {
+        &self.request_id
+    }
+
+    /// Sets how this call uses the client's cache.
+    ///
+    /// Has no effect if the client has no cache; see `Diffbot::with_cache`.
+    pub fn cache(mut self, policy: CachePolicy) -> Self {
+        self.cache = policy;
+        self
+    }
+
+    /// Runs the request and returns the parsed result.
+    ///
+    /// Fails with `Error::InvalidUrl` if the target URL was not valid.
+    pub fn send(self) -> DiffbotResult {
+        if self.max_pages > 1 && self.api == API::Article {
+            return self.paging(false).send_following_pages();
+        }
+        let client = self.client;
+        let request = try!(self.into_http_request());
+        client.execute(&request)
+    }
+
+    /// Runs the request and returns the body of the response as is.
+    ///
+    /// Use this to store or forward the JSON without parsing it into a
+    /// `serde_json::Value`. API errors are still reported as `Error::Api`.
+    pub fn send_raw(self) -> Result<Vec<u8>, Error> {
+        let client = self.client;
+        let request = try!(self.into_http_request());
+        client.execute_with(&request, response::parse_raw_response)
+    }
+
+    /// Runs the request and returns the parsed result, with the status,
+    /// headers and timing of the response.
+    ///
+    /// Following pages requested with `follow_pages` are not fetched.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("token");
+    /// let request = diffbot.request(API::Article, "http://diffbot.com");
+    /// if let Ok((result, meta)) = request.send_with_meta() {
+    ///     println!("{:?} ({:?})", result, meta.content_type());
+    /// }
+    /// # }
+    /// ```
+    pub fn send_with_meta(self)
+                          -> Result<(Map<String, Value>, ResponseMeta),
+                                    Error> {
+        let start = Instant::now();
+        let client = self.client;
+        let request = try!(self.into_http_request());
+        let (result, meta) = try!(client.execute_with(&request, |response| {
+            response::parse_response_with_meta(response, &request.request_id)
+        }));
+        Ok((result, meta.with_total_time(start.elapsed())))
+    }
+
+    /// Builds the HTTP request without sending it.
+    ///
+    /// Useful to check the parameters of a call, or to reproduce it with
+    /// another tool. Following pages requested with `follow_pages` are not
+    /// included.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate diffbot;
+    /// # use diffbot::*;
+    /// # fn main() {
+    /// # let diffbot = Diffbot::v3("0123456789abcdef");
+    /// let prepared = diffbot.request(API::Article, "http://diffbot.com")
+    ///                       .paging(false)
+    ///                       .prepare()
+    ///                       .unwrap();
+    /// assert!(prepared.to_curl().contains("token=012…cdef"));
+    /// # }
+    /// ```
+    pub fn prepare(self) -> Result<PreparedRequest, Error> {
+        let request = try!(self.into_http_request());
+        Ok(PreparedRequest::new(request))
+    }
+
+    // Sends the request, then one per following page, merging the pages
+    // into the first article.
+    fn send_following_pages(mut self) -> DiffbotResult {
+        let max_pages = self.max_pages;
+        self.max_pages = 1;
+        let first_url = match self.target_url {
+            Ok(ref url) => url.clone(),
+            Err(_) => return self.send(),
+        };
+        let mut result = try!(self.for_page(Ok(first_url.clone())).send());
+        let mut article = match first_article(&result) {
+            Some(article) => article,
+            None => return Ok(result),
+        };
+
+        let mut seen: HashSet<String> = HashSet::new();
+        seen.insert(first_url.to_string());
+        seen.extend(article.page_url.clone());
+        let mut queue = VecDeque::from(next_pages(&article));
+        let mut pages = 1;
+        while let Some(url) = queue.pop_front() {
+            if pages >= max_pages {
+                break;
+            }
+            if !seen.insert(url.clone()) {
+                continue;
+            }
+            let request = self.for_page(url.as_str().into_target_url());
+            let page = match first_article(&try!(request.send())) {
+                Some(page) => page,
+                None => continue,
+            };
+            article.append_page(&page);
+            pages += 1;
+            queue.extend(next_pages(&page));
+        }
+
+        if let Some(object) = first_object(&mut result) {
+            merge_pages(object, &article);
+        }
+        Ok(result)
+    }
+
+    // Returns a copy of this request for another page.
+    fn for_page(&self, target_url: Result<Url, Error>) -> Request<'a> {
+        Request {
+            client: self.client,
+            api: self.api.clone(),
+            target_url: target_url,
+            fields: self.fields.clone(),
+            options: self.options.clone(),
+            cache: self.cache,
+            max_pages: self.max_pages,
+            headers: self.headers.clone(),
+            request_id: self.request_id.clone(),
+        }
+    }
+
+    fn into_http_request(self) -> Result<HttpRequest, Error> {
+        let target_url = try!(self.target_url);
+        for &(ref name, ref value) in &self.headers {
+            try!(check_header(name, value));
+        }
+        let mut options = self.options;
+        if !self.fields.is_empty() {
+            options.push(("fields".to_string(), self.fields.to_string()));
+        }
+
+        let url = try!(self.client.prepare_url(&self.api, &target_url,
+                                               &options));
+        let mut request = HttpRequest::get(url)
+                              .with_target(self.api, target_url)
+                              .with_cache(self.cache);
+        request.headers = self.headers;
+        request.request_id = self.request_id;
+        Ok(self.client.forward_site_headers(request))
+    }
+
+    // Sets a header, replacing any previous value for the same name.
+    fn set_header(mut self, name: &str, value: String) -> Self {
+        self.headers.retain(|&(ref n, _)| !n.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value));
+        self
+    }
+
+    // Sets an option, replacing any previous value for the same key.
+    fn set<V: ToString>(mut self, key: &str, value: V) -> Self {
+        self.options.retain(|&(ref k, _)| k != key);
+        self.options.push((key.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// An API call built but not sent, as returned by `Request::prepare`.
+///
+/// Its headers and query parameters can still be changed, and it can be
+/// sent several times with `send`, always with the same request ID.
+///
+/// The `Display` and `Debug` output, and `to_curl`, mask the API token,
+/// wherever it is sent, and the credentials sent in headers.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # fn main() {
+/// # let diffbot = Diffbot::v3("token");
+/// let mut prepared = diffbot.prepare_request(API::Article,
+///                                            "http://diffbot.com")
+///                           .unwrap();
+/// prepared.set_query("discussion", "false");
+/// prepared.set_header("X-Forward-Accept-Language", "fr").unwrap();
+/// # println!("{:?}",
+/// prepared.send(&diffbot)
+/// # );
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct PreparedRequest {
+    request: HttpRequest,
+}
+
+impl PreparedRequest {
+    pub(crate) fn new(request: HttpRequest) -> Self {
+        PreparedRequest { request: request }
+    }
+
+    /// Returns the HTTP method, such as `GET`.
+    pub fn method(&self) -> String {
+        self.request.method.to_string()
+    }
+
+    /// Returns the full URL, including the API token.
+    pub fn url(&self) -> &Url {
+        &self.request.url
+    }
+
+    /// Returns the headers that would be sent.
+    pub fn headers(&self) -> Vec<(String, String)> {
+        self.request.all_headers()
+    }
+
+    /// Returns the body that would be sent, if any.
+    pub fn body(&self) -> Option<&[u8]> {
+        self.request.body.as_ref().map(Vec::as_slice)
+    }
+
+    /// Returns the value of a query parameter.
+    pub fn query(&self, key: &str) -> Option<String> {
+        self.request
+            .url
+            .query_pairs()
+            .find(|&(ref k, _)| k == key)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    /// Sets a query parameter, replacing any previous value.
+    pub fn set_query(&mut self, key: &str, value: &str) -> &mut Self {
+        self.remove_query(key);
+        self.request.url.query_pairs_mut().append_pair(key, value);
+        self
+    }
+
+    /// Removes a query parameter.
+    pub fn remove_query(&mut self, key: &str) -> &mut Self {
+        let pairs: Vec<(String, String)> =
+            self.request
+                .url
+                .query_pairs()
+                .filter(|&(ref k, _)| k != key)
+                .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                .collect();
+        self.request.url.query_pairs_mut().clear().extend_pairs(pairs);
+        self
+    }
+
+    /// Sets a header sent to Diffbot, replacing any previous value.
+    ///
+    /// Headers for the target site are named `X-Forward-<name>`; see
+    /// `Request::forward_header`. Fails with `Error::InvalidHeader` if the
+    /// header cannot be sent.
+    pub fn set_header(&mut self, name: &str, value: &str)
+                      -> Result<&mut Self, Error> {
+        try!(check_header(name, value));
+        if name.eq_ignore_ascii_case("X-Request-Id") {
+            self.request.request_id = value.to_string();
+            return Ok(self);
+        }
+        self.remove_header(name);
+        self.request.headers.push((name.to_string(), value.to_string()));
+        Ok(self)
+    }
+
+    /// Removes a header set on the request.
+    pub fn remove_header(&mut self, name: &str) -> &mut Self {
+        self.request
+            .headers
+            .retain(|&(ref n, _)| !n.eq_ignore_ascii_case(name));
+        self
+    }
+
+    /// Sends the request with `client`, and returns the parsed result.
+    ///
+    /// The client's retry policy and cache are used as for other calls.
+    pub fn send(&self, client: &Diffbot) -> DiffbotResult {
+        client.execute(&self.request)
+    }
+
+    /// Returns an equivalent `curl` command, with the API token masked.
+    pub fn to_curl(&self) -> String {
+        let url = redact::redact_url(&self.request.url);
+        let mut command = format!("curl -X {} {}",
+                                  self.request.method,
+                                  shell_quote(&url));
+        for (name, value) in redact::redact_headers(&self.headers()) {
+            command.push_str(" -H ");
+            command.push_str(&shell_quote(&format!("{}: {}", name, value)));
+        }
+        if let Some(body) = self.request.redacted_body() {
+            command.push_str(" --data-binary ");
+            command.push_str(&shell_quote(&body));
+        }
+        command
+    }
+}
+
+impl fmt::Display for PreparedRequest {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(fmt,
+                    "{} {}",
+                    self.request.method,
+                    redact::redact_url(&self.request.url)));
+        for (name, value) in redact::redact_headers(&self.headers()) {
+            try!(write!(fmt, "\n{}: {}", name, value));
+        }
+        if let Some(body) = self.request.redacted_body() {
+            try!(write!(fmt, "\n\n{}", body));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PreparedRequest {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("PreparedRequest")
+           .field("method", &self.request.method)
+           .field("url", &redact::redact_url(&self.request.url))
+           .field("headers", &redact::redact_headers(&self.headers()))
+           .field("body", &self.body().map(|body| body.len()))
+           .finish()
+    }
+}
+
+// Quotes a word for POSIX shells.
+fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+// Checks that a header can be sent as is.
+pub(crate) fn check_header(name: &str, value: &str) -> Result<(), Error> {
+    let valid_name = name.bytes().all(|b| {
+        b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+    });
+    if !valid_name || name.ends_with('-') {
+        return Err(Error::InvalidHeader(format!("{:?} is not a valid name",
+                                                name)));
+    }
+    if value.contains(|c| c == '\r' || c == '\n' || c == '\0') {
+        return Err(Error::InvalidHeader(format!("the value of {} contains \
+                                                 a line break",
+                                                name)));
+    }
+    Ok(())
+}
+
+// Parses the first object of an Article response.
+fn first_article(result: &Map<String, Value>) -> Option<Article> {
+    result.get("objects")
+          .and_then(|objects| objects.get(0))
+          .and_then(|object| serde_json::from_value(object.clone()).ok())
+}
+
+fn first_object(result: &mut Map<String, Value>)
+                -> Option<&mut Map<String, Value>> {
+    result.get_mut("objects")
+          .and_then(|objects| objects.get_mut(0))
+          .and_then(Value::as_object_mut)
+}
+
+// Copies the fields of an article built from several pages to its object.
+fn merge_pages(object: &mut Map<String, Value>, article: &Article) {
+    if let Ok(Value::Object(merged)) = serde_json::to_value(article) {
+        for key in &["text", "html", "images", "numPages"] {
+            match merged.get(*key) {
+                Some(&Value::Null) | None => {}
+                Some(value) => {
+                    object.insert(key.to_string(), value.clone());
+                }
+            }
+        }
+    }
+}
+
+// Returns the URLs of the pages following an article.
+fn next_pages(article: &Article) -> Vec<String> {
+    article.next_pages
+           .iter()
+           .chain(&article.next_page)
+           .cloned()
+           .collect()
+}
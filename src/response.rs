@@ -0,0 +1,280 @@
+//! Interpretation of the responses of the API.
+
+use std::time::Duration;
+
+use serde_json::{self, Map, Value};
+
+use {ApiError, DiffbotResult, Error, ErrorDetails, JsonError};
+use http::HttpResponse;
+
+/// Information about the HTTP response to a call.
+///
+/// Returned by `Request::send_with_meta` alongside the result. Responses
+/// served from the cache or from fixtures have no headers and no network
+/// timing.
+#[derive(Clone, Debug)]
+pub struct ResponseMeta {
+    status: u16,
+    headers: Vec<(String, String)>,
+    request_id: String,
+    elapsed: Option<Duration>,
+    total_time: Duration,
+}
+
+impl ResponseMeta {
+    pub(crate) fn new(response: &HttpResponse, request_id: String) -> Self {
+        ResponseMeta {
+            status: response.status,
+            headers: response.headers.clone(),
+            request_id: request_id,
+            elapsed: response.elapsed,
+            total_time: Duration::from_secs(0),
+        }
+    }
+
+    pub(crate) fn with_total_time(mut self, total_time: Duration) -> Self {
+        self.total_time = total_time;
+        self
+    }
+
+    /// Returns the HTTP status code.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns every header of the response, in the order received.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Returns the value of a header, ignoring the case of its name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|&&(ref n, _)| n.eq_ignore_ascii_case(name))
+            .map(|&(_, ref value)| value.as_str())
+    }
+
+    /// Returns the `Content-Type` header.
+    pub fn content_type(&self) -> Option<&str> {
+        self.header("Content-Type")
+    }
+
+    /// Returns the identifier of the call; see `Request::request_id`.
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    /// Returns the time between sending the request and reading the whole
+    /// response, for the attempt that produced it.
+    ///
+    /// This includes the processing time of Diffbot.
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.elapsed
+    }
+
+    /// Returns the time taken by the whole call, including failed attempts
+    /// and the delays between retries.
+    pub fn total_time(&self) -> Duration {
+        self.total_time
+    }
+
+    /// Returns the time the server reports spending on the call, from the
+    /// `dur` metrics of its `Server-Timing` headers.
+    ///
+    /// Subtract it from `elapsed` to estimate the network latency.
+    pub fn processing_time(&self) -> Option<Duration> {
+        let mut total = None;
+        let timings = self.headers
+                          .iter()
+                          .filter(|&&(ref n, _)| {
+                              n.eq_ignore_ascii_case("Server-Timing")
+                          });
+        for &(_, ref value) in timings {
+            for metric in value.split(',') {
+                let millis = metric.split(';')
+                                   .skip(1)
+                                   .filter_map(|param| {
+                                       let param = param.trim();
+                                       if param.starts_with("dur=") {
+                                           param[4..].parse::<f64>().ok()
+                                       } else {
+                                           None
+                                       }
+                                   })
+                                   .next();
+                if let Some(millis) = millis {
+                    total = Some(total.unwrap_or(0.0) + millis);
+                }
+            }
+        }
+        total.map(|millis: f64| {
+            Duration::from_micros((millis.max(0.0) * 1000.0).round() as u64)
+        })
+    }
+}
+
+// The fields of a response that tell whether it reports an error.
+#[derive(Deserialize)]
+struct ErrorProbe {
+    error: Option<String>,
+    #[serde(rename = "errorCode")]
+    error_code: Option<u64>,
+}
+
+// Parses a response like `parse_response`, keeping its status and headers.
+pub(crate) fn parse_response_with_meta(response: HttpResponse,
+                                       request_id: &str)
+                                       -> Result<(Map<String, Value>,
+                                                  ResponseMeta),
+                                                 Error> {
+    let meta = ResponseMeta::new(&response, request_id.to_string());
+    parse_response(response).map(|result| (result, meta))
+}
+
+// Parses a response into a JSON object, or the error it reports.
+pub(crate) fn parse_response(response: HttpResponse) -> DiffbotResult {
+    let json_result = match serde_json::from_slice(&response.body) {
+        Ok(Value::Object(obj)) => obj,
+        Ok(_) => return Err(api_error(0, "Invalid response", response)),
+        Err(err) => return Err(decode_error(err, response)),
+    };
+
+    if json_result.contains_key("error") {
+        let error_code = json_result.get("errorCode")
+                                    .and_then(|c| c.as_u64())
+                                    .unwrap_or(0u64);
+        let error = json_result["error"].as_str().unwrap_or("");
+        return Err(api_error(error_code as u32, error, response));
+    }
+
+    Ok(json_result)
+}
+
+// Parses a response into a JSON array, or the error it reports.
+#[cfg(all(feature = "jobs", feature = "kg"))]
+pub(crate) fn parse_array_response(response: HttpResponse)
+                                   -> Result<Vec<Value>, Error> {
+    match serde_json::from_slice(&response.body) {
+        Ok(Value::Array(items)) => Ok(items),
+        Ok(Value::Object(ref object)) if object.contains_key("error") => {
+            Err(parse_error(response))
+        }
+        Ok(_) => Err(api_error(0, "Invalid response", response)),
+        Err(err) => Err(decode_error(err, response)),
+    }
+}
+
+// Checks a response and returns its body untouched.
+//
+// The body is still scanned to find errors, but only the error fields are
+// kept.
+pub(crate) fn parse_raw_response(response: HttpResponse)
+                                 -> Result<Vec<u8>, Error> {
+    match serde_json::from_slice::<ErrorProbe>(&response.body) {
+        Ok(ErrorProbe { error: Some(error), error_code }) => {
+            let error_code = error_code.unwrap_or(0) as u32;
+            Err(api_error(error_code, &error, response))
+        }
+        Ok(_) => Ok(response.body),
+        Err(ref err) if err.is_data() => {
+            Err(api_error(0, "Invalid response", response))
+        }
+        Err(err) => Err(decode_error(err, response)),
+    }
+}
+
+// Returns the error reported by a response with an unsuccessful status.
+pub(crate) fn parse_error(response: HttpResponse) -> Error {
+    match serde_json::from_slice::<ErrorProbe>(&response.body) {
+        Ok(ErrorProbe { error: Some(error), error_code }) => {
+            let status = response.status as u64;
+            let error_code = error_code.unwrap_or(status) as u32;
+            api_error(error_code, &error, response)
+        }
+        _ => {
+            let status = response.status;
+            let message = if status == 429 {
+                "Too many requests"
+            } else {
+                "Unexpected response status"
+            };
+            api_error(status as u32, message, response)
+        }
+    }
+}
+
+fn api_error(code: u32, message: &str, response: HttpResponse) -> Error {
+    let err = ApiError::new(code, message);
+    let err = if err.is_throttled() {
+        err.with_retry_after(response.retry_after)
+    } else {
+        err
+    };
+    let details = ErrorDetails::new(response.status, response.body);
+    Error::Api(err.with_details(details))
+}
+
+fn decode_error(err: serde_json::Error, response: HttpResponse) -> Error {
+    // Throttling responses don't always come with a JSON body
+    if response.status == 429 {
+        return api_error(429, "Too many requests", response);
+    }
+    let details = ErrorDetails::new(response.status, response.body);
+    Error::Json(JsonError::new(err).with_details(details))
+}
+
+#[cfg(test)]
+fn response(status: u16, body: &str) -> HttpResponse {
+    HttpResponse {
+        status: status,
+        retry_after: None,
+        headers: Vec::new(),
+        body: body.as_bytes().to_vec(),
+        elapsed: None,
+    }
+}
+
+#[test]
+fn test_parse_raw_response() {
+    let body = r#"{"objects": [{"title": "T"}]}"#;
+    let raw = parse_raw_response(response(200, body)).unwrap();
+    assert_eq!(raw, body.as_bytes());
+
+    let error = r#"{"errorCode": 404, "error": "Not found"}"#;
+    let err = parse_raw_response(response(200, error)).unwrap_err();
+    assert!(err.is_not_found());
+    assert!(parse_raw_response(response(200, "[1]")).is_err());
+    assert!(parse_raw_response(response(429, "slow")).unwrap_err()
+                                                     .is_throttled());
+}
+
+#[test]
+fn test_response_meta() {
+    let mut response = response(200, r#"{"objects": []}"#);
+    response.headers = vec![("Content-Type".to_string(),
+                             "application/json".to_string()),
+                            ("X-RateLimit-Remaining".to_string(),
+                             "42".to_string())];
+    let (result, meta) = parse_response_with_meta(response, "abc").unwrap();
+    assert!(result.contains_key("objects"));
+    assert_eq!(meta.status(), 200);
+    assert_eq!(meta.content_type(), Some("application/json"));
+    assert_eq!(meta.header("x-ratelimit-remaining"), Some("42"));
+    assert_eq!(meta.request_id(), "abc");
+    assert_eq!(meta.elapsed(), None);
+    assert_eq!(meta.processing_time(), None);
+}
+
+#[test]
+fn test_processing_time() {
+    let mut response = response(200, "{}");
+    response.headers = vec![("Server-Timing".to_string(),
+                             "fetch;dur=1200.5, cache;desc=miss".to_string()),
+                            ("server-timing".to_string(),
+                             "extract;dur=300".to_string())];
+    response.elapsed = Some(Duration::from_secs(2));
+    let meta = ResponseMeta::new(&response, "abc".to_string());
+    assert_eq!(meta.processing_time(), Some(Duration::from_micros(1_500_500)));
+    assert_eq!(meta.elapsed(), Some(Duration::from_secs(2)));
+}
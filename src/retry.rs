@@ -0,0 +1,202 @@
+//! Automatic retries of failed calls, and polling of running jobs.
+
+use std::cmp;
+use std::time::Duration;
+
+use Error;
+#[cfg(feature = "jobs")]
+use models::Job;
+
+/// Controls how failed calls are retried.
+///
+/// Calls failing with a retryable error (see `Error::is_retryable`) are
+/// retried up to `max_retries` times. When the API sends a `Retry-After`
+/// header, the client waits that long; otherwise it backs off
+/// exponentially.
+///
+/// POST calls are not retried, unless made with `Diffbot::retrying_posts`.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # use std::time::Duration;
+/// # fn main() {
+/// let policy = RetryPolicy::new(3).initial_backoff(Duration::from_secs(2));
+/// let diffbot = Diffbot::v3("token").with_retries(policy);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Returns a policy retrying each call at most `max_retries` times.
+    pub fn new(max_retries: u32) -> Self {
+        RetryPolicy {
+            max_retries: max_retries,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+
+    /// Sets the delay before the first retry, doubled on each attempt.
+    ///
+    /// Defaults to one second.
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Sets the longest delay between two attempts.
+    ///
+    /// Defaults to one minute. Delays requested by the API with
+    /// `Retry-After` are not capped.
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Returns the maximum number of retries for a single call.
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    // Returns how long to wait before retrying after `err`, or `None` if the
+    // call should not be retried.
+    pub(crate) fn delay(&self, attempt: u32, err: &Error) -> Option<Duration> {
+        if attempt >= self.max_retries || !err.is_retryable() {
+            return None;
+        }
+        Some(err.retry_after().unwrap_or_else(|| self.backoff(attempt)))
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt).unwrap_or(u32::max_value());
+        let backoff = self.initial_backoff
+                          .checked_mul(factor)
+                          .unwrap_or(self.max_backoff);
+        cmp::min(backoff, self.max_backoff)
+    }
+}
+
+/// Controls how often the status of a job is checked while waiting for it.
+///
+/// # Example
+///
+/// ```no_run
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # use std::time::Duration;
+/// # fn main() {
+/// # let diffbot = Diffbot::v3("token");
+/// let policy = PollPolicy {
+///     interval: Duration::from_secs(30),
+///     timeout: Duration::from_secs(3600),
+/// };
+/// let job = diffbot.wait_for_crawl("my_crawl_job", &policy).unwrap();
+/// println!("{:?} objects found", job.objects_found);
+/// # }
+/// ```
+#[cfg(feature = "jobs")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PollPolicy {
+    /// The delay between two status checks.
+    pub interval: Duration,
+    /// How long to wait for the job before giving up.
+    pub timeout: Duration,
+}
+
+/// The progress of a job, reported after each status check while waiting
+/// for it.
+#[cfg(feature = "jobs")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct JobProgress {
+    /// The number of pages fetched.
+    pub pages_crawled: u64,
+    /// The number of pages processed.
+    pub pages_processed: u64,
+    /// The number of objects extracted.
+    pub objects_found: u64,
+    /// The time since the wait started.
+    pub elapsed: Duration,
+    /// The pages fetched per second since the first check, if known.
+    pub crawl_rate: Option<f64>,
+    /// The pages processed per second since the first check, if known.
+    pub process_rate: Option<f64>,
+}
+
+#[cfg(feature = "jobs")]
+impl JobProgress {
+    // Returns the progress of a job, with rates measured since the `first`
+    // report of the same wait.
+    pub(crate) fn new(job: &Job, elapsed: Duration,
+                      first: Option<&JobProgress>)
+                      -> Self {
+        let mut progress = JobProgress {
+            pages_crawled: job.page_crawl_successes.unwrap_or(0),
+            pages_processed: job.page_process_successes.unwrap_or(0),
+            objects_found: job.objects_found.unwrap_or(0),
+            elapsed: elapsed,
+            crawl_rate: None,
+            process_rate: None,
+        };
+        if let Some(first) = first {
+            let seconds = (elapsed - first.elapsed).as_secs_f64();
+            if seconds > 0.0 {
+                let rate = |now: u64, before: u64| {
+                    Some(now.saturating_sub(before) as f64 / seconds)
+                };
+                progress.crawl_rate = rate(progress.pages_crawled,
+                                           first.pages_crawled);
+                progress.process_rate = rate(progress.pages_processed,
+                                             first.pages_processed);
+            }
+        }
+        progress
+    }
+}
+
+#[test]
+fn test_backoff() {
+    let policy = RetryPolicy::new(10).max_backoff(Duration::from_secs(5));
+    assert_eq!(policy.backoff(0), Duration::from_secs(1));
+    assert_eq!(policy.backoff(2), Duration::from_secs(4));
+    assert_eq!(policy.backoff(3), Duration::from_secs(5));
+    assert_eq!(policy.backoff(40), Duration::from_secs(5));
+}
+
+#[test]
+fn test_retry_after_is_honored() {
+    use error::ApiError;
+
+    let policy = RetryPolicy::new(2);
+    let after = Some(Duration::from_secs(30));
+    let throttled = Error::Api(ApiError::new(429, "Too many requests")
+                                   .with_retry_after(after));
+    assert_eq!(policy.delay(0, &throttled), Some(Duration::from_secs(30)));
+    assert_eq!(policy.delay(2, &throttled), None);
+
+    let not_found = Error::Api(ApiError::new(404, "Not found"));
+    assert_eq!(policy.delay(0, &not_found), None);
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_job_progress() {
+    let mut job = Job::default();
+    job.page_crawl_successes = Some(100);
+    let first = JobProgress::new(&job, Duration::from_secs(0), None);
+    assert_eq!(first.crawl_rate, None);
+
+    job.page_crawl_successes = Some(400);
+    job.page_process_successes = Some(50);
+    let progress = JobProgress::new(&job, Duration::from_secs(10),
+                                    Some(&first));
+    assert_eq!(progress.crawl_rate, Some(30.0));
+    assert_eq!(progress.process_rate, Some(5.0));
+}
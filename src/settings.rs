@@ -0,0 +1,683 @@
+//! Typed settings for crawl and bulk jobs.
+//!
+//! The job APIs take many parameters whose names are easy to get wrong, and
+//! a misspelled one is silently ignored. These builders only expose the
+//! documented parameters, and are passed to `crawl_with_options` and
+//! `bulk_with_options` with `to_options`.
+
+use std::time::Duration;
+
+use serde_json::{Map, Value};
+
+use Error;
+use options::push;
+
+// Joins patterns with the `||` separator of the job APIs.
+fn push_patterns(pairs: &mut Vec<(&'static str, String)>, key: &'static str,
+                 patterns: &[String]) {
+    if !patterns.is_empty() {
+        pairs.push((key, patterns.join("||")));
+    }
+}
+
+// Converts pairs to the owned options accepted by the client.
+fn owned(pairs: Vec<(&'static str, String)>) -> Vec<(String, String)> {
+    pairs.into_iter()
+         .map(|(key, value)| (key.to_string(), value))
+         .collect()
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1e9
+}
+
+// The job APIs expect booleans as `0` or `1`.
+fn flag(value: bool) -> u8 {
+    value as u8
+}
+
+/// URL substrings that select which pages a crawl visits or processes.
+///
+/// A URL matches if it contains one of the allowed substrings, or if none
+/// is given, and none of the denied ones. The value is formatted with the
+/// `||` separators and `!` negations of the job APIs, and is URL-encoded
+/// when sent.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # fn main() {
+/// let patterns = UrlPatterns::new().allow("/blog/")
+///                                  .allow_prefix("https://news.")
+///                                  .deny("/tag/");
+/// assert_eq!(patterns.to_value().unwrap(),
+///            "/blog/||^https://news.||!/tag/");
+/// let settings = CrawlSettings::new().url_crawl_patterns(&patterns)
+///                                    .unwrap();
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UrlPatterns {
+    patterns: Vec<(&'static str, String, &'static str)>,
+}
+
+impl UrlPatterns {
+    /// Returns an empty list, which matches every URL.
+    pub fn new() -> Self {
+        UrlPatterns::default()
+    }
+
+    /// Matches URLs containing `substring`.
+    pub fn allow<S: Into<String>>(self, substring: S) -> Self {
+        self.push("", substring, "")
+    }
+
+    /// Matches URLs starting with `prefix`.
+    pub fn allow_prefix<S: Into<String>>(self, prefix: S) -> Self {
+        self.push("^", prefix, "")
+    }
+
+    /// Matches URLs ending with `suffix`.
+    pub fn allow_suffix<S: Into<String>>(self, suffix: S) -> Self {
+        self.push("", suffix, "$")
+    }
+
+    /// Excludes URLs containing `substring`.
+    pub fn deny<S: Into<String>>(self, substring: S) -> Self {
+        self.push("!", substring, "")
+    }
+
+    fn push<S: Into<String>>(mut self, prefix: &'static str, text: S,
+                             suffix: &'static str)
+                             -> Self {
+        self.patterns.push((prefix, text.into(), suffix));
+        self
+    }
+
+    /// Returns the value of the pattern parameter.
+    ///
+    /// Fails if a substring is empty, contains the `||` separator, or
+    /// starts with a character the API would read as an operator, as the
+    /// API has no way to escape them.
+    pub fn to_value(&self) -> Result<String, Error> {
+        let mut patterns = Vec::new();
+        for &(prefix, ref text, suffix) in &self.patterns {
+            let invalid = text.is_empty() || text.contains("||") ||
+                          text.starts_with('!') ||
+                          text.starts_with('^') ||
+                          text.ends_with('$');
+            if invalid {
+                let message = format!("unusable URL pattern {:?}", text);
+                return Err(Error::InvalidSetting(message));
+            }
+            patterns.push(format!("{}{}{}", prefix, text, suffix));
+        }
+        Ok(patterns.join("||"))
+    }
+}
+
+/// When a repeating job starts its rounds.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// use std::time::Duration;
+///
+/// # fn main() {
+/// let daily = Schedule::every(Duration::from_secs(24 * 3600))
+///                 .max_rounds(30);
+/// let settings = CrawlSettings::new().schedule(&daily).unwrap();
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Schedule {
+    interval: Duration,
+    max_rounds: Option<u32>,
+    start_round_now: bool,
+}
+
+impl Schedule {
+    /// Starts a new round at this interval, without limit on the number of
+    /// rounds.
+    pub fn every(interval: Duration) -> Self {
+        Schedule {
+            interval: interval,
+            max_rounds: None,
+            start_round_now: false,
+        }
+    }
+
+    /// Stops after this many rounds.
+    pub fn max_rounds(mut self, max_rounds: u32) -> Self {
+        self.max_rounds = Some(max_rounds);
+        self
+    }
+
+    /// Starts a new round as soon as the settings are sent, instead of
+    /// waiting for the end of the interval.
+    pub fn start_round_now(mut self) -> Self {
+        self.start_round_now = true;
+        self
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.interval < Duration::from_secs(1) {
+            let message = format!("repeat interval of {:?} is too short",
+                                  self.interval);
+            return Err(Error::InvalidSetting(message));
+        }
+        if self.max_rounds == Some(0) {
+            let message = "a schedule needs at least one round".to_string();
+            return Err(Error::InvalidSetting(message));
+        }
+        Ok(())
+    }
+
+    // The `repeat` parameter, in days.
+    fn repeat(&self) -> f64 {
+        seconds(self.interval) / 86_400.0
+    }
+
+    // The `maxRounds` parameter, where -1 means no limit.
+    fn max_rounds_value(&self) -> i32 {
+        self.max_rounds.map_or(-1, |rounds| rounds as i32)
+    }
+
+    fn round_start(&self) -> Option<u8> {
+        if self.start_round_now {
+            Some(1)
+        } else {
+            None
+        }
+    }
+}
+
+/// Settings of a crawl job.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # fn main() {
+/// # let diffbot = Diffbot::v3("token");
+/// let settings = CrawlSettings::new().max_hops(2)
+///                                    .max_to_crawl(1000)
+///                                    .url_process_pattern("/blog/");
+/// # println!("{:?}",
+/// diffbot.crawl_with_options("my_crawl_job", API::Article,
+///                            &["https://blog.diffbot.com"],
+///                            &settings.to_options())
+/// # );
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CrawlSettings {
+    max_hops: Option<i32>,
+    max_to_crawl: Option<u64>,
+    max_to_process: Option<u64>,
+    crawl_delay: Option<f64>,
+    obey_robots: Option<u8>,
+    restrict_domain: Option<u8>,
+    url_crawl_patterns: Vec<String>,
+    url_process_patterns: Vec<String>,
+    url_crawl_regex: Option<String>,
+    url_process_regex: Option<String>,
+    page_process_patterns: Vec<String>,
+    repeat: Option<f64>,
+    max_rounds: Option<i32>,
+    round_start: Option<u8>,
+    notify_email: Option<String>,
+    notify_webhook: Option<String>,
+    proxy: Option<String>,
+    proxy_auth: Option<String>,
+}
+
+impl CrawlSettings {
+    /// Returns empty settings, leaving every parameter to its default.
+    pub fn new() -> Self {
+        CrawlSettings::default()
+    }
+
+    /// Sets how many links away from the seeds pages are crawled; `-1`
+    /// removes the limit.
+    pub fn max_hops(mut self, max_hops: i32) -> Self {
+        self.max_hops = Some(max_hops);
+        self
+    }
+
+    /// Sets the maximum number of pages crawled.
+    pub fn max_to_crawl(mut self, max_to_crawl: u64) -> Self {
+        self.max_to_crawl = Some(max_to_crawl);
+        self
+    }
+
+    /// Sets the maximum number of pages processed.
+    pub fn max_to_process(mut self, max_to_process: u64) -> Self {
+        self.max_to_process = Some(max_to_process);
+        self
+    }
+
+    /// Sets the delay between two requests to the same site.
+    pub fn crawl_delay(mut self, delay: Duration) -> Self {
+        self.crawl_delay = Some(seconds(delay));
+        self
+    }
+
+    /// Sets whether `robots.txt` files are respected.
+    pub fn obey_robots(mut self, obey_robots: bool) -> Self {
+        self.obey_robots = Some(flag(obey_robots));
+        self
+    }
+
+    /// Sets whether the crawl stays on the domains of the seeds.
+    pub fn restrict_domain(mut self, restrict_domain: bool) -> Self {
+        self.restrict_domain = Some(flag(restrict_domain));
+        self
+    }
+
+    /// Only crawls URLs containing one of the given patterns.
+    ///
+    /// Can be called several times to add patterns.
+    pub fn url_crawl_pattern<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.url_crawl_patterns.push(pattern.into());
+        self
+    }
+
+    /// Only processes URLs containing one of the given patterns.
+    ///
+    /// Can be called several times to add patterns.
+    pub fn url_process_pattern<S: Into<String>>(mut self, pattern: S)
+                                                -> Self {
+        self.url_process_patterns.push(pattern.into());
+        self
+    }
+
+    /// Only processes pages whose HTML contains one of the given patterns.
+    ///
+    /// Can be called several times to add patterns.
+    pub fn page_process_pattern<S: Into<String>>(mut self, pattern: S)
+                                                 -> Self {
+        self.page_process_patterns.push(pattern.into());
+        self
+    }
+
+    /// Only crawls URLs matching `patterns`, replacing previous patterns.
+    ///
+    /// Fails if a pattern cannot be expressed; see `UrlPatterns::to_value`.
+    pub fn url_crawl_patterns(mut self, patterns: &UrlPatterns)
+                              -> Result<Self, Error> {
+        self.url_crawl_patterns = vec![try!(patterns.to_value())];
+        Ok(self)
+    }
+
+    /// Only processes URLs matching `patterns`, replacing previous patterns.
+    ///
+    /// Fails if a pattern cannot be expressed; see `UrlPatterns::to_value`.
+    pub fn url_process_patterns(mut self, patterns: &UrlPatterns)
+                                -> Result<Self, Error> {
+        self.url_process_patterns = vec![try!(patterns.to_value())];
+        Ok(self)
+    }
+
+    /// Only crawls URLs matching a regular expression.
+    ///
+    /// The API ignores `url_crawl_pattern` when a regular expression is set.
+    pub fn url_crawl_regex<S: Into<String>>(mut self, regex: S) -> Self {
+        self.url_crawl_regex = Some(regex.into());
+        self
+    }
+
+    /// Only processes URLs matching a regular expression.
+    ///
+    /// The API ignores `url_process_pattern` when a regular expression is
+    /// set.
+    pub fn url_process_regex<S: Into<String>>(mut self, regex: S) -> Self {
+        self.url_process_regex = Some(regex.into());
+        self
+    }
+
+    /// Repeats the crawl according to `schedule`.
+    ///
+    /// Fails if the interval is shorter than a second, or if no round is
+    /// allowed.
+    pub fn schedule(mut self, schedule: &Schedule) -> Result<Self, Error> {
+        try!(schedule.validate());
+        self.repeat = Some(schedule.repeat());
+        self.max_rounds = Some(schedule.max_rounds_value());
+        self.round_start = schedule.round_start();
+        Ok(self)
+    }
+
+    /// Sends an email to this address when each round completes.
+    pub fn notify_email<S: Into<String>>(mut self, email: S) -> Self {
+        self.notify_email = Some(email.into());
+        self
+    }
+
+    /// Sends a POST request to this URL when each round completes.
+    ///
+    /// Its body can be parsed with `notifications::JobNotification`.
+    pub fn notify_webhook<S: Into<String>>(mut self, url: S) -> Self {
+        self.notify_webhook = Some(url.into());
+        self
+    }
+
+    /// Fetches the pages through a proxy, given as `host:port`.
+    pub fn proxy<S: Into<String>>(mut self, address: S) -> Self {
+        self.proxy = Some(address.into());
+        self
+    }
+
+    /// Sets the credentials used to authenticate with the proxy.
+    pub fn proxy_auth(mut self, user: &str, password: &str) -> Self {
+        self.proxy_auth = Some(format!("{}:{}", user, password));
+        self
+    }
+
+    /// Returns the `(key, value)` parameters for these settings.
+    pub fn to_options(&self) -> Vec<(String, String)> {
+        let mut pairs = Vec::new();
+        push(&mut pairs, "maxHops", &self.max_hops);
+        push(&mut pairs, "maxToCrawl", &self.max_to_crawl);
+        push(&mut pairs, "maxToProcess", &self.max_to_process);
+        push(&mut pairs, "crawlDelay", &self.crawl_delay);
+        push(&mut pairs, "obeyRobots", &self.obey_robots);
+        push(&mut pairs, "restrictDomain", &self.restrict_domain);
+        push_patterns(&mut pairs, "urlCrawlPattern", &self.url_crawl_patterns);
+        push_patterns(&mut pairs,
+                      "urlProcessPattern",
+                      &self.url_process_patterns);
+        push(&mut pairs, "urlCrawlRegEx", &self.url_crawl_regex);
+        push(&mut pairs, "urlProcessRegEx", &self.url_process_regex);
+        push_patterns(&mut pairs,
+                      "pageProcessPattern",
+                      &self.page_process_patterns);
+        push(&mut pairs, "repeat", &self.repeat);
+        push(&mut pairs, "maxRounds", &self.max_rounds);
+        push(&mut pairs, "roundStart", &self.round_start);
+        push(&mut pairs, "notifyEmail", &self.notify_email);
+        push(&mut pairs, "notifyWebhook", &self.notify_webhook);
+        push(&mut pairs, "proxy", &self.proxy);
+        push(&mut pairs, "proxyAuth", &self.proxy_auth);
+        owned(pairs)
+    }
+}
+
+/// Settings of a bulk job.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// use std::time::Duration;
+///
+/// # fn main() {
+/// # let diffbot = Diffbot::v3("token");
+/// let settings = BulkSettings::new()
+///                    .repeat(Duration::from_secs(7 * 24 * 3600))
+///                    .notify_email("me@example.com");
+/// # println!("{:?}",
+/// diffbot.bulk_with_options("my_bulk_job", API::Analyze,
+///                           &["http://my.first.page.com"],
+///                           &settings.to_options())
+/// # );
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct BulkSettings {
+    repeat: Option<f64>,
+    max_rounds: Option<i32>,
+    round_start: Option<u8>,
+    notify_email: Option<String>,
+    notify_webhook: Option<String>,
+    page_process_patterns: Vec<String>,
+    proxy: Option<String>,
+    proxy_auth: Option<String>,
+}
+
+impl BulkSettings {
+    /// Returns empty settings, leaving every parameter to its default.
+    pub fn new() -> Self {
+        BulkSettings::default()
+    }
+
+    /// Processes the URLs again at this interval.
+    ///
+    /// The interval is sent as a fractional number of days.
+    pub fn repeat(mut self, interval: Duration) -> Self {
+        self.repeat = Some(seconds(interval) / 86_400.0);
+        self
+    }
+
+    /// Sets the maximum number of rounds of a repeating job; `-1` removes
+    /// the limit.
+    pub fn max_rounds(mut self, max_rounds: i32) -> Self {
+        self.max_rounds = Some(max_rounds);
+        self
+    }
+
+    /// Repeats the job according to `schedule`, replacing `repeat` and
+    /// `max_rounds`.
+    ///
+    /// Fails if the interval is shorter than a second, or if no round is
+    /// allowed.
+    pub fn schedule(mut self, schedule: &Schedule) -> Result<Self, Error> {
+        try!(schedule.validate());
+        self.repeat = Some(schedule.repeat());
+        self.max_rounds = Some(schedule.max_rounds_value());
+        self.round_start = schedule.round_start();
+        Ok(self)
+    }
+
+    /// Sends an email to this address when each round completes.
+    pub fn notify_email<S: Into<String>>(mut self, email: S) -> Self {
+        self.notify_email = Some(email.into());
+        self
+    }
+
+    /// Sends a POST request to this URL when each round completes.
+    ///
+    /// Its body can be parsed with `notifications::JobNotification`.
+    pub fn notify_webhook<S: Into<String>>(mut self, url: S) -> Self {
+        self.notify_webhook = Some(url.into());
+        self
+    }
+
+    /// Only processes pages whose HTML contains one of the given patterns.
+    ///
+    /// Can be called several times to add patterns.
+    pub fn page_process_pattern<S: Into<String>>(mut self, pattern: S)
+                                                 -> Self {
+        self.page_process_patterns.push(pattern.into());
+        self
+    }
+
+    /// Fetches the pages through a proxy, given as `host:port`.
+    pub fn proxy<S: Into<String>>(mut self, address: S) -> Self {
+        self.proxy = Some(address.into());
+        self
+    }
+
+    /// Sets the credentials used to authenticate with the proxy.
+    pub fn proxy_auth(mut self, user: &str, password: &str) -> Self {
+        self.proxy_auth = Some(format!("{}:{}", user, password));
+        self
+    }
+
+    /// Returns the `(key, value)` parameters for these settings.
+    pub fn to_options(&self) -> Vec<(String, String)> {
+        let mut pairs = Vec::new();
+        push(&mut pairs, "repeat", &self.repeat);
+        push(&mut pairs, "maxRounds", &self.max_rounds);
+        push(&mut pairs, "roundStart", &self.round_start);
+        push(&mut pairs, "notifyEmail", &self.notify_email);
+        push(&mut pairs, "notifyWebhook", &self.notify_webhook);
+        push_patterns(&mut pairs,
+                      "pageProcessPattern",
+                      &self.page_process_patterns);
+        push(&mut pairs, "proxy", &self.proxy);
+        push(&mut pairs, "proxyAuth", &self.proxy_auth);
+        owned(pairs)
+    }
+}
+
+/// The largest bulk job submitted by `Diffbot::bulk_chunked`.
+///
+/// Longer URL lists are split into several jobs that each fit both limits.
+///
+/// # Example
+///
+/// ```no_run
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # fn main() {
+/// # let diffbot = Diffbot::v3("token");
+/// # let urls: Vec<String> = Vec::new();
+/// let limits = BulkLimits::new().max_urls(10_000);
+/// for part in diffbot.bulk_chunked("my_bulk_job", API::Analyze, &urls,
+///                                  &limits, &[("repeat", "7.0")])
+///                    .unwrap() {
+///     println!("{}: {} URLs", part.name, part.urls);
+/// }
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct BulkLimits {
+    pub(crate) max_urls: usize,
+    pub(crate) max_body_bytes: usize,
+}
+
+impl BulkLimits {
+    /// Returns the default limits: 100,000 URLs and 8 MiB of URLs per job.
+    pub fn new() -> Self {
+        BulkLimits {
+            max_urls: 100_000,
+            max_body_bytes: 8 * 1024 * 1024,
+        }
+    }
+
+    /// Sets the maximum number of URLs of a job.
+    pub fn max_urls(mut self, max_urls: usize) -> Self {
+        self.max_urls = max_urls;
+        self
+    }
+
+    /// Sets the maximum size of the encoded URL list of a job, in bytes.
+    ///
+    /// A single URL longer than this is still sent, in its own job.
+    pub fn max_body_bytes(mut self, max_body_bytes: usize) -> Self {
+        self.max_body_bytes = max_body_bytes;
+        self
+    }
+}
+
+impl Default for BulkLimits {
+    fn default() -> Self {
+        BulkLimits::new()
+    }
+}
+
+/// A bulk job started by `Diffbot::bulk_chunked`.
+#[derive(Clone, Debug)]
+pub struct BulkPart {
+    /// The name of the job.
+    pub name: String,
+    /// The number of URLs submitted to the job.
+    pub urls: usize,
+    /// The response of the API when the job was started.
+    pub response: Map<String, Value>,
+}
+
+#[cfg(test)]
+fn get<'a>(options: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    options.iter()
+           .find(|&&(ref k, _)| k == key)
+           .map(|&(_, ref v)| v.as_str())
+}
+
+#[test]
+fn test_crawl_settings() {
+    let settings = CrawlSettings::new().max_hops(-1)
+                                       .max_to_crawl(100)
+                                       .crawl_delay(Duration::from_millis(250))
+                                       .obey_robots(false)
+                                       .url_crawl_pattern("/blog/")
+                                       .url_crawl_pattern("/news/");
+    let options = settings.to_options();
+    assert_eq!(get(&options, "maxHops"), Some("-1"));
+    assert_eq!(get(&options, "maxToCrawl"), Some("100"));
+    assert_eq!(get(&options, "crawlDelay"), Some("0.25"));
+    assert_eq!(get(&options, "obeyRobots"), Some("0"));
+    assert_eq!(get(&options, "urlCrawlPattern"), Some("/blog/||/news/"));
+    assert_eq!(get(&options, "restrictDomain"), None);
+
+    let options = CrawlSettings::new().notify_webhook("https://a.com/hook")
+                                      .to_options();
+    assert_eq!(get(&options, "notifyWebhook"), Some("https://a.com/hook"));
+
+    let options = CrawlSettings::new().proxy("1.2.3.4:8080")
+                                      .proxy_auth("user", "secret")
+                                      .to_options();
+    assert_eq!(get(&options, "proxy"), Some("1.2.3.4:8080"));
+    assert_eq!(get(&options, "proxyAuth"), Some("user:secret"));
+}
+
+#[test]
+fn test_url_patterns() {
+    let patterns = UrlPatterns::new().allow("/a b/")
+                                     .allow_suffix(".html")
+                                     .deny("?print=1");
+    assert_eq!(patterns.to_value().unwrap(), "/a b/||.html$||!?print=1");
+    let settings = CrawlSettings::new().url_crawl_pattern("/old/")
+                                       .url_crawl_patterns(&patterns)
+                                       .unwrap();
+    assert_eq!(get(&settings.to_options(), "urlCrawlPattern"),
+               Some("/a b/||.html$||!?print=1"));
+
+    for bad in &["", "a||b", "!a", "^a", "a$"] {
+        let err = UrlPatterns::new().allow(*bad).to_value().unwrap_err();
+        match err {
+            Error::InvalidSetting(_) => {}
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+    assert_eq!(UrlPatterns::new().to_value().unwrap(), "");
+}
+
+#[test]
+fn test_bulk_settings() {
+    let settings = BulkSettings::new().repeat(Duration::from_secs(3 * 3600))
+                                      .max_rounds(-1)
+                                      .notify_webhook("https://a.com/hook");
+    let options = settings.to_options();
+    assert_eq!(get(&options, "repeat"), Some("0.125"));
+    assert_eq!(get(&options, "maxRounds"), Some("-1"));
+    assert_eq!(get(&options, "notifyWebhook"), Some("https://a.com/hook"));
+    assert_eq!(get(&options, "notifyEmail"), None);
+}
+
+#[test]
+fn test_schedule() {
+    let weekly = Schedule::every(Duration::from_secs(7 * 86_400))
+                     .start_round_now();
+    let options = CrawlSettings::new().schedule(&weekly).unwrap().to_options();
+    assert_eq!(get(&options, "repeat"), Some("7"));
+    assert_eq!(get(&options, "maxRounds"), Some("-1"));
+    assert_eq!(get(&options, "roundStart"), Some("1"));
+
+    let options = BulkSettings::new().schedule(&weekly.max_rounds(4))
+                                     .unwrap()
+                                     .to_options();
+    assert_eq!(get(&options, "maxRounds"), Some("4"));
+
+    let too_fast = Schedule::every(Duration::from_millis(10));
+    assert!(CrawlSettings::new().schedule(&too_fast).is_err());
+    let no_round = weekly.max_rounds(0);
+    assert!(BulkSettings::new().schedule(&no_round).is_err());
+}
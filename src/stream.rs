@@ -0,0 +1,859 @@
+//! Incremental parsing of large result sets.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+use std::marker::PhantomData;
+#[cfg(feature = "stream")]
+use std::time::Duration;
+
+#[cfg(feature = "stream")]
+use futures::channel::{mpsc, oneshot};
+#[cfg(feature = "stream")]
+use futures::Future;
+use serde::de::DeserializeOwned;
+use serde_json;
+#[cfg(any(feature = "kg", feature = "stream"))]
+use serde_json::Value;
+
+#[cfg(any(feature = "jobs", feature = "kg", feature = "search"))]
+use Diffbot;
+use Error;
+#[cfg(feature = "search")]
+use PageType;
+use http::HttpResponse;
+#[cfg(feature = "search")]
+use models::{self, SearchHit, SearchResponse};
+use response;
+#[cfg(feature = "stream")]
+use models::Job;
+
+/// The format of the data downloaded from a crawl or bulk job, or exported
+/// from the Knowledge Graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataFormat {
+    /// A JSON array of the extracted objects.
+    Json,
+    /// A CSV table of the extracted objects, one per row.
+    Csv,
+}
+
+impl fmt::Display for DataFormat {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(match *self {
+            DataFormat::Json => "json",
+            DataFormat::Csv => "csv",
+        })
+    }
+}
+
+enum Format {
+    Array,
+    Lines,
+}
+
+/// An iterator over the objects of a JSON document, parsed one at a time.
+///
+/// The document is either a JSON array, as returned by the crawl and bulk
+/// data downloads, or newline-delimited JSON. Only one object is held in
+/// memory at a time, whatever the size of the document.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # extern crate serde_json;
+/// # use diffbot::*;
+/// # fn main() {
+/// let json = r#"[{"title": "a"}, {"title": "b"}]"#;
+/// let titles: Vec<String> =
+///     JsonStream::<serde_json::Value>::array(json.as_bytes())
+///         .map(|object| object.unwrap()["title"].to_string())
+///         .collect();
+/// assert_eq!(titles, vec![r#""a""#, r#""b""#]);
+/// # }
+/// ```
+pub struct JsonStream<T> {
+    reader: BufReader<Box<dyn Read + Send>>,
+    format: Format,
+    started: bool,
+    done: bool,
+    item: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> JsonStream<T> {
+    /// Parses the elements of a JSON array.
+    ///
+    /// If the document is a JSON object instead, it is treated as an API
+    /// response: the error it reports is returned as the only item.
+    pub fn array<R: Read + Send + 'static>(reader: R) -> Self {
+        JsonStream::new(Box::new(reader), Format::Array)
+    }
+
+    /// Parses one JSON value per line, skipping blank lines.
+    pub fn lines<R: Read + Send + 'static>(reader: R) -> Self {
+        JsonStream::new(Box::new(reader), Format::Lines)
+    }
+
+    fn new(reader: Box<dyn Read + Send>, format: Format) -> Self {
+        JsonStream {
+            reader: BufReader::new(reader),
+            format: format,
+            started: false,
+            done: false,
+            item: PhantomData,
+        }
+    }
+
+    fn next_line(&mut self) -> Result<Option<T>, Error> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if try!(self.reader.read_line(&mut line)) == 0 {
+                return Ok(None);
+            }
+            if !line.trim().is_empty() {
+                return Ok(Some(try!(serde_json::from_str(&line))));
+            }
+        }
+    }
+
+    fn next_element(&mut self) -> Result<Option<T>, Error> {
+        let separator = if self.started {
+            try!(self.next_token())
+        } else {
+            self.started = true;
+            match try!(self.next_token()) {
+                Some(b'[') => {
+                    if try!(self.peek_token()) == Some(b']') {
+                        try!(self.next_token());
+                        return Ok(None);
+                    }
+                    Some(b',')
+                }
+                Some(b'{') => return Err(self.object_error()),
+                other => return Err(unexpected(other)),
+            }
+        };
+        match separator {
+            Some(b',') => {}
+            Some(b']') => return Ok(None),
+            other => return Err(unexpected(other)),
+        }
+
+        let value = try!(self.read_value());
+        Ok(Some(try!(serde_json::from_slice(&value))))
+    }
+
+    // Reads the bytes of the next value, which must be an object or array.
+    fn read_value(&mut self) -> Result<Vec<u8>, Error> {
+        let mut value = Vec::new();
+        let mut depth = 0;
+        let mut in_string = false;
+        let mut escaped = false;
+        match try!(self.next_token()) {
+            Some(byte) if byte == b'{' || byte == b'[' => {
+                value.push(byte);
+                depth += 1;
+            }
+            other => return Err(unexpected(other)),
+        }
+        while depth > 0 {
+            let byte = match try!(self.next_byte()) {
+                Some(byte) => byte,
+                None => return Err(unexpected(None)),
+            };
+            value.push(byte);
+            if in_string {
+                if escaped {
+                    escaped = false;
+                } else if byte == b'\\' {
+                    escaped = true;
+                } else if byte == b'"' {
+                    in_string = false;
+                }
+                continue;
+            }
+            match byte {
+                b'"' => in_string = true,
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' => depth -= 1,
+                _ => {}
+            }
+        }
+        Ok(value)
+    }
+
+    // Reads the rest of a document that is an object instead of an array.
+    fn object_error(&mut self) -> Error {
+        let mut body = b"{".to_vec();
+        if let Err(err) = self.reader.read_to_end(&mut body) {
+            return Error::Io(err);
+        }
+        let response = HttpResponse {
+            status: 200,
+            retry_after: None,
+            headers: Vec::new(),
+            body: body,
+            elapsed: None,
+        };
+        match response::parse_response(response) {
+            Err(err) => err,
+            Ok(_) => unexpected(Some(b'{')),
+        }
+    }
+
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        let byte = try!(self.peek_byte());
+        if byte.is_some() {
+            self.reader.consume(1);
+        }
+        Ok(byte)
+    }
+
+    fn peek_byte(&mut self) -> io::Result<Option<u8>> {
+        Ok(try!(self.reader.fill_buf()).first().cloned())
+    }
+
+    // Returns the next byte that is not whitespace.
+    fn next_token(&mut self) -> io::Result<Option<u8>> {
+        let token = try!(self.peek_token());
+        if token.is_some() {
+            self.reader.consume(1);
+        }
+        Ok(token)
+    }
+
+    fn peek_token(&mut self) -> io::Result<Option<u8>> {
+        loop {
+            match try!(self.peek_byte()) {
+                Some(byte) if (byte as char).is_whitespace() => {
+                    self.reader.consume(1)
+                }
+                other => return Ok(other),
+            }
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for JsonStream<T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Result<T, Error>> {
+        if self.done {
+            return None;
+        }
+        let item = match self.format {
+            Format::Array => self.next_element(),
+            Format::Lines => self.next_line(),
+        };
+        match item {
+            Ok(Some(item)) => Some(Ok(item)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "jobs")]
+const DEFAULT_PAGE_SIZE: u64 = 1000;
+
+#[cfg(feature = "jobs")]
+/// An iterator over the objects of a crawl or bulk job, downloaded a page
+/// at a time.
+///
+/// Each page is requested with the `offset` and `num` parameters once the
+/// previous one has been read, and is parsed as a stream. Iteration stops
+/// after a short page, or after the first error.
+pub struct JobResults<'a, T> {
+    client: &'a Diffbot,
+    api: &'static str,
+    name: String,
+    page_size: u64,
+    offset: u64,
+    page: Option<JsonStream<T>>,
+    page_len: u64,
+    done: bool,
+}
+
+#[cfg(feature = "jobs")]
+impl<'a, T: DeserializeOwned> JobResults<'a, T> {
+    pub(crate) fn new(client: &'a Diffbot, api: &'static str, name: &str)
+                      -> Self {
+        JobResults {
+            client: client,
+            api: api,
+            name: name.to_string(),
+            page_size: DEFAULT_PAGE_SIZE,
+            offset: 0,
+            page: None,
+            page_len: 0,
+            done: false,
+        }
+    }
+
+    /// Sets the number of objects requested at once, 1000 by default.
+    pub fn page_size(mut self, page_size: u64) -> Self {
+        self.page_size = ::std::cmp::max(page_size, 1);
+        self
+    }
+
+    /// Starts at the given object instead of the first one.
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    fn next_page(&mut self) -> Result<JsonStream<T>, Error> {
+        let offset = self.offset.to_string();
+        let num = self.page_size.to_string();
+        self.client.job_data(self.api,
+                             &self.name,
+                             &[("offset", &offset), ("num", &num)])
+    }
+}
+
+#[cfg(feature = "jobs")]
+impl<'a, T: DeserializeOwned> Iterator for JobResults<'a, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Result<T, Error>> {
+        while !self.done {
+            if self.page.is_none() {
+                match self.next_page() {
+                    Ok(page) => self.page = Some(page),
+                    Err(err) => {
+                        self.done = true;
+                        return Some(Err(err));
+                    }
+                }
+                self.page_len = 0;
+            }
+
+            match self.page.as_mut().and_then(Iterator::next) {
+                Some(Ok(item)) => {
+                    self.page_len += 1;
+                    self.offset += 1;
+                    return Some(Ok(item));
+                }
+                Some(Err(err)) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+                None => {
+                    self.page = None;
+                    self.done = self.page_len < self.page_size;
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(feature = "search")]
+const DEFAULT_SEARCH_PAGE_SIZE: u64 = 100;
+
+#[cfg(feature = "search")]
+/// An iterator over the objects matching a search, requested a page at a
+/// time.
+///
+/// Each page is requested with the `start` and `num` parameters once the
+/// previous one has been read. Iteration stops after a short page, once
+/// every hit has been returned, or after the first error.
+pub struct SearchResults<'a, T> {
+    client: &'a Diffbot,
+    col: String,
+    query: String,
+    page_size: u64,
+    start: u64,
+    page: ::std::vec::IntoIter<SearchHit<T>>,
+    done: bool,
+}
+
+#[cfg(feature = "search")]
+impl<'a, T: DeserializeOwned> SearchResults<'a, T> {
+    pub(crate) fn new(client: &'a Diffbot, col: &str, query: &str) -> Self {
+        SearchResults {
+            client: client,
+            col: col.to_string(),
+            query: query.to_string(),
+            page_size: DEFAULT_SEARCH_PAGE_SIZE,
+            start: 0,
+            page: Vec::new().into_iter(),
+            done: false,
+        }
+    }
+
+    /// Sets the number of objects requested at once, 100 by default.
+    pub fn page_size(mut self, page_size: u64) -> Self {
+        self.page_size = ::std::cmp::max(page_size, 1);
+        self
+    }
+
+    /// Only returns the objects of the given type.
+    ///
+    /// The type is added to the query, so the other objects are not
+    /// downloaded.
+    pub fn page_type(mut self, page_type: PageType) -> Self {
+        let filter = format!("type:{}", page_type);
+        self.query = if self.query.is_empty() {
+            filter
+        } else {
+            format!("{} {}", self.query, filter)
+        };
+        self
+    }
+
+    fn next_page(&mut self) -> Result<Vec<SearchHit<T>>, Error> {
+        let start = self.start.to_string();
+        let num = self.page_size.to_string();
+        let result = self.client
+                         .search_with_options(&self.col,
+                                              &self.query,
+                                              &[("start", &*start),
+                                                ("num", &*num)]);
+        let response: SearchResponse<T> =
+            try!(result.and_then(models::parse));
+        let len = response.objects.len() as u64;
+        self.start += len;
+        self.done = len < self.page_size ||
+                    response.hits.map_or(false, |hits| self.start >= hits);
+        Ok(response.objects)
+    }
+}
+
+#[cfg(feature = "search")]
+impl<'a, T: DeserializeOwned> Iterator for SearchResults<'a, T> {
+    type Item = Result<SearchHit<T>, Error>;
+
+    fn next(&mut self) -> Option<Result<SearchHit<T>, Error>> {
+        loop {
+            if let Some(hit) = self.page.next() {
+                return Some(Ok(hit));
+            }
+            if self.done {
+                return None;
+            }
+            match self.next_page() {
+                Ok(page) => self.page = page.into_iter(),
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "kg")]
+const DEFAULT_KG_PAGE_SIZE: u64 = 50;
+
+/// An iterator over the entities matching a Knowledge Graph query,
+/// requested a page at a time.
+///
+/// When the Knowledge Graph returns a `cursor` with a page, the next page is
+/// requested with it; otherwise the iterator moves the `from` offset
+/// forward. Each page is requested once the previous one has been read.
+/// Iteration stops after a short page, once every hit has been returned, or
+/// after the first error.
+#[cfg(feature = "kg")]
+pub struct KgResults<'a, T> {
+    client: &'a Diffbot,
+    query: String,
+    page_size: u64,
+    from: u64,
+    cursor: Option<String>,
+    page: ::std::vec::IntoIter<Value>,
+    done: bool,
+    item: PhantomData<T>,
+}
+
+#[cfg(feature = "kg")]
+impl<'a, T: DeserializeOwned> KgResults<'a, T> {
+    pub(crate) fn new(client: &'a Diffbot, query: &str) -> Self {
+        KgResults {
+            client: client,
+            query: query.to_string(),
+            page_size: DEFAULT_KG_PAGE_SIZE,
+            from: 0,
+            cursor: None,
+            page: Vec::new().into_iter(),
+            done: false,
+            item: PhantomData,
+        }
+    }
+
+    /// Sets the number of entities requested at once, 50 by default.
+    pub fn page_size(mut self, page_size: u64) -> Self {
+        self.page_size = ::std::cmp::max(page_size, 1);
+        self
+    }
+
+    /// Starts at the given entity instead of the first one.
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.from = offset;
+        self
+    }
+
+    fn next_page(&mut self) -> Result<Vec<Value>, Error> {
+        let size = self.page_size.to_string();
+        let position = match self.cursor.take() {
+            Some(cursor) => ("cursor", cursor),
+            None => ("from", self.from.to_string()),
+        };
+        let mut result = try!(self.client
+                                  .kg_query_with_options(&self.query,
+                                                         &[(position.0,
+                                                            &*position.1),
+                                                           ("size", &*size)]));
+        let hits = result.get("hits").and_then(Value::as_u64);
+        self.cursor = result.get("cursor")
+                            .and_then(Value::as_str)
+                            .map(str::to_string);
+        let data = match result.remove("data") {
+            Some(Value::Array(data)) => data,
+            _ => Vec::new(),
+        };
+        let len = data.len() as u64;
+        self.from += len;
+        self.done = len < self.page_size ||
+                    hits.map_or(false, |hits| self.from >= hits);
+        Ok(data)
+    }
+}
+
+#[cfg(feature = "kg")]
+impl<'a, T: DeserializeOwned> Iterator for KgResults<'a, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Result<T, Error>> {
+        loop {
+            if let Some(mut item) = self.page.next() {
+                // Entities come with their score, unless asked otherwise.
+                let entity = match item.get_mut("entity") {
+                    Some(entity) => entity.take(),
+                    None => item,
+                };
+                return Some(serde_json::from_value(entity)
+                                .map_err(Error::from));
+            }
+            if self.done {
+                return None;
+            }
+            match self.next_page() {
+                Ok(page) => self.page = page.into_iter(),
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+// How many objects the background thread of a stream reads ahead.
+#[cfg(feature = "stream")]
+const STREAM_BUFFER: usize = 16;
+
+// Iterates over the results of a job on a new thread, sending them to the
+// returned stream.
+#[cfg(feature = "stream")]
+pub(crate) fn spawn_results(client: Diffbot, api: &'static str, name: &str)
+                            -> mpsc::Receiver<Result<Value, Error>> {
+    use futures::SinkExt;
+    use futures::executor;
+
+    let (mut sender, receiver) = mpsc::channel(STREAM_BUFFER);
+    let name = name.to_string();
+    ::std::thread::spawn(move || {
+        for item in JobResults::new(&client, api, &name) {
+            if executor::block_on(sender.send(item)).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+// Iterates over the entities matching a Knowledge Graph query on a new
+// thread, sending them to the returned stream.
+#[cfg(all(feature = "stream", feature = "kg"))]
+pub(crate) fn spawn_kg_results(client: Diffbot, query: &str)
+                               -> mpsc::Receiver<Result<Value, Error>> {
+    use futures::SinkExt;
+    use futures::executor;
+
+    let (mut sender, receiver) = mpsc::channel(STREAM_BUFFER);
+    let query = query.to_string();
+    ::std::thread::spawn(move || {
+        for item in KgResults::new(&client, &query) {
+            if executor::block_on(sender.send(item)).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+// The delays between two status checks of `spawn_completion`, doubled after
+// each check.
+#[cfg(feature = "stream")]
+const FIRST_POLL: Duration = Duration::from_secs(5);
+#[cfg(feature = "stream")]
+const MAX_POLL: Duration = Duration::from_secs(300);
+
+// Polls a job on a new thread until it finishes, and returns a future of its
+// last status. Retryable errors are polled through, waiting as long as the
+// API asks when it does. The thread stops when the future is dropped.
+#[cfg(feature = "stream")]
+pub(crate) fn spawn_completion(client: Diffbot, api: &'static str,
+                               name: &str)
+                               -> impl Future<Output = Result<Job, Error>> {
+    use futures::FutureExt;
+
+    let (sender, receiver) = oneshot::channel();
+    let name = name.to_string();
+    ::std::thread::spawn(move || {
+        let mut delay = FIRST_POLL;
+        let result = loop {
+            let mut wait = delay;
+            match client.find_job(api, &name) {
+                Ok(ref job) if !job.job_status
+                                   .as_ref()
+                                   .map_or(false, |s| s.is_finished()) => {}
+                Err(ref err) if err.is_retryable() => {
+                    wait = err.retry_after().unwrap_or(delay);
+                }
+                result => break result,
+            }
+            ::std::thread::sleep(wait);
+            if sender.is_canceled() {
+                return;
+            }
+            delay = ::std::cmp::min(delay * 2, MAX_POLL);
+        };
+        let _ = sender.send(result);
+    });
+    receiver.map(|result| {
+        result.unwrap_or_else(|_| {
+            let message = "job polling stopped unexpectedly";
+            Err(Error::Io(io::Error::new(io::ErrorKind::Other, message)))
+        })
+    })
+}
+
+fn unexpected(byte: Option<u8>) -> Error {
+    let message = match byte {
+        Some(byte) => format!("unexpected `{}` in JSON array", byte as char),
+        None => "unexpected end of JSON array".to_string(),
+    };
+    Error::Io(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+#[test]
+fn test_array_stream() {
+    use serde_json::Value;
+
+    let json = r#" [ {"a": "x]}\"{"}, {"b": [1, {"c": 2}]} ] "#;
+    let items: Vec<Value> = JsonStream::array(json.as_bytes())
+                                .collect::<Result<_, _>>()
+                                .unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0]["a"], "x]}\"{");
+    assert_eq!(items[1]["b"][1]["c"], 2);
+
+    assert_eq!(JsonStream::<Value>::array(&b"[]"[..]).count(), 0);
+    let mut truncated = JsonStream::<Value>::array(&b"[{}, {"[..]);
+    assert!(truncated.next().unwrap().is_ok());
+    assert!(truncated.next().unwrap().is_err());
+    assert!(truncated.next().is_none());
+}
+
+#[test]
+fn test_array_stream_error_object() {
+    use serde_json::Value;
+
+    let json = r#"{"errorCode": 404, "error": "No such job"}"#;
+    let mut stream = JsonStream::<Value>::array(json.as_bytes());
+    assert!(stream.next().unwrap().unwrap_err().is_not_found());
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn test_lines_stream() {
+    use serde_json::Value;
+
+    let json = "{\"a\": 1}\n\n{\"a\": 2}\n";
+    let items: Vec<Value> = JsonStream::lines(json.as_bytes())
+                                .collect::<Result<_, _>>()
+                                .unwrap();
+    assert_eq!(items[1]["a"], 2);
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_job_results() {
+    use serde_json::Value;
+    use stub::{StubResponse, StubServer};
+
+    let server = StubServer::start();
+    server.push(StubResponse::json("[{\"n\": 1}, {\"n\": 2}]"));
+    server.push(StubResponse::json("[{\"n\": 3}, {\"n\": 4}]"));
+    server.push(StubResponse::json("[{\"n\": 5}]"));
+    let diffbot = server.client("token");
+
+    let numbers: Vec<u64> = diffbot.crawl_results::<Value>("my_crawl")
+                                   .page_size(2)
+                                   .map(|item| item.unwrap()["n"].as_u64())
+                                   .map(Option::unwrap)
+                                   .collect();
+    assert_eq!(numbers, vec![1, 2, 3, 4, 5]);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[2].query("offset"), Some("4"));
+    assert_eq!(requests[2].query("num"), Some("2"));
+}
+
+#[cfg(all(feature = "jobs", feature = "search"))]
+#[test]
+fn test_crawl_results_since() {
+    use serde_json::Value;
+    use stub::{StubResponse, StubServer};
+
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"hits": 3, "objects": [
+        {"docId": 1, "timestamp": 1500000000000},
+        {"docId": 2, "timestamp": 1500000001000}]}"#));
+    server.push(StubResponse::json(r#"{"hits": 3, "objects": [
+        {"docId": 3, "timestamp": 1500000002000}]}"#));
+    let diffbot = server.client("token");
+
+    let ids: Vec<u64> = diffbot.crawl_results_since::<Value>("my_crawl",
+                                                             1500000000000)
+                               .page_size(2)
+                               .map(|hit| hit.unwrap().doc_id.unwrap())
+                               .collect();
+    assert_eq!(ids, vec![1, 2, 3]);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].path(), "/v3/search");
+    assert_eq!(requests[0].query("col"), Some("my_crawl"));
+    assert_eq!(requests[0].query("query"),
+               Some("min:timestamp:1500000000000"));
+    assert_eq!(requests[1].query("start"), Some("2"));
+    assert_eq!(requests[1].query("num"), Some("2"));
+}
+
+#[cfg(all(feature = "jobs", feature = "search"))]
+#[test]
+fn test_job_results_by_type() {
+    use serde_json::Value;
+    use stub::{StubResponse, StubServer};
+    use PageType;
+
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": [{"type": "product"}]}"#));
+    server.push(StubResponse::json(r#"{"objects": []}"#));
+    let diffbot = server.client("token");
+
+    let products: Vec<_> =
+        diffbot.job_results_by_type::<Value>("my_crawl", PageType::Product)
+               .collect();
+    assert_eq!(products.len(), 1);
+    let _ = diffbot.crawl_results_since::<Value>("my_crawl", 1000)
+                   .page_type(PageType::Article)
+                   .next();
+    let requests = server.requests();
+    assert_eq!(requests[0].query("col"), Some("my_crawl"));
+    assert_eq!(requests[0].query("query"), Some("type:product"));
+    assert_eq!(requests[1].query("query"),
+               Some("min:timestamp:1000 type:article"));
+}
+
+#[cfg(feature = "kg")]
+#[test]
+fn test_kg_results() {
+    use stub::{StubResponse, StubServer};
+
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"hits": 3, "data": [
+        {"score": 1.0, "entity": {"name": "A"}},
+        {"score": 0.9, "entity": {"name": "B"}}]}"#));
+    server.push(StubResponse::json(r#"{"hits": 3, "data": [
+        {"name": "C"}]}"#));
+    let diffbot = server.client("token");
+
+    let names: Vec<Value> = diffbot.kg_query_iter::<Value>("type:Person")
+                                   .page_size(2)
+                                   .map(|entity| entity.unwrap())
+                                   .map(|mut entity| entity["name"].take())
+                                   .collect();
+    assert_eq!(names, vec!["A", "B", "C"]);
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].query("query"), Some("type:Person"));
+    assert_eq!(requests[0].query("from"), Some("0"));
+    assert_eq!(requests[1].query("from"), Some("2"));
+    assert_eq!(requests[1].query("size"), Some("2"));
+
+    server.push(StubResponse::json(r#"{"hits": 3, "cursor": "c1", "data": [
+        {"name": "A"}, {"name": "B"}]}"#));
+    server.push(StubResponse::json(r#"{"hits": 3, "data": [
+        {"name": "C"}]}"#));
+    let count = diffbot.kg_query_iter::<Value>("type:Person")
+                       .page_size(2)
+                       .count();
+    assert_eq!(count, 3);
+    let requests = server.requests();
+    assert_eq!(requests[3].query("cursor"), Some("c1"));
+    assert_eq!(requests[3].query("from"), None);
+}
+
+#[cfg(feature = "stream")]
+#[test]
+fn test_results_stream() {
+    use futures::{executor, StreamExt};
+    use stub::{StubResponse, StubServer};
+
+    let server = StubServer::start();
+    server.push(StubResponse::json("[{\"n\": 1}, {\"n\": 2}]"));
+    let diffbot = server.client("token");
+
+    let items: Vec<_> =
+        executor::block_on(diffbot.crawl_results_stream("my_crawl")
+                                  .collect());
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[1].as_ref().unwrap()["n"], 2);
+}
+
+#[cfg(feature = "stream")]
+#[test]
+fn test_crawl_completion() {
+    use futures::executor;
+    use stub::{StubResponse, StubServer};
+
+    let finished = r#"{"jobs": [{"name": "news",
+                                 "jobStatus": {"status": 9}}]}"#;
+    let server = StubServer::start();
+    server.push(StubResponse::json(finished));
+    let diffbot = server.client("token");
+
+    let job = executor::block_on(diffbot.crawl_completion("news")).unwrap();
+    assert_eq!(job.job_status.unwrap().status, 9);
+
+    server.push(StubResponse::json(r#"{"jobs": []}"#));
+    let err = executor::block_on(diffbot.crawl_completion("news"))
+                  .unwrap_err();
+    assert!(err.is_not_found());
+
+    let throttled = r#"{"errorCode": 429, "error": "slow down"}"#;
+    server.push(StubResponse::new(429, throttled).header("Retry-After", "0"));
+    server.push(StubResponse::json(finished));
+    let job = executor::block_on(diffbot.crawl_completion("news")).unwrap();
+    assert_eq!(job.job_status.unwrap().status, 9);
+}
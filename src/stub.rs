@@ -0,0 +1,364 @@
+//! A minimal HTTP server standing in for the Diffbot API in tests.
+//!
+//! Only enabled with the `test-util` feature. The server listens on a local
+//! port, answers each request with the next queued `StubResponse` and
+//! records what it received.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use url::{form_urlencoded, Url};
+
+use Diffbot;
+
+/// A canned response served by `StubServer`.
+#[derive(Clone, Debug)]
+pub struct StubResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    delay: Option<Duration>,
+}
+
+impl StubResponse {
+    /// Returns a response with the given status and body.
+    pub fn new<B: Into<Vec<u8>>>(status: u16, body: B) -> Self {
+        StubResponse {
+            status: status,
+            headers: Vec::new(),
+            body: body.into(),
+            delay: None,
+        }
+    }
+
+    /// Returns a `200 OK` response with a JSON body.
+    pub fn json<S: Into<String>>(body: S) -> Self {
+        StubResponse::new(200, body.into())
+            .header("Content-Type", "application/json")
+    }
+
+    /// Adds a header to the response.
+    pub fn header<K, V>(mut self, name: K, value: V) -> Self
+        where K: Into<String>,
+              V: Into<String>
+    {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Waits before sending the response, to simulate a slow API.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+}
+
+/// A request received by `StubServer`.
+#[derive(Clone, Debug)]
+pub struct StubRequest {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl StubRequest {
+    /// Returns the HTTP method, such as `"GET"`.
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Returns the path, such as `"/v3/article"`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the first query parameter named `key`.
+    pub fn query(&self, key: &str) -> Option<&str> {
+        find(&self.query, key)
+    }
+
+    /// Returns the first header named `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|&&(ref k, _)| k.eq_ignore_ascii_case(name))
+            .map(|&(_, ref v)| v.as_str())
+    }
+
+    /// Returns the body.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns the first parameter named `key` of a form-urlencoded body.
+    pub fn form(&self, key: &str) -> Option<String> {
+        form_urlencoded::parse(&self.body)
+            .find(|&(ref k, _)| k == key)
+            .map(|(_, v)| v.into_owned())
+    }
+}
+
+fn find<'a>(pairs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    pairs.iter()
+         .find(|&&(ref k, _)| k == key)
+         .map(|&(_, ref v)| v.as_str())
+}
+
+#[derive(Default)]
+struct State {
+    responses: VecDeque<StubResponse>,
+    requests: Vec<StubRequest>,
+}
+
+/// A local HTTP server answering with queued responses.
+///
+/// The server stops when dropped.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # #[cfg(feature = "test-util")]
+/// # fn main() {
+/// use diffbot::testing::{StubResponse, StubServer};
+///
+/// let server = StubServer::start();
+/// server.push(StubResponse::json(r#"{"objects": []}"#));
+///
+/// let diffbot = server.client("token");
+/// diffbot.call(API::Article, "http://diffbot.com").unwrap();
+///
+/// let request = &server.requests()[0];
+/// assert_eq!(request.path(), "/v3/article");
+/// assert_eq!(request.query("url"), Some("http://diffbot.com/"));
+/// # }
+/// # #[cfg(not(feature = "test-util"))]
+/// # fn main() {}
+/// ```
+pub struct StubServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl StubServer {
+    /// Starts a server on a free local port.
+    ///
+    /// # Panics
+    ///
+    /// If no port can be bound.
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+                           .expect("cannot start the stub server");
+        let server = StubServer {
+            addr: listener.local_addr().unwrap(),
+            state: Arc::new(Mutex::new(State::default())),
+            stopped: Arc::new(AtomicBool::new(false)),
+        };
+
+        let state = server.state.clone();
+        let stopped = server.stopped.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    // A broken connection only fails the request using it.
+                    let state = state.clone();
+                    thread::spawn(move || serve(stream, &state));
+                }
+            }
+        });
+        server
+    }
+
+    /// Returns the root URL of the server.
+    pub fn url(&self) -> Url {
+        Url::parse(&format!("http://{}/", self.addr)).unwrap()
+    }
+
+    /// Returns a v3 client sending its requests to this server.
+    pub fn client<S: ToString>(&self, token: S) -> Diffbot {
+        Diffbot::v3(token).with_base_url(self.url().as_str()).unwrap()
+    }
+
+    /// Queues the response to the next request.
+    pub fn push(&self, response: StubResponse) {
+        self.state.lock().unwrap().responses.push_back(response);
+    }
+
+    /// Returns every request received so far.
+    pub fn requests(&self) -> Vec<StubRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+impl Drop for StubServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wakes the server thread up so it sees the flag.
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+// Reads one request and answers it.
+fn serve(stream: TcpStream, state: &Mutex<State>) -> io::Result<()> {
+    let mut reader = BufReader::new(try!(stream.try_clone()));
+    let mut line = String::new();
+    try!(reader.read_line(&mut line));
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let target = parts.next().unwrap_or("/").to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        try!(reader.read_line(&mut line));
+        let header = line.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(colon) = header.find(':') {
+            headers.push((header[..colon].trim().to_string(),
+                          header[colon + 1..].trim().to_string()));
+        }
+    }
+
+    let header = |name: &str| {
+        headers.iter()
+               .find(|&&(ref k, _)| k.eq_ignore_ascii_case(name))
+               .map(|&(_, ref v)| v.to_ascii_lowercase())
+    };
+    let body = if header("Transfer-Encoding")
+                      .map_or(false, |coding| coding.contains("chunked")) {
+        try!(read_chunked(&mut reader))
+    } else {
+        let length = header("Content-Length")
+                         .and_then(|length| length.parse().ok())
+                         .unwrap_or(0);
+        let mut body = vec![0; length];
+        try!(reader.read_exact(&mut body));
+        body
+    };
+
+    let url = Url::parse("http://stub").unwrap().join(&target).unwrap();
+    let request = StubRequest {
+        method: method,
+        path: url.path().to_string(),
+        query: url.query_pairs().into_owned().collect(),
+        headers: headers,
+        body: body,
+    };
+
+    let response = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(request);
+        state.responses.pop_front()
+    };
+    let response = response.unwrap_or_else(|| {
+        StubResponse::new(500,
+                          r#"{"errorCode":500,"error":"no stub response"}"#)
+    });
+    if let Some(delay) = response.delay {
+        thread::sleep(delay);
+    }
+    write_response(stream, &response)
+}
+
+// Reads a body sent with `Transfer-Encoding: chunked`, up to and including
+// the trailer.
+fn read_chunked<R: BufRead>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        try!(reader.read_line(&mut line));
+        let size = line.split(';').next().unwrap_or("").trim();
+        let size = try!(usize::from_str_radix(size, 16).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData,
+                           format!("invalid chunk size {:?}", size))
+        }));
+        if size == 0 {
+            break;
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        try!(reader.read_exact(&mut body[start..]));
+        line.clear();
+        try!(reader.read_line(&mut line));
+    }
+    // Skips the trailer fields, up to the empty line ending the request.
+    loop {
+        line.clear();
+        if try!(reader.read_line(&mut line)) == 0 || line.trim().is_empty() {
+            return Ok(body);
+        }
+    }
+}
+
+fn write_response(mut stream: TcpStream, response: &StubResponse)
+                  -> io::Result<()> {
+    try!(write!(stream, "HTTP/1.1 {} Stub\r\n", response.status));
+    for &(ref name, ref value) in &response.headers {
+        try!(write!(stream, "{}: {}\r\n", name, value));
+    }
+    try!(write!(stream,
+                "Content-Length: {}\r\nConnection: close\r\n\r\n",
+                response.body.len()));
+    try!(stream.write_all(&response.body));
+    stream.flush()
+}
+
+#[test]
+fn test_stub_server() {
+    use API;
+
+    let server = StubServer::start();
+    server.push(StubResponse::json(r#"{"objects": [{"title": "T"}]}"#));
+    server.push(StubResponse::new(429, "slow down")
+                    .header("Retry-After", "7"));
+
+    let diffbot = server.client("0123456789abcdef");
+    let result = diffbot.call(API::Article, "http://a.com").unwrap();
+    assert_eq!(result["objects"][0]["title"], "T");
+    let err = diffbot.call(API::Article, "http://a.com").unwrap_err();
+    assert!(err.is_throttled());
+    assert_eq!(err.retry_after(), Some(::std::time::Duration::from_secs(7)));
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].method(), "GET");
+    assert_eq!(requests[0].path(), "/v3/article");
+    assert_eq!(requests[0].query("token"), Some("0123456789abcdef"));
+}
+
+#[test]
+fn test_stub_chunked_body() {
+    let server = StubServer::start();
+    server.push(StubResponse::json("{}"));
+
+    let mut stream = TcpStream::connect(server.addr).unwrap();
+    stream.write_all(b"POST /v3/article HTTP/1.1\r\n\
+                       host: stub\r\n\
+                       TRANSFER-ENCODING: Chunked\r\n\r\n\
+                       5;ext=1\r\nHello\r\n\
+                       7\r\n, world\r\n\
+                       0\r\nx-trailer: 1\r\n\r\n")
+          .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+    let requests = server.requests();
+    assert_eq!(requests[0].body(), b"Hello, world");
+    assert_eq!(requests[0].header("transfer-encoding"), Some("Chunked"));
+}
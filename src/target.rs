@@ -0,0 +1,316 @@
+//! Conversion and validation of target page URLs.
+
+use std::collections::HashSet;
+#[cfg(feature = "jobs")]
+use std::io::{BufRead, BufReader, Read};
+
+use url::Url;
+#[cfg(feature = "jobs")]
+use url::form_urlencoded::byte_serialize;
+
+use Error;
+
+/// A value that can be used as the URL of a page to process.
+///
+/// Implemented for `&str`, `String` and `url::Url`. The URL is parsed and
+/// checked before anything is sent, so an invalid URL is reported as
+/// `Error::InvalidUrl` instead of a confusing API error.
+pub trait IntoTargetUrl {
+    /// Parses and validates the URL.
+    fn into_target_url(self) -> Result<Url, Error>;
+}
+
+impl IntoTargetUrl for Url {
+    fn into_target_url(self) -> Result<Url, Error> {
+        match self.scheme() {
+            "http" | "https" => Ok(self),
+            scheme => {
+                Err(Error::InvalidUrl(format!("unsupported scheme `{}` in {}",
+                                              scheme,
+                                              self)))
+            }
+        }
+    }
+}
+
+impl<'a> IntoTargetUrl for &'a Url {
+    fn into_target_url(self) -> Result<Url, Error> {
+        self.clone().into_target_url()
+    }
+}
+
+impl<'a> IntoTargetUrl for &'a str {
+    fn into_target_url(self) -> Result<Url, Error> {
+        match Url::parse(self) {
+            Ok(url) => url.into_target_url(),
+            Err(err) => {
+                Err(Error::InvalidUrl(format!("{}: {}", err, self)))
+            }
+        }
+    }
+}
+
+impl<'a> IntoTargetUrl for &'a String {
+    fn into_target_url(self) -> Result<Url, Error> {
+        self.as_str().into_target_url()
+    }
+}
+
+impl IntoTargetUrl for String {
+    fn into_target_url(self) -> Result<Url, Error> {
+        self.as_str().into_target_url()
+    }
+}
+
+/// Checks a list of URLs before submitting them to a crawl or bulk job.
+///
+/// Unlike `bulk` and `crawl`, which fail on the first invalid URL, the
+/// validator sorts the whole list into accepted and rejected URLs, so the
+/// valid ones can still be submitted.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// # use diffbot::*;
+/// # fn main() {
+/// let urls = ["http://Example.COM/a#top", "example.com/b"];
+/// let checked = UrlValidator::new().normalize(true)
+///                                  .dedupe(true)
+///                                  .check(&urls);
+/// assert_eq!(checked.accepted[0].as_str(), "http://example.com/a");
+/// assert_eq!(checked.rejected[0].index, 1);
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct UrlValidator {
+    normalize: bool,
+    dedupe: bool,
+}
+
+impl UrlValidator {
+    /// Returns a validator that only checks the URLs.
+    pub fn new() -> Self {
+        UrlValidator::default()
+    }
+
+    /// Removes the fragment of accepted URLs.
+    ///
+    /// The host is always lowercased when the URL is parsed.
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Drops the accepted URLs that were already accepted, keeping the
+    /// first occurrence.
+    ///
+    /// URLs are compared after normalization, if enabled.
+    pub fn dedupe(mut self, dedupe: bool) -> Self {
+        self.dedupe = dedupe;
+        self
+    }
+
+    /// Parses every URL, accepting `http` and `https` URLs with a host.
+    pub fn check<U: AsRef<str>>(&self, urls: &[U]) -> ValidatedUrls {
+        let mut checked = ValidatedUrls::default();
+        let mut seen = HashSet::new();
+        for (index, input) in urls.iter().enumerate() {
+            let input = input.as_ref();
+            match self.check_url(input) {
+                Ok(ref url) if self.dedupe &&
+                               !seen.insert(url.as_str().to_string()) => {
+                    checked.duplicates += 1;
+                }
+                Ok(url) => checked.accepted.push(url),
+                Err(reason) => {
+                    checked.rejected.push(RejectedUrl {
+                        index: index,
+                        url: input.to_string(),
+                        reason: reason,
+                    })
+                }
+            }
+        }
+        checked
+    }
+
+    fn check_url(&self, input: &str) -> Result<Url, String> {
+        let mut url = match input.trim().into_target_url() {
+            Ok(url) => url,
+            Err(Error::InvalidUrl(reason)) => return Err(reason),
+            Err(err) => return Err(err.to_string()),
+        };
+        if url.host_str().map_or(true, str::is_empty) {
+            return Err(format!("missing host in {}", input));
+        }
+        if self.normalize {
+            url.set_fragment(None);
+        }
+        Ok(url)
+    }
+}
+
+/// The result of `UrlValidator::check`.
+#[derive(Clone, Debug, Default)]
+pub struct ValidatedUrls {
+    /// The valid URLs, in their original order.
+    pub accepted: Vec<Url>,
+    /// The invalid URLs, in their original order.
+    pub rejected: Vec<RejectedUrl>,
+    /// The number of duplicate URLs dropped.
+    pub duplicates: usize,
+}
+
+/// A URL refused by `UrlValidator::check`.
+#[derive(Clone, Debug)]
+pub struct RejectedUrl {
+    /// The position of the URL in the checked list.
+    pub index: usize,
+    /// The URL as given.
+    pub url: String,
+    /// Why the URL was refused.
+    pub reason: String,
+}
+
+#[cfg(feature = "jobs")]
+// Validates every URL and joins them with spaces, as expected by the crawl
+// and bulk APIs.
+pub(crate) fn join_target_urls<U: IntoTargetUrl + Clone>(urls: &[U])
+                                                         -> Result<String,
+                                                                   Error> {
+    let mut joined = String::new();
+    for url in urls {
+        let url = try!(url.clone().into_target_url());
+        if !joined.is_empty() {
+            joined.push(' ');
+        }
+        joined.push_str(url.as_str());
+    }
+    Ok(joined)
+}
+
+#[cfg(feature = "jobs")]
+// Joins URLs into lists of at most `max_urls` URLs whose form-encoded size
+// stays under `max_bytes`, except for a single URL larger than that.
+pub(crate) fn chunk_target_urls(urls: &[Url], max_urls: usize,
+                                max_bytes: usize)
+                                -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    let (mut count, mut size) = (0, 0);
+    for url in urls {
+        let encoded: usize = byte_serialize(url.as_str().as_bytes())
+                                 .map(str::len)
+                                 .sum();
+        let separator = if count == 0 { 0 } else { 1 };
+        if count > 0 &&
+           (count >= max_urls || size + separator + encoded > max_bytes) {
+            chunks.push(chunk);
+            chunk = String::new();
+            count = 0;
+            size = 0;
+        }
+        if count > 0 {
+            chunk.push(' ');
+            size += 1;
+        }
+        chunk.push_str(url.as_str());
+        count += 1;
+        size += encoded;
+    }
+    if count > 0 {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+#[cfg(feature = "jobs")]
+// Reads one URL per line, skipping blank lines and `#` comments, and
+// validates each of them.
+pub(crate) fn read_target_urls<R: Read>(reader: R) -> Result<Vec<Url>, Error> {
+    let mut urls = Vec::new();
+    for (index, line) in BufReader::new(reader).lines().enumerate() {
+        let line = try!(line);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.into_target_url() {
+            Ok(url) => urls.push(url),
+            Err(Error::InvalidUrl(message)) => {
+                let message = format!("line {}: {}", index + 1, message);
+                return Err(Error::InvalidUrl(message));
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(urls)
+}
+
+#[test]
+fn test_target_url_validation() {
+    assert!("http://diffbot.com".into_target_url().is_ok());
+    assert!("diffbot.com".into_target_url().is_err());
+    assert!("ftp://diffbot.com/file".into_target_url().is_err());
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_join_target_urls() {
+    let joined = join_target_urls(&["http://a.com", "https://b.com/x y"]);
+    assert_eq!(joined.unwrap(), "http://a.com/ https://b.com/x%20y");
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_read_target_urls() {
+    let list = "# seeds\nhttp://a.com\n\n  https://b.com/x  \n";
+    let urls = read_target_urls(list.as_bytes()).unwrap();
+    assert_eq!(urls.len(), 2);
+    assert_eq!(urls[1].as_str(), "https://b.com/x");
+
+    let err = read_target_urls("http://a.com\nb.com\n".as_bytes())
+                  .unwrap_err();
+    assert!(err.to_string().contains("line 2"));
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_chunk_target_urls() {
+    let urls: Vec<Url> = ["http://a.com/", "http://b.com/", "http://c.com/"]
+                             .iter()
+                             .map(|url| url.into_target_url().unwrap())
+                             .collect();
+    assert_eq!(chunk_target_urls(&urls, 2, 1000),
+               vec!["http://a.com/ http://b.com/", "http://c.com/"]);
+    // Each URL is 21 bytes once encoded
+    assert_eq!(chunk_target_urls(&urls, 10, 50).len(), 2);
+    assert_eq!(chunk_target_urls(&urls, 10, 10).len(), 3);
+    assert!(chunk_target_urls(&[], 10, 10).is_empty());
+}
+
+#[test]
+fn test_url_validator() {
+    let urls = ["http://A.com/x#frag", "ftp://b.com/", "not a url",
+                " https://c.com/ "];
+    let checked = UrlValidator::new().check(&urls);
+    assert_eq!(checked.accepted.len(), 2);
+    assert_eq!(checked.accepted[0].as_str(), "http://a.com/x#frag");
+    let indices: Vec<_> = checked.rejected.iter().map(|r| r.index).collect();
+    assert_eq!(indices, vec![1, 2]);
+    assert!(checked.rejected[0].reason.contains("ftp"));
+
+    let checked = UrlValidator::new().normalize(true).check(&urls);
+    assert_eq!(checked.accepted[0].as_str(), "http://a.com/x");
+
+    let urls = ["http://a.com/x#one", "http://A.com/x#two", "http://a.com/x"];
+    let checked = UrlValidator::new().dedupe(true).check(&urls);
+    assert_eq!(checked.accepted.len(), 3);
+    assert_eq!(checked.duplicates, 0);
+    let checked = UrlValidator::new().normalize(true)
+                                     .dedupe(true)
+                                     .check(&urls);
+    assert_eq!(checked.accepted.len(), 1);
+    assert_eq!(checked.duplicates, 2);
+}
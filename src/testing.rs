@@ -0,0 +1,375 @@
+//! Test doubles for code using the client.
+//!
+//! `MockDiffbot` implements `DiffbotApi` with responses queued by the test,
+//! and records every call so the test can check what was asked.
+//!
+//! With the `test-util` feature, `StubServer` goes one level down: it is a
+//! local HTTP server that a real `Diffbot` client can be pointed at, to test
+//! what is actually sent over the wire.
+//!
+//! # Example
+//!
+//! ```
+//! # extern crate diffbot;
+//! # extern crate serde_json;
+//! use diffbot::*;
+//! use diffbot::testing::MockDiffbot;
+//!
+//! fn count_objects(client: &dyn DiffbotApi, url: &str) -> usize {
+//!     client.call_with_options(API::Article, url, &[("paging", "false")])
+//!           .ok()
+//!           .and_then(|result| {
+//!               result.get("objects")
+//!                     .and_then(|objects| objects.as_array())
+//!                     .map(|objects| objects.len())
+//!           })
+//!           .unwrap_or(0)
+//! }
+//!
+//! # fn main() {
+//! let mock = MockDiffbot::new();
+//! mock.push_json(serde_json::from_str(r#"{"objects": [{}, {}]}"#).unwrap());
+//!
+//! assert_eq!(count_objects(&mock, "http://diffbot.com"), 2);
+//! mock.assert_called_with_options(API::Article,
+//!                                 "http://diffbot.com",
+//!                                 &[("paging", "false")]);
+//! # }
+//! ```
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+use serde_json::Value;
+
+use {API, DiffbotApi, DiffbotResult};
+#[cfg(feature = "jobs")]
+use {Error, models};
+
+#[cfg(any(test, feature = "test-util"))]
+pub use stub::{StubRequest, StubResponse, StubServer};
+
+/// The method of `DiffbotApi` that was called.
+///
+/// Methods without options are recorded like their `_with_options`
+/// counterpart, with an empty option list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallKind {
+    /// `call` or `call_with_options`.
+    Call,
+    /// `post_body` or `post_body_with_options`.
+    PostBody,
+    /// `search` or `search_with_options`.
+    Search,
+    /// `list_crawls`.
+    ListCrawls,
+    /// `bulk` or `bulk_with_options`.
+    Bulk,
+    /// `list_bulk_jobs`.
+    ListBulkJobs,
+    /// `get_bulk`.
+    GetBulk,
+    /// `crawl` or `crawl_with_options`.
+    Crawl,
+    /// `get_crawl`.
+    GetCrawl,
+}
+
+/// A call recorded by `MockDiffbot`.
+#[derive(Clone, Debug)]
+pub struct MockCall {
+    kind: CallKind,
+    api: Option<API>,
+    target_url: Option<String>,
+    name: Option<String>,
+    urls: Vec<String>,
+    body: Option<Vec<u8>>,
+    options: Vec<(String, String)>,
+}
+
+impl MockCall {
+    fn new(kind: CallKind) -> Self {
+        MockCall {
+            kind: kind,
+            api: None,
+            target_url: None,
+            name: None,
+            urls: Vec::new(),
+            body: None,
+            options: Vec::new(),
+        }
+    }
+
+    fn with_options(mut self, options: &[(&str, &str)]) -> Self {
+        self.options = options.iter()
+                              .map(|&(k, v)| (k.to_string(), v.to_string()))
+                              .collect();
+        self
+    }
+
+    /// Returns the method that was called.
+    pub fn kind(&self) -> CallKind {
+        self.kind
+    }
+
+    /// Returns the API used, if the method takes one.
+    pub fn api(&self) -> Option<&API> {
+        self.api.as_ref()
+    }
+
+    /// Returns the target URL, for `call` and `post_body`.
+    pub fn target_url(&self) -> Option<&str> {
+        self.target_url.as_ref().map(|url| url.as_str())
+    }
+
+    /// Returns the job name for bulk and crawl jobs, or the collection for
+    /// `search`.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_ref().map(|name| name.as_str())
+    }
+
+    /// Returns the URLs or seeds given to `bulk` and `crawl`, or the query
+    /// given to `search`.
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    /// Returns the body given to `post_body`.
+    pub fn body(&self) -> Option<&[u8]> {
+        self.body.as_ref().map(|body| &body[..])
+    }
+
+    /// Returns the extra options, in the order they were given.
+    pub fn options(&self) -> &[(String, String)] {
+        &self.options
+    }
+
+    /// Returns the value of the first option named `key`.
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|&&(ref k, _)| k == key)
+            .map(|&(_, ref v)| v.as_str())
+    }
+
+    // Returns `true` if every expected option was given.
+    fn has_options(&self, options: &[(&str, &str)]) -> bool {
+        options.iter().all(|&(key, value)| {
+            self.options.iter().any(|&(ref k, ref v)| k == key && v == value)
+        })
+    }
+}
+
+/// A fake client returning queued responses.
+///
+/// Each call pops the oldest queued response, and panics if there is none.
+#[derive(Debug, Default)]
+pub struct MockDiffbot {
+    responses: RefCell<VecDeque<DiffbotResult>>,
+    calls: RefCell<Vec<MockCall>>,
+}
+
+impl MockDiffbot {
+    /// Returns a mock with no queued response.
+    pub fn new() -> Self {
+        MockDiffbot::default()
+    }
+
+    /// Queues the result of the next call.
+    pub fn push_response(&self, response: DiffbotResult) {
+        self.responses.borrow_mut().push_back(response);
+    }
+
+    /// Queues a successful response.
+    ///
+    /// # Panics
+    ///
+    /// If `json` is not an object, as the API always returns objects.
+    pub fn push_json(&self, json: Value) {
+        match json {
+            Value::Object(map) => self.push_response(Ok(map)),
+            other => {
+                panic!("MockDiffbot responses must be objects: {}", other)
+            }
+        }
+    }
+
+    /// Returns every call made so far.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.borrow().clone()
+    }
+
+    /// Returns the most recent call.
+    pub fn last_call(&self) -> Option<MockCall> {
+        self.calls.borrow().last().cloned()
+    }
+
+    /// Returns the number of queued responses not used yet.
+    pub fn remaining_responses(&self) -> usize {
+        self.responses.borrow().len()
+    }
+
+    /// Panics unless exactly `count` calls were made.
+    pub fn assert_call_count(&self, count: usize) {
+        let calls = self.calls.borrow();
+        assert!(calls.len() == count,
+                "expected {} calls, got {}: {:?}",
+                count,
+                calls.len(),
+                *calls);
+    }
+
+    /// Panics unless `api` was called on `target_url`.
+    pub fn assert_called(&self, api: API, target_url: &str) {
+        self.assert_called_with_options(api, target_url, &[]);
+    }
+
+    /// Panics unless `api` was called on `target_url` with (at least) the
+    /// given options.
+    pub fn assert_called_with_options(&self, api: API, target_url: &str,
+                                      options: &[(&str, &str)]) {
+        let calls = self.calls.borrow();
+        let found = calls.iter().any(|call| {
+            call.api.as_ref() == Some(&api) &&
+            call.target_url() == Some(target_url) &&
+            call.has_options(options)
+        });
+        assert!(found,
+                "no {} call on {} with options {:?}; calls: {:?}",
+                api,
+                target_url,
+                options,
+                *calls);
+    }
+
+    /// Panics if some queued responses were not used.
+    pub fn assert_all_responses_used(&self) {
+        let remaining = self.remaining_responses();
+        assert!(remaining == 0, "{} queued responses not used", remaining);
+    }
+
+    // Records a call and returns the next queued response.
+    fn respond(&self, call: MockCall) -> DiffbotResult {
+        let kind = call.kind;
+        self.calls.borrow_mut().push(call);
+        match self.responses.borrow_mut().pop_front() {
+            Some(response) => response,
+            None => panic!("MockDiffbot: no response queued for {:?}", kind),
+        }
+    }
+
+    // Records a call and parses the next queued response as a job list.
+    #[cfg(feature = "jobs")]
+    fn respond_jobs(&self, call: MockCall) -> Result<Vec<models::Job>, Error> {
+        let result = self.respond(call);
+        let response: models::JobsResponse =
+            try!(result.and_then(models::parse));
+        Ok(response.jobs)
+    }
+}
+
+impl DiffbotApi for MockDiffbot {
+    fn call_with_options(&self, api: API, target_url: &str,
+                         options: &[(&str, &str)])
+                         -> DiffbotResult {
+        let mut call = MockCall::new(CallKind::Call).with_options(options);
+        call.api = Some(api);
+        call.target_url = Some(target_url.to_string());
+        self.respond(call)
+    }
+
+    fn post_body_with_options(&self, api: API, target_url: &str,
+                              body: &[u8], options: &[(&str, &str)])
+                              -> DiffbotResult {
+        let mut call = MockCall::new(CallKind::PostBody).with_options(options);
+        call.api = Some(api);
+        call.target_url = Some(target_url.to_string());
+        call.body = Some(body.to_vec());
+        self.respond(call)
+    }
+
+    #[cfg(feature = "search")]
+    fn search_with_options(&self, col: &str, query: &str,
+                           options: &[(&str, &str)])
+                           -> DiffbotResult {
+        let mut call = MockCall::new(CallKind::Search).with_options(options);
+        call.name = Some(col.to_string());
+        call.urls = vec![query.to_string()];
+        self.respond(call)
+    }
+
+    #[cfg(feature = "jobs")]
+    fn list_crawls(&self) -> Result<Vec<models::Job>, Error> {
+        self.respond_jobs(MockCall::new(CallKind::ListCrawls))
+    }
+
+    #[cfg(feature = "jobs")]
+    fn bulk_with_options(&self, name: &str, api: API, urls: &[&str],
+                         options: &[(&str, &str)])
+                         -> DiffbotResult {
+        let mut call = MockCall::new(CallKind::Bulk).with_options(options);
+        call.api = Some(api);
+        call.name = Some(name.to_string());
+        call.urls = urls.iter().map(|url| url.to_string()).collect();
+        self.respond(call)
+    }
+
+    #[cfg(feature = "jobs")]
+    fn list_bulk_jobs(&self) -> Result<Vec<models::Job>, Error> {
+        self.respond_jobs(MockCall::new(CallKind::ListBulkJobs))
+    }
+
+    #[cfg(feature = "jobs")]
+    fn get_bulk(&self, name: &str) -> DiffbotResult {
+        let mut call = MockCall::new(CallKind::GetBulk);
+        call.name = Some(name.to_string());
+        self.respond(call)
+    }
+
+    #[cfg(feature = "jobs")]
+    fn crawl_with_options(&self, name: &str, api: API, seeds: &[&str],
+                          options: &[(&str, &str)])
+                          -> DiffbotResult {
+        let mut call = MockCall::new(CallKind::Crawl).with_options(options);
+        call.api = Some(api);
+        call.name = Some(name.to_string());
+        call.urls = seeds.iter().map(|url| url.to_string()).collect();
+        self.respond(call)
+    }
+
+    #[cfg(feature = "jobs")]
+    fn get_crawl(&self, name: &str) -> DiffbotResult {
+        let mut call = MockCall::new(CallKind::GetCrawl);
+        call.name = Some(name.to_string());
+        self.respond(call)
+    }
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+fn test_mock_records_calls() {
+    use Error;
+
+    let mock = MockDiffbot::new();
+    mock.push_json(::serde_json::from_str(r#"{"title": "x"}"#).unwrap());
+    mock.push_response(Err(Error::InvalidUrl("nope".to_string())));
+
+    let result = mock.call(API::Article, "http://a.com").unwrap();
+    assert_eq!(result["title"], "x");
+    assert!(mock.crawl("job", API::Analyze, &["http://b.com"]).is_err());
+
+    mock.assert_call_count(2);
+    mock.assert_called(API::Article, "http://a.com");
+    mock.assert_all_responses_used();
+    let crawl = mock.last_call().unwrap();
+    assert_eq!(crawl.kind(), CallKind::Crawl);
+    assert_eq!(crawl.name(), Some("job"));
+    assert_eq!(crawl.urls(), &["http://b.com".to_string()][..]);
+}
+
+#[cfg(feature = "jobs")]
+#[test]
+#[should_panic(expected = "no response queued")]
+fn test_mock_panics_without_response() {
+    let _ = MockDiffbot::new().list_crawls();
+}
@@ -0,0 +1,257 @@
+//! Text processing of extracted articles.
+//!
+//! Diffbot returns both the plain `text` and the normalized `html` of an
+//! article. These helpers turn the HTML into paragraphs of plain text, and
+//! compute simple statistics on it.
+//!
+//! ```
+//! # extern crate diffbot;
+//! use diffbot::text;
+//!
+//! # fn main() {
+//! let html = "<h1>Robots</h1><p>They shop&nbsp;online.</p><p>Often.</p>";
+//! let plain = text::html_to_text(html);
+//! assert_eq!(text::paragraphs(&plain), vec!["Robots", "They shop online.",
+//!                                           "Often."]);
+//! assert_eq!(text::word_count(&plain), 5);
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use models::Article;
+
+/// The reading speed used by `reading_time`, in words per minute.
+pub const WORDS_PER_MINUTE: u64 = 200;
+
+// Elements whose start and end separate paragraphs.
+const BLOCK_TAGS: &'static [&'static str] = &["address", "article", "aside",
+                                              "blockquote", "dd", "div",
+                                              "dl", "dt", "figcaption",
+                                              "figure", "footer", "h1",
+                                              "h2", "h3", "h4", "h5", "h6",
+                                              "header", "hr", "li", "ol",
+                                              "p", "pre", "section",
+                                              "table", "td", "th", "tr",
+                                              "ul"];
+
+// Elements whose content is not text.
+const SKIPPED_TAGS: &'static [&'static str] = &["script", "style"];
+
+/// Converts HTML to plain text.
+///
+/// Tags are removed, entities decoded and whitespace collapsed. Block
+/// elements such as paragraphs and headings are separated by a blank line,
+/// and line breaks are kept.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    let mut skipping: Option<String> = None;
+    while !rest.is_empty() {
+        if rest.starts_with('<') {
+            let end = rest.find('>').map_or(rest.len(), |end| end + 1);
+            let (tag, closing) = tag_name(&rest[1..end]);
+            rest = &rest[end..];
+            if let Some(skipped) = skipping.clone() {
+                if closing && tag == skipped {
+                    skipping = None;
+                }
+                continue;
+            }
+            if !closing && SKIPPED_TAGS.contains(&tag.as_str()) {
+                skipping = Some(tag);
+            } else if tag == "br" {
+                break_line(&mut text, "\n");
+            } else if BLOCK_TAGS.contains(&tag.as_str()) {
+                break_line(&mut text, "\n\n");
+            }
+            continue;
+        }
+
+        let end = rest.find('<').unwrap_or(rest.len());
+        if skipping.is_none() {
+            push_text(&mut text, &decode_entities(&rest[..end]));
+        }
+        rest = &rest[end..];
+    }
+    text.trim().to_string()
+}
+
+/// Returns the text of an article, converted from its HTML if the plain
+/// text is missing.
+pub fn article_text(article: &Article) -> String {
+    match (&article.text, &article.html) {
+        (&Some(ref text), _) => text.clone(),
+        (&None, &Some(ref html)) => html_to_text(html),
+        (&None, &None) => String::new(),
+    }
+}
+
+/// Splits text into paragraphs, separated by blank lines.
+///
+/// Paragraphs are trimmed, and empty ones are skipped.
+pub fn paragraphs(text: &str) -> Vec<&str> {
+    let mut paragraphs = Vec::new();
+    let mut start = None;
+    let mut end = 0;
+    let mut offset = 0;
+    for line in text.split('\n') {
+        let line_start = offset;
+        offset += line.len() + 1;
+        if line.trim().is_empty() {
+            if let Some(start) = start.take() {
+                paragraphs.push(text[start..end].trim());
+            }
+            continue;
+        }
+        if start.is_none() {
+            start = Some(line_start);
+        }
+        end = line_start + line.len();
+    }
+    if let Some(start) = start {
+        paragraphs.push(text[start..end].trim());
+    }
+    paragraphs
+}
+
+/// Counts the words of a text, separated by whitespace.
+pub fn word_count(text: &str) -> usize {
+    text.split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .count()
+}
+
+/// Estimates how long it takes to read a text, at `WORDS_PER_MINUTE`.
+///
+/// The estimate is rounded up to the next second.
+pub fn reading_time(text: &str) -> Duration {
+    let words = word_count(text) as u64;
+    Duration::from_secs((words * 60 + WORDS_PER_MINUTE - 1) /
+                        WORDS_PER_MINUTE)
+}
+
+// Returns the lowercase name of a tag, and whether it is a closing tag.
+fn tag_name(tag: &str) -> (String, bool) {
+    let tag = tag.trim_end_matches('>').trim();
+    let closing = tag.starts_with('/');
+    let name = tag.trim_start_matches('/')
+                  .split(|c: char| c.is_whitespace() || c == '/')
+                  .next()
+                  .unwrap_or("");
+    (name.to_lowercase(), closing)
+}
+
+// Ends the current line or paragraph, without leading or repeated breaks.
+fn break_line(text: &mut String, separator: &str) {
+    while text.ends_with(' ') {
+        text.pop();
+    }
+    if text.is_empty() || text.ends_with("\n\n") {
+        return;
+    }
+    if text.ends_with('\n') {
+        if separator == "\n\n" {
+            text.push('\n');
+        }
+        return;
+    }
+    text.push_str(separator);
+}
+
+// Appends text, collapsing whitespace.
+fn push_text(text: &mut String, more: &str) {
+    for c in more.chars() {
+        if c.is_whitespace() {
+            if !text.is_empty() && !text.ends_with(' ') &&
+               !text.ends_with('\n') {
+                text.push(' ');
+            }
+        } else {
+            text.push(c);
+        }
+    }
+}
+
+// Decodes the character references of HTML text.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(';') {
+            Some(end) if end <= 10 => end,
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        match decode_entity(&rest[1..end]) {
+            Some(c) => decoded.push(c),
+            None => decoded.push_str(&rest[..end + 1]),
+        }
+        rest = &rest[end + 1..];
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    if entity.starts_with("#x") || entity.starts_with("#X") {
+        return u32::from_str_radix(&entity[2..], 16)
+                   .ok()
+                   .and_then(::std::char::from_u32);
+    }
+    if entity.starts_with('#') {
+        return entity[1..].parse().ok().and_then(::std::char::from_u32);
+    }
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        "mdash" => Some('—'),
+        "ndash" => Some('–'),
+        "hellip" => Some('…'),
+        "rsquo" => Some('’'),
+        "lsquo" => Some('‘'),
+        "rdquo" => Some('”'),
+        "ldquo" => Some('“'),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_html_to_text() {
+    let html = "<figure><img src=\"a.png\"></figure>\
+                <p>First  line<br/>second &amp; last &#8220;line&#x201D;</p>\n\
+                <script>var x = '<p>';</script>\
+                <ul><li>One</li><li>Two</li></ul>\
+                <p>Fish &amp chips &unknown;</p>";
+    assert_eq!(html_to_text(html),
+               "First line\nsecond & last \u{201c}line\u{201d}\n\n\
+                One\n\nTwo\n\nFish &amp chips &unknown;");
+}
+
+#[test]
+fn test_paragraphs() {
+    let text = "\n  One\nstill one \n\n\n Two\n \nThree";
+    assert_eq!(paragraphs(text), vec!["One\nstill one", "Two", "Three"]);
+    assert!(paragraphs("  \n").is_empty());
+}
+
+#[test]
+fn test_reading_time() {
+    assert_eq!(word_count("Robots — they shop, online."), 4);
+    let text = vec!["word"; 450].join(" ");
+    assert_eq!(reading_time(&text), Duration::from_secs(135));
+    assert_eq!(reading_time(""), Duration::from_secs(0));
+
+    let mut article = Article::default();
+    article.html = Some("<p>Hello <b>world</b></p>".to_string());
+    assert_eq!(article_text(&article), "Hello world");
+}
@@ -0,0 +1,472 @@
+//! Sending requests through a custom network path.
+//!
+//! By default, the client sends its requests with its own HTTP client,
+//! directly to the API. A `Transport` set with `Diffbot::with_transport`
+//! replaces it, to go through a SOCKS proxy, a Unix socket, or any other
+//! path the built-in client cannot use.
+//!
+//! With the `curl` feature, `CurlTransport` sends the requests with
+//! libcurl, for programs that already link it. With the `ureq` feature,
+//! `UreqTransport` sends them with the small blocking client `ureq`.
+
+#[cfg(feature = "ureq")]
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+#[cfg(feature = "curl")]
+use std::io::Cursor;
+#[cfg(feature = "ureq")]
+use std::io;
+#[cfg(feature = "curl")]
+use std::net::IpAddr;
+#[cfg(feature = "ureq")]
+use std::net::{SocketAddr, ToSocketAddrs};
+#[cfg(feature = "curl")]
+use std::str;
+#[cfg(feature = "ureq")]
+use std::sync::{Arc, Mutex};
+#[cfg(any(feature = "curl", feature = "ureq"))]
+use std::time::Duration;
+#[cfg(feature = "ureq")]
+use std::time::Instant;
+
+#[cfg(feature = "curl")]
+use curl;
+#[cfg(feature = "curl")]
+use curl::easy::{Easy, List};
+#[cfg(feature = "ureq")]
+use ureq;
+
+use {Error, PreparedRequest};
+#[cfg(any(feature = "curl", feature = "ureq"))]
+use {TransportError, TransportErrorKind};
+
+/// Sends the HTTP requests of a client.
+///
+/// The request gives the method, the full URL (with the API token), the
+/// headers and the body to send. The transport returns the status, headers
+/// and body of the response, whatever the status: errors returned by the
+/// API are interpreted by the client.
+///
+/// Return a `TransportError` for network failures, converted into
+/// `Error::Http`, so that they are retried like the failures of the
+/// built-in client.
+///
+/// # Example
+///
+/// ```
+/// # extern crate diffbot;
+/// use std::io::Cursor;
+/// use std::sync::Arc;
+/// use diffbot::*;
+///
+/// // Answers every call without touching the network.
+/// struct Offline;
+///
+/// impl Transport for Offline {
+///     fn send(&self, request: &PreparedRequest)
+///             -> Result<TransportResponse, Error> {
+///         println!("{}", request);
+///         let body = Cursor::new(b"{\"objects\": []}".to_vec());
+///         Ok(TransportResponse::new(200, Vec::new(), body))
+///     }
+/// }
+///
+/// # fn main() {
+/// let diffbot = Diffbot::v3("token").with_transport(Arc::new(Offline));
+/// let result = diffbot.call(API::Article, "http://diffbot.com").unwrap();
+/// assert!(result["objects"].is_array());
+/// # }
+/// ```
+pub trait Transport: Send + Sync {
+    /// Sends a request and returns the response.
+    fn send(&self, request: &PreparedRequest)
+            -> Result<TransportResponse, Error>;
+}
+
+/// A response received by a `Transport`.
+pub struct TransportResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Box<dyn Read + Send>,
+}
+
+impl TransportResponse {
+    /// Returns a response with the given status, headers and body.
+    ///
+    /// The body is read only as needed, so the results of a job can be
+    /// streamed.
+    pub fn new<R>(status: u16, headers: Vec<(String, String)>, body: R)
+                  -> Self
+        where R: Read + Send + 'static
+    {
+        TransportResponse {
+            status: status,
+            headers: headers,
+            body: Box::new(body),
+        }
+    }
+
+    /// Returns the HTTP status.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the headers, in the order they were received.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Returns the value of a header, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|&&(ref n, _)| n.eq_ignore_ascii_case(name))
+            .map(|&(_, ref value)| value.as_str())
+    }
+
+    /// Returns the body.
+    pub fn into_body(self) -> Box<dyn Read + Send> {
+        self.body
+    }
+}
+
+impl fmt::Debug for TransportResponse {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("TransportResponse")
+           .field("status", &self.status)
+           .field("headers", &self.headers)
+           .finish()
+    }
+}
+
+/// A `Transport` sending the requests with libcurl.
+///
+/// Each request uses a new handle, so a transport can be shared between
+/// threads. The body of the response is read in full before it is
+/// returned. Requires the `curl` feature.
+///
+/// # Example
+///
+/// ```no_run
+/// # extern crate diffbot;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use diffbot::*;
+///
+/// # fn main() {
+/// let curl = CurlTransport::new().with_timeout(Duration::from_secs(60));
+/// let diffbot = Diffbot::v3("token").with_transport(Arc::new(curl));
+/// # }
+/// ```
+#[cfg(feature = "curl")]
+#[derive(Clone, Debug, Default)]
+pub struct CurlTransport {
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    // Entries of `CURLOPT_RESOLVE`, as `host:port:address`.
+    resolve: Vec<String>,
+}
+
+#[cfg(feature = "curl")]
+impl CurlTransport {
+    /// Returns a transport without timeouts.
+    pub fn new() -> Self {
+        CurlTransport::default()
+    }
+
+    /// Sets the maximum time a whole request may take.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum time to wait for a connection to the API.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Connects to `addrs` for `host` on `port` without looking the host up,
+    /// like the `--resolve` option of the `curl` command.
+    ///
+    /// This pins the addresses of the API servers, saving a DNS lookup for
+    /// each request.
+    pub fn with_resolve(mut self, host: &str, port: u16, addrs: &[IpAddr])
+                        -> Self {
+        let addrs: Vec<String> = addrs.iter()
+                                      .map(|addr| match *addr {
+                                          IpAddr::V4(ref v4) => v4.to_string(),
+                                          IpAddr::V6(ref v6) => {
+                                              format!("[{}]", v6)
+                                          }
+                                      })
+                                      .collect();
+        self.resolve.push(format!("{}:{}:{}", host, port, addrs.join(",")));
+        self
+    }
+
+    fn perform(&self, request: &PreparedRequest)
+               -> Result<TransportResponse, curl::Error> {
+        let mut easy = Easy::new();
+        try!(easy.url(request.url().as_str()));
+        try!(easy.custom_request(&request.method()));
+        try!(easy.follow_location(true));
+        if let Some(timeout) = self.timeout {
+            try!(easy.timeout(timeout));
+        }
+        if let Some(timeout) = self.connect_timeout {
+            try!(easy.connect_timeout(timeout));
+        }
+        if !self.resolve.is_empty() {
+            let mut resolve = List::new();
+            for entry in &self.resolve {
+                try!(resolve.append(entry));
+            }
+            try!(easy.resolve(resolve));
+        }
+        let mut headers = List::new();
+        for (name, value) in request.headers() {
+            try!(headers.append(&format!("{}: {}", name, value)));
+        }
+        try!(easy.http_headers(headers));
+        if let Some(body) = request.body() {
+            try!(easy.post_fields_copy(body));
+        }
+
+        let mut response_headers = Vec::new();
+        let mut body = Vec::new();
+        {
+            let mut transfer = easy.transfer();
+            try!(transfer.header_function(|line| {
+                if let Ok(line) = str::from_utf8(line) {
+                    if line.starts_with("HTTP/") {
+                        // A new response, after a redirection.
+                        response_headers.clear();
+                    } else if let Some(colon) = line.find(':') {
+                        response_headers.push((line[..colon].trim()
+                                                            .to_string(),
+                                               line[colon + 1..].trim()
+                                                                .to_string()));
+                    }
+                }
+                true
+            }));
+            try!(transfer.write_function(|data| {
+                body.extend_from_slice(data);
+                Ok(data.len())
+            }));
+            try!(transfer.perform());
+        }
+        let status = try!(easy.response_code());
+        Ok(TransportResponse::new(status as u16,
+                                  response_headers,
+                                  Cursor::new(body)))
+    }
+}
+
+#[cfg(feature = "curl")]
+impl Transport for CurlTransport {
+    fn send(&self, request: &PreparedRequest)
+            -> Result<TransportResponse, Error> {
+        self.perform(request).map_err(|err| {
+            let kind = if err.is_too_many_redirects() {
+                TransportErrorKind::Redirect
+            } else if err.is_couldnt_connect() ||
+                      err.is_couldnt_resolve_host() ||
+                      err.is_operation_timedout() ||
+                      err.is_send_error() || err.is_recv_error() ||
+                      err.is_got_nothing() {
+                TransportErrorKind::Connection
+            } else {
+                TransportErrorKind::Other
+            };
+            TransportError::new(kind, err.description()).into()
+        })
+    }
+}
+
+/// A `Transport` sending the requests with `ureq`, a small blocking HTTP
+/// client.
+///
+/// The body of the response is read as it is consumed, so the results of a
+/// job can be streamed. Requires the `ureq` feature.
+///
+/// # Example
+///
+/// ```no_run
+/// # extern crate diffbot;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use diffbot::*;
+///
+/// # fn main() {
+/// let ureq = UreqTransport::new().with_timeout(Duration::from_secs(60));
+/// let diffbot = Diffbot::v3("token").with_transport(Arc::new(ureq));
+/// # }
+/// ```
+#[cfg(feature = "ureq")]
+#[derive(Clone, Debug)]
+pub struct UreqTransport {
+    agent: ureq::Agent,
+    timeout: Option<Duration>,
+}
+
+#[cfg(feature = "ureq")]
+impl UreqTransport {
+    /// Returns a transport with the default settings of `ureq`.
+    pub fn new() -> Self {
+        UreqTransport::from_agent(ureq::agent())
+    }
+
+    /// Returns a transport sending the requests with `agent`, configured
+    /// with a proxy, TLS settings or connection timeouts.
+    pub fn from_agent(agent: ureq::Agent) -> Self {
+        UreqTransport {
+            agent: agent,
+            timeout: None,
+        }
+    }
+
+    /// Returns a transport looking host names up with `resolver`, such as
+    /// a closure returning fixed addresses or a cache of DNS answers.
+    ///
+    /// The resolver is given `host:port`, and returns the addresses to try
+    /// in order.
+    pub fn from_resolver<R>(resolver: R) -> Self
+        where R: ureq::Resolver + 'static
+    {
+        UreqTransport::from_agent(ureq::AgentBuilder::new()
+                                      .resolver(resolver)
+                                      .build())
+    }
+
+    /// Sets the maximum time a whole request may take.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+#[cfg(feature = "ureq")]
+impl Default for UreqTransport {
+    fn default() -> Self {
+        UreqTransport::new()
+    }
+}
+
+#[cfg(feature = "ureq")]
+impl Transport for UreqTransport {
+    fn send(&self, request: &PreparedRequest)
+            -> Result<TransportResponse, Error> {
+        let mut call = self.agent
+                           .request(&request.method(), request.url().as_str());
+        if let Some(timeout) = self.timeout {
+            call = call.timeout(timeout);
+        }
+        for (name, value) in request.headers() {
+            call = call.set(&name, &value);
+        }
+        let result = match request.body() {
+            Some(body) => call.send_bytes(body),
+            None => call.call(),
+        };
+        let response = match result {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(ureq::Error::Transport(err)) => {
+                let kind = match err.kind() {
+                    ureq::ErrorKind::TooManyRedirects => {
+                        TransportErrorKind::Redirect
+                    }
+                    ureq::ErrorKind::Dns |
+                    ureq::ErrorKind::ConnectionFailed |
+                    ureq::ErrorKind::ProxyConnect |
+                    ureq::ErrorKind::Io => TransportErrorKind::Connection,
+                    _ => TransportErrorKind::Other,
+                };
+                return Err(TransportError::new(kind, err.to_string()).into());
+            }
+        };
+        let headers = response.headers_names()
+                              .into_iter()
+                              .filter_map(|name| {
+                                  let value = response.header(&name)
+                                                      .map(str::to_string);
+                                  value.map(|value| (name, value))
+                              })
+                              .collect();
+        Ok(TransportResponse::new(response.status(),
+                                  headers,
+                                  response.into_reader()))
+    }
+}
+
+/// A resolver keeping the addresses of each host for a fixed time, for
+/// `UreqTransport::from_resolver` or `Diffbot::with_resolver`.
+///
+/// Hosts are looked up with the system resolver at most once per `ttl`.
+/// If a lookup fails, the addresses found last are used again, so a
+/// transient DNS failure does not fail the call. Clones share the same
+/// entries. Requires the `ureq` feature.
+///
+/// # Example
+///
+/// ```no_run
+/// # extern crate diffbot;
+/// use std::time::Duration;
+/// use diffbot::*;
+///
+/// # fn main() {
+/// let dns = DnsCache::new(Duration::from_secs(300));
+/// let diffbot = Diffbot::v3("token").with_resolver(dns);
+/// # }
+/// ```
+#[cfg(feature = "ureq")]
+#[derive(Clone, Debug)]
+pub struct DnsCache {
+    ttl: Duration,
+    entries: Arc<Mutex<HashMap<String, (Instant, Vec<SocketAddr>)>>>,
+}
+
+#[cfg(feature = "ureq")]
+impl DnsCache {
+    /// Returns an empty cache keeping addresses for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        DnsCache {
+            ttl: ttl,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+#[cfg(feature = "ureq")]
+impl ureq::Resolver for DnsCache {
+    fn resolve(&self, netloc: &str) -> io::Result<Vec<SocketAddr>> {
+        if let Some(&(expiry, ref addrs)) = self.entries
+                                                .lock()
+                                                .unwrap()
+                                                .get(netloc) {
+            if expiry > Instant::now() {
+                return Ok(addrs.clone());
+            }
+        }
+        // The lock is not held during the lookup, which may be slow.
+        match netloc.to_socket_addrs() {
+            Ok(addrs) => {
+                let addrs: Vec<SocketAddr> = addrs.collect();
+                let expiry = Instant::now() + self.ttl;
+                self.entries
+                    .lock()
+                    .unwrap()
+                    .insert(netloc.to_string(), (expiry, addrs.clone()));
+                Ok(addrs)
+            }
+            Err(err) => {
+                match self.entries.lock().unwrap().get(netloc) {
+                    Some(&(_, ref addrs)) => Ok(addrs.clone()),
+                    None => Err(err),
+                }
+            }
+        }
+    }
+}